@@ -0,0 +1,75 @@
+use clap::Parser;
+
+use super::output::OutputBinseqOptions;
+
+/// Encode and decode synthetic reads and report throughput.
+///
+/// Random FASTQ records are generated in memory, encoded through the regular
+/// `encode` pipeline, then decoded through the regular `decode` pipeline into
+/// a null sink. Encode/decode rates (records/sec and FASTQ MB/sec) and the
+/// encoded file size are printed, which makes it easy to compare hardware and
+/// settings without staging real data.
+///
+/// The synthetic FASTQ is held in memory, so `--records` x `--length` x ~2 bytes
+/// must fit in RAM.
+#[derive(Parser, Debug)]
+pub struct BenchmarkCommand {
+    #[clap(flatten)]
+    pub synthetic: SyntheticOptions,
+
+    /// Keep the encoded file at this path [default: temporary file, removed afterwards]
+    #[clap(long, value_name = "PATH")]
+    pub keep: Option<String>,
+
+    #[clap(flatten)]
+    pub options: OutputBinseqOptions,
+}
+
+#[derive(Parser, Debug)]
+#[clap(next_help_heading = "SYNTHETIC DATA OPTIONS")]
+pub struct SyntheticOptions {
+    /// Number of records to generate (accepts K/M/G suffixes, e.g. 10M)
+    #[clap(short = 'n', long, default_value = "1M", value_parser = parse_count)]
+    pub records: usize,
+
+    /// Length of each generated sequence
+    #[clap(short = 'L', long, default_value = "150")]
+    pub length: usize,
+
+    /// Seed for the synthetic data generator
+    #[clap(long, default_value = "42")]
+    pub seed: u64,
+}
+
+/// Parses a record count with optional decimal K/M/G suffixes (e.g. `10M` = 10,000,000)
+fn parse_count(input: &str) -> Result<usize, String> {
+    let input = input.trim().replace('_', "");
+    let (number_str, multiplier) = match input.chars().last() {
+        Some('K' | 'k') => (&input[..input.len() - 1], 1_000),
+        Some('M' | 'm') => (&input[..input.len() - 1], 1_000_000),
+        Some('G' | 'g') => (&input[..input.len() - 1], 1_000_000_000),
+        Some(c) if c.is_ascii_digit() => (input.as_str(), 1),
+        _ => return Err(format!("Invalid record count: {input}")),
+    };
+    match number_str.parse::<usize>() {
+        Ok(0) => Err("Record count must be greater than 0".to_string()),
+        Ok(number) => Ok(number * multiplier),
+        Err(_) => Err(format!("Failed to parse number: {number_str}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_count;
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!(parse_count("150"), Ok(150));
+        assert_eq!(parse_count("10M"), Ok(10_000_000));
+        assert_eq!(parse_count("2k"), Ok(2_000));
+        assert_eq!(parse_count("1_000"), Ok(1_000));
+        assert!(parse_count("0").is_err());
+        assert!(parse_count("M").is_err());
+        assert!(parse_count("ten").is_err());
+    }
+}
@@ -10,4 +10,24 @@ pub struct CatCommand {
 
     #[clap(flatten)]
     pub output: OutputBinseq,
+
+    /// Report the number of records in the concatenated output
+    ///
+    /// Logs per-file record counts and the merged total once concatenation
+    /// completes. For BQ outputs written to a file, the merged file is also
+    /// re-read and its record count checked against the per-file sum.
+    #[clap(long)]
+    pub report: bool,
+
+    /// Append the records of the other inputs onto the first input in place
+    ///
+    /// BQ only. The first file is extended with the record bytes of the
+    /// remaining files, which must have the same sequence lengths and bitsize,
+    /// without rewriting its existing contents.
+    #[clap(long, conflicts_with_all = ["output", "pipe"])]
+    pub append: bool,
+
+    /// Only validate that the inputs can be appended and print the resulting record count
+    #[clap(long, requires = "append")]
+    pub check: bool,
 }
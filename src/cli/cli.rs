@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use clap::{
     builder::{
         styling::{AnsiColor, Effects},
@@ -7,8 +9,10 @@ use clap::{
 };
 
 use super::{
-    CatCommand, DecodeCommand, EncodeCommand, GrepCommand, InfoCommand, PipeCommand, QcCommand,
-    RevcompCommand, SampleCommand, SplitCommand, VerifyCommand,
+    BenchmarkCommand, CatCommand, CorruptCommand, DecodeCommand, DoctorCommand, EncodeCommand,
+    FormatsCommand, GrepCommand, HeadCommand, InfoCommand, MergePairsCommand, PipeCommand,
+    QcCommand, RecompressCommand, RevcompCommand, SampleCommand, SortCommand, SplitCommand,
+    ValidateCommand, VerifyCommand,
 };
 
 // Configures Clap v3-style help menu colors
@@ -24,6 +28,65 @@ const STYLES: Styles = Styles::styled()
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Commands,
+
+    /// Only log errors (overridden by `BQTOOLS_LOG`)
+    #[clap(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Increase logging verbosity (-v: debug, -vv: trace; overridden by `BQTOOLS_LOG`)
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Refuse to overwrite existing output files
+    #[clap(long, global = true, overrides_with = "overwrite")]
+    pub no_clobber: bool,
+
+    /// Overwrite existing output files (default; overrides an earlier `--no-clobber`)
+    #[clap(long, global = true, overrides_with = "no_clobber")]
+    pub overwrite: bool,
+
+    /// Maximum number of output files held open at once [default: unbounded]
+    ///
+    /// `decode --group-by` closes the least recently used partition when the
+    /// bound is reached and reopens it in append mode on demand. `split` and
+    /// `encode --bam-split-rg` refuse to exceed the bound, since BINSEQ outputs
+    /// cannot be reopened.
+    #[clap(
+        long,
+        global = true,
+        value_name = "N",
+        visible_alias = "concurrency-limit",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub max_open_files: Option<usize>,
+
+    /// Directory for FIFOs and temporary files [default: system temp directory]
+    ///
+    /// Relative `pipe --basepath` values are placed inside this directory.
+    #[clap(long, global = true, value_name = "DIR")]
+    pub tmpdir: Option<PathBuf>,
+
+    /// Times to retry opening a `gs://` input after a transient failure
+    ///
+    /// Waits double after each failed attempt (1s, 2s, 4s, ... capped at 30s).
+    /// Retries cover opening the remote object; an error after streaming has
+    /// begun still aborts the encode.
+    #[cfg(feature = "gcs")]
+    #[clap(long, global = true, value_name = "N", default_value_t = crate::commands::DEFAULT_GCS_RETRIES)]
+    pub gcs_retries: usize,
+}
+impl Cli {
+    /// Log level selected by `-q`/`-v`, defaulting to `Info`
+    pub fn log_level(&self) -> log::LevelFilter {
+        if self.quiet {
+            return log::LevelFilter::Error;
+        }
+        match self.verbose {
+            0 => log::LevelFilter::Info,
+            1 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        }
+    }
 }
 
 #[derive(Parser)]
@@ -32,6 +95,8 @@ pub enum Commands {
 
     Decode(DecodeCommand),
 
+    Head(HeadCommand),
+
     Cat(CatCommand),
 
     Info(InfoCommand),
@@ -40,6 +105,8 @@ pub enum Commands {
 
     Sample(SampleCommand),
 
+    Sort(SortCommand),
+
     Split(SplitCommand),
 
     Pipe(PipeCommand),
@@ -49,4 +116,86 @@ pub enum Commands {
     Revcomp(RevcompCommand),
 
     Verify(VerifyCommand),
+
+    Validate(ValidateCommand),
+
+    MergePairs(MergePairsCommand),
+
+    Recompress(RecompressCommand),
+
+    Benchmark(BenchmarkCommand),
+
+    Formats(FormatsCommand),
+
+    Doctor(DoctorCommand),
+
+    #[command(hide = true)]
+    Corrupt(CorruptCommand),
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use log::LevelFilter;
+
+    use super::Cli;
+
+    #[test]
+    fn test_log_level_flags() {
+        let level = |args: &[&str]| Cli::try_parse_from(args).unwrap().log_level();
+        assert_eq!(level(&["bqtools", "info", "x.vbq"]), LevelFilter::Info);
+        assert_eq!(
+            level(&["bqtools", "-q", "info", "x.vbq"]),
+            LevelFilter::Error
+        );
+        assert_eq!(
+            level(&["bqtools", "-v", "info", "x.vbq"]),
+            LevelFilter::Debug
+        );
+        assert_eq!(
+            level(&["bqtools", "-vv", "info", "x.vbq"]),
+            LevelFilter::Trace
+        );
+        assert!(Cli::try_parse_from(["bqtools", "-q", "-v", "info", "x.vbq"]).is_err());
+
+        // subcommand flags sharing the names are unaffected
+        let cli = Cli::try_parse_from(["bqtools", "grep", "x.vbq", "ACGT", "-v"]).unwrap();
+        assert_eq!(cli.log_level(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_no_clobber_flags() {
+        let no_clobber = |args: &[&str]| Cli::try_parse_from(args).unwrap().no_clobber;
+        assert!(!no_clobber(&["bqtools", "info", "x.vbq"]));
+        assert!(no_clobber(&["bqtools", "--no-clobber", "info", "x.vbq"]));
+        assert!(no_clobber(&["bqtools", "info", "x.vbq", "--no-clobber"]));
+        assert!(!no_clobber(&[
+            "bqtools",
+            "--no-clobber",
+            "info",
+            "x.vbq",
+            "--overwrite"
+        ]));
+    }
+
+    #[test]
+    fn test_max_open_files_flag() {
+        let limit = |args: &[&str]| Cli::try_parse_from(args).map(|cli| cli.max_open_files);
+        assert_eq!(limit(&["bqtools", "info", "x.vbq"]).unwrap(), None);
+        assert_eq!(
+            limit(&["bqtools", "decode", "x.vbq", "--max-open-files", "64"]).unwrap(),
+            Some(64)
+        );
+        assert!(limit(&["bqtools", "--max-open-files", "0", "info", "x.vbq"]).is_err());
+    }
+
+    #[test]
+    fn test_tmpdir_flag_is_global() {
+        let tmpdir = |args: &[&str]| Cli::try_parse_from(args).unwrap().tmpdir;
+        assert_eq!(tmpdir(&["bqtools", "info", "x.vbq"]), None);
+        assert_eq!(
+            tmpdir(&["bqtools", "pipe", "x.vbq", "--tmpdir", "/scratch"]),
+            Some("/scratch".into())
+        );
+    }
 }
@@ -0,0 +1,62 @@
+use clap::Parser;
+
+use super::InputBinseq;
+
+/// Inject reproducible random errors into a BINSEQ file (for testing).
+///
+/// By default records are decoded, each base is substituted with a different
+/// nucleotide at `--rate`, and the result is re-encoded with the input's
+/// configuration. With `--raw`, the file is copied and bytes inside the record
+/// blocks are XOR-flipped at `--rate` instead, leaving the file header and
+/// block index intact so readers can still open the file.
+///
+/// Mutations are derived from `--seed` and the record index (or byte offset),
+/// so the same records are corrupted regardless of the number of threads.
+#[derive(Parser, Debug)]
+pub struct CorruptCommand {
+    #[clap(flatten)]
+    pub input: InputBinseq,
+
+    /// Output binseq file
+    #[clap(short = 'o', long)]
+    pub output: String,
+
+    #[clap(flatten)]
+    pub options: CorruptOptions,
+}
+
+#[derive(Parser, Debug)]
+#[clap(next_help_heading = "CORRUPTION OPTIONS")]
+pub struct CorruptOptions {
+    /// Probability of mutating each base (or byte with `--raw`)
+    #[clap(short, long, default_value = "0.001", value_parser = parse_rate)]
+    pub rate: f64,
+
+    /// Seed for the mutation generator
+    #[clap(short = 'S', long, default_value = "42")]
+    pub seed: u64,
+
+    /// Flip raw bytes in record blocks instead of substituting decoded bases
+    #[clap(long)]
+    pub raw: bool,
+
+    /// Number of threads to use for re-encoding (0 for auto)
+    #[clap(short = 'T', long, default_value = "0")]
+    pub threads: usize,
+}
+impl CorruptOptions {
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => num_cpus::get(),
+            n => n.min(num_cpus::get()),
+        }
+    }
+}
+
+fn parse_rate(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        Ok(_) => Err("Rate must be between 0 and 1".to_string()),
+        Err(_) => Err(format!("Invalid rate: {input}")),
+    }
+}
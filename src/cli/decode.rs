@@ -1,13 +1,503 @@
 use clap::Parser;
 
-use super::{InputBinseq, OutputFile};
+use super::{
+    merge_pairs::MergeArgs, output::parse_memory_size, InputBinseq, LengthFilterArgs, OutputFile,
+    Span,
+};
+use crate::commands::grep::{FlagExpr, FlagMask};
 
 /// Decode BINSEQ files to FASTQ or FASTA.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct DecodeCommand {
     #[clap(flatten)]
     pub input: InputBinseq,
 
+    /// Additional input BINSEQ files (requires `--output-dir`)
+    #[clap(
+        value_name = "INPUTS",
+        requires = "output_dir",
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub extra_inputs: Vec<String>,
+
+    /// Skip the first `N` records
+    ///
+    /// Pages through a file together with `--limit`: `--skip 1000 --limit 100`
+    /// decodes records 1000..1100, seeking rather than decoding the skipped ones.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with = "span",
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub skip: Option<usize>,
+
+    /// Decode at most `N` records (after `--skip`)
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with = "span",
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub limit: Option<usize>,
+
     #[clap(flatten)]
     pub output: OutputFile,
+
+    /// Decode each input to its own file in this directory
+    ///
+    /// Outputs are named after the input without its extension plus the format
+    /// extension and any `-c` compression suffix, e.g. `out/sample1.fq.gz` for
+    /// `sample1.vbq --output-dir out -c g`. The format defaults to FASTQ.
+    #[clap(
+        long,
+        value_name = "DIR",
+        visible_alias = "output-per-input",
+        conflicts_with_all = ["output", "prefix"]
+    )]
+    pub output_dir: Option<String>,
+
+    /// Partition decoded records into one output per group
+    ///
+    /// With `flag`, records are routed by their flag value to
+    /// `<prefix>_<flag>.<ext>` (or `<prefix>_<flag>_R[12].<ext>` for paired files).
+    /// Records without a flag are written to `<prefix>_none.<ext>`. Requires `-p`.
+    #[clap(long, value_name = "KEY", requires = "prefix")]
+    pub group_by: Option<GroupBy>,
+
+    /// Emit records from the last to the first
+    ///
+    /// Blocks (VBQ/CBQ) or fixed-size record chunks (BQ) are visited
+    /// last-to-first using random access and each is decoded in reverse, so
+    /// the output is never buffered as a whole. Decoding is single-threaded
+    /// and strictly ordered; `-T` still applies to output compression.
+    #[clap(long, conflicts_with_all = ["group_by", "length_bins"])]
+    pub reverse: bool,
+
+    /// Partition decoded records into outputs by primary sequence length
+    ///
+    /// Takes increasing bin edges, e.g. `0,50,100,150`. Bins are half-open, so
+    /// records are written to `<prefix>_0-50.<ext>`, `<prefix>_50-100.<ext>`,
+    /// ..., and `<prefix>_150-inf.<ext>` for records of at least the last edge.
+    /// Pairs are binned by the length of the first mate. Requires `-p`.
+    #[clap(
+        long,
+        value_name = "EDGES",
+        value_delimiter = ',',
+        requires = "prefix",
+        conflicts_with = "group_by"
+    )]
+    pub length_bins: Vec<u64>,
+
+    /// Rotate to a new output file once this many bytes were written (e.g. `2G`)
+    ///
+    /// Outputs are named `<prefix>_0000.<ext>`, `<prefix>_0001.<ext>`, ... (as
+    /// R1/R2 pairs for paired files decoding both mates). Sizes count bytes
+    /// before compression, so compressed outputs are smaller than the limit.
+    /// Files are rotated between batches of whole records, so each may exceed
+    /// the limit by up to one batch. Requires `-p`.
+    #[clap(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_memory_size,
+        visible_alias = "output-split-size",
+        requires = "prefix",
+        conflicts_with_all = ["group_by", "length_bins", "tsv_columns"]
+    )]
+    pub split_bytes: Option<usize>,
+
+    /// Derived fields to append to each header as a FASTA/FASTQ comment
+    ///
+    /// Writes `@<id> len=.. gc=.. flag=..` (in the order given) so the ID stays
+    /// clean while tools that read comments still see the metadata. `gc` is
+    /// the G+C fraction of the sequence; `flag` is omitted for records without
+    /// a flag.
+    #[clap(
+        long,
+        value_name = "FIELDS",
+        value_delimiter = ',',
+        conflicts_with_all = ["group_by", "length_bins", "reverse"]
+    )]
+    pub comment_fields: Vec<CommentField>,
+
+    /// Columns to write for TSV output, one row per record (or pair)
+    ///
+    /// Replaces the default `header<TAB>sequence` rows with the given columns
+    /// (in order) and a header row naming them. `x*` columns hold the second
+    /// mate of paired files and are empty for single-end files, as are `squal`
+    /// and `xqual` for files without qualities and `flag` for unflagged records.
+    #[clap(
+        long,
+        value_name = "COLUMNS",
+        value_delimiter = ',',
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "comment_fields", "prefix", "n_count"]
+    )]
+    pub tsv_columns: Vec<TsvColumn>,
+
+    /// Field delimiter for TSV output [default: tab]
+    ///
+    /// For CSV-style tools, e.g. `--delimiter comma`. Sequences and qualities
+    /// never contain any of the choices and are written unquoted; headers are
+    /// written as-is.
+    #[clap(long, value_name = "CHAR")]
+    pub delimiter: Option<Delimiter>,
+
+    /// Synthesize qualities for records stored without them
+    ///
+    /// Replaces the constant `?` (Q30) placeholder in FASTQ output. `flat`
+    /// writes Q30 at every position; `linear-decline` falls linearly from Q38
+    /// at the first base to Q20 at the last, mimicking 3' quality decay.
+    /// Records with stored qualities are written unchanged.
+    #[clap(
+        long,
+        value_name = "MODEL",
+        visible_alias = "fastq-from-fasta",
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "tsv_columns"]
+    )]
+    pub synth_qual: Option<SynthQual>,
+
+    /// Reverse complement these mates before writing
+    ///
+    /// Sequences are reverse complemented and qualities reversed after
+    /// decoding, so 2-bit and 4-bit files behave the same. Selects mates by
+    /// their position in the file, so `--revcomp both -m 2` writes only R2,
+    /// reverse complemented. `r1` applies to single-end files.
+    #[clap(
+        long,
+        value_name = "MATES",
+        default_value = "none",
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "tsv_columns", "merge_overlap"]
+    )]
+    pub revcomp: RevcompMates,
+
+    #[clap(flatten)]
+    pub length: LengthFilterArgs,
+
+    /// Write each record's flag alongside its sequence
+    ///
+    /// Shorthand for `--comment-fields flag` with FASTA/FASTQ output. With TSV
+    /// output, writes header, sequence, and flag columns for the selected
+    /// mates (as `--tsv-columns` would). Records without a flag get no
+    /// comment or an empty column.
+    #[clap(
+        long,
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "tsv_columns"]
+    )]
+    pub emit_flag: bool,
+
+    /// Write each overlapping pair as a single merged record
+    ///
+    /// A preview of `merge-pairs`: R1 is aligned against the reverse
+    /// complement of R2 (see `--min-overlap` and `--max-mismatch-rate`) and
+    /// overlapping positions keep the higher quality base. The merged record
+    /// takes the R1 header. Pairs without an acceptable overlap are written as
+    /// interleaved mates. Requires a paired file.
+    #[clap(
+        long,
+        visible_alias = "merge-qual",
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "tsv_columns", "emit_flag", "n_count", "prefix", "mate"]
+    )]
+    pub merge_overlap: bool,
+
+    #[clap(flatten)]
+    pub merge: MergeArgs,
+
+    /// Check that the mates of every pair share an ID before writing them
+    ///
+    /// A safeguard against pairing errors: R1 and R2 IDs are compared ignoring
+    /// comments and a trailing `/1`/`/2`, and decoding stops at the first
+    /// mismatch with its record index. Applies when both mates are written
+    /// (interleaved or with `-p`) and skipped for records without headers.
+    #[clap(
+        long,
+        visible_alias = "interleaved-validate",
+        conflicts_with_all = ["group_by", "length_bins", "reverse", "tsv_columns", "merge_overlap"]
+    )]
+    pub verify_pairing: bool,
+
+    /// Write the 2-bit packed sequences as stored instead of ASCII bases
+    ///
+    /// Each sequence is copied as whole little-endian `u64` words (32 bases per
+    /// word, first base in the low bits, A=0 C=1 G=2 T=3) with no unpacking.
+    /// Sequence lengths, needed to find record boundaries, go to a TSV sidecar
+    /// at `<output>.len`. `-m` selects the mates written. Requires `-o` and a
+    /// 2-bit encoded file.
+    #[clap(
+        long,
+        visible_alias = "raw-2bit",
+        conflicts_with_all = [
+            "group_by",
+            "length_bins",
+            "reverse",
+            "comment_fields",
+            "tsv_columns",
+            "emit_flag",
+            "merge_overlap",
+            "split_bytes",
+            "prefix",
+            "paired_only",
+            "singletons_only",
+            "n_count",
+            "flag_where",
+            "on_orphan",
+            "verify_pairing",
+            "synth_qual",
+            "revcomp",
+        ]
+    )]
+    pub two_bit_raw: bool,
+
+    /// Decode only a random fraction of the records
+    ///
+    /// Inclusion is decided per record index, so the same records are selected
+    /// for a given seed regardless of the number of threads.
+    #[clap(
+        short = 'F',
+        long,
+        value_parser = parse_fraction,
+        conflicts_with_all = ["group_by", "length_bins"],
+        help_heading = "SUBSAMPLE OPTIONS"
+    )]
+    pub fraction: Option<f64>,
+
+    /// Seed to use for `--fraction` subsampling
+    #[clap(
+        short = 'S',
+        long,
+        default_value = "42",
+        requires = "fraction",
+        help_heading = "SUBSAMPLE OPTIONS"
+    )]
+    pub seed: u64,
+
+    /// Append an `n_count` column with the number of `N` bases in each sequence
+    ///
+    /// Only supported for TSV output (`-f t`).
+    #[clap(long, conflicts_with_all = ["group_by", "length_bins", "reverse", "emit_flag"])]
+    pub n_count: bool,
+    /// Only decode complete pairs, dropping singletons
+    ///
+    /// A record is a singleton when the file is single-end or when its flag
+    /// has any of the `--singleton-mask` bits set (e.g. a pair whose mate was
+    /// removed upstream and stored with a placeholder).
+    #[clap(
+        long,
+        conflicts_with_all = ["singletons_only", "group_by", "length_bins", "reverse"],
+        help_heading = "MATE FILTER OPTIONS"
+    )]
+    pub paired_only: bool,
+
+    /// Only decode singletons, dropping complete pairs (see `--paired-only`)
+    #[clap(
+        long,
+        conflicts_with_all = ["group_by", "length_bins", "reverse"],
+        help_heading = "MATE FILTER OPTIONS"
+    )]
+    pub singletons_only: bool,
+
+    /// Flag bits marking a record as a singleton (decimal or 0x-hex)
+    #[clap(
+        long,
+        value_name = "MASK",
+        default_value = "0x1",
+        help_heading = "MATE FILTER OPTIONS"
+    )]
+    pub singleton_mask: FlagMask,
+
+    /// What to do with paired records whose second mate is empty
+    ///
+    /// Malformed paired files may hold records with no extended sequence, which
+    /// are otherwise written with an empty R2. `skip` drops such records, `pad`
+    /// writes the missing mate as a single `N` (quality `!`) under the R1
+    /// header if it has none, and `error` stops at the first one, reporting its
+    /// record index.
+    #[clap(
+        long,
+        value_name = "ACTION",
+        visible_alias = "orphan-handling",
+        conflicts_with_all = ["group_by", "length_bins", "reverse"],
+        help_heading = "MATE FILTER OPTIONS"
+    )]
+    pub on_orphan: Option<OnOrphan>,
+
+    /// Only decode records whose flag satisfies this expression
+    ///
+    /// e.g. `'(flag & 0x3) == 0x1'` or `'flag & 0x4 && !(flag & 0x100)'`. Supports
+    /// `flag`, decimal or 0x-hex numbers, `&`, `|`, `^`, `==`, `!=`, `!`, `&&`,
+    /// `||`, and parentheses; bit operators bind tighter than comparisons.
+    /// Records without a flag are evaluated with a flag of 0.
+    #[clap(
+        long,
+        value_name = "EXPR",
+        conflicts_with_all = ["group_by", "length_bins", "reverse"],
+        help_heading = "FLAG FILTER OPTIONS"
+    )]
+    pub flag_where: Option<FlagExpr>,
+}
+impl DecodeCommand {
+    /// Records to decode: `--span`, or the range given by `--skip`/`--limit`
+    pub fn span(&self) -> Option<Span> {
+        if self.skip.is_none() && self.limit.is_none() {
+            return self.input.span;
+        }
+        let span = Span::default().skip(self.skip.unwrap_or(0));
+        Some(self.limit.map_or(span, |n| span.first(n)))
+    }
+
+    /// Byte separating TSV fields: `--delimiter`, or a tab
+    pub fn delimiter_byte(&self) -> u8 {
+        self.delimiter.unwrap_or(Delimiter::Tab).byte()
+    }
+}
+
+fn parse_fraction(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(f),
+        Ok(_) => Err("Fraction must be between 0 and 1".to_string()),
+        Err(_) => Err(format!("Invalid fraction: {input}")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CommentField {
+    /// Sequence length
+    Len,
+    /// G+C fraction of the sequence
+    Gc,
+    /// Record flag
+    Flag,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum TsvColumn {
+    /// Record index in the file
+    Id,
+    /// Primary header
+    Sheader,
+    /// Primary sequence
+    #[value(alias = "seq")]
+    Sseq,
+    /// Primary quality scores
+    #[value(alias = "qual")]
+    Squal,
+    /// Extended (R2) header
+    Xheader,
+    /// Extended (R2) sequence
+    Xseq,
+    /// Extended (R2) quality scores
+    Xqual,
+    /// Record flag
+    Flag,
+}
+impl TsvColumn {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Sheader => "sheader",
+            Self::Sseq => "sseq",
+            Self::Squal => "squal",
+            Self::Xheader => "xheader",
+            Self::Xseq => "xseq",
+            Self::Xqual => "xqual",
+            Self::Flag => "flag",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Delimiter {
+    /// Tab (`\t`)
+    #[value(alias = "\t", alias = "\\t")]
+    Tab,
+    /// Comma (`,`)
+    #[value(alias = ",")]
+    Comma,
+    /// Semicolon (`;`)
+    #[value(alias = ";")]
+    Semicolon,
+    /// Pipe (`|`)
+    #[value(alias = "|")]
+    Pipe,
+    /// Space (` `)
+    #[value(alias = " ")]
+    Space,
+}
+impl Delimiter {
+    pub fn byte(self) -> u8 {
+        match self {
+            Self::Tab => b'\t',
+            Self::Comma => b',',
+            Self::Semicolon => b';',
+            Self::Pipe => b'|',
+            Self::Space => b' ',
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum RevcompMates {
+    /// Write both mates as stored
+    #[default]
+    None,
+    /// Reverse complement the primary mate
+    R1,
+    /// Reverse complement the extended mate
+    R2,
+    /// Reverse complement both mates
+    Both,
+}
+impl RevcompMates {
+    pub fn primary(self) -> bool {
+        matches!(self, Self::R1 | Self::Both)
+    }
+
+    pub fn extended(self) -> bool {
+        matches!(self, Self::R2 | Self::Both)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SynthQual {
+    /// Q30 at every position
+    Flat,
+    /// Q38 at the first base declining linearly to Q20 at the last
+    LinearDecline,
+}
+impl SynthQual {
+    /// Fills `qual` with `len` Phred+33 scores following the model
+    pub fn fill(self, qual: &mut Vec<u8>, len: usize) {
+        const FLAT: u8 = 30;
+        const FIRST: u8 = 38;
+        const LAST: u8 = 20;
+        qual.clear();
+        match self {
+            Self::Flat => qual.resize(len, b'!' + FLAT),
+            Self::LinearDecline => {
+                let span = len.saturating_sub(1).max(1);
+                let drop = usize::from(FIRST - LAST);
+                qual.extend((0..len).map(|i| {
+                    #[allow(clippy::cast_possible_truncation)]
+                    let decline = (i * drop / span) as u8;
+                    b'!' + FIRST - decline
+                }));
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnOrphan {
+    /// Drop the record
+    Skip,
+    /// Write a single-`N` placeholder mate
+    Pad,
+    /// Fail with the record index
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// Group records by their flag value
+    Flag,
 }
@@ -0,0 +1,18 @@
+use clap::Parser;
+
+/// Diagnose the build, environment, and (optionally) input files.
+///
+/// Reports the compiled features and linked library versions, checks runtime
+/// settings such as the temporary directory, and gives guidance for missing
+/// capabilities (e.g. a build without `htslib`). Any files given are opened
+/// and their format identified. Exits with an error if a check fails.
+#[derive(Parser, Debug)]
+pub struct DoctorCommand {
+    /// Files to probe (BINSEQ, FASTX, or SAM/BAM/CRAM)
+    #[clap(value_name = "FILE")]
+    pub inputs: Vec<String>,
+
+    /// Print the diagnostics in JSON format
+    #[clap(short, long)]
+    pub json: bool,
+}
@@ -3,7 +3,10 @@ use log::{error, trace};
 
 use crate::commands::encode::utils::generate_output_name;
 
-use super::{BinseqMode, InputFile, OutputBinseq};
+use super::{
+    AsciiHeaders, BinseqConfig, BinseqMode, InputFile, NameFrom, OutputBinseq, PolyTrim,
+    ProgressArgs,
+};
 
 #[derive(clap::Parser, Debug, Clone)]
 /// Encode FASTQ or FASTA files to BINSEQ.
@@ -13,6 +16,120 @@ pub struct EncodeCommand {
 
     #[clap(flatten)]
     pub output: OutputBinseq,
+
+    /// Uppercase input sequences and map IUPAC ambiguity codes to `N`
+    ///
+    /// Lets soft-masked (lowercase) or IUPAC-coded (e.g. `R`, `Y`) inputs encode
+    /// cleanly, with ambiguity codes resolved by the N-policy (`-p`).
+    #[clap(long, help_heading = "INPUT FILE OPTIONS")]
+    pub canonicalize: bool,
+
+    /// Require record headers to be ASCII before they are stored
+    ///
+    /// `strict` fails on the first header with a non-ASCII byte; `sanitize`
+    /// replaces each non-ASCII byte with `_`. Protects downstream tools with
+    /// strict header parsers.
+    #[clap(long, value_name = "MODE", help_heading = "INPUT FILE OPTIONS")]
+    pub ascii_headers: Option<AsciiHeaders>,
+
+    /// What to store as each record's header
+    ///
+    /// `id` keeps the input record name, `none` stores empty headers, and
+    /// `index` stores the record's 0-based position in the input (records
+    /// dropped during encoding leave gaps). `index` encodes on a single thread
+    /// so the same input always yields the same headers. Has no effect when
+    /// headers are not stored (`-H`).
+    #[clap(
+        long,
+        value_name = "SOURCE",
+        default_value = "id",
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub name_from: NameFrom,
+
+    /// Trim 3' poly-G runs of at least `N` bases before encoding
+    ///
+    /// Removes the G tails produced by dark cycles on two-color chemistries
+    /// (NovaSeq/NextSeq). Qualities are trimmed with the sequence. For BQ the
+    /// fixed record length is taken from the trimmed first record, so records
+    /// trimmed to a different length are rejected like any other length mismatch.
+    #[clap(long, value_name = "N", help_heading = "INPUT FILE OPTIONS")]
+    pub trim_polyg: Option<usize>,
+
+    /// Trim 3' poly-A runs of at least `N` bases before encoding
+    ///
+    /// Applied after `--trim-polyg`, so a poly-A tail followed by poly-G is
+    /// removed as a whole. Qualities are trimmed with the sequence.
+    #[clap(long, value_name = "N", help_heading = "INPUT FILE OPTIONS")]
+    pub trim_polya: Option<usize>,
+
+    /// Cap stored quality scores at Phred `Q` (e.g. `41`)
+    ///
+    /// Normalizes implausibly high or spiking qualities for downstream tools
+    /// that assume a maximum score, and can improve quality compression. Has
+    /// no effect when qualities are not stored (FASTA input or `--skip-quality`).
+    #[clap(
+        long,
+        value_name = "Q",
+        value_parser = clap::value_parser!(u8).range(0..=93),
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub max_quality: Option<u8>,
+
+    /// Skip reads whose sequence complexity is below `F` (0-1)
+    ///
+    /// Complexity is the Shannon entropy of the read's 3-mers, normalized to
+    /// 0 for a homopolymer and about 1 for random sequence; dinucleotide
+    /// repeats score around 0.2. Applied after trimming. Pairs are skipped
+    /// when either mate falls below the threshold. Skipped reads are counted
+    /// separately from those dropped by the N-policy.
+    #[clap(
+        long,
+        value_name = "F",
+        value_parser = parse_complexity,
+        help_heading = "INPUT FILE OPTIONS"
+    )]
+    pub min_complexity: Option<f64>,
+
+    /// Sort each batch of reads by sequence prefix before encoding (experimental)
+    ///
+    /// Each thread sorts the batch of records it parsed by the first bases of
+    /// the primary sequence before pushing them to the writer, so similar reads
+    /// tend to land next to each other for zstd. Sorting is per parsing batch,
+    /// not per VBQ/CBQ block: a block can hold several batches and a batch can
+    /// fill several blocks. The compression gain is not measured; compare the
+    /// output size against an unsorted encode. Records are independent, so
+    /// decoding is unaffected, but record indices no longer follow input order.
+    #[clap(long)]
+    pub sort_batch: bool,
+
+    /// Write the block index of the VBQ/CBQ output to a standalone TSV file
+    ///
+    /// One row per block with its byte offset, compressed length, record count,
+    /// and cumulative record count. Only applies to single-output encodes.
+    #[clap(long, value_name = "PATH", visible_alias = "output-index")]
+    pub emit_index: Option<String>,
+
+    /// Write a TSV mapping each record index to its original header
+    ///
+    /// Rows are `index<TAB>header` (`index<TAB>sheader<TAB>xheader` for paired
+    /// input) for every record written, so names can be rejoined later when
+    /// headers are not stored in the file. Indices are positions in the output,
+    /// which only follow input order with `-T 1`. Only applies to single-output encodes.
+    #[clap(long, value_name = "PATH", visible_alias = "header-index-map")]
+    pub index_map: Option<String>,
+
+    /// Fail if fewer than `N` records were written
+    ///
+    /// Catches truncated inputs or records dropped by the N-policy (`-p i`).
+    /// When batch encoding, the check applies to each output: a failing output
+    /// is removed like any other failed encode, the remaining entries are still
+    /// encoded, and the run fails once the batch is done.
+    #[clap(long, value_name = "N")]
+    pub min_records: Option<usize>,
+
+    #[clap(flatten)]
+    pub progress: ProgressArgs,
 }
 impl EncodeCommand {
     pub fn mode(&self) -> Result<BinseqMode> {
@@ -24,10 +141,27 @@ impl EncodeCommand {
             self.output.mode()
         }
     }
+    /// Writer configuration for this command, including encode-only settings
+    pub fn config(&self) -> BinseqConfig {
+        let mut config: BinseqConfig = self.output.options.into();
+        config.canonicalize = self.canonicalize;
+        config.ascii_headers = self.ascii_headers;
+        config.poly_trim = PolyTrim {
+            polya: self.trim_polya,
+            polyg: self.trim_polyg,
+        };
+        config.max_quality = self.max_quality;
+        config.sort_batch = self.sort_batch;
+        config.min_complexity = self.min_complexity;
+        config.name_from = self.name_from;
+        config.strict_pairing = self.input.strict_pairing;
+        config.check_interleaving = self.input.check_interleaving;
+        config
+    }
     pub fn output_path(&self) -> Result<Option<String>> {
-        if let Some(path) = &self.output.output {
-            Ok(Some(path.clone()))
-        } else if self.output.pipe {
+        if let Some(path) = self.output.output_path() {
+            Ok(Some(path.to_string()))
+        } else if self.output.pipe || self.output.output.is_some() {
             Ok(None)
         } else if self.input.is_stdin() {
             error!("Output path must be provided if using stdin");
@@ -48,3 +182,11 @@ impl EncodeCommand {
         }
     }
 }
+
+fn parse_complexity(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(f) if (0.0..=1.0).contains(&f) => Ok(f),
+        Ok(_) => Err("Complexity must be between 0 and 1".to_string()),
+        Err(_) => Err(format!("Invalid complexity: {input}")),
+    }
+}
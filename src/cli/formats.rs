@@ -14,6 +14,9 @@ pub enum FileFormat {
     /// TSV file format (decode only)
     #[clap(name = "t")]
     Tsv,
+    /// JSON lines, one `{"id","seq","qual"}` object per record (decode only)
+    #[clap(name = "j")]
+    Jsonl,
 }
 impl FileFormat {
     pub fn from_path(path: &str) -> Option<Self> {
@@ -27,6 +30,7 @@ impl FileFormat {
             "fastq" | "fq" => Some(Self::Fastq),
             "sam" | "bam" | "cram" => Some(Self::Bam),
             "tsv" | "txt" => Some(Self::Tsv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
             _ => None,
         }
     }
@@ -36,6 +40,7 @@ impl FileFormat {
             Self::Fasta => "fa",
             Self::Fastq => "fq",
             Self::Tsv => "tsv",
+            Self::Jsonl => "jsonl",
             Self::Bam => "bam",
         }
     }
@@ -90,6 +95,18 @@ mod tests {
         assert_eq!(FileFormat::from_path("reads.txt"), Some(FileFormat::Tsv));
     }
 
+    #[test]
+    fn from_path_jsonl() {
+        assert_eq!(
+            FileFormat::from_path("reads.jsonl"),
+            Some(FileFormat::Jsonl)
+        );
+        assert_eq!(
+            FileFormat::from_path("reads.ndjson.gz"),
+            Some(FileFormat::Jsonl)
+        );
+    }
+
     #[test]
     fn from_path_unknown_extension() {
         assert_eq!(FileFormat::from_path("reads.bin"), None);
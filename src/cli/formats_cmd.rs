@@ -0,0 +1,14 @@
+use clap::Parser;
+
+/// List the formats, BINSEQ modes, compression types, and optional features
+/// supported by this build.
+///
+/// Support for some inputs depends on compile-time features (e.g. `htslib`
+/// for SAM/BAM/CRAM, `gcs` for `gs://` paths), so the output reflects the
+/// capabilities of the running binary.
+#[derive(Parser, Debug)]
+pub struct FormatsCommand {
+    /// Print the capabilities in JSON format
+    #[clap(short, long)]
+    pub json: bool,
+}
@@ -10,10 +10,13 @@ use paraseq::{fasta, Record};
 
 use crate::{
     cli::FileFormat,
-    commands::grep::{Pattern, PatternCollection, SimpleRange},
+    commands::{
+        grep::{FlagFilter, FlagMask, Pattern, PatternCollection, SimpleRange},
+        is_stdio,
+    },
 };
 
-use super::{InputBinseq, OutputFile};
+use super::{InputBinseq, LengthFilterArgs, OutputFile, ProgressArgs};
 
 /// Grep a BINSEQ file and output to FASTQ or FASTA.
 #[derive(Parser, Debug)]
@@ -26,23 +29,40 @@ pub struct GrepCommand {
 
     #[clap(flatten)]
     pub grep: GrepArgs,
+
+    #[clap(flatten)]
+    pub length: LengthFilterArgs,
+
+    #[clap(flatten)]
+    pub progress: ProgressArgs,
 }
 impl GrepCommand {
     pub fn should_color(&self) -> bool {
-        if self.grep.header {
+        if self.grep.header || self.should_report_coordinates() {
             // Match positions refer to header text, not the sequence buffer
             // that colorized output highlights.
             return false;
         }
         match self.output.format() {
-            Ok(FileFormat::Bam) => false,
+            Ok(FileFormat::Bam | FileFormat::Jsonl) => false,
             _ => {
-                self.output.output.is_none()
+                self.output.output_path().is_none()
                     && self.output.prefix.is_none()
                     && self.grep.color.should_color()
             }
         }
     }
+
+    /// Whether match positions are written as a TSV column (`--color-safe`).
+    ///
+    /// Coordinate rows are written to a single stream, so split mate outputs
+    /// (`--prefix`) fall back to plain TSV.
+    pub fn should_report_coordinates(&self) -> bool {
+        self.grep.color_safe
+            && !self.grep.header
+            && self.output.prefix.is_none()
+            && matches!(self.output.format(), Ok(FileFormat::Tsv))
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -101,6 +121,26 @@ pub struct GrepArgs {
     #[clap(short = 'P', long, conflicts_with = "count")]
     pub pattern_count: bool,
 
+    /// Print a histogram of match start positions instead of records
+    ///
+    /// Outputs a `position\tcount` TSV counting, across all matching records,
+    /// how often a pattern match starts at each sequence offset (both mates
+    /// combined). Each distinct match interval in a record is counted once.
+    #[clap(long, conflicts_with_all = ["count", "frac", "pattern_count", "invert", "header"])]
+    pub position_matrix: bool,
+
+    /// Write every match interval as a TSV row instead of the matching records
+    ///
+    /// Columns are `record_index`, `mate` (1 or 2), `start`, `end`
+    /// (end-exclusive), and `match` (the matched bases). Coordinates refer to
+    /// the full sequence even with `--range`, and fuzzy matches report the
+    /// interval actually aligned by `sassy`.
+    #[clap(
+        long,
+        conflicts_with_all = ["count", "frac", "pattern_count", "position_matrix", "invert", "header", "prefix"]
+    )]
+    pub positions: bool,
+
     /// Denotes patterns are fixed strings (non-regex)
     ///
     /// Allows usage of Aho-Corasick algorithm for efficient matching.
@@ -135,6 +175,49 @@ pub struct GrepArgs {
     )]
     color: ColorWhen,
 
+    /// Report match positions in an extra TSV column instead of inline color
+    ///
+    /// Each TSV row gains a trailing column of merged, end-exclusive match
+    /// intervals (`start-end,...`), keeping the output machine-parseable.
+    /// Only applies to TSV output (`-f t`) written to a single stream, not to
+    /// split mate outputs (`--prefix`).
+    #[clap(long)]
+    pub color_safe: bool,
+
+    /// Only keep records whose flag has all of these bits set (decimal or 0x-hex)
+    ///
+    /// Combined with any pattern logic. Can be used without a pattern.
+    /// Records without a stored flag are treated as having no bits set.
+    #[clap(long, value_name = "MASK")]
+    pub flag_require: Option<FlagMask>,
+
+    /// Drop records whose flag has any of these bits set (decimal or 0x-hex)
+    ///
+    /// Combined with any pattern logic. Can be used without a pattern.
+    #[clap(long, value_name = "MASK")]
+    pub flag_exclude: Option<FlagMask>,
+
+    /// Write the indices of matching records to a file, one per line
+    ///
+    /// Indices are 0-based positions in the input file, sorted ascending
+    /// regardless of thread scheduling, so a search can be reproduced or the
+    /// records re-extracted later. Written alongside the matching records
+    /// unless `--index-only` is set.
+    #[clap(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["pattern_count", "position_matrix"]
+    )]
+    pub index_out: Option<String>,
+
+    /// Only write `--index-out`, not the matching records
+    #[clap(
+        long,
+        requires = "index_out",
+        conflicts_with_all = ["count", "frac", "positions"]
+    )]
+    pub index_only: bool,
+
     #[cfg(feature = "fuzzy")]
     #[clap(flatten)]
     pub fuzzy_args: FuzzyArgs,
@@ -145,14 +228,41 @@ pub struct GrepArgs {
 
 impl GrepArgs {
     pub fn validate(&self) -> Result<()> {
-        if self.reg1.is_empty()
+        if !self.has_patterns() {
+            if self.flag_filter().is_none() {
+                anyhow::bail!("At least one pattern (or a flag mask) must be specified");
+            }
+            if self.pattern_count {
+                anyhow::bail!("Pattern counting (-P) requires at least one pattern");
+            }
+            if self.invert {
+                anyhow::bail!(
+                    "Inverting (-v) requires at least one pattern - use `--flag-exclude` to negate flag bits"
+                );
+            }
+            if self.position_matrix {
+                anyhow::bail!("`--position-matrix` requires at least one pattern");
+            }
+            if self.positions {
+                anyhow::bail!("`--positions` requires at least one pattern");
+            }
+        }
+        Ok(())
+    }
+    pub fn has_patterns(&self) -> bool {
+        !(self.reg1.is_empty()
             && self.reg2.is_empty()
             && self.reg.is_empty()
-            && self.file_args.empty()
-        {
-            anyhow::bail!("At least one pattern must be specified");
+            && self.file_args.empty())
+    }
+    pub fn flag_filter(&self) -> Option<FlagFilter> {
+        if self.flag_require.is_none() && self.flag_exclude.is_none() {
+            return None;
         }
-        Ok(())
+        Some(FlagFilter {
+            require: self.flag_require.unwrap_or_default().0,
+            exclude: self.flag_exclude.unwrap_or_default().0,
+        })
     }
     pub fn and_logic(&self) -> bool {
         if self.file_args.empty() {
@@ -177,6 +287,7 @@ impl GrepArgs {
         if self.file_args.empty_file(filetype) {
             Ok(PatternCollection(cli_iter.collect()))
         } else {
+            self.file_args.check_stdin()?;
             let file_patterns = self.file_args.patterns(filetype)?;
             Ok(PatternCollection(cli_iter.chain(file_patterns).collect()))
         }
@@ -210,6 +321,17 @@ pub struct FuzzyArgs {
     #[clap(short = 'k', long, default_value = "1")]
     pub distance: usize,
 
+    /// Distance metric used to accept fuzzy matches
+    ///
+    /// `levenshtein` allows substitutions, insertions, and deletions.
+    /// `hamming` only allows substitutions, which avoids indel-shifted hits
+    /// when matching fixed-length barcodes. Hamming matches are found by
+    /// discarding indel alignments from the edit-distance search, so a
+    /// substitution-only hit can be missed where a cheaper indel alignment
+    /// ends at the same position.
+    #[clap(long, value_name = "TYPE", default_value = "levenshtein")]
+    pub distance_type: DistanceType,
+
     /// Only return inexact matches on fuzzy matching
     ///
     /// This will capture matches that are not exact, but are within the specified edit distance.
@@ -227,6 +349,16 @@ pub struct FuzzyArgs {
     pub max_n_frac: Option<f32>,
 }
 
+#[cfg(feature = "fuzzy")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DistanceType {
+    /// Edit distance (substitutions, insertions, deletions)
+    #[default]
+    Levenshtein,
+    /// Substitutions only
+    Hamming,
+}
+
 #[cfg(feature = "fuzzy")]
 fn parse_max_n_frac(input: &str) -> Result<f32, String> {
     let value: f32 = input
@@ -278,6 +410,7 @@ pub struct PatternFileArgs {
     /// (sequences are used as patterns), or TSV (alias / pattern).
     /// FASTA files and TSVs are auto-detected.
     /// Patterns may be regex or literal (fuzzy doesn't support regex).
+    /// Use `-` to read patterns from stdin.
     #[clap(long)]
     pub file: Option<String>,
 
@@ -287,6 +420,7 @@ pub struct PatternFileArgs {
     /// (sequences are used as patterns), or TSV (alias / pattern).
     /// FASTA files and TSVs are auto-detected.
     /// Patterns may be regex or literal (fuzzy doesn't support regex).
+    /// Use `-` to read patterns from stdin.
     #[clap(long)]
     pub sfile: Option<String>,
 
@@ -296,6 +430,7 @@ pub struct PatternFileArgs {
     /// (sequences are used as patterns), or TSV (alias / pattern).
     /// FASTA files and TSVs are auto-detected.
     /// Patterns may be regex or literal (fuzzy doesn't support regex).
+    /// Use `-` to read patterns from stdin.
     #[clap(long)]
     pub xfile: Option<String>,
 }
@@ -323,25 +458,48 @@ impl PatternFileArgs {
             .ok_or_else(|| anyhow::anyhow!("Specified file type {filetype:?} not provided at CLI"))
     }
 
-    /// Returns true if the file starts with '>' (FASTA format).
-    fn is_fasta(path: &str) -> Result<bool> {
-        let file = fs::File::open(path)?;
-        // only take up to 10 bytes to determine fasta status
-        for byte in io::BufReader::new(file).bytes().take(10) {
-            let b = byte?;
-            if b != b'\n' && b != b'\r' {
-                return Ok(b == b'>');
-            }
+    /// Stdin can only be read once, so at most one pattern file may be `-`.
+    fn check_stdin(&self) -> Result<()> {
+        let from_stdin = [&self.file, &self.sfile, &self.xfile]
+            .into_iter()
+            .flatten()
+            .filter(|path| is_stdio(path))
+            .count();
+        if from_stdin > 1 {
+            anyhow::bail!(
+                "Only one of `--file`, `--sfile`, and `--xfile` can read from stdin (`-`)"
+            );
         }
-        Ok(false)
+        Ok(())
     }
 
-    /// Returns true if the file is a two-column TSV (tab-separated values)
-    fn is_tsv(path: &str) -> Result<bool> {
+    /// Reads a pattern file into memory, or stdin when `path` is `-`.
+    fn read_source(path: &str) -> Result<Vec<u8>> {
+        if is_stdio(path) {
+            let mut contents = Vec::new();
+            io::stdin().lock().read_to_end(&mut contents)?;
+            Ok(contents)
+        } else {
+            Ok(fs::read(path)?)
+        }
+    }
+
+    /// Returns true if the contents start with '>' (FASTA format).
+    fn is_fasta(contents: &[u8]) -> bool {
+        // only take up to 10 bytes to determine fasta status
+        contents
+            .iter()
+            .take(10)
+            .find(|&&b| b != b'\n' && b != b'\r')
+            .is_some_and(|&b| b == b'>')
+    }
+
+    /// Returns true if the contents are a two-column TSV (tab-separated values)
+    fn is_tsv(contents: &[u8]) -> Result<bool> {
         let mut reader = csv::ReaderBuilder::new()
             .delimiter(b'\t')
             .has_headers(false)
-            .from_path(path)?;
+            .from_reader(contents);
         for res in reader.records().take(10) {
             let record = res?;
             if record.len() != 2 {
@@ -351,11 +509,17 @@ impl PatternFileArgs {
         Ok(true)
     }
 
-    /// Load patterns from a file, auto-detecting FASTA vs plain text.
+    /// Load patterns from a file (or stdin for `-`), auto-detecting FASTA vs plain text.
     fn load_patterns(path: &str) -> Result<Vec<Pattern>> {
-        if Self::is_fasta(path)? {
-            trace!("Loading patterns from fasta: {path}");
-            let mut reader = fasta::Reader::from_path(path)?;
+        let contents = Self::read_source(path)?;
+        Self::parse_patterns(&contents, path)
+    }
+
+    /// Parse patterns read from `source`, auto-detecting FASTA vs plain text.
+    fn parse_patterns(contents: &[u8], source: &str) -> Result<Vec<Pattern>> {
+        if Self::is_fasta(contents) {
+            trace!("Loading patterns from fasta: {source}");
+            let mut reader = fasta::Reader::new(contents);
             let mut rset = fasta::RecordSet::default();
             let mut patterns = Vec::new();
 
@@ -370,12 +534,12 @@ impl PatternFileArgs {
             }
 
             Ok(patterns)
-        } else if Self::is_tsv(path)? {
-            trace!("Loading alias+patterns from tsv: {path}");
+        } else if Self::is_tsv(contents)? {
+            trace!("Loading alias+patterns from tsv: {source}");
             let mut reader = csv::ReaderBuilder::new()
                 .delimiter(b'\t')
                 .has_headers(false)
-                .from_path(path)?;
+                .from_reader(contents);
             let mut patterns = Vec::new();
             for result in reader.records() {
                 let record = result?;
@@ -389,8 +553,8 @@ impl PatternFileArgs {
             }
             Ok(patterns)
         } else {
-            trace!("Loading patterns from txt: {path}");
-            let contents = std::fs::read_to_string(path)?;
+            trace!("Loading patterns from txt: {source}");
+            let contents = std::str::from_utf8(contents)?;
             Ok(contents
                 .lines()
                 .map(|line| Pattern {
@@ -409,6 +573,7 @@ impl PatternFileArgs {
     pub fn load_all_patterns(
         &self,
     ) -> Result<(PatternCollection, PatternCollection, PatternCollection)> {
+        self.check_stdin()?;
         let pat1 = if let Some(ref path) = self.sfile {
             Self::load_patterns(path)?
         } else {
@@ -461,3 +626,27 @@ impl ColorWhen {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PatternFileArgs;
+
+    /// Pattern contents are sniffed the same way whether they come from a file or stdin.
+    #[test]
+    fn test_parse_patterns_formats() -> anyhow::Result<()> {
+        let txt = PatternFileArgs::parse_patterns(b"ACGT\nTTAA\n", "-")?;
+        assert_eq!(txt.len(), 2);
+        assert!(txt[0].name.is_none());
+        assert_eq!(txt[1].sequence, b"TTAA");
+
+        let fasta = PatternFileArgs::parse_patterns(b"\n>first desc\nACGT\n>second\nGG\n", "-")?;
+        assert_eq!(fasta.len(), 2);
+        assert_eq!(fasta[0].name.as_deref(), Some("first"));
+        assert_eq!(fasta[1].sequence, b"GG");
+
+        let tsv = PatternFileArgs::parse_patterns(b"alias\tACGT\n", "-")?;
+        assert_eq!(tsv[0].name.as_deref(), Some("alias"));
+        assert_eq!(tsv[0].sequence, b"ACGT");
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use clap::Parser;
+
+use super::{InputBinseq, OutputFile};
+
+/// Decode the first records of a BINSEQ file to FASTQ, FASTA, or TSV.
+#[derive(Parser, Debug)]
+pub struct HeadCommand {
+    #[clap(flatten)]
+    pub input: InputBinseq,
+
+    #[clap(flatten)]
+    pub output: OutputFile,
+
+    /// Number of records (pairs for paired files) to decode
+    ///
+    /// Only the blocks holding these records are read, so this is fast on
+    /// large files. With `--span`, counts from the start of the span.
+    #[clap(short = 'n', long, default_value = "10", help_heading = "HEAD OPTIONS")]
+    pub num: usize,
+}
@@ -1,4 +1,6 @@
-use clap::Parser;
+use clap::{ArgGroup, Parser};
+
+use crate::commands::info::RecordQuery;
 
 #[derive(Parser, Debug)]
 /// Show information about a BINSEQ file.
@@ -12,21 +14,115 @@ pub struct InfoCommand {
 
 #[derive(Parser, Debug)]
 #[clap(next_help_heading = "INFO OPTIONS")]
+#[clap(group(
+    ArgGroup::new("mode")
+        .multiple(false)
+        .args([
+            "num", "json", "show_index", "show_headers", "block_stats", "ns", "assert_equal",
+            "weighted", "gc_summary", "distinct", "estimate", "where_expr",
+        ])
+))]
 #[allow(clippy::struct_excessive_bools)]
 pub struct InfoOpts {
     /// Only print the number of records in the file
-    #[clap(short, long, conflicts_with_all=["json", "show_index", "show_headers"])]
+    #[clap(short, long)]
     pub num: bool,
 
     /// Print the file in JSON format
-    #[clap(short, long, conflicts_with_all=["show_index", "show_headers", "num"])]
+    #[clap(short, long)]
     pub json: bool,
 
     /// Print the index of the file
-    #[clap(long, conflicts_with_all=["json", "show_headers", "num"])]
+    #[clap(long)]
     pub show_index: bool,
 
     /// Print the block headers of the file
-    #[clap(long, conflicts_with_all=["json", "show_index", "num"])]
+    #[clap(long)]
     pub show_headers: bool,
+
+    /// Print the distribution of compressed block sizes and records per block (VBQ/CBQ)
+    ///
+    /// Writes TSV rows of min/median/mean/p95/max read from the block index,
+    /// without decoding any records.
+    #[clap(long)]
+    pub block_stats: bool,
+
+    /// Count records containing an N and the total number of Ns
+    ///
+    /// Writes TSV rows of `path`, `num_records`, `records_with_n`, and `num_n`.
+    /// 4-bit BQ/VBQ files are scanned in their packed form without decoding;
+    /// 2-bit files cannot store N and are reported as such.
+    #[clap(long, visible_alias = "count-ns")]
+    pub ns: bool,
+
+    /// Check that all inputs hold the same number of records
+    ///
+    /// Prints each input's record count (as `-n` does) and exits with an error
+    /// if they differ. Inputs may mix BINSEQ and FASTX files; FASTX files are
+    /// read through to count their records, and paired BINSEQ files count
+    /// pairs (so compare against the R1 file).
+    #[clap(long)]
+    pub assert_equal: bool,
+
+    /// Sum the multiplicity stored in each record's flag instead of counting records
+    ///
+    /// For files whose flags carry duplicate counts (e.g. after deduplication),
+    /// reports the number of reads they represent. Writes TSV rows of `path`,
+    /// `num_records`, and `weighted_count`; unflagged records count once.
+    #[clap(long)]
+    pub weighted: bool,
+
+    /// Summarize the distribution of per-record GC content
+    ///
+    /// Writes TSV rows of `path`, `num_records`, `mean_gc`, and `stdev_gc`,
+    /// computed in one parallel pass. GC is the fraction of G/C bases over
+    /// both mates of paired records; records without bases are skipped.
+    #[clap(long)]
+    pub gc_summary: bool,
+
+    /// Also print a histogram of per-record GC in this many equal-width bins
+    ///
+    /// Follows the summary (after a blank line) as TSV rows of `path`,
+    /// `gc_from`, `gc_to`, and `count`; the last bin includes a GC of 1.
+    #[clap(
+        long,
+        value_name = "N",
+        requires = "gc_summary",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub gc_bins: Option<usize>,
+
+    /// Count distinct primary sequences and the library complexity
+    ///
+    /// Writes TSV rows of `path`, `num_records`, `distinct`, and `complexity`
+    /// (distinct / records), computed in one parallel pass. The distinct count
+    /// is a `HyperLogLog` estimate (about 1% error) unless `--exact` is set.
+    #[clap(long)]
+    pub distinct: bool,
+
+    /// Count distinct sequences exactly by keeping every one in memory
+    #[clap(long, requires = "distinct")]
+    pub exact: bool,
+
+    /// Report record counts from the block index without decoding (VBQ/CBQ)
+    ///
+    /// Writes TSV rows of `path`, `num_blocks`, `num_records`, and `bytes`.
+    /// VBQ/CBQ counts sum the per-block record counts stored in the index, so
+    /// no block is decompressed; BQ counts follow from the file size.
+    #[clap(long)]
+    pub estimate: bool,
+
+    /// Count the records matching an expression over derived fields
+    ///
+    /// Fields are `len` and `gc` (over both mates), `flag`, and `mean_qual`
+    /// (mean Phred+33 quality), compared with numbers by `<`, `<=`, `>`, `>=`,
+    /// `==`, or `!=`, and combined with `&&`, `||`, and parentheses, e.g.
+    /// `len > 100 && gc > 0.5`. Writes TSV rows of `path`, `num_records`, and
+    /// `matching`.
+    #[clap(long = "where", value_name = "EXPR")]
+    pub where_expr: Option<RecordQuery>,
+
+    /// Number of threads to use for `--ns`, `--weighted`, `--gc-summary`, `--distinct`, and `--where` (0: all available)
+    #[clap(short = 'T', long, default_value = "0")]
+    pub threads: usize,
 }
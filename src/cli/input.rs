@@ -1,17 +1,33 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
 use anyhow::{bail, Result};
 use binseq::BinseqReader;
 use clap::Parser;
+use gzp::{
+    deflate::{Bgzf, Mgzip},
+    par::decompress::ParDecompressBuilder,
+};
 use log::{debug, error, warn};
 use paraseq::fastx;
+use parking_lot::Mutex;
 
-use crate::{cli::BinseqMode, types::BoxedReader};
+use crate::{
+    cli::BinseqMode,
+    commands::{encode::utils::is_glob_pattern, is_stdio, open_binseq, progress},
+    types::BoxedReader,
+};
 
 use super::FileFormat;
 
 #[derive(Parser, Debug, Clone)]
 #[clap(next_help_heading = "INPUT FILE OPTIONS")]
+#[allow(clippy::struct_excessive_bools)]
 pub struct InputFile {
     /// Input file [default: stdin]
     ///
@@ -19,7 +35,7 @@ pub struct InputFile {
     ///
     /// If more than two files are provided they will be collated into a single collection.
     /// Use the `--paired` option to specify paired-end input (number of files must be even).
-    #[clap(help = "Input file [default: stdin]", num_args = 0..)]
+    #[clap(help = "Input file (`-` for stdin) [default: stdin]", num_args = 0..)]
     pub input: Vec<String>,
 
     #[clap(short, long, help = "Input file format")]
@@ -32,10 +48,69 @@ pub struct InputFile {
     #[clap(short, long)]
     pub batch_size: Option<usize>,
 
+    /// Threads to use for decompressing each gzipped input file.
+    ///
+    /// Block-gzipped inputs (BGZF, as written by `bgzip`, or mgzip) are
+    /// decompressed in parallel on this many threads, in addition to `-T`.
+    /// Ordinary single-stream gzip cannot be split and falls back to
+    /// single-threaded decompression.
+    #[clap(
+        long,
+        value_name = "N",
+        default_value = "1",
+        visible_alias = "gzip-input-threads",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub input_threads: usize,
+
     /// Input is paired-interleaved
     #[clap(short = 'I', long, conflicts_with = "paired")]
     pub interleaved: bool,
 
+    /// Validate that each R1/R2 input pair holds the same records.
+    ///
+    /// Mate IDs are compared as pairs are encoded (ignoring comments and a
+    /// trailing `/1`/`/2`), and the records of each input are tallied as they are
+    /// read, so a desynced pair fails with both file names and their record counts
+    /// instead of being silently truncated. Works on streamed inputs (FIFOs);
+    /// remote (`gs://`) inputs only get the mate ID comparison.
+    #[clap(long)]
+    pub strict_pairing: bool,
+
+    /// Validate that consecutive records of an interleaved input are mates.
+    ///
+    /// The IDs of each adjacent record pair are compared as it is encoded
+    /// (ignoring comments and a trailing `/1`/`/2`), so a file that is not
+    /// actually interleaved fails naming the two mismatched records instead of
+    /// being silently scrambled.
+    #[clap(long, requires = "interleaved", visible_alias = "mate-check")]
+    pub check_interleaving: bool,
+
+    /// Only encode BAM/SAM/CRAM records belonging to this read group (`RG` aux tag)
+    #[clap(long = "bam-rg", value_name = "ID", conflicts_with = "bam_split_rg")]
+    pub bam_rg: Option<String>,
+
+    /// Split BAM/SAM/CRAM records into one output per read group (`RG` aux tag)
+    ///
+    /// Outputs are named `<output stem>.<RG>.<ext>` and cannot be written to stdout.
+    /// Records without a read group are written to `<output stem>.unassigned.<ext>`.
+    #[clap(long = "bam-split-rg")]
+    pub bam_split_rg: bool,
+
+    /// Batch size (in records) to use when encoding BAM/SAM/CRAM inputs
+    ///
+    /// The htslib counterpart of `--batch-size`: lower it for very long
+    /// records (e.g. long reads) to keep all threads busy, raise it for short
+    /// records to reduce synchronization. Read-group filtering and splitting
+    /// hand records to their routing threads in fixed chunks of 1024 records.
+    #[clap(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["bam_rg", "bam_split_rg"],
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub bam_batch_size: Option<usize>,
+
     /// Apply encoding to all fasta/fastq files in the provided directory input.
     ///
     /// For R1/R2 encodings pair this with the `--paired` option.
@@ -62,7 +137,7 @@ impl InputFile {
     pub fn single_path(&self) -> Result<Option<&str>> {
         match self.input.len() {
             0 => Ok(None),
-            1 => Ok(Some(&self.input[0])),
+            1 => Ok(Some(self.input[0].as_str()).filter(|path| !is_stdio(path))),
             _ => bail!("Requested single input file, but multiple files were provided."),
         }
     }
@@ -103,8 +178,14 @@ impl InputFile {
         }
     }
 
+    /// Whether any input is a glob pattern left unexpanded by the shell
+    pub fn has_glob(&self) -> bool {
+        self.input.iter().any(|arg| is_glob_pattern(arg))
+    }
+
+    /// Whether input is read from stdin (no path, or a single `-`)
     pub fn is_stdin(&self) -> bool {
-        self.input.is_empty()
+        self.single_path().is_ok_and(|path| path.is_none())
     }
 
     pub fn as_directory(&self) -> Result<PathBuf> {
@@ -120,18 +201,33 @@ impl InputFile {
 
     pub fn build_single_reader(&self) -> Result<fastx::Reader<BoxedReader>> {
         let path = self.single_path()?;
-        let reader = load_reader(path, self.batch_size)?;
-        Ok(reader)
+        self.load_input_reader(path)
     }
 
     /// Builds a vector of readers from the input paths.
     fn build_readers_from_paths(&self) -> Result<Vec<fastx::Reader<BoxedReader>>> {
         self.input
             .iter()
-            .map(|path| load_reader(Some(path), self.batch_size))
+            .map(|path| self.load_input_reader(Some(path)))
             .collect()
     }
 
+    /// Opens an input for encoding, decompressing block-gzipped files on
+    /// `--input-threads` threads when more than one is requested.
+    fn load_input_reader(&self, path: Option<&str>) -> Result<fastx::Reader<BoxedReader>> {
+        if self.input_threads > 1 {
+            if let Some(path) = path.filter(|path| !is_stdio(path) && is_rereadable(path)) {
+                if let Some(reader) =
+                    load_parallel_gzip_reader(path, self.input_threads, self.batch_size)?
+                {
+                    return Ok(reader);
+                }
+                debug!("{path} is not block-gzipped, decompressing on a single thread");
+            }
+        }
+        load_tracked_reader(path, self.batch_size)
+    }
+
     pub fn build_single_collection(&self) -> Result<fastx::Collection<BoxedReader>> {
         self.build_collection_with_optional_stdin(fastx::CollectionType::Single)
     }
@@ -154,6 +250,56 @@ impl InputFile {
         Ok(collection)
     }
 
+    /// Like [`Self::build_paired_collection`], but each local reader tallies the
+    /// records it consumes so [`PairingCheck::verify`] can compare the R1/R2
+    /// counts once encoding is done (`--strict-pairing`).
+    pub fn build_strict_paired_collection(
+        &self,
+    ) -> Result<(fastx::Collection<BoxedReader>, PairingCheck)> {
+        if self.input.is_empty() {
+            bail!("Cannot build paired collection from stdin");
+        }
+        if !self.input.len().is_multiple_of(2) {
+            bail!("Input must contain an even number of paths for paired collection");
+        }
+        let mut readers = Vec::with_capacity(self.input.len());
+        let mut tallies = Vec::with_capacity(self.input.len());
+        for path in &self.input {
+            let (reader, tally) = self.load_tallied_reader(path)?;
+            readers.push(reader);
+            tallies.push((path.clone(), tally));
+        }
+        let collection = fastx::Collection::new(readers, fastx::CollectionType::Paired)?;
+        Ok((collection, PairingCheck { tallies }))
+    }
+
+    /// Opens an input whose decompressed bytes are tallied into a record count.
+    ///
+    /// Remote inputs are decompressed inside the fastx reader and cannot be
+    /// tallied, so they only get the mate ID comparison.
+    fn load_tallied_reader(
+        &self,
+        path: &str,
+    ) -> Result<(fastx::Reader<BoxedReader>, Option<RecordTally>)> {
+        if path.starts_with("gs://") {
+            warn!("`--strict-pairing` only compares mate IDs for remote input: {path}");
+            return Ok((self.load_input_reader(Some(path))?, None));
+        }
+        let parallel = if self.input_threads > 1 && is_rereadable(path) {
+            open_parallel_gzip(path, self.input_threads)?
+        } else {
+            None
+        };
+        let inner = match parallel {
+            Some(inner) => inner,
+            None => niffler::send::get_reader(tracked_file(File::open(path)?, path))?.0,
+        };
+        debug!("building record-tallied fastx reader from: {path}");
+        let tally = RecordTally::new(inner);
+        let reader = new_fastx_reader(Box::new(tally.clone()), self.batch_size)?;
+        Ok((reader, Some(tally)))
+    }
+
     fn build_collection_with_optional_stdin(
         &self,
         collection_type: fastx::CollectionType,
@@ -171,7 +317,7 @@ fn load_reader(
     path: Option<&str>,
     batch_size: Option<usize>,
 ) -> Result<fastx::Reader<BoxedReader>> {
-    if let Some(path) = path {
+    if let Some(path) = path.filter(|path| !is_stdio(path)) {
         if path.starts_with("gs://") {
             #[cfg(not(feature = "gcs"))]
             {
@@ -188,6 +334,252 @@ fn load_reader(
     }
 }
 
+/// Like [`load_reader`], but counts the bytes read from local files toward the
+/// byte-based progress estimate while a progress reporter is running.
+fn load_tracked_reader(
+    path: Option<&str>,
+    batch_size: Option<usize>,
+) -> Result<fastx::Reader<BoxedReader>> {
+    let Some(path) =
+        path.filter(|p| progress::tracking_bytes() && progress::is_sized_input(Path::new(p)))
+    else {
+        return load_reader(path, batch_size);
+    };
+    debug!("building byte-counted fastx reader from: {path}");
+    let counted: BoxedReader = Box::new(progress::CountingReader::new(File::open(path)?));
+    let (inner, _format) = niffler::send::get_reader(counted)?;
+    new_fastx_reader(inner, batch_size)
+}
+
+/// Builds a fastx reader over an already decompressed byte stream
+fn new_fastx_reader(
+    inner: BoxedReader,
+    batch_size: Option<usize>,
+) -> Result<fastx::Reader<BoxedReader>> {
+    let reader = if let Some(size) = batch_size {
+        fastx::Reader::new_with_batch_size(inner, size)?
+    } else {
+        fastx::Reader::new(inner)?
+    };
+    Ok(reader)
+}
+
+/// Counts the bytes read from `file` toward the progress estimate when it is
+/// a sized local input and a progress reporter is running.
+fn tracked_file(file: File, path: &str) -> BoxedReader {
+    if progress::tracking_bytes() && progress::is_sized_input(Path::new(path)) {
+        Box::new(progress::CountingReader::new(file))
+    } else {
+        Box::new(file)
+    }
+}
+
+/// Multi-member gzip layouts whose members can be inflated independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockGzip {
+    Bgzf,
+    Mgzip,
+}
+impl BlockGzip {
+    /// Identifies the layout from the `FEXTRA` subfield of the first gzip member
+    /// (`BC` for BGZF, `IG` for mgzip).
+    fn detect(header: &[u8]) -> Option<Self> {
+        const FEXTRA: u8 = 0x04;
+        match header {
+            [0x1f, 0x8b, 0x08, flags, _, _, _, _, _, _, _, _, b'B', b'C', ..]
+                if flags & FEXTRA != 0 =>
+            {
+                Some(Self::Bgzf)
+            }
+            [0x1f, 0x8b, 0x08, flags, _, _, _, _, _, _, _, _, b'I', b'G', ..]
+                if flags & FEXTRA != 0 =>
+            {
+                Some(Self::Mgzip)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Builds a fastx reader over a block-gzipped file decompressed on `threads`
+/// threads, or returns `None` if the file is not block-gzipped.
+fn load_parallel_gzip_reader(
+    path: &str,
+    threads: usize,
+    batch_size: Option<usize>,
+) -> Result<Option<fastx::Reader<BoxedReader>>> {
+    open_parallel_gzip(path, threads)?
+        .map(|inner| new_fastx_reader(inner, batch_size))
+        .transpose()
+}
+
+/// Opens a block-gzipped file decompressed on `threads` threads, or returns
+/// `None` if the file is not block-gzipped.
+fn open_parallel_gzip(path: &str, threads: usize) -> Result<Option<BoxedReader>> {
+    let mut header = [0u8; 14];
+    let mut file = File::open(path)?;
+    let n = std::io::Read::read(&mut file, &mut header)?;
+    let Some(layout) = BlockGzip::detect(&header[..n]) else {
+        return Ok(None);
+    };
+    std::io::Seek::rewind(&mut file)?;
+    let file = tracked_file(file, path);
+    debug!("building {layout:?} reader with {threads} decompression threads from: {path}");
+    let inner: BoxedReader = match layout {
+        BlockGzip::Bgzf => Box::new(
+            ParDecompressBuilder::<Bgzf>::new()
+                .num_threads(threads)?
+                .from_reader(file),
+        ),
+        BlockGzip::Mgzip => Box::new(
+            ParDecompressBuilder::<Mgzip>::new()
+                .num_threads(threads)?
+                .from_reader(file),
+        ),
+    };
+    Ok(Some(inner))
+}
+
+/// Decompressed FASTX stream that tallies the records passing through it.
+///
+/// FASTQ records are counted as every fourth line and FASTA records by their
+/// `>` lines, so the count follows the bytes the parser consumed without
+/// parsing anything twice.
+#[derive(Clone)]
+struct RecordTally {
+    state: Arc<Mutex<TallyState>>,
+}
+struct TallyState {
+    inner: BoxedReader,
+    /// Whether the stream is FASTA, known once its first byte is read
+    fasta: Option<bool>,
+    lines: usize,
+    headers: usize,
+    /// Blank lines only count once a later line shows they are not trailing
+    blank_lines: usize,
+    at_line_start: bool,
+}
+impl TallyState {
+    fn scan(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.at_line_start {
+                if byte == b'\n' {
+                    self.blank_lines += 1;
+                } else {
+                    let fasta = *self.fasta.get_or_insert(byte == b'>');
+                    self.lines += std::mem::take(&mut self.blank_lines) + 1;
+                    if fasta && byte == b'>' {
+                        self.headers += 1;
+                    }
+                }
+            }
+            self.at_line_start = byte == b'\n';
+        }
+    }
+
+    fn num_records(&self) -> usize {
+        match self.fasta {
+            Some(true) => self.headers,
+            Some(false) => self.lines.div_ceil(4),
+            None => 0,
+        }
+    }
+}
+impl RecordTally {
+    fn new(inner: BoxedReader) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TallyState {
+                inner,
+                fasta: None,
+                lines: 0,
+                headers: 0,
+                blank_lines: 0,
+                at_line_start: true,
+            })),
+        }
+    }
+
+    /// Reads whatever the parser left unread and returns the total record count
+    fn finish(&self) -> std::io::Result<usize> {
+        let mut state = self.state.lock();
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = state.inner.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            state.scan(&buf[..n]);
+        }
+        Ok(state.num_records())
+    }
+}
+impl Read for RecordTally {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut state = self.state.lock();
+        let n = state.inner.read(buf)?;
+        state.scan(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Record tallies of the `--strict-pairing` inputs, in R1/R2 order
+pub struct PairingCheck {
+    tallies: Vec<(String, Option<RecordTally>)>,
+}
+impl PairingCheck {
+    /// Fails on the first R1/R2 pair whose record counts differ.
+    ///
+    /// Call once the collection is processed: an input that ended early stops
+    /// paired processing before the other is exhausted, so the remainder of
+    /// each input is read through here to report exact counts.
+    pub fn verify(&self) -> Result<()> {
+        for pair in self.tallies.chunks(2) {
+            let [(r1, Some(t1)), (r2, Some(t2))] = pair else {
+                continue;
+            };
+            let (n1, n2) = (t1.finish()?, t2.finish()?);
+            if n1 != n2 {
+                error!("Paired inputs are out of sync: {r1} has {n1} records but {r2} has {n2}");
+                bail!("Paired inputs are out of sync: {r1} has {n1} records but {r2} has {n2}");
+            }
+            debug!("Validated pairing of {r1} and {r2} ({n1} records)");
+        }
+        Ok(())
+    }
+}
+
+/// Whether a path can be read twice (i.e. is a regular local file).
+fn is_rereadable(path: &str) -> bool {
+    !path.starts_with("gs://") && std::fs::metadata(path).is_ok_and(|m| m.is_file())
+}
+
+/// Counts the records in a FASTX file with a single sequential pass.
+pub(crate) fn count_records(path: &str, batch_size: Option<usize>) -> Result<usize> {
+    let mut reader = load_reader(Some(path), batch_size)?;
+    let mut rset = reader.new_record_set();
+    let mut num_records = 0;
+    while rset.fill(&mut reader)? {
+        for record in rset.iter() {
+            record?;
+            num_records += 1;
+        }
+    }
+    Ok(num_records)
+}
+
+/// Strips the comment and a trailing `/1` or `/2` mate suffix from a record ID
+pub(crate) fn mate_id(id: &[u8]) -> &[u8] {
+    let end = id
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .unwrap_or(id.len());
+    let name = &id[..end];
+    match name {
+        [stem @ .., b'/', b'1' | b'2'] => stem,
+        _ => name,
+    }
+}
+
 fn load_simple_reader(
     path: Option<&str>,
     batch_size: Option<usize>,
@@ -206,17 +598,40 @@ fn load_simple_reader(
     }
 }
 
+/// Delay before the first GCS retry, doubled after each further failure
+#[cfg(feature = "gcs")]
+const GCS_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Upper bound on the delay between GCS retries
+#[cfg(feature = "gcs")]
+const GCS_MAX_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Opens a GCS reader, retrying with exponential backoff up to `--gcs-retries` times
 #[cfg(feature = "gcs")]
 fn load_gcs_reader(
     path: &str,
     batch_size: Option<usize>,
 ) -> Result<fastx::Reader<BoxedReader>, paraseq::Error> {
-    if let Some(size) = batch_size {
-        debug!("building GCS fastx reader with batch size {size} from: {path}");
-        fastx::Reader::from_gcs_with_batch_size(path, size)
-    } else {
-        debug!("building GCS fastx reader from: {path}");
-        fastx::Reader::from_gcs(path)
+    let retries = crate::commands::gcs_retries();
+    let mut delay = GCS_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        let result = if let Some(size) = batch_size {
+            debug!("building GCS fastx reader with batch size {size} from: {path}");
+            fastx::Reader::from_gcs_with_batch_size(path, size)
+        } else {
+            debug!("building GCS fastx reader from: {path}");
+            fastx::Reader::from_gcs(path)
+        };
+        match result {
+            Err(err) if attempt < retries => {
+                attempt += 1;
+                warn!("Failed to open {path}: {err} - retrying in {delay:?} ({attempt}/{retries})");
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(GCS_MAX_RETRY_DELAY);
+            }
+            result => return result,
+        }
     }
 }
 
@@ -238,9 +653,39 @@ pub struct BatchEncodingOptions {
     /// Collate all input files into a single output file. Will respect paired records if `--paired` is specified.
     #[clap(short = 'C', long)]
     pub collate: bool,
+
+    /// Write a TSV manifest of every BINSEQ file produced (path and record count).
+    ///
+    /// Each output is appended as soon as it completes, so the manifest only lists
+    /// files that were encoded successfully. Ignored unless batch encoding
+    /// (`--manifest`, `--recursive`, or more than two input files).
+    #[clap(long)]
+    pub output_manifest: Option<String>,
+
+    /// Write a TSV of every BINSEQ file produced with its size and xxh3 checksum.
+    ///
+    /// Rows are `path<TAB>size<TAB>xxh3` (hex digest of the file bytes), hashed
+    /// once each output is complete, for verifying copies after transfer
+    /// (e.g. with `xxhsum -H3`). Ignored unless batch encoding, like
+    /// `--output-manifest`.
+    #[clap(long, value_name = "PATH")]
+    pub checksum_manifest: Option<String>,
+
+    /// Number of files (or pairs) to encode concurrently when batch encoding.
+    ///
+    /// Each file is encoded with the full `-T` threads, so `--parallel-files 4 -T 8`
+    /// keeps four outputs in flight on 32 threads. Files are handed to the next
+    /// free slot as others finish. By default `-T` is divided across the files
+    /// in proportion to their size (at least one thread each).
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub parallel_files: Option<usize>,
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[clap(next_help_heading = "INPUT FILE OPTIONS")]
 pub struct InputBinseq {
     #[clap(help = "Input binseq file")]
@@ -256,7 +701,7 @@ impl InputBinseq {
     }
 
     pub fn mode(&self) -> Result<BinseqMode> {
-        let reader = BinseqReader::new(&self.input)?;
+        let reader = open_binseq(&self.input)?;
         match reader {
             BinseqReader::Bq(_) => Ok(BinseqMode::Bq),
             BinseqReader::Vbq(_) => Ok(BinseqMode::Vbq),
@@ -273,7 +718,7 @@ pub struct MultiInputBinseq {
     pub input: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Span {
     start: Option<usize>,
     end: Option<usize>,
@@ -298,6 +743,23 @@ impl Span {
         }
         Ok(())
     }
+    /// Moves the start of the span `n` records later
+    #[must_use]
+    pub fn skip(self, n: usize) -> Self {
+        Self {
+            start: Some(self.start.unwrap_or(0).saturating_add(n)),
+            end: self.end,
+        }
+    }
+    /// Restricts the span to at most its first `n` records
+    #[must_use]
+    pub fn first(self, n: usize) -> Self {
+        let end = self.start.unwrap_or(0).saturating_add(n);
+        Self {
+            start: self.start,
+            end: Some(self.end.map_or(end, |e| e.min(end))),
+        }
+    }
     pub fn get_range(&mut self, max_records: usize) -> Result<std::ops::Range<usize>> {
         self.validate(max_records)?;
         match (self.start, self.end) {
@@ -0,0 +1,50 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+
+use crate::commands::decode::LengthFilter;
+
+#[derive(Parser, Debug, Clone)]
+#[clap(next_help_heading = "LENGTH FILTER OPTIONS")]
+pub struct LengthFilterArgs {
+    /// Drop records with a sequence shorter than this many bases
+    #[clap(long, value_name = "N")]
+    pub min_length: Option<u64>,
+
+    /// Drop records with a sequence longer than this many bases
+    #[clap(long, value_name = "N")]
+    pub max_length: Option<u64>,
+
+    /// How the bounds apply to the two mates of paired records
+    ///
+    /// `each` requires both mates to be within the bounds (regardless of
+    /// `-m`); `sum` checks their combined length.
+    #[clap(long, value_name = "MODE", default_value = "each")]
+    pub length_combine: LengthCombine,
+}
+impl LengthFilterArgs {
+    /// The requested length bounds, if any
+    pub fn filter(&self) -> Result<Option<LengthFilter>> {
+        if self.min_length.is_none() && self.max_length.is_none() {
+            return Ok(None);
+        }
+        let min = self.min_length.unwrap_or(0);
+        let max = self.max_length.unwrap_or(u64::MAX);
+        if min > max {
+            bail!("`--min-length` ({min}) must not exceed `--max-length` ({max})");
+        }
+        Ok(Some(LengthFilter {
+            min,
+            max,
+            combine: self.length_combine,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LengthCombine {
+    /// Each mate must be within the bounds
+    #[default]
+    Each,
+    /// The summed length of both mates must be within the bounds
+    Sum,
+}
@@ -0,0 +1,44 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+
+use super::{InputBinseq, OutputBinseq};
+
+/// Merge overlapping mates of a paired BINSEQ file into single-end reads.
+///
+/// Each pair is merged by aligning the tail of R1 against the head of the
+/// reverse complemented R2. Pairs without an acceptable overlap are dropped.
+/// The output mirrors the input's BINSEQ configuration as a single-end file.
+#[derive(Parser, Debug)]
+pub struct MergePairsCommand {
+    #[clap(flatten)]
+    pub input: InputBinseq,
+
+    #[clap(flatten)]
+    pub output: OutputBinseq,
+
+    #[clap(flatten)]
+    pub merge: MergeArgs,
+}
+
+#[derive(Parser, Debug, Clone)]
+#[clap(next_help_heading = "MERGE OPTIONS")]
+pub struct MergeArgs {
+    /// Minimum overlap (in bases) required between R1 and reverse complemented R2
+    #[clap(long, default_value = "10")]
+    pub min_overlap: usize,
+
+    /// Maximum fraction of mismatched bases allowed within the overlap
+    #[clap(long, default_value = "0.1")]
+    pub max_mismatch_rate: f64,
+}
+impl MergeArgs {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_overlap == 0 {
+            bail!("Minimum overlap must be greater than 0");
+        }
+        if !(0.0..=1.0).contains(&self.max_mismatch_rate) {
+            bail!("Maximum mismatch rate must be between 0 and 1");
+        }
+        Ok(())
+    }
+}
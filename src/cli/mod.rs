@@ -1,33 +1,60 @@
+mod benchmark;
 mod cat;
 mod cli;
+mod corrupt;
 mod decode;
+mod doctor;
 mod encode;
 mod formats;
+mod formats_cmd;
 mod grep;
+mod head;
 mod info;
 mod input;
+mod length_filter;
+mod merge_pairs;
 mod output;
 mod pipe;
+mod progress;
 mod qc;
+mod recompress;
 mod revcomp;
 mod sample;
+mod sort;
 mod split;
+mod validate;
 mod verify;
 
+pub use benchmark::BenchmarkCommand;
 pub use cat::CatCommand;
 pub use cli::{Cli, Commands};
-pub use decode::DecodeCommand;
+pub use corrupt::CorruptCommand;
+pub use decode::{
+    CommentField, DecodeCommand, GroupBy, OnOrphan, RevcompMates, SynthQual, TsvColumn,
+};
+pub use doctor::DoctorCommand;
 pub use encode::EncodeCommand;
 pub use formats::FileFormat;
+pub use formats_cmd::FormatsCommand;
 #[cfg(feature = "fuzzy")]
-pub use grep::FuzzyArgs;
+pub use grep::{DistanceType, FuzzyArgs};
 pub use grep::{GrepCommand, PatternFileArgs};
+pub use head::HeadCommand;
 pub use info::InfoCommand;
-pub use input::{InputBinseq, InputFile, MultiInputBinseq};
-pub use output::{BinseqConfig, BinseqMode, Mate, OutputBinseq, OutputFile};
-pub use pipe::PipeCommand;
+pub(crate) use input::{count_records, mate_id};
+pub use input::{InputBinseq, InputFile, MultiInputBinseq, Span};
+pub use length_filter::{LengthCombine, LengthFilterArgs};
+pub use merge_pairs::MergePairsCommand;
+pub use output::{
+    AsciiHeaders, BinseqConfig, BinseqMode, Mate, NameFrom, OutputBinseq, OutputFile, PolyTrim,
+};
+pub use pipe::{Balance, PipeCommand};
+pub use progress::ProgressArgs;
 pub use qc::{QcCommand, QcOptions};
+pub use recompress::RecompressCommand;
 pub use revcomp::RevcompCommand;
 pub use sample::SampleCommand;
+pub use sort::{SortCommand, SortKey};
 pub use split::SplitCommand;
+pub use validate::ValidateCommand;
 pub use verify::{VerifyCommand, VerifyOptions};
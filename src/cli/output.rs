@@ -6,13 +6,20 @@ use std::{io::Write, path::Path};
 
 use crate::{
     cli::FileFormat,
-    commands::{compress_passthrough, match_output, CompressionType},
+    commands::{
+        compress_passthrough, decode::SplitWriter, is_stdio, match_output, match_output_append,
+        CompressionType, OutputWriter,
+    },
 };
 
 #[derive(Parser, Debug, Clone)]
 #[clap(next_help_heading = "OUTPUT FILE OPTIONS")]
 pub struct OutputFile {
-    #[clap(short = 'o', long, help = "Output file [default: stdout]")]
+    #[clap(
+        short = 'o',
+        long,
+        help = "Output file (`-` for stdout) [default: stdout]"
+    )]
     pub output: Option<String>,
 
     #[clap(
@@ -29,6 +36,14 @@ pub struct OutputFile {
     #[clap(short = 'm', long, default_value = "both")]
     pub mate: Mate,
 
+    /// File naming template for split paired outputs
+    ///
+    /// `{prefix}` is the output prefix, `{mate}` the mate number (1 or 2), and
+    /// `{ext}` the format extension including any compression suffix, e.g.
+    /// `{prefix}_{mate}.{ext}` or `{prefix}.R{mate}.{ext}`.
+    #[clap(long, default_value = DEFAULT_MATE_NAMING, value_name = "TEMPLATE")]
+    pub mate_naming: String,
+
     #[clap(short, long, help = "Output file format")]
     pub format: Option<FileFormat>,
 
@@ -42,17 +57,39 @@ pub struct OutputFile {
         default_value = "0"
     )]
     pub threads: usize,
+
+    /// Flush the output once at the end instead of after every batch
+    ///
+    /// Saves a flush per batch when writing to fast local disks. Records may
+    /// sit in memory buffers until the run finishes, so output is less
+    /// durable if the process is killed; a final flush still runs on success
+    /// and on error.
+    #[clap(long)]
+    pub defer_flush: bool,
+
+    /// Make gzip output rsync- and dedup-friendly
+    ///
+    /// Restarts gzip compression at content-defined points (about every MiB),
+    /// so re-running on slightly changed input leaves most of the compressed
+    /// file byte-identical. Output is slightly larger and compressed on a
+    /// single thread. Only applies to gzip output.
+    #[clap(long, visible_alias = "output-gzip-rsyncable")]
+    pub rsyncable: bool,
 }
 impl OutputFile {
-    pub fn as_writer(&self) -> Result<Box<dyn Write + Send>> {
-        let writer = match_output(self.output.as_deref())?;
-        compress_passthrough(writer, self.compress(), self.threads())
+    pub fn as_writer(&self) -> Result<OutputWriter> {
+        let writer = match_output(self.output_path())?;
+        compress_passthrough(writer, self.compress(), self.threads(), self.rsyncable)
+    }
+
+    /// The output path, or `None` for stdout (no `-o`, or `-o -`)
+    pub fn output_path(&self) -> Option<&str> {
+        self.output.as_deref().filter(|path| !is_stdio(path))
     }
 
     #[allow(clippy::case_sensitive_file_extension_comparisons)]
     pub fn compress(&self) -> CompressionType {
-        self.output
-            .as_ref()
+        self.output_path()
             .map_or(CompressionType::Uncompressed, |path| {
                 if path.ends_with(".gz") {
                     CompressionType::Gzip
@@ -68,16 +105,20 @@ impl OutputFile {
         self.mate
     }
 
-    pub fn format(&self) -> Result<FileFormat> {
-        let format = if let Some(format) = self.format {
-            format
-        } else if let Some(path) = self.output.as_ref() {
+    /// The requested or inferred output format, including BAM (`decode` only)
+    pub fn format_with_bam(&self) -> Result<FileFormat> {
+        if let Some(format) = self.format {
+            Ok(format)
+        } else if let Some(path) = self.output_path() {
             FileFormat::from_path(path)
-                .ok_or_else(|| anyhow::anyhow!("Could not infer file format."))?
+                .ok_or_else(|| anyhow::anyhow!("Could not infer file format."))
         } else {
-            FileFormat::Tsv
-        };
+            Ok(FileFormat::Tsv)
+        }
+    }
 
+    pub fn format(&self) -> Result<FileFormat> {
+        let format = self.format_with_bam()?;
         if format == FileFormat::Bam {
             bail!(
                 "BAM output is not supported here; use FASTA (-f a), FASTQ (-f q), or TSV (-f t) instead"
@@ -97,37 +138,103 @@ impl OutputFile {
         }
     }
 
-    pub fn as_paired_writer(
-        &self,
-        format: FileFormat,
-    ) -> Result<(Box<dyn Write + Send>, Box<dyn Write + Send>)> {
+    pub fn as_paired_writer(&self, format: FileFormat) -> Result<(OutputWriter, OutputWriter)> {
         // Check for prefix
         let prefix = self.prefix.as_ref().ok_or_else(|| {
             anyhow::anyhow!("Output file format prefix is required for paired BINSEQ files")
         })?;
 
-        // Construct the output file names
-        let r1_name = if let Some(ext) = self.compress.extension() {
-            format!("{}_R1.{}.{}", prefix, format.extension(), ext)
+        self.open_mates(prefix, format, false)
+    }
+
+    /// This output redirected to `<dir>/<stem>.<ext>` (FASTQ unless `-f` is set)
+    pub fn in_directory(&self, dir: &str, stem: &str) -> Self {
+        let format = self.format.unwrap_or(FileFormat::Fastq);
+        let path = Path::new(dir).join(format!("{stem}.{}", self.extension(format)));
+        Self {
+            output: Some(path.to_string_lossy().into_owned()),
+            ..self.clone()
+        }
+    }
+
+    /// File extension for `format`, including the compression suffix if any
+    fn extension(&self, format: FileFormat) -> String {
+        if let Some(ext) = self.compress.extension() {
+            format!("{}.{ext}", format.extension())
         } else {
-            format!("{}_R1.{}", prefix, format.extension())
-        };
-        let r2_name = if let Some(ext) = self.compress.extension() {
-            format!("{}_R2.{}.{}", prefix, format.extension(), ext)
+            format.extension().to_string()
+        }
+    }
+
+    /// Opens `path`, compressing if requested.
+    ///
+    /// With `append`, a file already written in this run is reopened instead of
+    /// truncated; compressed outputs gain a new gzip member / zstd frame, which
+    /// decompressors read as one stream.
+    fn open_path(&self, path: &str, append: bool) -> Result<OutputWriter> {
+        let writer = if append {
+            match_output_append(path)?
         } else {
-            format!("{}_R2.{}", prefix, format.extension())
+            match_output(Some(path))?
         };
+        compress_passthrough(writer, self.compress, self.threads(), self.rsyncable)
+    }
 
-        // Open the output files
-        let r1 = match_output(Some(&r1_name))?;
-        let r2 = match_output(Some(&r2_name))?;
+    /// Opens the R1/R2 writers for `prefix` named by the `--mate-naming` template.
+    fn open_mates(
+        &self,
+        prefix: &str,
+        format: FileFormat,
+        append: bool,
+    ) -> Result<(OutputWriter, OutputWriter)> {
+        let ext = self.extension(format);
+        let r1 = render_mate_name(&self.mate_naming, prefix, 1, &ext)?;
+        let r2 = render_mate_name(&self.mate_naming, prefix, 2, &ext)?;
+        Ok((self.open_path(&r1, append)?, self.open_path(&r2, append)?))
+    }
 
-        // Compress the output files (if necessary)
-        let r1 = compress_passthrough(r1, self.compress, self.threads())?;
-        let r2 = compress_passthrough(r2, self.compress, self.threads())?;
+    /// Opens the writer for a single `--group-by` partition named `<prefix>_<group>`.
+    ///
+    /// Paired files decoding both mates are split into `_R1`/`_R2` files per group.
+    /// `append` reopens a partition that was closed earlier (see `--max-open-files`).
+    pub fn as_group_writer(
+        &self,
+        group: &str,
+        format: FileFormat,
+        paired: bool,
+        append: bool,
+    ) -> Result<SplitWriter> {
+        let prefix = self.prefix.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("An output prefix (`-p`) is required when grouping records")
+        })?;
+        let stem = format!("{prefix}_{group}");
+        if paired && self.mate == Mate::Both {
+            let (r1, r2) = self.open_mates(&stem, format, append)?;
+            Ok(SplitWriter::new_split(r1, r2))
+        } else {
+            let path = format!("{stem}.{}", self.extension(format));
+            Ok(SplitWriter::new_interleaved(self.open_path(&path, append)?))
+        }
+    }
+}
 
-        Ok((r1, r2))
+/// Default `--mate-naming` template (`<prefix>_R1.<ext>` / `<prefix>_R2.<ext>`)
+const DEFAULT_MATE_NAMING: &str = "{prefix}_R{mate}.{ext}";
+
+/// Renders a `--mate-naming` template for a single mate.
+///
+/// The template must reference both `{prefix}` and `{mate}` so that the two
+/// mates (and distinct prefixes) never resolve to the same file.
+fn render_mate_name(template: &str, prefix: &str, mate: u8, ext: &str) -> Result<String> {
+    if !template.contains("{prefix}") || !template.contains("{mate}") {
+        bail!(
+            "Invalid `--mate-naming` template: {template} (must contain {{prefix}} and {{mate}})"
+        );
     }
+    Ok(template
+        .replace("{prefix}", prefix)
+        .replace("{mate}", &mate.to_string())
+        .replace("{ext}", ext))
 }
 
 #[derive(ValueEnum, PartialEq, Eq, Clone, Copy, Debug, Default)]
@@ -147,7 +254,7 @@ pub struct OutputBinseq {
     #[clap(short = 'o', long)]
     /// Output binseq file
     ///
-    /// To output to stdout, use the `--pipe` flag.
+    /// To output to stdout, use the `--pipe` flag or `-o -`.
     pub output: Option<String>,
 
     #[clap(flatten)]
@@ -159,19 +266,25 @@ pub struct OutputBinseq {
 }
 impl OutputBinseq {
     pub fn as_writer(&self) -> Result<Box<dyn Write + Send>> {
+        // `-o -` asks for stdout as explicitly as `--pipe`
         if self.output.is_none() && !self.pipe {
             bail!(
                 "Refusing to write binary BINSEQ data to stdout. Provide an output path with `-o/--output`, or pass `--pipe` to write to stdout explicitly."
             );
         }
-        let writer = match_output(self.output.as_deref())?;
+        let writer = match_output(self.output_path())?;
         Ok(writer)
     }
 
+    /// The output path, or `None` for stdout (`--pipe` or `-o -`)
+    pub fn output_path(&self) -> Option<&str> {
+        self.output.as_deref().filter(|path| !is_stdio(path))
+    }
+
     pub fn mode(&self) -> Result<BinseqMode> {
         if let Some(mode) = self.options.mode {
             Ok(mode)
-        } else if let Some(ref path) = self.output {
+        } else if let Some(path) = self.output_path() {
             BinseqMode::determine(path)
         } else {
             // STDOUT
@@ -402,7 +515,7 @@ impl From<BinseqMode> for binseq::write::Format {
     }
 }
 
-fn parse_memory_size(input: &str) -> Result<usize, String> {
+pub(crate) fn parse_memory_size(input: &str) -> Result<usize, String> {
     let input = input.trim().to_uppercase();
     let last_char = input.chars().last().unwrap_or('0');
 
@@ -421,6 +534,7 @@ fn parse_memory_size(input: &str) -> Result<usize, String> {
 }
 
 #[derive(Copy, Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct BinseqConfig {
     pub compress: bool,
     pub quality: bool,
@@ -430,6 +544,52 @@ pub struct BinseqConfig {
     pub headers: bool,
     pub threads: usize,
     pub compression_level: i32,
+    /// Uppercase sequences and map IUPAC ambiguity codes to `N` before encoding
+    pub canonicalize: bool,
+    /// How non-ASCII record headers are handled before encoding
+    pub ascii_headers: Option<AsciiHeaders>,
+    /// Homopolymer tails trimmed from the 3' end before encoding
+    pub poly_trim: PolyTrim,
+    /// Cap on stored Phred quality scores
+    pub max_quality: Option<u8>,
+    /// Sort each batch of records by sequence prefix before it is blocked
+    pub sort_batch: bool,
+    /// Drop records whose sequence complexity falls below this threshold
+    pub min_complexity: Option<f64>,
+    /// Source of the stored record headers
+    pub name_from: NameFrom,
+    /// Fail on paired records whose mates have different IDs
+    pub strict_pairing: bool,
+    /// Fail on interleaved records whose mates have different IDs
+    pub check_interleaving: bool,
+}
+
+/// Minimum 3' homopolymer run lengths to trim (`--trim-polyg`/`--trim-polya`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PolyTrim {
+    pub polya: Option<usize>,
+    pub polyg: Option<usize>,
+}
+
+/// Source of the headers stored for encoded records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum NameFrom {
+    /// The input record's name
+    #[default]
+    Id,
+    /// Empty headers
+    None,
+    /// The record's 0-based position in the input
+    Index,
+}
+
+/// Handling of record headers containing non-ASCII bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AsciiHeaders {
+    /// Fail on the first non-ASCII header
+    Strict,
+    /// Replace each non-ASCII byte with `_`
+    Sanitize,
 }
 impl From<OutputBinseqOptions> for BinseqConfig {
     fn from(options: OutputBinseqOptions) -> Self {
@@ -442,15 +602,30 @@ impl From<OutputBinseqOptions> for BinseqConfig {
             headers: options.headers(),
             threads: options.threads(),
             compression_level: options.level,
+            canonicalize: false,
+            ascii_headers: None,
+            poly_trim: PolyTrim::default(),
+            max_quality: None,
+            sort_batch: false,
+            min_complexity: None,
+            name_from: NameFrom::default(),
+            strict_pairing: false,
+            check_interleaving: false,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::{Read, Write};
+
     use clap::Parser;
 
-    use super::OutputBinseq;
+    use super::{render_mate_name, OutputBinseq, OutputFile, DEFAULT_MATE_NAMING};
+    use crate::{
+        cli::FileFormat,
+        commands::{CompressionType, OutputWriter},
+    };
 
     /// Without `-o` or `--pipe`, writing binary BINSEQ data to stdout must be
     /// refused rather than silently dumping binary into the terminal.
@@ -473,4 +648,54 @@ mod tests {
             OutputBinseq::try_parse_from(["output", "-o", tmp.path().to_str().unwrap()]).unwrap();
         assert!(args.as_writer().is_ok());
     }
+
+    /// `-o -` means stdout, not a file literally named `-`
+    #[test]
+    fn test_dash_output_means_stdout() {
+        let args = OutputBinseq::try_parse_from(["output", "-o", "-", "-m", "vbq"]).unwrap();
+        assert_eq!(args.output_path(), None);
+        assert!(args.as_writer().is_ok());
+
+        let args = OutputFile::try_parse_from(["output", "-o", "-"]).unwrap();
+        assert_eq!(args.output_path(), None);
+        assert!(matches!(args.format(), Ok(FileFormat::Tsv)));
+        assert!(matches!(args.compress(), CompressionType::Uncompressed));
+    }
+
+    /// `--rsyncable` gzip output is complete once the writer is finished
+    #[test]
+    fn test_as_writer_rsyncable() {
+        let tmp = tempfile::NamedTempFile::with_suffix(".fastq.gz").unwrap();
+        let args = OutputFile::try_parse_from([
+            "output",
+            "-o",
+            tmp.path().to_str().unwrap(),
+            "--rsyncable",
+        ])
+        .unwrap();
+        let mut writer = args.as_writer().unwrap();
+        assert!(matches!(writer, OutputWriter::Rsyncable(_)));
+        writer.write_all(b"@r\nACGT\n+\nIIII\n").unwrap();
+        writer.finish().unwrap();
+
+        let mut decoded = String::new();
+        flate2::read::MultiGzDecoder::new(std::fs::File::open(tmp.path()).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "@r\nACGT\n+\nIIII\n");
+    }
+
+    #[test]
+    fn test_render_mate_name() {
+        assert_eq!(
+            render_mate_name(DEFAULT_MATE_NAMING, "out", 1, "fq").unwrap(),
+            "out_R1.fq"
+        );
+        assert_eq!(
+            render_mate_name("{prefix}.R{mate}.{ext}", "out", 2, "fq.gz").unwrap(),
+            "out.R2.fq.gz"
+        );
+        assert!(render_mate_name("{prefix}.{ext}", "out", 1, "fq").is_err());
+        assert!(render_mate_name("reads_{mate}.fq", "out", 1, "fq").is_err());
+    }
 }
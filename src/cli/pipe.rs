@@ -52,6 +52,32 @@ pub struct PipeOptions {
     /// Mutually exclusive with `--exec`.
     #[clap(short = 'X', long, conflicts_with = "exec")]
     exec_batch: Option<String>,
+
+    /// How records are divided between pipes
+    ///
+    /// `records` gives each pipe an equal number of records. `bytes` uses the
+    /// VBQ/CBQ block index to give each pipe a roughly equal share of the file's
+    /// bytes, which balances load better when record lengths vary widely.
+    #[clap(long, default_value = "records")]
+    balance: Balance,
+
+    /// Write both mates of a paired file to a single interleaved FIFO per pipe
+    ///
+    /// Each pipe writes R1 followed by R2 for every pair to one FIFO (named and
+    /// substituted like a single-end FIFO, `{}`), halving the FIFO count for
+    /// tools that read interleaved paired input from one stream. Ignored for
+    /// single-end files.
+    #[clap(long, visible_alias = "interleaved-output")]
+    interleaved: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Balance {
+    /// Equal record counts per pipe
+    #[default]
+    Records,
+    /// Roughly equal byte spans per pipe (block granularity)
+    Bytes,
 }
 
 impl PipeCommand {
@@ -77,4 +103,10 @@ impl PipeCommand {
     pub fn exec_batch(&self) -> Option<&str> {
         self.pipe.exec_batch.as_deref()
     }
+    pub fn balance(&self) -> Balance {
+        self.pipe.balance
+    }
+    pub fn interleaved(&self) -> bool {
+        self.pipe.interleaved
+    }
 }
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use clap::Parser;
+
+use crate::commands::progress::JsonProgress;
+
+#[derive(Parser, Debug, Clone)]
+#[clap(next_help_heading = "PROGRESS OPTIONS")]
+pub struct ProgressArgs {
+    /// Periodically write JSON status lines to stderr
+    ///
+    /// Each line has the form `{"records":N,"elapsed":S,"rate":R}` and a final
+    /// summary line with `"done":true` is written on completion.
+    ///
+    /// When encoding local FASTX inputs the lines also report `bytes` read
+    /// against `total_bytes` (the on-disk, possibly compressed, input size) with
+    /// a `percent` and an `eta` in seconds, since the record total is unknown.
+    /// When reading BINSEQ files the record total is known and reported as
+    /// `total_records` alongside `percent` and `eta`.
+    #[clap(long)]
+    pub progress_json: bool,
+
+    /// Seconds between `--progress-json` status lines
+    #[clap(long, default_value = "5", requires = "progress_json")]
+    pub progress_interval: u64,
+}
+impl ProgressArgs {
+    /// Starts the `--progress-json` reporter, if requested
+    pub fn spawn(&self) -> Option<JsonProgress> {
+        self.progress_json
+            .then(|| JsonProgress::spawn(Duration::from_secs(self.progress_interval.max(1))))
+    }
+}
@@ -0,0 +1,86 @@
+use binseq::BitSize;
+use clap::Parser;
+
+use super::{
+    output::{parse_memory_size, PolicyWrapper},
+    InputBinseq,
+};
+
+/// Rewrite a VBQ/CBQ file with new compression settings.
+///
+/// Records are read back and re-encoded with the input's own configuration,
+/// overriding only the compression level, virtual block size, and/or (VBQ)
+/// bitsize. Sequences, qualities, and headers are carried over unchanged,
+/// except for non-ACGT bases when downconverting to 2-bit.
+#[derive(Parser, Debug)]
+pub struct RecompressCommand {
+    #[clap(flatten)]
+    pub input: InputBinseq,
+
+    /// Output binseq file (must match the input's BINSEQ format)
+    #[clap(short = 'o', long, required_unless_present = "in_place")]
+    pub output: Option<String>,
+
+    /// Replace the input file with the recompressed output
+    ///
+    /// The output is written to a temporary file beside the input and only
+    /// renamed over it once recompression has finished successfully.
+    #[clap(long, conflicts_with = "output")]
+    pub in_place: bool,
+
+    #[clap(flatten)]
+    pub options: RecompressOptions,
+}
+
+#[derive(Parser, Debug)]
+#[clap(next_help_heading = "RECOMPRESSION OPTIONS")]
+pub struct RecompressOptions {
+    /// Zstd compression level [default: keep the input's level]
+    ///
+    /// Level 0 disables compression.
+    #[clap(short, long)]
+    pub level: Option<i32>,
+
+    /// Virtual block size in bytes [default: keep the input's block size]
+    #[clap(short = 'B', long, value_parser = parse_memory_size)]
+    pub block_size: Option<usize>,
+
+    /// Nucleotide bitsize (2 or 4) [default: keep the input's bitsize]
+    ///
+    /// Re-encoding a 4-bit VBQ (e.g. from `encode --archive`) as 2-bit saves
+    /// space when it has no Ns. Records with non-ACGT bases are resolved by
+    /// `--policy` and reported. Only used by vbq.
+    #[clap(short = 'S', long, value_parser = parse_bitsize)]
+    pub bitsize: Option<BitSize>,
+
+    /// Policy for records with non-ACGT bases when re-encoding to 2-bit [default: i]
+    #[clap(short = 'p', long, requires = "bitsize")]
+    pub policy: Option<PolicyWrapper>,
+
+    /// Number of threads to use for parallel reading and writing.
+    ///
+    /// The number of threads is by default 0 [sets to maximum], and all other values are clamped to maximum.
+    #[clap(short = 'T', long, default_value = "0")]
+    pub threads: usize,
+}
+fn parse_bitsize(input: &str) -> Result<BitSize, String> {
+    match input {
+        "2" => Ok(BitSize::Two),
+        "4" => Ok(BitSize::Four),
+        _ => Err(format!("Invalid bitsize: {input} (expected 2 or 4)")),
+    }
+}
+
+impl RecompressOptions {
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => num_cpus::get(),
+            n => n.min(num_cpus::get()),
+        }
+    }
+
+    /// N-policy for `--bitsize`, dropping records that cannot be represented by default
+    pub fn policy(&self) -> PolicyWrapper {
+        self.policy.unwrap_or(PolicyWrapper::IgnoreSequence)
+    }
+}
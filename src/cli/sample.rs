@@ -1,7 +1,7 @@
 use anyhow::Result;
 use clap::Parser;
 
-use super::{InputBinseq, OutputFile};
+use super::{InputBinseq, OutputFile, ProgressArgs};
 
 /// Subsample a BINSEQ file and output to FASTQ, FASTA, or TSV
 #[derive(Parser)]
@@ -14,23 +14,52 @@ pub struct SampleCommand {
 
     #[clap(flatten)]
     pub sample: SampleArgs,
+
+    #[clap(flatten)]
+    pub progress: ProgressArgs,
 }
 
 #[derive(Parser, Debug)]
 #[clap(next_help_heading = "SAMPLE OPTIONS")]
 pub struct SampleArgs {
     /// Fraction of the input reads to sample
-    #[clap(short = 'F', long)]
-    pub fraction: f64,
+    #[clap(short = 'F', long, required_unless_present = "num")]
+    pub fraction: Option<f64>,
+
+    /// Sample exactly this many records (all of them if the file has fewer)
+    ///
+    /// Mutually exclusive with `--fraction`. Each record index is keyed by a
+    /// seeded hash and the records with the `num` smallest keys are kept, so
+    /// the selection (not the output order) is independent of the thread
+    /// count. Records are written in file order only with `-T 1`.
+    #[clap(
+        short = 'n',
+        long,
+        conflicts_with_all = ["fraction", "by_index"],
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub num: Option<usize>,
 
     /// Seed to use for random sampling
     #[clap(short = 'S', long, default_value = "42")]
     pub seed: u64,
+
+    /// Decide inclusion from the record index and seed alone
+    ///
+    /// Sampling two files whose records are in the same order (e.g. R1 and R2
+    /// encoded separately) with the same fraction and seed then keeps the same
+    /// records from each, regardless of thread count. Record `i` is kept when
+    /// `(xxh3_64(i as u64 little-endian bytes, seed) >> 11) / 2^53 < fraction`;
+    /// this hash is part of the CLI contract and will not change between versions.
+    #[clap(long, visible_alias = "paired-aware")]
+    pub by_index: bool,
 }
 impl SampleArgs {
     pub fn validate(&self) -> Result<()> {
-        if self.fraction <= 0.0 || self.fraction > 1.0 {
-            anyhow::bail!("Fraction must be between 0 and 1");
+        if let Some(fraction) = self.fraction {
+            if fraction <= 0.0 || fraction > 1.0 {
+                anyhow::bail!("Fraction must be between 0 and 1");
+            }
         }
         Ok(())
     }
@@ -0,0 +1,33 @@
+use clap::Parser;
+
+use super::{InputBinseq, OutputBinseq};
+
+/// Reorder the records of a BINSEQ file by sequence, header, or length.
+///
+/// All records are held in memory while sorting, so expect memory use on the
+/// order of the decoded input size.
+#[derive(Parser, Debug)]
+pub struct SortCommand {
+    #[clap(flatten)]
+    pub input: InputBinseq,
+
+    #[clap(flatten)]
+    pub output: OutputBinseq,
+
+    /// Key to order records by
+    ///
+    /// Keys are taken from the primary mate. Records with equal keys keep
+    /// their input order, so the output is deterministic.
+    #[clap(long, default_value = "seq", help_heading = "SORT OPTIONS")]
+    pub by: SortKey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Lexicographic on the decoded primary sequence
+    Seq,
+    /// Lexicographic on the primary header
+    Header,
+    /// Primary sequence length (shortest first)
+    Length,
+}
@@ -0,0 +1,28 @@
+use clap::Parser;
+
+/// Check BINSEQ files for truncation or corruption.
+///
+/// Every record is decoded with the stored configuration and the decoded
+/// count is compared against the file's record count. For VBQ/CBQ the block
+/// index is cross-checked against the file size, and a failing file is
+/// rescanned block by block to report the first block that cannot be read.
+/// BQ files are checked for a partial trailing record. Exits with an error
+/// on the first file that fails.
+#[derive(Parser, Debug)]
+pub struct ValidateCommand {
+    /// Input binseq files
+    #[clap(num_args = 1.., required = true)]
+    pub input: Vec<String>,
+
+    /// Number of threads to use [0: auto]
+    #[clap(short = 'T', long, default_value_t = 0)]
+    pub threads: usize,
+}
+impl ValidateCommand {
+    pub fn threads(&self) -> usize {
+        match self.threads {
+            0 => num_cpus::get(),
+            n => n,
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    io::{self, Cursor, Write},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use log::info;
+use paraseq::fastx;
+use rand::{RngExt, SeedableRng};
+use thousands::Separable;
+
+use crate::{
+    cli::{BenchmarkCommand, BinseqConfig, FileFormat},
+    commands::{
+        decode::{Decoder, SplitWriter},
+        encode::encode_collection,
+        temp_path,
+        utils::process_binseq,
+        OutputWriter, TempPaths,
+    },
+    types::BoxedReader,
+};
+
+/// Generates `num_records` random FASTQ records of length `slen`.
+///
+/// Sequences are drawn from `ACGT` (no `N`s, so the N-policy does not skew the
+/// timing) and qualities are drawn uniformly from Phred 2-40.
+fn synthetic_fastq(num_records: usize, slen: usize, seed: u64) -> Vec<u8> {
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+    let mut buf = Vec::with_capacity(num_records * (2 * slen + 24));
+    for idx in 0..num_records {
+        writeln!(buf, "@bench.{idx}").expect("writing to a Vec cannot fail");
+        buf.extend((0..slen).map(|_| b"ACGT"[rng.random_range(0..4)]));
+        buf.extend_from_slice(b"\n+\n");
+        buf.extend((0..slen).map(|_| rng.random_range(b'#'..=b'I')));
+        buf.push(b'\n');
+    }
+    buf
+}
+
+/// Throughput of a single benchmark phase
+struct Rate {
+    elapsed: Duration,
+    records: usize,
+    bytes: usize,
+}
+impl Rate {
+    fn records_per_sec(&self) -> f64 {
+        self.records as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    fn mb_per_sec(&self) -> f64 {
+        self.bytes as f64 / 1e6 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn pprint(&self, label: &str) {
+        println!(
+            "{label:<20}: {:.3}s, {} records/s, {:.1} MB/s",
+            self.elapsed.as_secs_f64(),
+            (self.records_per_sec().round() as usize).separate_with_underscores(),
+            self.mb_per_sec(),
+        );
+    }
+}
+
+/// Path the encoded benchmark file is written to
+fn output_path(args: &BenchmarkCommand, extension: &str) -> String {
+    if let Some(path) = &args.keep {
+        path.clone()
+    } else {
+        temp_path(&format!(
+            "bqtools-benchmark-{}{extension}",
+            std::process::id()
+        ))
+        .to_string_lossy()
+        .into_owned()
+    }
+}
+
+fn encode_phase(fastq: Vec<u8>, path: &str, args: &BenchmarkCommand) -> Result<Rate> {
+    let mode = args.options.mode.unwrap_or_default();
+    let config: BinseqConfig = args.options.into();
+    let bytes = fastq.len();
+
+    let start = Instant::now();
+    let reader: BoxedReader = Box::new(Cursor::new(fastq));
+    let collection = fastx::Collection::new(
+        vec![fastx::Reader::new(reader)?],
+        fastx::CollectionType::Single,
+    )?;
+    let (records, _skipped) = encode_collection(collection, Some(path), mode, config, None)?;
+    Ok(Rate {
+        elapsed: start.elapsed(),
+        records,
+        bytes,
+    })
+}
+
+fn decode_phase(path: &str, bytes: usize, threads: usize) -> Result<Rate> {
+    let start = Instant::now();
+    let writer = SplitWriter::new_interleaved(OutputWriter::Plain(Box::new(io::sink())));
+    let proc = Decoder::new(writer, FileFormat::Fastq, None);
+    process_binseq(path, proc.clone(), threads, None)?;
+    Ok(Rate {
+        elapsed: start.elapsed(),
+        records: proc.num_records(),
+        bytes,
+    })
+}
+
+pub fn run(args: &BenchmarkCommand) -> Result<()> {
+    let mode = args.options.mode.unwrap_or_default();
+    let threads = args.options.threads();
+    let path = output_path(args, mode.extension());
+
+    info!(
+        "Generating {} synthetic records of length {}",
+        args.synthetic.records.separate_with_underscores(),
+        args.synthetic.length
+    );
+    let fastq = synthetic_fastq(
+        args.synthetic.records,
+        args.synthetic.length,
+        args.synthetic.seed,
+    );
+    let input_size = fastq.len();
+
+    // Removes the encoded file however the benchmark exits (unless `--keep`)
+    let mut cleanup = TempPaths::new();
+    if args.keep.is_none() {
+        cleanup.push(&path);
+    }
+    let encode = encode_phase(fastq, &path, args)?;
+    let encoded_size = std::fs::metadata(&path)?.len();
+    let decode = decode_phase(&path, input_size, threads)?;
+    drop(cleanup);
+
+    println!("Mode                : {}", &mode.extension()[1..]);
+    println!("Threads             : {threads}");
+    println!(
+        "Records             : {}",
+        encode.records.separate_with_underscores()
+    );
+    println!("Sequence Length     : {}", args.synthetic.length);
+    println!(
+        "FASTQ Size          : {} bytes",
+        input_size.separate_with_underscores()
+    );
+    println!(
+        "Encoded Size        : {} bytes ({:.2}x smaller)",
+        encoded_size.separate_with_underscores(),
+        input_size as f64 / encoded_size.max(1) as f64
+    );
+    encode.pprint("Encode");
+    decode.pprint("Decode");
+    if let Some(keep) = &args.keep {
+        info!("Kept encoded file at: {keep}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::testutils::count_binseq;
+
+    #[test]
+    #[allow(clippy::naive_bytecount)]
+    fn test_synthetic_fastq_is_deterministic() {
+        let a = super::synthetic_fastq(10, 20, 7);
+        assert_eq!(a, super::synthetic_fastq(10, 20, 7));
+        assert_ne!(a, super::synthetic_fastq(10, 20, 8));
+        assert_eq!(a.iter().filter(|&&b| b == b'\n').count(), 40);
+    }
+
+    #[test]
+    fn test_benchmark_keeps_encoded_file() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::BenchmarkCommand::try_parse_from([
+                "benchmark",
+                "-n",
+                "2K",
+                "-L",
+                "50",
+                "-m",
+                &mode.extension()[1..],
+                "--keep",
+                out_tmp.path().to_str().unwrap(),
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(count_binseq(out_tmp.path())?, 2_000, "records for {mode:?}");
+        }
+        Ok(())
+    }
+}
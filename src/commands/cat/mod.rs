@@ -1,13 +1,17 @@
-use std::{fs::File, io::Write};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::mpsc::{self, SyncSender},
+    thread,
+};
 
 use anyhow::{bail, Result};
 use binseq::{bq, cbq, vbq, BinseqReader, BinseqWriterBuilder, ParallelReader};
-use log::{error, trace, warn};
-use memmap2::MmapOptions;
+use log::{debug, error, info, trace, warn};
 
 use crate::{
     cli::{BinseqMode, CatCommand},
-    commands::encode::processor::Encoder,
+    commands::{encode::processor::Encoder, open_binseq},
 };
 
 fn strip_header(path: &str) -> Result<bq::FileHeader> {
@@ -16,12 +20,22 @@ fn strip_header(path: &str) -> Result<bq::FileHeader> {
 }
 
 fn recover_header(paths: &[String]) -> Result<bq::FileHeader> {
-    let mut exp_header = None;
+    let mut exp_header: Option<bq::FileHeader> = None;
     for path in paths {
         let header = strip_header(path)?;
         if let Some(exp) = exp_header {
             if exp != header {
-                bail!("Inconsistent headers.");
+                let bits: u8 = header.bits.into();
+                let exp_bits: u8 = exp.bits.into();
+                bail!(
+                    "Inconsistent headers: {path} has slen={} xlen={} bitsize={} but expected slen={} xlen={} bitsize={}",
+                    header.slen,
+                    header.xlen,
+                    bits,
+                    exp.slen,
+                    exp.xlen,
+                    exp_bits,
+                );
             }
         } else {
             exp_header = Some(header);
@@ -33,7 +47,7 @@ fn recover_header(paths: &[String]) -> Result<bq::FileHeader> {
 fn determine_mode(paths: &[String]) -> Result<BinseqMode> {
     let mut mode = None;
     for path in paths {
-        let reader = BinseqReader::new(path)?;
+        let reader = open_binseq(path)?;
         if let Some(current_mode) = mode {
             match (current_mode, reader) {
                 (BinseqMode::Bq, BinseqReader::Bq(_))
@@ -57,19 +71,145 @@ fn determine_mode(paths: &[String]) -> Result<BinseqMode> {
     mode.ok_or_else(|| anyhow::anyhow!("No input files."))
 }
 
-fn run_bq(args: CatCommand) -> Result<()> {
+/// Size of the chunks BQ bodies are copied in
+const COPY_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Chunks buffered between the reader thread and the writer
+const CHUNKS_IN_FLIGHT: usize = 2;
+
+/// Sends the body of the BQ at `path` (everything after its header) in chunks.
+///
+/// Stops early without error if the receiving side has hung up.
+fn send_bq_body(path: &str, tx: &SyncSender<io::Result<Vec<u8>>>) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(bq::SIZE_HEADER as u64))?;
+    loop {
+        let mut chunk = Vec::with_capacity(COPY_CHUNK_SIZE);
+        let n = (&mut file)
+            .take(COPY_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)?;
+        if n == 0 || tx.send(Ok(chunk)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Copies the BQ bodies of `paths` into `out` in order, returning the bytes written.
+///
+/// A reader thread fills fixed-size chunks while the previous ones are
+/// written, so reads overlap writes (across file boundaries too) and at most
+/// `CHUNKS_IN_FLIGHT + 2` chunks are resident at once.
+fn copy_bq_bodies<W: Write>(paths: &[String], out: &mut W) -> Result<u64> {
+    let (tx, rx) = mpsc::sync_channel(CHUNKS_IN_FLIGHT);
+    let paths = paths.to_vec();
+    let reader = thread::spawn(move || {
+        for path in &paths {
+            if let Err(e) = send_bq_body(path, &tx) {
+                // the writer stops at the first error
+                let _ = tx.send(Err(io::Error::new(e.kind(), format!("{path}: {e}"))));
+                return;
+            }
+        }
+    });
+    let mut written = 0;
+    for chunk in rx {
+        let chunk = chunk?;
+        out.write_all(&chunk)?;
+        written += chunk.len() as u64;
+    }
+    reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("BQ reader thread panicked"))?;
+    Ok(written)
+}
+
+/// Concatenates BQ bodies byte-for-byte, returning the merged record count.
+fn run_bq(args: &CatCommand) -> Result<usize> {
     let header = recover_header(&args.input.input)?;
     let mut out_handle = args.output.as_writer()?;
 
-    header.write_bytes(&mut out_handle)?;
-    for path in args.input.input {
-        let file = File::open(path)?;
-        let mmap = unsafe { MmapOptions::new().map(&file)? };
-        out_handle.write_all(&mmap[bq::SIZE_HEADER..])?;
+    let mut num_records = 0;
+    for path in &args.input.input {
+        let file_records = open_binseq(path)?.num_records()?;
+        debug!("{path}: {file_records} records");
+        num_records += file_records;
     }
+    header.write_bytes(&mut out_handle)?;
+    copy_bq_bodies(&args.input.input, &mut out_handle)?;
     out_handle.flush()?;
+    drop(out_handle);
 
-    Ok(())
+    if args.report {
+        if let Some(opath) = args.output.output_path() {
+            let written = open_binseq(opath)?.num_records()?;
+            if written != num_records {
+                bail!("Merged BQ holds {written} records but inputs sum to {num_records}");
+            }
+        }
+    }
+
+    Ok(num_records)
+}
+
+/// Appends the BQ bodies of `input[1..]` onto `input[0]`, returning its new record count.
+///
+/// With `--check` nothing is written; the compatibility checks still run.
+fn run_bq_append(args: &CatCommand) -> Result<usize> {
+    let Some((target, others)) = args.input.input.split_first() else {
+        bail!("No input files.");
+    };
+    if others.is_empty() {
+        bail!("`--append` needs a file to append to and at least one file to append");
+    }
+    recover_header(&args.input.input)?;
+    let canonical_target = std::fs::canonicalize(target)?;
+    for path in others {
+        if std::fs::canonicalize(path)? == canonical_target {
+            bail!("Refusing to append {path} onto itself");
+        }
+    }
+
+    let mut num_records = open_binseq(target)?.num_records()?;
+    debug!("{target}: {num_records} records");
+    for path in others {
+        let file_records = open_binseq(path)?.num_records()?;
+        debug!("{path}: {file_records} records");
+        num_records += file_records;
+    }
+    if args.check {
+        return Ok(num_records);
+    }
+
+    append_bq_bodies(target, others, num_records)?;
+    Ok(num_records)
+}
+
+/// Appends the BQ bodies of `others` onto `target` and checks it then holds `num_records`.
+///
+/// On any failure `target` is truncated back to its original length, so a
+/// failed append never leaves it holding a partial body.
+fn append_bq_bodies(target: &str, others: &[String], num_records: usize) -> Result<()> {
+    let original_len = std::fs::metadata(target)?.len();
+    let result = (|| -> Result<()> {
+        let mut out_handle = BufWriter::new(OpenOptions::new().append(true).open(target)?);
+        copy_bq_bodies(others, &mut out_handle)?;
+        out_handle.flush()?;
+        drop(out_handle);
+
+        let written = open_binseq(target)?.num_records()?;
+        if written != num_records {
+            bail!("Appended BQ holds {written} records but inputs sum to {num_records}");
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        warn!("Append failed - restoring {target} to its original {original_len} bytes");
+        OpenOptions::new()
+            .write(true)
+            .open(target)?
+            .set_len(original_len)?;
+    }
+    result
 }
 
 fn record_vbq_header(paths: &[String]) -> Result<vbq::FileHeader> {
@@ -106,7 +246,8 @@ fn record_cbq_header(paths: &[String]) -> Result<cbq::FileHeader> {
     Ok(header)
 }
 
-fn run_cat(args: CatCommand, mode: BinseqMode) -> Result<()> {
+/// Re-encodes VBQ/CBQ records into a single writer, returning the merged record count.
+fn run_cat(args: &CatCommand, mode: BinseqMode) -> Result<usize> {
     // initialize output handle
     let ohandle = args.output.as_writer()?;
 
@@ -121,20 +262,43 @@ fn run_cat(args: CatCommand, mode: BinseqMode) -> Result<()> {
 
     // Concatenate
     let mut processor = Encoder::new(writer)?;
-    for path in args.input.input {
-        let reader = BinseqReader::new(&path)?;
+    for path in &args.input.input {
+        let reader = open_binseq(path)?;
+        debug!("{path}: {} records", reader.num_records()?);
         reader.process_parallel(processor.clone(), args.output.threads())?;
     }
     processor.finish()?;
-    Ok(())
+    Ok(processor.get_global_record_count())
 }
 
-pub fn run(args: CatCommand) -> Result<()> {
-    match determine_mode(&args.input.input)? {
+pub fn run(args: &CatCommand) -> Result<()> {
+    let report = args.report;
+    let num_inputs = args.input.input.len();
+    let mode = determine_mode(&args.input.input)?;
+    if args.append {
+        if mode != BinseqMode::Bq {
+            bail!("`--append` only supports BQ files (found {mode:?})");
+        }
+        let num_records = run_bq_append(args)?;
+        if args.check {
+            println!("{num_records}");
+        } else if report {
+            info!(
+                "Appended {} files; {num_records} records total",
+                num_inputs - 1
+            );
+        }
+        return Ok(());
+    }
+    let num_records = match mode {
         BinseqMode::Bq => run_bq(args),
         BinseqMode::Vbq => run_cat(args, BinseqMode::Vbq),
         BinseqMode::Cbq => run_cat(args, BinseqMode::Cbq),
+    }?;
+    if report {
+        info!("Concatenated {num_records} records from {num_inputs} files");
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -145,7 +309,10 @@ mod tests {
     use tempfile::NamedTempFile;
 
     use crate::cli::{BinseqMode, FileFormat};
-    use crate::testutils::{count_binseq, write_fastx, Compression, DEFAULT_NUM_RECORDS};
+    use crate::testutils::{
+        count_binseq, read_flags, write_fastx, write_flagged_binseq, Compression,
+        DEFAULT_NUM_RECORDS,
+    };
 
     fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
         let cmd = crate::cli::EncodeCommand::try_parse_from([
@@ -164,7 +331,7 @@ mod tests {
         }
         args.extend(["-o".to_string(), out_path.to_str().unwrap().to_string()]);
         let cmd = crate::cli::CatCommand::try_parse_from(args)?;
-        super::run(cmd)
+        super::run(&cmd)
     }
 
     /// Concatenating two N-record files must produce 2*N records.
@@ -215,6 +382,151 @@ mod tests {
         Ok(())
     }
 
+    /// The merged count returned for `--report` is the sum of per-file counts.
+    #[test]
+    fn test_cat_report_counts() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in1 = write_fastx().call()?;
+            let in2 = write_fastx().nrec(DEFAULT_NUM_RECORDS / 2).call()?;
+            let bq1 = NamedTempFile::with_suffix(mode.extension())?;
+            let bq2 = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in1.path(), bq1.path())?;
+            encode(in2.path(), bq2.path())?;
+
+            let out = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::CatCommand::try_parse_from([
+                "cat",
+                bq1.path().to_str().unwrap(),
+                bq2.path().to_str().unwrap(),
+                "-o",
+                out.path().to_str().unwrap(),
+                "--report",
+            ])?;
+            let num_records = match mode {
+                BinseqMode::Bq => super::run_bq(&cmd)?,
+                _ => super::run_cat(&cmd, mode)?,
+            };
+
+            let expected = DEFAULT_NUM_RECORDS + DEFAULT_NUM_RECORDS / 2;
+            assert_eq!(num_records, expected, "reported count wrong for {mode:?}");
+            assert_eq!(count_binseq(out.path())?, expected);
+        }
+        Ok(())
+    }
+
+    /// `--append` extends the first BQ in place; `--check` leaves it untouched.
+    #[test]
+    fn test_cat_append_bq() -> Result<()> {
+        let parts: Vec<_> = (0..3)
+            .map(|_| {
+                let in_tmp = write_fastx().call()?;
+                let bq = NamedTempFile::with_suffix(".bq")?;
+                encode(in_tmp.path(), bq.path())?;
+                Ok::<_, anyhow::Error>((in_tmp, bq))
+            })
+            .collect::<Result<_>>()?;
+        let paths: Vec<_> = parts
+            .iter()
+            .map(|(_, bq)| bq.path().to_str().unwrap())
+            .collect();
+        let append = |extra: &[&str]| {
+            let mut args = vec!["cat", "--append"];
+            args.extend_from_slice(extra);
+            args.extend_from_slice(&paths);
+            super::run(&crate::cli::CatCommand::try_parse_from(args)?)
+        };
+
+        append(&["--check"])?;
+        assert_eq!(count_binseq(parts[0].1.path())?, DEFAULT_NUM_RECORDS);
+        append(&[])?;
+        assert_eq!(count_binseq(parts[0].1.path())?, DEFAULT_NUM_RECORDS * 3);
+        Ok(())
+    }
+
+    /// A failed append leaves the target byte-identical to before.
+    #[test]
+    fn test_cat_append_restores_target_on_error() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let (target, other) = (
+            NamedTempFile::with_suffix(".bq")?,
+            NamedTempFile::with_suffix(".bq")?,
+        );
+        encode(in_tmp.path(), target.path())?;
+        encode(in_tmp.path(), other.path())?;
+        let original = std::fs::read(target.path())?;
+
+        // the first body is copied before the missing input fails the append
+        let others = [
+            other.path().to_str().unwrap().to_string(),
+            target
+                .path()
+                .with_extension("missing.bq")
+                .to_str()
+                .unwrap()
+                .to_string(),
+        ];
+        let result = super::append_bq_bodies(
+            target.path().to_str().unwrap(),
+            &others,
+            DEFAULT_NUM_RECORDS * 3,
+        );
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(target.path())?, original);
+        Ok(())
+    }
+
+    /// `--append` refuses VBQ inputs and BQ inputs with a different sequence length.
+    #[test]
+    fn test_cat_append_rejects_incompatible() -> Result<()> {
+        let append = |paths: &[&std::path::Path]| {
+            let mut args = vec!["cat", "--append"];
+            args.extend(paths.iter().map(|p| p.to_str().unwrap()));
+            super::run(&crate::cli::CatCommand::try_parse_from(args)?)
+        };
+        let in_tmp = write_fastx().call()?;
+        let bq = NamedTempFile::with_suffix(".bq")?;
+        let vbq = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq.path())?;
+        encode(in_tmp.path(), vbq.path())?;
+        assert!(append(&[bq.path(), vbq.path()]).is_err());
+
+        let vbq2 = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), vbq2.path())?;
+        assert!(append(&[vbq.path(), vbq2.path()]).is_err());
+
+        let short = write_fastx().slen(50).call()?;
+        let short_bq = NamedTempFile::with_suffix(".bq")?;
+        encode(short.path(), short_bq.path())?;
+        assert!(append(&[bq.path(), short_bq.path()]).is_err());
+        assert_eq!(count_binseq(bq.path())?, DEFAULT_NUM_RECORDS);
+        Ok(())
+    }
+
+    /// The chunked BQ copy writes the header once followed by each input's body.
+    #[test]
+    fn test_cat_bq_byte_identical() -> Result<()> {
+        let parts: Vec<_> = (0..3)
+            .map(|_| {
+                let in_tmp = write_fastx().call()?;
+                let bq = NamedTempFile::with_suffix(".bq")?;
+                encode(in_tmp.path(), bq.path())?;
+                Ok::<_, anyhow::Error>((in_tmp, bq))
+            })
+            .collect::<Result<_>>()?;
+        let bq_paths: Vec<_> = parts.iter().map(|(_, bq)| bq.path()).collect();
+        let out = NamedTempFile::with_suffix(".bq")?;
+        cat(&bq_paths, out.path())?;
+
+        let mut expected = Vec::new();
+        for (i, path) in bq_paths.iter().enumerate() {
+            let bytes = std::fs::read(path)?;
+            let start = if i == 0 { 0 } else { binseq::bq::SIZE_HEADER };
+            expected.extend_from_slice(&bytes[start..]);
+        }
+        assert_eq!(std::fs::read(out.path())?, expected);
+        Ok(())
+    }
+
     #[test]
     fn test_cat_compressed_inputs() -> Result<()> {
         for (mode, comp) in iproduct!(BinseqMode::enum_iter(), Compression::all()) {
@@ -236,4 +548,21 @@ mod tests {
         }
         Ok(())
     }
+
+    /// Re-encoded VBQ/CBQ concatenation keeps every record's flag.
+    #[test]
+    fn test_cat_preserves_flags() -> Result<()> {
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let a = write_flagged_binseq(mode, DEFAULT_NUM_RECORDS)?;
+            let b = write_flagged_binseq(mode, DEFAULT_NUM_RECORDS)?;
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            cat(&[a.path(), b.path()], out_tmp.path())?;
+            // both inputs carry flags 1..=N
+            let expected: Vec<u64> = (1..=DEFAULT_NUM_RECORDS as u64)
+                .flat_map(|flag| [flag, flag])
+                .collect();
+            assert_eq!(read_flags(out_tmp.path())?, expected, "{mode:?}");
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,365 @@
+use std::{io::Write, ops::Range, sync::Arc};
+
+use anyhow::{bail, Result};
+use binseq::{bq, BinseqRecord, BinseqWriter, ParallelProcessor, SequencingRecordBuilder};
+use log::info;
+use parking_lot::Mutex;
+use rand::{rngs::SmallRng, RngExt, SeedableRng};
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+use crate::{
+    cli::{BinseqMode, CorruptCommand},
+    commands::{load_block_spans, match_output, revcomp::get_builder, utils::process_binseq},
+};
+
+/// Substitutes each base of `seq` with a different nucleotide with probability `rate`.
+///
+/// Returns the number of substituted bases.
+fn mutate_bases(seq: &mut [u8], rng: &mut SmallRng, rate: f64) -> usize {
+    let mut mutated = 0;
+    for base in seq.iter_mut() {
+        if !rng.random_bool(rate) {
+            continue;
+        }
+        *base = match b"ACGT".iter().position(|b| b == base) {
+            // draw from the three other bases
+            Some(current) => {
+                let draw = rng.random_range(0..3);
+                b"ACGT"[if draw >= current { draw + 1 } else { draw }]
+            }
+            None => b"ACGT"[rng.random_range(0..4)],
+        };
+        mutated += 1;
+    }
+    mutated
+}
+
+/// XOR-flips bytes within `ranges` of `data` with probability `rate`.
+///
+/// Returns the number of flipped bytes.
+fn flip_bytes(data: &mut [u8], ranges: &[Range<usize>], seed: u64, rate: f64) -> usize {
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut flipped = 0;
+    for range in ranges {
+        for byte in &mut data[range.clone()] {
+            if rng.random_bool(rate) {
+                *byte ^= rng.random_range(1..=u8::MAX);
+                flipped += 1;
+            }
+        }
+    }
+    flipped
+}
+
+/// Byte ranges holding record data: the blocks of a VBQ/CBQ file, or everything
+/// after the header of a BQ file.
+#[allow(clippy::single_range_in_vec_init)]
+fn record_byte_ranges(path: &str, file_len: usize) -> Result<Vec<Range<usize>>> {
+    Ok(match load_block_spans(path)? {
+        Some(blocks) => blocks
+            .iter()
+            .map(|block| block.offset as usize..(block.offset + block.len) as usize)
+            .collect(),
+        None => vec![bq::SIZE_HEADER.min(file_len)..file_len],
+    })
+}
+
+/// Re-encodes records after substituting bases at a fixed rate.
+pub struct CorruptProcessor<W: Write + Send> {
+    rate: f64,
+    seed: u64,
+
+    /// Thread-local writer and counts
+    t_writer: BinseqWriter<Vec<u8>>,
+    t_count: usize,
+    t_mutated: usize,
+
+    /// Thread-local scratch buffers for the mutated sequences
+    sseq: Vec<u8>,
+    xseq: Vec<u8>,
+
+    /// Global writer and counts
+    writer: Arc<Mutex<BinseqWriter<W>>>,
+    count: Arc<Mutex<usize>>,
+    mutated: Arc<Mutex<usize>>,
+}
+impl<W: Write + Send> Clone for CorruptProcessor<W> {
+    fn clone(&self) -> Self {
+        Self {
+            rate: self.rate,
+            seed: self.seed,
+            t_writer: self.t_writer.clone(),
+            t_count: 0,
+            t_mutated: 0,
+            sseq: Vec::new(),
+            xseq: Vec::new(),
+            writer: self.writer.clone(),
+            count: self.count.clone(),
+            mutated: self.mutated.clone(),
+        }
+    }
+}
+impl<W: Write + Send> CorruptProcessor<W> {
+    pub fn new(writer: BinseqWriter<W>, rate: f64, seed: u64) -> binseq::Result<Self> {
+        let t_writer = writer.new_headless_buffer()?;
+        Ok(Self {
+            rate,
+            seed,
+            t_writer,
+            t_count: 0,
+            t_mutated: 0,
+            sseq: Vec::new(),
+            xseq: Vec::new(),
+            writer: Arc::new(Mutex::new(writer)),
+            count: Arc::new(Mutex::new(0)),
+            mutated: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    pub fn finish(&mut self) -> binseq::Result<()> {
+        self.writer.lock().finish()
+    }
+
+    pub fn get_global_record_count(&self) -> usize {
+        *self.count.lock()
+    }
+
+    pub fn get_global_mutated_count(&self) -> usize {
+        *self.mutated.lock()
+    }
+}
+
+impl<W: Write + Send> ParallelProcessor for CorruptProcessor<W> {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        // Seeding per record keeps the mutations independent of thread scheduling
+        let mut rng =
+            SmallRng::seed_from_u64(xxh3_64_with_seed(&record.index().to_le_bytes(), self.seed));
+        self.sseq.clear();
+        self.sseq.extend_from_slice(record.sseq());
+        self.t_mutated += mutate_bases(&mut self.sseq, &mut rng, self.rate);
+
+        let squal = record.has_quality().then(|| record.squal());
+        let rec = if record.is_paired() {
+            self.xseq.clear();
+            self.xseq.extend_from_slice(record.xseq());
+            self.t_mutated += mutate_bases(&mut self.xseq, &mut rng, self.rate);
+            SequencingRecordBuilder::default()
+                .s_seq(&self.sseq)
+                .opt_s_qual(squal)
+                .s_header(record.sheader())
+                .x_seq(&self.xseq)
+                .opt_x_qual(record.has_quality().then(|| record.xqual()))
+                .x_header(record.xheader())
+                .opt_flag(record.flag())
+                .build()?
+        } else {
+            SequencingRecordBuilder::default()
+                .s_seq(&self.sseq)
+                .opt_s_qual(squal)
+                .s_header(record.sheader())
+                .opt_flag(record.flag())
+                .build()?
+        };
+        if self.t_writer.push(rec)? {
+            self.t_count += 1;
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        *self.count.lock() += self.t_count;
+        *self.mutated.lock() += self.t_mutated;
+        self.t_count = 0;
+        self.t_mutated = 0;
+        self.writer.lock().ingest_completed(&mut self.t_writer)
+    }
+
+    fn on_thread_complete(&mut self) -> binseq::Result<()> {
+        self.writer.lock().ingest(&mut self.t_writer)
+    }
+}
+
+fn run_raw(args: &CorruptCommand) -> Result<()> {
+    if args.input.span.is_some() {
+        bail!("`--span` is not supported with `--raw`");
+    }
+    let mut data = std::fs::read(args.input.path())?;
+    let ranges = record_byte_ranges(args.input.path(), data.len())?;
+    let flipped = flip_bytes(&mut data, &ranges, args.options.seed, args.options.rate);
+    let mut writer = match_output(Some(&args.output))?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    info!(
+        "Flipped {flipped} bytes in record blocks; wrote: {}",
+        args.output
+    );
+    Ok(())
+}
+
+pub fn run(args: &CorruptCommand) -> Result<()> {
+    if args.output == args.input.path() {
+        bail!("Output path is the same as the input");
+    }
+    if args.options.raw {
+        return run_raw(args);
+    }
+    let mode = args.input.mode()?;
+    if BinseqMode::determine(&args.output).is_ok_and(|omode| omode != mode) {
+        bail!(
+            "Output path {} does not match the input format ({})",
+            args.output,
+            mode.extension()
+        );
+    }
+    let builder = get_builder(&args.input)?;
+    let writer = builder.build(match_output(Some(&args.output))?)?;
+    let mut proc = CorruptProcessor::new(writer, args.options.rate, args.options.seed)?;
+    process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.options.threads(),
+        args.input.span,
+    )?;
+    proc.finish()?;
+    info!(
+        "Substituted {} bases across {} records; wrote: {}",
+        proc.get_global_mutated_count(),
+        proc.get_global_record_count(),
+        args.output
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use super::{flip_bytes, mutate_bases};
+    use crate::cli::BinseqMode;
+    use crate::testutils::{
+        count_binseq, read_flags, write_fastx, write_flagged_binseq, DEFAULT_NUM_RECORDS,
+    };
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_mutate_bases_always_changes_base() {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(1);
+        let original = b"ACGTNACGT".to_vec();
+        let mut seq = original.clone();
+        assert_eq!(mutate_bases(&mut seq, &mut rng, 1.0), seq.len());
+        for (before, after) in original.iter().zip(&seq) {
+            assert_ne!(before, after);
+            assert!(b"ACGT".contains(after));
+        }
+        assert_eq!(mutate_bases(&mut seq, &mut rng, 0.0), 0);
+    }
+
+    #[test]
+    fn test_flip_bytes_stays_in_ranges() {
+        let mut data = vec![0u8; 100];
+        let flipped = flip_bytes(&mut data, &[10..20, 50..60], 7, 1.0);
+        assert_eq!(flipped, 20);
+        for (idx, byte) in data.iter().enumerate() {
+            let inside = (10..20).contains(&idx) || (50..60).contains(&idx);
+            assert_eq!(*byte != 0, inside, "byte {idx}");
+        }
+    }
+
+    /// Substitution keeps every record readable and is reproducible for a seed.
+    #[test]
+    fn test_corrupt_is_seed_reproducible() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                bq_tmp.path().to_str().unwrap(),
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let mut outputs = Vec::new();
+            for threads in ["1", "1", "4"] {
+                let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+                let cmd = crate::cli::CorruptCommand::try_parse_from([
+                    "corrupt",
+                    bq_tmp.path().to_str().unwrap(),
+                    "-o",
+                    out_tmp.path().to_str().unwrap(),
+                    "--rate",
+                    "0.05",
+                    "-T",
+                    threads,
+                ])?;
+                super::run(&cmd)?;
+                assert_eq!(count_binseq(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+                outputs.push(out_tmp);
+            }
+            // identical single-threaded runs produce identical files
+            assert_eq!(
+                std::fs::read(outputs[0].path())?,
+                std::fs::read(outputs[1].path())?,
+                "{mode:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_raw_preserves_header_and_index() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::CorruptCommand::try_parse_from([
+            "corrupt",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--raw",
+            "--rate",
+            "0.01",
+        ])?;
+        super::run(&cmd)?;
+
+        let original = std::fs::read(bq_tmp.path())?;
+        let corrupted = std::fs::read(out_tmp.path())?;
+        assert_eq!(original.len(), corrupted.len());
+        assert_ne!(original, corrupted);
+        // the block index is untouched, so the corrupted file still opens
+        let blocks = crate::commands::load_block_spans(out_tmp.path().to_str().unwrap())?;
+        assert!(blocks.is_some_and(|b| !b.is_empty()));
+        Ok(())
+    }
+
+    /// Mutated records keep their flags.
+    #[test]
+    fn test_corrupt_preserves_flags() -> Result<()> {
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let in_tmp = write_flagged_binseq(mode, DEFAULT_NUM_RECORDS)?;
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::CorruptCommand::try_parse_from([
+                "corrupt",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--rate",
+                "0.05",
+            ])?;
+            super::run(&cmd)?;
+            let expected: Vec<u64> = (1..=DEFAULT_NUM_RECORDS as u64).collect();
+            assert_eq!(read_flags(out_tmp.path())?, expected, "{mode:?}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+
+use anyhow::{anyhow, bail, Result};
+use binseq::prelude::*;
+use paraseq::rust_htslib::bam::{self, header::HeaderRecord};
+
+use crate::cli::{mate_id, Mate, OutputFile, Span};
+use crate::commands::{
+    check_clobber,
+    sample::IndexSampler,
+    utils::{open_binseq, process_binseq},
+};
+
+/// Template having multiple segments
+const FLAG_PAIRED: u16 = 0x1;
+/// First segment in the template
+const FLAG_READ1: u16 = 0x40;
+/// Last segment in the template
+const FLAG_READ2: u16 = 0x80;
+
+/// BAM sentinel for a base without a quality score
+const MISSING_QUAL: u8 = 0xFF;
+
+/// An unaligned read handed from the decoding workers to the BAM writer thread
+struct BamRead {
+    qname: Vec<u8>,
+    seq: Vec<u8>,
+    /// Raw Phred scores (no `+33` offset)
+    qual: Vec<u8>,
+    flag: u16,
+}
+impl BamRead {
+    fn new(header: &[u8], seq: &[u8], qual: Option<&[u8]>, flag: u16) -> Self {
+        Self {
+            qname: mate_id(header).to_vec(),
+            seq: seq.to_vec(),
+            qual: qual.map_or_else(
+                || vec![MISSING_QUAL; seq.len()],
+                |qual| qual.iter().map(|q| q.saturating_sub(b'!')).collect(),
+            ),
+            flag,
+        }
+    }
+}
+
+/// Decodes BINSEQ records into unaligned BAM records.
+///
+/// `rust_htslib` writers cannot be shared across threads, so workers collect
+/// each batch of reads and send it to a single writer thread (see [`run_bam`]).
+#[derive(Clone)]
+struct BamDecoder {
+    batch: Vec<BamRead>,
+    mate: Option<Mate>,
+    sampler: Option<IndexSampler>,
+    sender: SyncSender<Vec<BamRead>>,
+}
+impl BamDecoder {
+    fn push<B: BinseqRecord>(&mut self, record: &B, primary: bool, flag: u16) {
+        let has_quality = record.has_quality();
+        let read = if primary {
+            BamRead::new(
+                record.sheader(),
+                record.sseq(),
+                has_quality.then(|| record.squal()),
+                flag,
+            )
+        } else {
+            BamRead::new(
+                record.xheader(),
+                record.xseq(),
+                has_quality.then(|| record.xqual()),
+                flag,
+            )
+        };
+        self.batch.push(read);
+    }
+}
+impl ParallelProcessor for BamDecoder {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        if self
+            .sampler
+            .is_some_and(|sampler| !sampler.includes(record.index()))
+        {
+            return Ok(());
+        }
+        match self.mate {
+            None | Some(Mate::One) => self.push(&record, true, 0),
+            Some(Mate::Two) => self.push(&record, false, 0),
+            Some(Mate::Both) => {
+                self.push(&record, true, FLAG_PAIRED | FLAG_READ1);
+                self.push(&record, false, FLAG_PAIRED | FLAG_READ2);
+            }
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        // fails only if the writer thread stopped, which reports its own error
+        self.sender
+            .send(std::mem::take(&mut self.batch))
+            .map_err(|_| std::io::Error::other("BAM writer stopped"))?;
+        Ok(())
+    }
+}
+
+/// Opens an unaligned BAM writer on `-o` (SAM for `.sam` paths) or stdout
+fn open_bam_writer(args: &OutputFile) -> Result<bam::Writer> {
+    let mut header = bam::Header::new();
+    let mut hd = HeaderRecord::new(b"HD");
+    hd.push_tag(b"VN", "1.6").push_tag(b"SO", "unsorted");
+    header.push_record(&hd);
+    let mut pg = HeaderRecord::new(b"PG");
+    pg.push_tag(b"ID", "bqtools")
+        .push_tag(b"PN", "bqtools")
+        .push_tag(b"VN", env!("CARGO_PKG_VERSION"));
+    header.push_record(&pg);
+
+    let mut writer = match args.output_path() {
+        Some(path) if path.ends_with(".cram") => {
+            bail!("CRAM output is not supported; write `.bam` or `.sam` instead")
+        }
+        Some(path) => {
+            check_clobber(path)?;
+            let format = if path.ends_with(".sam") {
+                bam::Format::Sam
+            } else {
+                bam::Format::Bam
+            };
+            bam::Writer::from_path(path, &header, format)?
+        }
+        None => bam::Writer::from_stdout(&header, bam::Format::Bam)?,
+    };
+    writer.set_threads(args.threads())?;
+    Ok(writer)
+}
+
+/// Writes every received batch as unmapped records, returning the number written
+fn write_bam(args: &OutputFile, receiver: Receiver<Vec<BamRead>>) -> Result<usize> {
+    let mut writer = open_bam_writer(args)?;
+    let mut record = bam::Record::new();
+    let mut num_records = 0;
+    for batch in receiver {
+        for read in batch {
+            record.set(&read.qname, None, &read.seq, &read.qual);
+            record.set_flags(read.flag);
+            record.set_tid(-1);
+            record.set_pos(-1);
+            record.set_mtid(-1);
+            record.set_mpos(-1);
+            writer.write(&record)?;
+            num_records += 1;
+        }
+    }
+    Ok(num_records)
+}
+
+/// Decodes the BINSEQ file at `path` to an unaligned BAM, returning the number
+/// of BAM records written.
+///
+/// Paired files decoding both mates write each pair as two records flagged
+/// `0x1|0x40` and `0x1|0x80` under the same name; all other records have flag 0.
+pub fn run_bam(
+    args: &OutputFile,
+    path: &str,
+    span: Option<Span>,
+    sampler: Option<IndexSampler>,
+) -> Result<usize> {
+    if args.prefix.is_some() {
+        bail!("BAM output holds both mates in one file; use `-o` instead of `-p`");
+    }
+    let paired = open_binseq(path)?.is_paired();
+    let threads = args.threads();
+    let (sender, receiver) = sync_channel(2 * threads);
+    let output = args.clone();
+    let writer = std::thread::spawn(move || write_bam(&output, receiver));
+    let proc = BamDecoder {
+        batch: Vec::new(),
+        mate: paired.then(|| args.mate()),
+        sampler,
+        sender,
+    };
+    let processed = process_binseq(path, proc, threads, span);
+    // every sender is dropped by now, so the writer drains and returns
+    let num_records = writer
+        .join()
+        .map_err(|_| anyhow!("BAM writer thread panicked"))??;
+    processed?;
+    Ok(num_records)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use paraseq::rust_htslib::bam::{self, Read};
+    use tempfile::NamedTempFile;
+
+    use super::{FLAG_PAIRED, FLAG_READ1, FLAG_READ2};
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    /// Number of records in a BAM/SAM file and the flags of the first two
+    fn read_bam_flags(path: &str) -> Result<(usize, Vec<u16>)> {
+        let mut reader = bam::Reader::from_path(path)?;
+        let mut flags = Vec::new();
+        let mut count = 0;
+        for record in reader.records() {
+            let record = record?;
+            if flags.len() < 2 {
+                flags.push(record.flags());
+            }
+            count += 1;
+        }
+        Ok((count, flags))
+    }
+
+    fn decode_bam(input: &std::path::Path, extra: &[&str]) -> Result<NamedTempFile> {
+        let out_tmp = NamedTempFile::with_suffix(".bam")?;
+        let mut args = vec![
+            "decode",
+            input.to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let cmd = crate::cli::DecodeCommand::try_parse_from(args)?;
+        crate::commands::decode::run(&cmd)?;
+        Ok(out_tmp)
+    }
+
+    /// Single-end records get flag 0; pairs get the paired and first/last segment bits.
+    #[test]
+    fn test_decode_bam_flags() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let single = NamedTempFile::with_suffix(".cbq")?;
+        let paired = NamedTempFile::with_suffix(".cbq")?;
+        for (inputs, output) in [(vec![&r1], &single), (vec![&r1, &r2], &paired)] {
+            let mut args = vec!["encode"];
+            args.extend(inputs.iter().map(|f| f.path().to_str().unwrap()));
+            args.extend(["-o", output.path().to_str().unwrap()]);
+            let cmd = crate::cli::EncodeCommand::try_parse_from(args)?;
+            crate::commands::encode::run(&cmd)?;
+        }
+
+        let out = decode_bam(single.path(), &[])?;
+        let (count, flags) = read_bam_flags(out.path().to_str().unwrap())?;
+        assert_eq!(count, DEFAULT_NUM_RECORDS);
+        assert_eq!(flags, [0, 0]);
+
+        let out = decode_bam(paired.path(), &["-T", "1"])?;
+        let (count, flags) = read_bam_flags(out.path().to_str().unwrap())?;
+        assert_eq!(count, 2 * DEFAULT_NUM_RECORDS);
+        assert_eq!(flags, [FLAG_PAIRED | FLAG_READ1, FLAG_PAIRED | FLAG_READ2]);
+
+        let out = decode_bam(paired.path(), &["-m", "2"])?;
+        let (count, flags) = read_bam_flags(out.path().to_str().unwrap())?;
+        assert_eq!(count, DEFAULT_NUM_RECORDS);
+        assert_eq!(flags, [0, 0]);
+        Ok(())
+    }
+}
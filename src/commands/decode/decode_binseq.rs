@@ -4,8 +4,72 @@ use binseq::prelude::*;
 use binseq::Result;
 use parking_lot::Mutex;
 
-use super::{write_record_pair, SplitWriter};
-use crate::cli::{FileFormat, Mate};
+use super::{
+    utils::{write_header_comment, write_tsv_columns, TsvFields, TSV_DELIMITER},
+    write_record, write_record_pair, write_tsv_n_count, SplitWriter,
+};
+use crate::cli::{
+    mate_id, CommentField, FileFormat, LengthCombine, Mate, OnOrphan, RevcompMates, SynthQual,
+    TsvColumn,
+};
+use crate::commands::{
+    grep::FlagExpr,
+    merge_pairs::overlap::{find_overlap, merge_pair},
+    revcomp::reverse_complement,
+    sample::IndexSampler,
+};
+
+/// Keeps either complete pairs or singletons (`--paired-only`/`--singletons-only`).
+///
+/// Records of single-end files and records whose flag has any `singleton_mask`
+/// bits set are singletons.
+#[derive(Clone, Copy, Debug)]
+pub struct PairFilter {
+    pub singletons: bool,
+    pub singleton_mask: u64,
+}
+impl PairFilter {
+    pub fn includes<B: BinseqRecord>(&self, record: &B) -> bool {
+        let singleton = !record.is_paired()
+            || record
+                .flag()
+                .is_some_and(|flag| flag & self.singleton_mask != 0);
+        singleton == self.singletons
+    }
+}
+
+/// Keeps records whose sequence length is within `min..=max` (`--min-length`/`--max-length`).
+///
+/// Paired records are checked per mate or by their summed length (see [`LengthCombine`]).
+#[derive(Clone, Copy, Debug)]
+pub struct LengthFilter {
+    pub min: u64,
+    pub max: u64,
+    pub combine: LengthCombine,
+}
+impl LengthFilter {
+    pub fn includes<B: BinseqRecord>(&self, record: &B) -> bool {
+        let within = |len: u64| (self.min..=self.max).contains(&len);
+        if !record.is_paired() {
+            return within(record.slen());
+        }
+        match self.combine {
+            LengthCombine::Each => within(record.slen()) && within(record.xlen()),
+            LengthCombine::Sum => within(record.slen() + record.xlen()),
+        }
+    }
+}
+
+/// Placeholder mate written for orphaned paired records (`--on-orphan pad`)
+const ORPHAN_PAD_SEQ: &[u8] = b"N";
+const ORPHAN_PAD_QUAL: &[u8] = b"!";
+
+/// Overlap thresholds for merging pairs into single records (`--merge-overlap`).
+#[derive(Clone, Copy, Debug)]
+pub struct MergeOverlap {
+    pub min_overlap: usize,
+    pub max_mismatch_rate: f64,
+}
 
 /// A struct for decoding BINSEQ data back to FASTQ format.
 #[derive(Clone)]
@@ -21,15 +85,45 @@ pub struct Decoder {
     squal: Vec<u8>,
     /// Quality buffer (extended)
     xqual: Vec<u8>,
+    /// Header buffers for headers with comments
+    sheader: Vec<u8>,
+    xheader: Vec<u8>,
+    /// Reverse complemented R2 and merged record buffers (`--merge-overlap`)
+    rcseq: Vec<u8>,
+    rcqual: Vec<u8>,
+    mseq: Vec<u8>,
+    mqual: Vec<u8>,
+    /// Reverse complemented mate buffers (`--revcomp`)
+    rsseq: Vec<u8>,
+    rsqual: Vec<u8>,
+    rxseq: Vec<u8>,
+    rxqual: Vec<u8>,
 
     /// Options
     format: FileFormat,
     mate: Option<Mate>,
     is_split: bool,
+    sampler: Option<IndexSampler>,
+    n_count: bool,
+    pair_filter: Option<PairFilter>,
+    length_filter: Option<LengthFilter>,
+    flag_where: Option<FlagExpr>,
+    on_orphan: Option<OnOrphan>,
+    verify_pairing: bool,
+    synth_qual: Option<SynthQual>,
+    revcomp: RevcompMates,
+    comment_fields: Vec<CommentField>,
+    tsv_columns: Vec<TsvColumn>,
+    delimiter: u8,
+    merge_overlap: Option<MergeOverlap>,
+    defer_flush: bool,
 
     /// Global values
     global_writer: Arc<Mutex<SplitWriter>>,
     num_records: Arc<Mutex<usize>>,
+    /// Records dropped by `length_filter`
+    local_length_skipped: usize,
+    length_skipped: Arc<Mutex<usize>>,
 }
 
 impl Decoder {
@@ -41,27 +135,391 @@ impl Decoder {
             local_count: 0,
             squal: Vec::new(),
             xqual: Vec::new(),
+            sheader: Vec::new(),
+            xheader: Vec::new(),
+            rcseq: Vec::new(),
+            rcqual: Vec::new(),
+            mseq: Vec::new(),
+            mqual: Vec::new(),
+            rsseq: Vec::new(),
+            rsqual: Vec::new(),
+            rxseq: Vec::new(),
+            rxqual: Vec::new(),
             format,
             mate,
             is_split: writer.is_split(),
+            sampler: None,
+            n_count: false,
+            pair_filter: None,
+            length_filter: None,
+            flag_where: None,
+            on_orphan: None,
+            verify_pairing: false,
+            synth_qual: None,
+            revcomp: RevcompMates::None,
+            comment_fields: Vec::new(),
+            tsv_columns: Vec::new(),
+            delimiter: TSV_DELIMITER,
+            merge_overlap: None,
+            defer_flush: false,
             global_writer: Arc::new(Mutex::new(writer)),
             num_records: Arc::new(Mutex::new(0)),
+            local_length_skipped: 0,
+            length_skipped: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Only decode the records selected by `sampler`
+    #[must_use]
+    pub fn with_sampler(mut self, sampler: Option<IndexSampler>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Append an `n_count` column to each TSV row
+    #[must_use]
+    pub fn with_n_count(mut self, n_count: bool) -> Self {
+        self.n_count = n_count;
+        self
+    }
+
+    /// Writes the selected mates of a record as TSV rows with an `n_count` column
+    fn write_n_count_rows<B: BinseqRecord>(&mut self, record: &B) -> std::io::Result<()> {
+        let (sheader, sbuf) = (record.sheader(), record.sseq());
+        let (xheader, xbuf) = (record.xheader(), record.xseq());
+        let delimiter = self.delimiter;
+        match self.mate {
+            Some(Mate::Both) => {
+                if self.is_split {
+                    write_tsv_n_count(&mut self.left, sheader, sbuf, delimiter)?;
+                    if !xbuf.is_empty() {
+                        write_tsv_n_count(&mut self.right, xheader, xbuf, delimiter)?;
+                    }
+                } else {
+                    write_tsv_n_count(&mut self.mixed, sheader, sbuf, delimiter)?;
+                    if !xbuf.is_empty() {
+                        write_tsv_n_count(&mut self.mixed, xheader, xbuf, delimiter)?;
+                    }
+                }
+            }
+            Some(Mate::One) | None => write_tsv_n_count(&mut self.mixed, sheader, sbuf, delimiter)?,
+            Some(Mate::Two) => write_tsv_n_count(&mut self.mixed, xheader, xbuf, delimiter)?,
+        }
+        Ok(())
+    }
+
+    /// Only decode complete pairs or only singletons (see [`PairFilter`])
+    #[must_use]
+    pub fn with_pair_filter(mut self, pair_filter: Option<PairFilter>) -> Self {
+        self.pair_filter = pair_filter;
+        self
+    }
+
+    /// Only decode records whose length is within bounds (see [`LengthFilter`])
+    #[must_use]
+    pub fn with_length_filter(mut self, length_filter: Option<LengthFilter>) -> Self {
+        self.length_filter = length_filter;
+        self
+    }
+
+    /// Only decode records whose flag satisfies `flag_where` (see [`FlagExpr`])
+    #[must_use]
+    pub fn with_flag_where(mut self, flag_where: Option<FlagExpr>) -> Self {
+        self.flag_where = flag_where;
+        self
+    }
+
+    /// Handle paired records with an empty second mate (see [`OnOrphan`])
+    #[must_use]
+    pub fn with_on_orphan(mut self, on_orphan: Option<OnOrphan>) -> Self {
+        self.on_orphan = on_orphan;
+        self
+    }
+
+    /// Fail on pairs whose mates have different IDs (see [`mate_id`])
+    #[must_use]
+    pub fn with_verify_pairing(mut self, verify_pairing: bool) -> Self {
+        self.verify_pairing = verify_pairing;
+        self
+    }
+
+    /// Synthesize qualities for records stored without them (see [`SynthQual`])
+    #[must_use]
+    pub fn with_synth_qual(mut self, synth_qual: Option<SynthQual>) -> Self {
+        self.synth_qual = synth_qual;
+        self
+    }
+
+    /// Reverse complement the selected mates before writing
+    #[must_use]
+    pub fn with_revcomp(mut self, revcomp: RevcompMates) -> Self {
+        self.revcomp = revcomp;
+        self
+    }
+
+    /// Append derived fields to each header as a comment (see [`write_header_comment`])
+    #[must_use]
+    pub fn with_comment_fields(mut self, comment_fields: Vec<CommentField>) -> Self {
+        self.comment_fields = comment_fields;
+        self
+    }
+
+    /// Write one TSV row of the selected columns per record (see [`write_tsv_columns`])
+    #[must_use]
+    pub fn with_tsv_columns(mut self, tsv_columns: Vec<TsvColumn>) -> Self {
+        self.tsv_columns = tsv_columns;
+        self
+    }
+
+    /// Separate TSV fields with `delimiter` instead of a tab
+    #[must_use]
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Write overlapping pairs as a single merged record (see [`merge_pair`])
+    #[must_use]
+    pub fn with_merge_overlap(mut self, merge_overlap: Option<MergeOverlap>) -> Self {
+        self.merge_overlap = merge_overlap;
+        self
+    }
+
+    /// Skip the per-batch flush, leaving it to [`Self::finish`]
+    #[must_use]
+    pub fn with_defer_flush(mut self, defer_flush: bool) -> Self {
+        self.defer_flush = defer_flush;
+        self
+    }
+
+    /// Finishes the shared writer (the only flush with `--defer-flush`)
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.global_writer.lock().finish()
+    }
+
+    /// Merges the mates of `record` into `mseq`/`mqual` if they overlap.
+    ///
+    /// Returns `false` (leaving the pair to be written as-is) when they do not.
+    fn merge_mates<B: BinseqRecord>(&mut self, record: &B, merge: MergeOverlap) -> bool {
+        let has_quality = record.has_quality();
+        self.rcseq.clear();
+        self.rcseq.extend_from_slice(record.xseq());
+        reverse_complement(&mut self.rcseq);
+        let sseq = record.sseq();
+        let Some(overlap) = find_overlap(
+            sseq,
+            &self.rcseq,
+            merge.min_overlap,
+            merge.max_mismatch_rate,
+        ) else {
+            return false;
+        };
+        if has_quality {
+            self.rcqual.clear();
+            self.rcqual.extend_from_slice(record.xqual());
+            self.rcqual.reverse();
+        }
+        let (squal, xqual) = if has_quality {
+            (Some(record.squal()), Some(self.rcqual.as_slice()))
+        } else {
+            (None, None)
+        };
+        merge_pair(
+            sseq,
+            squal,
+            &self.rcseq,
+            xqual,
+            overlap.len,
+            &mut self.mseq,
+            &mut self.mqual,
+        );
+        if !has_quality {
+            match self.synth_qual {
+                Some(model) => model.fill(&mut self.mqual, self.mseq.len()),
+                None => self.mqual.resize(self.mseq.len(), b'?'),
+            }
         }
+        true
+    }
+
+    /// Whether `record` passes the sampler and every record filter.
+    ///
+    /// Counts records dropped by the length filter.
+    fn accepts<B: BinseqRecord>(&mut self, record: &B) -> bool {
+        if self
+            .sampler
+            .is_some_and(|sampler| !sampler.includes(record.index()))
+        {
+            return false;
+        }
+        if self
+            .pair_filter
+            .is_some_and(|filter| !filter.includes(record))
+        {
+            return false;
+        }
+        if self
+            .length_filter
+            .is_some_and(|filter| !filter.includes(record))
+        {
+            self.local_length_skipped += 1;
+            return false;
+        }
+        if self
+            .flag_where
+            .as_ref()
+            .is_some_and(|expr| !expr.accepts(record.flag()))
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Writes the merged mates of `record` as a single record.
+    ///
+    /// Returns `false` (writing nothing) when the mates do not overlap.
+    fn write_merged<B: BinseqRecord>(&mut self, record: &B, merge: MergeOverlap) -> Result<bool> {
+        if !self.merge_mates(record, merge) {
+            return Ok(false);
+        }
+        let header = if self.comment_fields.is_empty() {
+            record.sheader()
+        } else {
+            write_header_comment(
+                &mut self.sheader,
+                record.sheader(),
+                &self.mseq,
+                record.flag(),
+                &self.comment_fields,
+            );
+            &self.sheader
+        };
+        write_record(
+            &mut self.mixed,
+            header,
+            &self.mseq,
+            &self.mqual,
+            self.format,
+            self.delimiter,
+        )?;
+        Ok(true)
+    }
+
+    /// Writes the selected columns of `record` as a single row
+    fn write_tsv_row<B: BinseqRecord>(&mut self, record: &B) -> std::io::Result<()> {
+        let squal: &[u8] = if record.has_quality() {
+            record.squal()
+        } else {
+            &[]
+        };
+        let (xheader, xseq, xqual): (&[u8], &[u8], &[u8]) = match record.is_paired() {
+            true if record.has_quality() => (record.xheader(), record.xseq(), record.xqual()),
+            true => (record.xheader(), record.xseq(), &[]),
+            false => (&[], &[], &[]),
+        };
+        let fields = TsvFields {
+            index: record.index(),
+            sheader: record.sheader(),
+            sseq: record.sseq(),
+            squal,
+            xheader,
+            xseq,
+            xqual,
+            flag: record.flag(),
+        };
+        write_tsv_columns(&mut self.mixed, &self.tsv_columns, &fields, self.delimiter)
     }
 
     pub fn num_records(&self) -> usize {
         *self.num_records.lock()
     }
+
+    /// Number of records dropped by the length filter
+    pub fn num_length_skipped(&self) -> usize {
+        *self.length_skipped.lock()
+    }
+}
+
+/// Writes the reverse complement of `seq` into `rseq` and the reversed `qual` into `rqual`.
+///
+/// `qual` may be a placeholder buffer longer than `seq`; only its first
+/// `seq.len()` bytes are kept so the qualities stay aligned with the bases.
+fn reverse_complement_into(seq: &[u8], qual: &[u8], rseq: &mut Vec<u8>, rqual: &mut Vec<u8>) {
+    rseq.clear();
+    rseq.extend_from_slice(seq);
+    reverse_complement(rseq);
+    rqual.clear();
+    rqual.extend_from_slice(&qual[..seq.len().min(qual.len())]);
+    rqual.reverse();
+}
+
+/// Error for a paired record whose second mate is empty
+fn orphan_error(index: u64) -> binseq::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("Paired record {index} has an empty second mate"),
+    )
+    .into()
+}
+
+/// Fails if both mates of `record` have headers and their IDs differ
+fn check_mate_ids<B: BinseqRecord>(record: &B) -> Result<()> {
+    let (sheader, xheader) = (record.sheader(), record.xheader());
+    if !sheader.is_empty() && !xheader.is_empty() && mate_id(sheader) != mate_id(xheader) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Mates of paired record {} have different IDs: {} != {}",
+                record.index(),
+                String::from_utf8_lossy(sheader),
+                String::from_utf8_lossy(xheader),
+            ),
+        )
+        .into());
+    }
+    Ok(())
 }
 
 impl ParallelProcessor for Decoder {
     fn process_record<B: BinseqRecord>(&mut self, record: B) -> Result<()> {
+        if !self.accepts(&record) {
+            return Ok(());
+        }
+        let orphan = record.is_paired() && record.xlen() == 0;
+        let pad = match self.on_orphan.filter(|_| orphan) {
+            Some(OnOrphan::Skip) => return Ok(()),
+            Some(OnOrphan::Error) => return Err(orphan_error(record.index())),
+            Some(OnOrphan::Pad) => true,
+            None => false,
+        };
+        if !self.tsv_columns.is_empty() {
+            self.write_tsv_row(&record)?;
+            self.local_count += 1;
+            return Ok(());
+        }
+        if self.n_count {
+            self.write_n_count_rows(&record)?;
+            self.local_count += 1;
+            return Ok(());
+        }
+        if let Some(merge) = self.merge_overlap.filter(|_| record.is_paired() && !pad) {
+            if self.write_merged(&record, merge)? {
+                self.local_count += 1;
+                return Ok(());
+            }
+        }
+        if self.verify_pairing && matches!(self.mate, Some(Mate::Both)) && record.is_paired() {
+            check_mate_ids(&record)?;
+        }
         let sbuf = record.sseq();
-        let xbuf = record.xseq();
+        let xbuf = if pad { ORPHAN_PAD_SEQ } else { record.xseq() };
 
         // decode sequences
         let squal = if record.has_quality() {
             record.squal()
+        } else if let Some(model) = self.synth_qual {
+            model.fill(&mut self.squal, sbuf.len());
+            &self.squal
         } else {
             if self.squal.len() < sbuf.len() {
                 self.squal.resize(sbuf.len(), b'?');
@@ -69,15 +527,13 @@ impl ParallelProcessor for Decoder {
             &self.squal
         };
 
-        let xqual = if record.is_paired() {
-            if record.has_quality() {
-                record.xqual()
-            } else {
-                if self.xqual.len() < xbuf.len() {
-                    self.xqual.resize(xbuf.len(), b'?');
-                }
-                &self.xqual
-            }
+        let xqual = if pad {
+            ORPHAN_PAD_QUAL
+        } else if record.is_paired() && record.has_quality() {
+            record.xqual()
+        } else if let Some(model) = self.synth_qual.filter(|_| record.is_paired()) {
+            model.fill(&mut self.xqual, xbuf.len());
+            &self.xqual
         } else {
             if self.xqual.len() < xbuf.len() {
                 self.xqual.resize(xbuf.len(), b'?');
@@ -85,6 +541,38 @@ impl ParallelProcessor for Decoder {
             &self.xqual
         };
 
+        let (sbuf, squal) = if self.revcomp.primary() {
+            reverse_complement_into(sbuf, squal, &mut self.rsseq, &mut self.rsqual);
+            (self.rsseq.as_slice(), self.rsqual.as_slice())
+        } else {
+            (sbuf, squal)
+        };
+        let (xbuf, xqual) = if self.revcomp.extended() && record.is_paired() && !pad {
+            reverse_complement_into(xbuf, xqual, &mut self.rxseq, &mut self.rxqual);
+            (self.rxseq.as_slice(), self.rxqual.as_slice())
+        } else {
+            (xbuf, xqual)
+        };
+
+        let xheader = match record.xheader() {
+            header if pad && header.is_empty() => record.sheader(),
+            header => header,
+        };
+        let (sheader, xheader) = if self.comment_fields.is_empty() {
+            (record.sheader(), xheader)
+        } else {
+            let flag = record.flag();
+            write_header_comment(
+                &mut self.sheader,
+                record.sheader(),
+                sbuf,
+                flag,
+                &self.comment_fields,
+            );
+            write_header_comment(&mut self.xheader, xheader, xbuf, flag, &self.comment_fields);
+            (self.sheader.as_slice(), self.xheader.as_slice())
+        };
+
         write_record_pair(
             &mut self.left,
             &mut self.right,
@@ -93,11 +581,12 @@ impl ParallelProcessor for Decoder {
             self.is_split,
             sbuf,
             squal,
-            record.sheader(),
+            sheader,
             xbuf,
             xqual,
-            record.xheader(),
+            xheader,
             self.format,
+            self.delimiter,
         )?;
 
         self.local_count += 1;
@@ -114,19 +603,25 @@ impl ParallelProcessor for Decoder {
             } else {
                 writer.write_interleaved(&self.mixed)?;
             }
-            writer.flush()?;
+            if !self.defer_flush {
+                writer.flush()?;
+            }
         }
         // Lock the mutex to update the number of records
         {
             let mut num_records = self.num_records.lock();
             *num_records += self.local_count;
         }
+        if self.local_length_skipped > 0 {
+            *self.length_skipped.lock() += self.local_length_skipped;
+        }
 
         // Clear the local buffer and reset the local record count
         self.mixed.clear();
         self.left.clear();
         self.right.clear();
         self.local_count = 0;
+        self.local_length_skipped = 0;
         Ok(())
     }
 }
@@ -0,0 +1,331 @@
+use std::{hash::Hash, io, sync::Arc};
+
+use binseq::prelude::*;
+use binseq::Result;
+use hashbrown::{HashMap, HashSet};
+use log::debug;
+use parking_lot::Mutex;
+
+use super::{write_record_pair, SplitWriter, TSV_DELIMITER};
+use crate::{
+    cli::{FileFormat, Mate, OutputFile},
+    commands::max_open_files,
+};
+
+/// Thread-local write buffers for a single group
+#[derive(Clone, Default)]
+struct GroupBuffers {
+    mixed: Vec<u8>,
+    left: Vec<u8>,
+    right: Vec<u8>,
+    count: usize,
+}
+
+/// Open writers keyed by group, bounded by `--max-open-files`.
+///
+/// When the bound is reached the least recently used writer is finished and
+/// closed; its group is reopened in append mode the next time it is needed.
+struct WriterPool<K> {
+    open: HashMap<K, (SplitWriter, u64)>,
+    /// Every group opened so far (reopening must append rather than truncate)
+    created: HashSet<K>,
+    limit: Option<usize>,
+    tick: u64,
+    reopened: usize,
+}
+impl<K: Hash + Eq + Copy> WriterPool<K> {
+    fn new(limit: Option<usize>) -> Self {
+        Self {
+            open: HashMap::new(),
+            created: HashSet::new(),
+            limit,
+            tick: 0,
+            reopened: 0,
+        }
+    }
+
+    /// Returns the writer for `key`, opening it with `open(append)` if needed
+    fn get_or_open(
+        &mut self,
+        key: K,
+        open: impl FnOnce(bool) -> anyhow::Result<SplitWriter>,
+    ) -> io::Result<&mut SplitWriter> {
+        self.tick += 1;
+        if !self.open.contains_key(&key) {
+            if let Some(limit) = self.limit {
+                while self.open.len() >= limit {
+                    self.close_lru()?;
+                }
+            }
+            let append = !self.created.insert(key);
+            self.reopened += usize::from(append);
+            let writer = open(append).map_err(|e| io::Error::other(e.to_string()))?;
+            self.open.insert(key, (writer, self.tick));
+        }
+        let (writer, last_used) = self.open.get_mut(&key).expect("writer was just opened");
+        *last_used = self.tick;
+        Ok(writer)
+    }
+
+    fn close_lru(&mut self) -> io::Result<()> {
+        let lru = self
+            .open
+            .iter()
+            .min_by_key(|(_, (_, last_used))| *last_used)
+            .map(|(key, _)| *key);
+        if let Some((mut writer, _)) = lru.and_then(|key| self.open.remove(&key)) {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes every open writer, leaving the pool empty
+    fn finish(&mut self) -> io::Result<()> {
+        for (_, (mut writer, _)) in self.open.drain() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// How records are assigned to groups
+#[derive(Clone, Debug)]
+pub enum Grouping {
+    /// One group per flag value (`None` for unflagged records)
+    Flag,
+    /// Half-open primary sequence length bins between sorted `edges`
+    Length(Arc<[u64]>),
+}
+impl Grouping {
+    /// Group key of a record: its flag, or the index of its length bin.
+    ///
+    /// Length bin `i` covers `[edges[i - 1], edges[i])`; `0` holds records
+    /// shorter than the first edge and `edges.len()` those at least the last.
+    fn key<B: BinseqRecord>(&self, record: &B) -> Option<u64> {
+        match self {
+            Self::Flag => record.flag(),
+            Self::Length(edges) => {
+                let len = record.sseq().len() as u64;
+                Some(edges.partition_point(|&edge| edge <= len) as u64)
+            }
+        }
+    }
+
+    /// Renders a group key as it appears in output file names
+    fn name(&self, key: Option<u64>) -> String {
+        match (self, key) {
+            (Self::Flag, Some(flag)) => flag.to_string(),
+            (Self::Flag, None) => "none".to_string(),
+            (Self::Length(edges), Some(bin)) => {
+                let bin = bin as usize;
+                let lower = if bin == 0 { 0 } else { edges[bin - 1] };
+                match edges.get(bin) {
+                    Some(upper) => format!("{lower}-{upper}"),
+                    None => format!("{lower}-inf"),
+                }
+            }
+            (Self::Length(_), None) => unreachable!("every length maps to a bin"),
+        }
+    }
+}
+
+/// Decodes BINSEQ records into one FASTX output per group (see [`Grouping`]).
+///
+/// Writers are opened lazily the first time a batch contains a new group.
+#[derive(Clone)]
+pub struct GroupedDecoder {
+    /// Thread-local buffers keyed by group
+    buffers: HashMap<Option<u64>, GroupBuffers>,
+    /// Quality buffers for records without quality scores
+    squal: Vec<u8>,
+    xqual: Vec<u8>,
+
+    /// Options
+    output: Arc<OutputFile>,
+    format: FileFormat,
+    mate: Option<Mate>,
+    is_split: bool,
+    grouping: Grouping,
+    delimiter: u8,
+
+    /// Global writers and record counts keyed by group
+    writers: Arc<Mutex<WriterPool<Option<u64>>>>,
+    counts: Arc<Mutex<HashMap<Option<u64>, usize>>>,
+}
+
+impl GroupedDecoder {
+    pub fn new(
+        output: OutputFile,
+        format: FileFormat,
+        mate: Option<Mate>,
+        grouping: Grouping,
+    ) -> Self {
+        let is_split = mate == Some(Mate::Both);
+        Self {
+            buffers: HashMap::new(),
+            squal: Vec::new(),
+            xqual: Vec::new(),
+            is_split,
+            output: Arc::new(output),
+            format,
+            mate,
+            grouping,
+            delimiter: TSV_DELIMITER,
+            // split R1/R2 outputs hold two files per group
+            writers: Arc::new(Mutex::new(WriterPool::new(
+                max_open_files().map(|n| (n / if is_split { 2 } else { 1 }).max(1)),
+            ))),
+            counts: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Separate TSV fields with `delimiter` instead of a tab
+    #[must_use]
+    pub fn with_delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Record counts per group name, in group key order
+    pub fn group_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: Vec<_> = self
+            .counts
+            .lock()
+            .iter()
+            .map(|(key, count)| (*key, *count))
+            .collect();
+        counts.sort_unstable();
+        let counts = counts
+            .into_iter()
+            .map(|(key, count)| (self.grouping.name(key), count))
+            .collect();
+        let reopened = self.writers.lock().reopened;
+        if reopened > 0 {
+            debug!("Reopened group outputs {reopened} times to stay within `--max-open-files`");
+        }
+        counts
+    }
+
+    /// Finishes the outputs of every group still open
+    pub fn finish(&self) -> io::Result<()> {
+        self.writers.lock().finish()
+    }
+}
+
+impl ParallelProcessor for GroupedDecoder {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> Result<()> {
+        let sbuf = record.sseq();
+        let xbuf = record.xseq();
+
+        let squal = if record.has_quality() {
+            record.squal()
+        } else {
+            if self.squal.len() < sbuf.len() {
+                self.squal.resize(sbuf.len(), b'?');
+            }
+            &self.squal
+        };
+        let xqual = if record.is_paired() && record.has_quality() {
+            record.xqual()
+        } else {
+            if self.xqual.len() < xbuf.len() {
+                self.xqual.resize(xbuf.len(), b'?');
+            }
+            &self.xqual
+        };
+
+        let group = self.buffers.entry(self.grouping.key(&record)).or_default();
+        write_record_pair(
+            &mut group.left,
+            &mut group.right,
+            &mut group.mixed,
+            self.mate,
+            self.is_split,
+            sbuf,
+            squal,
+            record.sheader(),
+            xbuf,
+            xqual,
+            record.xheader(),
+            self.format,
+            self.delimiter,
+        )?;
+        group.count += 1;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        {
+            let mut writers = self.writers.lock();
+            let mut counts = self.counts.lock();
+            for (key, group) in &mut self.buffers {
+                if group.count == 0 {
+                    continue;
+                }
+                let writer = writers.get_or_open(*key, |append| {
+                    self.output.as_group_writer(
+                        &self.grouping.name(*key),
+                        self.format,
+                        self.is_split,
+                        append,
+                    )
+                })?;
+                if writer.is_split() {
+                    writer.write_split(&group.left, true)?;
+                    writer.write_split(&group.right, false)?;
+                } else {
+                    writer.write_interleaved(&group.mixed)?;
+                }
+                writer.flush()?;
+                *counts.entry(*key).or_default() += group.count;
+
+                group.mixed.clear();
+                group.left.clear();
+                group.right.clear();
+                group.count = 0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::OutputWriter;
+
+    #[test]
+    fn test_writer_pool_reopens_in_append_mode() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = |key: u8| dir.path().join(format!("{key}.txt"));
+        let mut pool = WriterPool::new(Some(1));
+        for key in [1, 2, 1, 2, 1] {
+            let writer = pool.get_or_open(key, |append| {
+                let file = std::fs::File::options()
+                    .create(true)
+                    .append(append)
+                    .write(true)
+                    .truncate(!append)
+                    .open(path(key))?;
+                Ok(SplitWriter::new_interleaved(OutputWriter::Plain(Box::new(
+                    file,
+                ))))
+            })?;
+            writer.write_interleaved(b"x")?;
+            assert_eq!(pool.open.len(), 1);
+        }
+        drop(pool);
+        assert_eq!(std::fs::read(path(1))?, b"xxx");
+        assert_eq!(std::fs::read(path(2))?, b"xx");
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_bin_names() {
+        let grouping = Grouping::Length(Arc::from([50, 100, 150].as_slice()));
+        let names: Vec<_> = (0..4).map(|bin| grouping.name(Some(bin))).collect();
+        assert_eq!(names, ["0-50", "50-100", "100-150", "150-inf"]);
+        assert_eq!(Grouping::Flag.name(None), "none");
+        assert_eq!(Grouping::Flag.name(Some(3)), "3");
+    }
+}
@@ -1,18 +1,42 @@
-use std::io::Write;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
 
+#[cfg(feature = "htslib")]
+mod bam;
 mod decode_binseq;
+mod grouped;
+mod packed;
+mod reverse;
 mod utils;
 
-use crate::cli::{DecodeCommand, Mate, OutputFile};
-use decode_binseq::Decoder;
-pub use utils::{write_record, write_record_pair, SplitWriter};
+#[cfg(feature = "htslib")]
+use crate::cli::RevcompMates;
+use crate::cli::{CommentField, DecodeCommand, FileFormat, GroupBy, Mate, OutputFile, TsvColumn};
+use crate::commands::{
+    is_stdio, is_stream, match_output, open_binseq,
+    sample::IndexSampler,
+    temp_dir,
+    utils::{make_directory, process_binseq},
+    OutputWriter, TempPaths,
+};
+pub use decode_binseq::{Decoder, LengthFilter, MergeOverlap, PairFilter};
+use grouped::{GroupedDecoder, Grouping};
+use packed::PackedDecoder;
+pub use utils::{
+    write_record, write_record_pair, write_tsv_n_count, ShardOpener, SplitWriter, TSV_DELIMITER,
+};
 
 use anyhow::{bail, Result};
 use binseq::prelude::*;
 use log::{info, warn};
 
 /// Convenience type wrapper
-pub type Writer = Box<dyn Write + Send>;
+pub type Writer = OutputWriter;
 
 pub fn build_writer(args: &OutputFile, paired: bool) -> Result<SplitWriter> {
     let format = args.format()?;
@@ -49,28 +73,385 @@ pub fn build_writer(args: &OutputFile, paired: bool) -> Result<SplitWriter> {
     }
 }
 
-pub fn run(args: &DecodeCommand) -> Result<()> {
-    let reader = BinseqReader::new(args.input.path())?;
-    let writer = build_writer(&args.output, reader.is_paired())?;
+/// Builds a writer rotating across `<prefix>_<NNNN>` outputs every `limit` bytes
+fn build_rotating_writer(args: &OutputFile, paired: bool, limit: usize) -> Result<SplitWriter> {
+    if limit == 0 {
+        bail!("`--split-bytes` must be greater than 0");
+    }
+    let format = args.format()?;
+    let output = args.clone();
+    let open: ShardOpener = Box::new(move |index| {
+        output.as_group_writer(&format!("{index:04}"), format, paired, false)
+    });
+    SplitWriter::new_rotating(open, limit as u64)
+}
+
+/// Length bin edges from `--length-bins`, with a leading `0` edge dropped
+fn length_bin_edges(bins: &[u64]) -> Result<Arc<[u64]>> {
+    if bins.windows(2).any(|pair| pair[0] >= pair[1]) {
+        bail!("`--length-bins` must be strictly increasing");
+    }
+    // records shorter than the first edge already form the `0-<first>` bin
+    let edges = bins.strip_prefix(&[0]).unwrap_or(bins);
+    if edges.is_empty() {
+        bail!("`--length-bins` needs at least one edge above 0");
+    }
+    Ok(Arc::from(edges))
+}
+
+fn run_grouped(args: &DecodeCommand, reader: &BinseqReader, grouping: Grouping) -> Result<()> {
     let format = args.output.format()?;
     let mate = if reader.is_paired() {
         Some(args.output.mate())
     } else {
         None
     };
-    let proc = Decoder::new(writer, format, mate);
-    if let Some(mut span) = args.input.span {
-        let num_records = reader.num_records()?;
-        reader.process_parallel_range(
-            proc.clone(),
-            args.output.threads(),
-            span.get_range(num_records)?,
-        )?;
+    let proc = GroupedDecoder::new(args.output.clone(), format, mate, grouping)
+        .with_delimiter(args.delimiter_byte());
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.span(),
+    );
+    let finished = proc.finish();
+    result?;
+    finished?;
+    let counts = proc.group_counts();
+    for (group, count) in &counts {
+        info!("Wrote {count} records for group {group}");
+    }
+    info!(
+        "Processed {} records into {} groups",
+        counts.iter().map(|(_, c)| c).sum::<usize>(),
+        counts.len()
+    );
+    Ok(())
+}
+
+/// Writes records as an unaligned BAM (see [`bam::run_bam`])
+#[cfg(feature = "htslib")]
+fn run_bam(args: &DecodeCommand) -> Result<()> {
+    let unsupported = [
+        ("--group-by", args.group_by.is_some()),
+        ("--length-bins", !args.length_bins.is_empty()),
+        ("--reverse", args.reverse),
+        ("--split-bytes", args.split_bytes.is_some()),
+        ("--comment-fields", !args.comment_fields.is_empty()),
+        ("--tsv-columns", !args.tsv_columns.is_empty()),
+        ("--emit-flag", args.emit_flag),
+        ("--merge-overlap", args.merge_overlap),
+        ("--two-bit-raw", args.two_bit_raw),
+        ("--paired-only", args.paired_only),
+        ("--singletons-only", args.singletons_only),
+        ("--flag-where", args.flag_where.is_some()),
+        ("--on-orphan", args.on_orphan.is_some()),
+        ("--verify-pairing", args.verify_pairing),
+        ("--synth-qual", args.synth_qual.is_some()),
+        ("--revcomp", args.revcomp != RevcompMates::None),
+        ("--min-length/--max-length", args.length.filter()?.is_some()),
+    ];
+    if let Some((flag, _)) = unsupported.iter().find(|(_, set)| *set) {
+        bail!("`{flag}` is not supported with BAM output");
+    }
+    let sampler = args
+        .fraction
+        .map(|fraction| IndexSampler::new(fraction, args.seed));
+    let num_records = bam::run_bam(&args.output, args.input.path(), args.span(), sampler)?;
+    info!("Wrote {num_records} BAM records...");
+    Ok(())
+}
+
+#[cfg(not(feature = "htslib"))]
+fn run_bam(_args: &DecodeCommand) -> Result<()> {
+    log::error!("Missing feature flag - htslib. Please compile with htslib feature flag enabled to write BAM files");
+    bail!("Missing feature flag - htslib")
+}
+
+/// Writes the packed sequences to `-o` and their lengths to `<output>.len`
+fn run_packed(
+    args: &DecodeCommand,
+    reader: &BinseqReader,
+    sampler: Option<IndexSampler>,
+) -> Result<()> {
+    let Some(path) = args.output.output_path() else {
+        bail!("`--two-bit-raw` requires an output path (-o) to name the lengths sidecar");
+    };
+    let lengths_path = format!("{path}.len");
+    let mate = if reader.is_paired() {
+        Some(args.output.mate())
+    } else {
+        None
+    };
+    let proc = PackedDecoder::new(
+        args.output.as_writer()?,
+        match_output(Some(&lengths_path))?.into(),
+        mate,
+    )
+    .with_sampler(sampler);
+    proc.write_lengths_header()?;
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.span(),
+    );
+    let finished = proc.finish();
+    result?;
+    finished?;
+    info!(
+        "Processed {} records; wrote lengths to: {lengths_path}",
+        proc.num_records()
+    );
+    Ok(())
+}
+
+fn run_reversed(
+    args: &DecodeCommand,
+    reader: &BinseqReader,
+    mut writer: SplitWriter,
+    format: FileFormat,
+    mate: Option<Mate>,
+    sampler: Option<IndexSampler>,
+) -> Result<usize> {
+    let num_records = reader.num_records()?;
+    let range = match args.span() {
+        Some(mut span) => span.get_range(num_records)?,
+        None => 0..num_records,
+    };
+    reverse::decode_reversed(
+        args.input.path(),
+        range,
+        &mut writer,
+        format,
+        args.delimiter_byte(),
+        mate,
+        sampler,
+    )
+}
+
+/// Resolves the comment fields and TSV columns to write, folding `--emit-flag`
+/// into whichever of the two the output format uses.
+fn emitted_fields(
+    args: &DecodeCommand,
+    format: FileFormat,
+    mate: Option<Mate>,
+) -> Result<(Vec<CommentField>, Vec<TsvColumn>)> {
+    let mut comment_fields = args.comment_fields.clone();
+    let mut tsv_columns = args.tsv_columns.clone();
+    if !args.emit_flag {
+        return Ok((comment_fields, tsv_columns));
+    }
+    match format {
+        FileFormat::Fasta | FileFormat::Fastq => {
+            if !comment_fields.contains(&CommentField::Flag) {
+                comment_fields.push(CommentField::Flag);
+            }
+        }
+        FileFormat::Tsv => {
+            if args.output.prefix.is_some() {
+                bail!("`--emit-flag` with TSV output cannot be split with `-p`");
+            }
+            tsv_columns = match mate {
+                None | Some(Mate::One) => vec![TsvColumn::Sheader, TsvColumn::Sseq],
+                Some(Mate::Two) => vec![TsvColumn::Xheader, TsvColumn::Xseq],
+                Some(Mate::Both) => vec![
+                    TsvColumn::Sheader,
+                    TsvColumn::Sseq,
+                    TsvColumn::Xheader,
+                    TsvColumn::Xseq,
+                ],
+            };
+            tsv_columns.push(TsvColumn::Flag);
+        }
+        _ => bail!("`--emit-flag` requires FASTA, FASTQ, or TSV output"),
+    }
+    Ok((comment_fields, tsv_columns))
+}
+
+/// Decodes every input to its own file in `--output-dir`, named after the input
+fn run_per_input(args: &DecodeCommand, dir: &str) -> Result<()> {
+    make_directory(dir)?;
+    let mut outputs = HashSet::new();
+    for input in std::iter::once(&args.input.input).chain(&args.extra_inputs) {
+        let Some(stem) = Path::new(input).file_stem().and_then(|stem| stem.to_str()) else {
+            bail!("Cannot derive an output name from input: {input}");
+        };
+        let mut single = args.clone();
+        single.input.input.clone_from(input);
+        single.extra_inputs.clear();
+        single.output_dir = None;
+        single.output = args.output.in_directory(dir, stem);
+        let output = single.output.output.clone().unwrap_or_default();
+        if !outputs.insert(output.clone()) {
+            bail!("Multiple inputs would be decoded to {output}; rename inputs with the same name");
+        }
+        info!("Decoding {input} to {output}");
+        run(&single)?;
+    }
+    Ok(())
+}
+
+/// Decodes a non-seekable input (stdin or a FIFO) by first copying it to a temporary file.
+///
+/// BINSEQ readers memory-map their input, so a stream cannot be decoded in place.
+/// The copy is placed in `--tmpdir` and removed when decoding finishes.
+fn run_spooled(args: &DecodeCommand) -> Result<()> {
+    let input = args.input.path();
+    let mut source: Box<dyn Read> = if is_stdio(input) {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(File::open(input)?)
+    };
+    let (spool, path) = tempfile::Builder::new()
+        .prefix("bqtools-decode-")
+        .suffix(".binseq")
+        .tempfile_in(temp_dir())?
+        .keep()?;
+    let mut guard = TempPaths::new();
+    guard.push(&path);
+    let mut spool = BufWriter::new(spool);
+    let num_bytes = io::copy(&mut source, &mut spool)?;
+    spool.flush()?;
+    info!(
+        "Buffered {num_bytes} bytes from {} to {}",
+        if is_stdio(input) { "stdin" } else { input },
+        path.display()
+    );
+
+    let mut spooled = args.clone();
+    spooled.input.input = path.to_string_lossy().into_owned();
+    run(&spooled)
+}
+
+/// Rejects (or warns about) record options that don't apply to `format`
+fn check_format_options(args: &DecodeCommand, format: FileFormat) -> Result<()> {
+    if !args.comment_fields.is_empty() && !matches!(format, FileFormat::Fasta | FileFormat::Fastq) {
+        bail!("`--comment-fields` requires FASTA or FASTQ output");
+    }
+    if args.synth_qual.is_some() && format != FileFormat::Fastq {
+        warn!("`--synth-qual` only applies to FASTQ output; ignoring");
+    }
+    if !args.tsv_columns.is_empty() && format != FileFormat::Tsv {
+        bail!("`--tsv-columns` requires TSV output (`-f t`)");
+    }
+    Ok(())
+}
+
+/// Mate-merging settings, if `--merge-overlap` is set
+fn merge_overlap(args: &DecodeCommand, paired: bool) -> Result<Option<MergeOverlap>> {
+    if !args.merge_overlap {
+        return Ok(None);
+    }
+    if !paired {
+        bail!("`--merge-overlap` requires a paired BINSEQ file");
+    }
+    args.merge.validate()?;
+    Ok(Some(MergeOverlap {
+        min_overlap: args.merge.min_overlap,
+        max_mismatch_rate: args.merge.max_mismatch_rate,
+    }))
+}
+
+pub fn run(args: &DecodeCommand) -> Result<()> {
+    if let Some(dir) = &args.output_dir {
+        return run_per_input(args, dir);
+    }
+    if is_stream(args.input.path()) {
+        return run_spooled(args);
+    }
+    if args.delimiter.is_some() && args.output.format()? != FileFormat::Tsv {
+        bail!("`--delimiter` requires TSV output (`-f t`)");
+    }
+    let reader = open_binseq(args.input.path())?;
+    if args.output.format_with_bam()? == FileFormat::Bam {
+        return run_bam(args);
+    }
+    let length_filter = args.length.filter()?;
+    if length_filter.is_some()
+        && (args.group_by.is_some()
+            || !args.length_bins.is_empty()
+            || args.reverse
+            || args.two_bit_raw)
+    {
+        bail!("`--min-length`/`--max-length` cannot be combined with `--group-by`, `--length-bins`, `--reverse`, or `--two-bit-raw`");
+    }
+    if let Some(GroupBy::Flag) = args.group_by {
+        return run_grouped(args, &reader, Grouping::Flag);
+    }
+    if !args.length_bins.is_empty() {
+        let edges = length_bin_edges(&args.length_bins)?;
+        return run_grouped(args, &reader, Grouping::Length(edges));
+    }
+    let sampler = args
+        .fraction
+        .map(|fraction| IndexSampler::new(fraction, args.seed));
+    if args.two_bit_raw {
+        return run_packed(args, &reader, sampler);
+    }
+    let format = args.output.format()?;
+    if args.n_count && format != FileFormat::Tsv {
+        bail!("`--n-count` is only supported for TSV output (`-f t`)");
+    }
+    let mut writer = if let Some(limit) = args.split_bytes {
+        build_rotating_writer(&args.output, reader.is_paired(), limit)?
+    } else {
+        build_writer(&args.output, reader.is_paired())?
+    };
+    let mate = if reader.is_paired() {
+        Some(args.output.mate())
     } else {
-        reader.process_parallel(proc.clone(), args.output.threads())?;
+        None
+    };
+    check_format_options(args, format)?;
+    let (comment_fields, tsv_columns) = emitted_fields(args, format, mate)?;
+    if !tsv_columns.is_empty() {
+        let mut header = Vec::new();
+        utils::write_tsv_header(&mut header, &tsv_columns, args.delimiter_byte())?;
+        writer.write_interleaved(&header)?;
+    }
+    if args.reverse {
+        let num_records = run_reversed(args, &reader, writer, format, mate, sampler)?;
+        info!("Processed {num_records} records in reverse order...");
+        return Ok(());
     }
+    let pair_filter = (args.paired_only || args.singletons_only).then_some(PairFilter {
+        singletons: args.singletons_only,
+        singleton_mask: args.singleton_mask.0,
+    });
+    let merge_overlap = merge_overlap(args, reader.is_paired())?;
+    let proc = Decoder::new(writer, format, mate)
+        .with_sampler(sampler)
+        .with_n_count(args.n_count)
+        .with_pair_filter(pair_filter)
+        .with_length_filter(length_filter)
+        .with_flag_where(args.flag_where.clone())
+        .with_on_orphan(args.on_orphan)
+        .with_verify_pairing(args.verify_pairing)
+        .with_synth_qual(args.synth_qual)
+        .with_revcomp(args.revcomp)
+        .with_comment_fields(comment_fields)
+        .with_tsv_columns(tsv_columns)
+        .with_delimiter(args.delimiter_byte())
+        .with_merge_overlap(merge_overlap)
+        .with_defer_flush(args.output.defer_flush);
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.span(),
+    );
+    let finished = proc.finish();
+    result?;
+    finished?;
     let num_records = proc.num_records();
     info!("Processed {num_records} records...");
+    if length_filter.is_some() {
+        info!(
+            "Skipped {} records outside the length bounds",
+            proc.num_length_skipped()
+        );
+    }
     Ok(())
 }
 
@@ -83,7 +464,8 @@ mod tests {
 
     use crate::cli::{BinseqMode, FileFormat};
     use crate::testutils::{
-        count_binseq, count_fastx_records, write_fastx, Compression, DEFAULT_NUM_RECORDS,
+        count_binseq, count_fastx_records, write_fastx, write_orphan_binseq, Compression,
+        DEFAULT_NUM_RECORDS,
     };
 
     fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
@@ -152,37 +534,47 @@ mod tests {
         Ok(())
     }
 
+    /// The single-threaded path writes the same records as the parallel path.
     #[test]
-    fn test_decode_output_formats() -> Result<()> {
-        let in_tmp = write_fastx().call()?;
+    fn test_decode_single_thread_matches_parallel() -> Result<()> {
+        let in_tmp = write_fastx().nrec(1000).call()?;
         let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
         encode(in_tmp.path(), bq_tmp.path())?;
 
-        for (fmt_flag, out_suffix) in [("a", ".fasta"), ("q", ".fastq")] {
-            let out_tmp = NamedTempFile::with_suffix(out_suffix)?;
+        let mut outputs = Vec::new();
+        for threads in ["1", "4"] {
+            let out_tmp = NamedTempFile::with_suffix(".fasta")?;
             let cmd = crate::cli::DecodeCommand::try_parse_from([
                 "decode",
                 bq_tmp.path().to_str().unwrap(),
                 "-o",
                 out_tmp.path().to_str().unwrap(),
                 "-f",
-                fmt_flag,
+                "a",
+                "-T",
+                threads,
             ])?;
             super::run(&cmd)?;
-            assert_eq!(count_fastx_records(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+            let contents = std::fs::read_to_string(out_tmp.path())?;
+            let mut records: Vec<String> = contents
+                .split('>')
+                .filter(|r| !r.is_empty())
+                .map(str::to_string)
+                .collect();
+            records.sort_unstable();
+            outputs.push(records);
         }
+        assert_eq!(outputs[0].len(), 1000);
+        assert_eq!(outputs[0], outputs[1]);
         Ok(())
     }
 
-    /// Paired decode: mate selection produces the correct number of output records.
-    ///
-    /// The paired CBQ has N records, each holding (R1, R2). Selecting one mate
-    /// yields N output records; selecting both (interleaved) yields 2×N.
+    /// Records without a flag land in the `none` group; paired files split per mate.
     #[test]
-    fn test_decode_paired_mate_selection() -> Result<()> {
+    fn test_decode_group_by_flag() -> Result<()> {
         let r1 = write_fastx().call()?;
         let r2 = write_fastx().call()?;
-        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
         let cmd = crate::cli::EncodeCommand::try_parse_from([
             "encode",
             r1.path().to_str().unwrap(),
@@ -192,96 +584,1317 @@ mod tests {
         ])?;
         crate::commands::encode::run(&cmd)?;
 
-        assert_eq!(count_binseq(bq_tmp.path())?, DEFAULT_NUM_RECORDS);
-
-        // mate=1 or mate=2: one mate per pair → N records
-        for mate in ["1", "2"] {
-            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let out_dir = tempfile::tempdir()?;
+        let prefix = out_dir.path().join("out");
+        for mate in ["both", "1"] {
             let cmd = crate::cli::DecodeCommand::try_parse_from([
                 "decode",
                 bq_tmp.path().to_str().unwrap(),
-                "-o",
-                out_tmp.path().to_str().unwrap(),
+                "--group-by",
+                "flag",
+                "-p",
+                prefix.to_str().unwrap(),
+                "-f",
+                "q",
                 "-m",
                 mate,
             ])?;
             super::run(&cmd)?;
+        }
+        for name in ["out_none_R1.fq", "out_none_R2.fq", "out_none.fq"] {
             assert_eq!(
-                count_fastx_records(out_tmp.path())?,
+                count_fastx_records(&out_dir.path().join(name))?,
                 DEFAULT_NUM_RECORDS,
-                "mate={mate} should yield one record per pair"
+                "unexpected record count in {name}"
             );
         }
 
-        // mate=both: R1 and R2 interleaved into a single file → 2×N records
+        // grouping needs a prefix to name the outputs
+        assert!(crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--group-by",
+            "flag",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// `--split-bytes` rotates outputs between batches, keeping R1/R2 shards in sync.
+    #[test]
+    fn test_decode_split_bytes() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+            "-B",
+            "4K",
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_dir = tempfile::tempdir()?;
+        let prefix = out_dir.path().join("out");
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--split-bytes",
+            "1",
+            "-p",
+            prefix.to_str().unwrap(),
+            "-f",
+            "q",
+            "-T",
+            "1",
+        ])?;
+        super::run(&cmd)?;
+
+        let mut total = 0;
+        let mut num_shards = 0;
+        while out_dir
+            .path()
+            .join(format!("out_{num_shards:04}_R1.fq"))
+            .exists()
         {
-            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
-            let cmd = crate::cli::DecodeCommand::try_parse_from([
+            let n1 =
+                count_fastx_records(&out_dir.path().join(format!("out_{num_shards:04}_R1.fq")))?;
+            let n2 =
+                count_fastx_records(&out_dir.path().join(format!("out_{num_shards:04}_R2.fq")))?;
+            assert_eq!(n1, n2, "mates out of sync in shard {num_shards}");
+            assert!(n1 > 0, "empty shard {num_shards}");
+            total += n1;
+            num_shards += 1;
+        }
+        assert!(num_shards > 1);
+        assert_eq!(total, DEFAULT_NUM_RECORDS);
+
+        // rotation needs a prefix to name the outputs
+        assert!(crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--split-bytes",
+            "1M",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// Bins are half-open, so a length equal to an edge starts the next bin.
+    #[test]
+    fn test_decode_length_bins() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_dir = tempfile::tempdir()?;
+        let prefix = out_dir.path().join("out");
+        let decode = |bins: &str| {
+            crate::cli::DecodeCommand::try_parse_from([
                 "decode",
                 bq_tmp.path().to_str().unwrap(),
-                "-o",
-                out_tmp.path().to_str().unwrap(),
-                "-m",
-                "both",
-            ])?;
-            super::run(&cmd)?;
-            assert_eq!(
-                count_fastx_records(out_tmp.path())?,
-                2 * DEFAULT_NUM_RECORDS,
-                "mate=both should yield two records per pair (interleaved)"
-            );
+                "--length-bins",
+                bins,
+                "-p",
+                prefix.to_str().unwrap(),
+                "-f",
+                "q",
+            ])
+        };
+        super::run(&decode("0,50,100,150")?)?;
+        assert_eq!(
+            count_fastx_records(&out_dir.path().join("out_100-150.fq"))?,
+            DEFAULT_NUM_RECORDS
+        );
+        for name in ["out_0-50.fq", "out_50-100.fq", "out_150-inf.fq"] {
+            assert!(!out_dir.path().join(name).exists(), "unexpected {name}");
         }
 
+        assert!(super::run(&decode("100,50")?).is_err());
+        assert!(super::run(&decode("0")?).is_err());
         Ok(())
     }
 
-    /// --span slices a contiguous range of records from the binseq file.
-    ///
-    /// decode --span 0..50  on a 200-record file → exactly 50 records
-    /// decode --span 50..   on a 200-record file → exactly 150 records
     #[test]
-    fn test_decode_span() -> Result<()> {
-        let nrec = 200usize;
+    fn test_decode_mate_naming() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
 
-        for mode in BinseqMode::enum_iter() {
-            let in_tmp = write_fastx().nrec(nrec).call()?;
-            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
-            encode(in_tmp.path(), bq_tmp.path())?;
+        let out_dir = tempfile::tempdir()?;
+        let prefix = out_dir.path().join("out");
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-p",
+            prefix.to_str().unwrap(),
+            "-f",
+            "q",
+            "--mate-naming",
+            "{prefix}_{mate}.{ext}",
+        ])?;
+        super::run(&cmd)?;
+        for name in ["out_1.fq", "out_2.fq"] {
+            assert_eq!(
+                count_fastx_records(&out_dir.path().join(name))?,
+                DEFAULT_NUM_RECORDS,
+                "unexpected record count in {name}"
+            );
+        }
+        assert!(!out_dir.path().join("out_R1.fq").exists());
+        Ok(())
+    }
 
-            // First 50 records
+    /// Subsampled decodes select the same records regardless of thread count.
+    #[test]
+    fn test_decode_fraction_is_thread_independent() -> Result<()> {
+        let nrec = 1000;
+        let in_tmp = write_fastx().nrec(nrec).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let mut outputs = Vec::new();
+        for threads in ["1", "4"] {
             let out_tmp = NamedTempFile::with_suffix(".fastq")?;
             let cmd = crate::cli::DecodeCommand::try_parse_from([
                 "decode",
                 bq_tmp.path().to_str().unwrap(),
                 "-o",
                 out_tmp.path().to_str().unwrap(),
-                "--span",
-                "0..50",
+                "--fraction",
+                "0.1",
+                "--seed",
+                "7",
+                "-T",
+                threads,
             ])?;
             super::run(&cmd)?;
-            assert_eq!(
-                count_fastx_records(out_tmp.path())?,
-                50,
-                "--span 0..50 should yield 50 records for {mode:?}"
-            );
+            let content = std::fs::read_to_string(out_tmp.path())?;
+            let lines: Vec<&str> = content.lines().collect();
+            let mut records: Vec<String> = lines.chunks(4).map(|c| c.join("\n")).collect();
+            records.sort_unstable();
+            outputs.push(records);
+        }
+        assert!(
+            (50..150).contains(&outputs[0].len()),
+            "{}",
+            outputs[0].len()
+        );
+        assert_eq!(outputs[0], outputs[1]);
 
-            // Records from 50 to end
-            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        assert!(crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--fraction",
+            "1.5",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_reverse() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().nrec(1000).include_n(false).call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                bq_tmp.path().to_str().unwrap(),
+                "-B",
+                "4K",
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let mut outputs = Vec::new();
+            for extra in [&["-T", "1"][..], &["--reverse"]] {
+                let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+                let mut args = vec![
+                    "decode",
+                    bq_tmp.path().to_str().unwrap(),
+                    "-o",
+                    out_tmp.path().to_str().unwrap(),
+                ];
+                args.extend_from_slice(extra);
+                super::run(&crate::cli::DecodeCommand::try_parse_from(args)?)?;
+                let content = std::fs::read_to_string(out_tmp.path())?;
+                let lines: Vec<&str> = content.lines().collect();
+                let records: Vec<String> = lines.chunks(4).map(|c| c.join("\n")).collect();
+                outputs.push(records);
+            }
+            let (mut forward, reversed) = (outputs.remove(0), outputs.remove(0));
+            assert_eq!(forward.len(), 1000, "{mode:?}");
+            forward.reverse();
+            assert_eq!(forward, reversed, "reverse order mismatch for {mode:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_output_formats() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        for (fmt_flag, out_suffix) in [("a", ".fasta"), ("q", ".fastq")] {
+            let out_tmp = NamedTempFile::with_suffix(out_suffix)?;
             let cmd = crate::cli::DecodeCommand::try_parse_from([
                 "decode",
                 bq_tmp.path().to_str().unwrap(),
                 "-o",
                 out_tmp.path().to_str().unwrap(),
-                "--span",
-                "50..",
+                "-f",
+                fmt_flag,
             ])?;
             super::run(&cmd)?;
-            assert_eq!(
-                count_fastx_records(out_tmp.path())?,
-                nrec - 50,
-                "--span 50.. should yield {} records for {mode:?}",
-                nrec - 50
-            );
+            assert_eq!(count_fastx_records(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_comment_fields() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--comment-fields",
+            "len,gc,flag",
+        ])?;
+        super::run(&cmd)?;
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 4 * DEFAULT_NUM_RECORDS);
+        for record in lines.chunks(4) {
+            let fields: Vec<&str> = record[0].split(' ').collect();
+            // unflagged records carry no `flag=` field
+            assert_eq!(fields.len(), 3, "{}", record[0]);
+            assert_eq!(fields[1], format!("len={}", record[1].len()));
+            assert!(fields[2].starts_with("gc="));
+        }
+
+        // TSV has no comment field
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-f",
+            "t",
+            "--comment-fields",
+            "len",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_tsv_columns() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--tsv-columns",
+            "id,seq,qual,xseq,flag",
+        ])?;
+        super::run(&cmd)?;
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some("id\tsseq\tsqual\txseq\tflag"));
+        let rows: Vec<Vec<&str>> = lines.map(|l| l.split('\t').collect()).collect();
+        // one row per pair
+        assert_eq!(rows.len(), DEFAULT_NUM_RECORDS);
+        for row in rows {
+            assert_eq!(row.len(), 5);
+            assert!(row[0].parse::<u64>().is_ok());
+            assert_eq!(row[1].len(), row[2].len());
+            assert!(!row[3].is_empty());
+            assert!(row[4].is_empty());
+        }
+
+        // columns only apply to TSV
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-f",
+            "q",
+            "--tsv-columns",
+            "id",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_emit_flag() -> Result<()> {
+        use crate::cli::CommentField;
+
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        for (mate, expected) in [
+            ("1", "sheader\tsseq\tflag"),
+            ("2", "xheader\txseq\tflag"),
+            ("both", "sheader\tsseq\txheader\txseq\tflag"),
+        ] {
+            let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-m",
+                mate,
+                "--emit-flag",
+            ])?;
+            super::run(&cmd)?;
+            let content = std::fs::read_to_string(out_tmp.path())?;
+            let mut lines = content.lines();
+            assert_eq!(lines.next(), Some(expected));
+            assert_eq!(lines.count(), DEFAULT_NUM_RECORDS);
+        }
+
+        // FASTQ output does not duplicate an explicitly requested flag field
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-f",
+            "q",
+            "--emit-flag",
+            "--comment-fields",
+            "len,flag",
+        ])?;
+        let (fields, columns) = super::emitted_fields(&cmd, FileFormat::Fastq, None)?;
+        assert_eq!(fields, [CommentField::Len, CommentField::Flag]);
+        assert!(columns.is_empty());
+
+        // not representable in JSONL
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--emit-flag",
+        ])?;
+        assert!(super::emitted_fields(&cmd, FileFormat::Jsonl, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_header_comment() {
+        use crate::cli::CommentField;
+        let mut buf = Vec::new();
+        let fields = [CommentField::Len, CommentField::Gc, CommentField::Flag];
+        super::utils::write_header_comment(&mut buf, b"read1", b"GGCA", Some(3), &fields);
+        assert_eq!(buf, b"read1 len=4 gc=0.7500 flag=3");
+        super::utils::write_header_comment(&mut buf, b"read2", b"", None, &fields);
+        assert_eq!(buf, b"read2 len=0 gc=0.0000");
+    }
+
+    #[test]
+    fn test_write_tsv_delimiter() -> Result<()> {
+        use crate::cli::TsvColumn;
+
+        let parse = |delimiter: &str| {
+            crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                "in.vbq",
+                "--delimiter",
+                delimiter,
+            ])
+        };
+        for (input, byte) in [("comma", b','), (",", b','), ("tab", b'\t'), ("\\t", b'\t')] {
+            assert_eq!(parse(input)?.delimiter_byte(), byte);
+        }
+        assert!(parse("A").is_err());
+
+        let mut buf = Vec::new();
+        super::utils::write_tsv_parts(&mut buf, b"read1", b"ACGT", b',')?;
+        let columns = [TsvColumn::Sheader, TsvColumn::Sseq, TsvColumn::Flag];
+        super::utils::write_tsv_header(&mut buf, &columns, b',')?;
+        let fields = super::utils::TsvFields {
+            index: 0,
+            sheader: b"read1",
+            sseq: b"ACGT",
+            squal: b"",
+            xheader: b"",
+            xseq: b"",
+            xqual: b"",
+            flag: Some(3),
+        };
+        super::utils::write_tsv_columns(&mut buf, &columns, &fields, b',')?;
+        assert_eq!(buf, b"read1,ACGT\nsheader,sseq,flag\nread1,ACGT,3\n");
+
+        // only meaningful for TSV output
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            "input.vbq",
+            "-f",
+            "q",
+            "--delimiter",
+            "comma",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+
+        // end to end: every TSV row is split on commas
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+        let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-f",
+            "t",
+            "--delimiter",
+            "comma",
+        ])?;
+        super::run(&cmd)?;
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        assert_eq!(content.lines().count(), DEFAULT_NUM_RECORDS);
+        assert!(content
+            .lines()
+            .all(|line| line.split(',').count() == 2 && !line.contains('\t')));
+        Ok(())
+    }
+
+    /// JSON lines: one object per single-end record, one nested `r1`/`r2`
+    /// object per pair when mates are interleaved.
+    #[test]
+    fn test_decode_jsonl() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        for inputs in [vec![&r1], vec![&r1, &r2]] {
+            let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+            let mut args = vec!["encode".to_string()];
+            args.extend(
+                inputs
+                    .iter()
+                    .map(|f| f.path().to_str().unwrap().to_string()),
+            );
+            args.extend([
+                "-o".to_string(),
+                bq_tmp.path().to_str().unwrap().to_string(),
+            ]);
+            crate::commands::encode::run(&crate::cli::EncodeCommand::try_parse_from(args)?)?;
+
+            let out_tmp = NamedTempFile::with_suffix(".jsonl")?;
+            decode(bq_tmp.path(), out_tmp.path())?;
+            let content = std::fs::read_to_string(out_tmp.path())?;
+            let lines: Vec<serde_json::Value> = content
+                .lines()
+                .map(serde_json::from_str)
+                .collect::<std::result::Result<_, _>>()?;
+            assert_eq!(lines.len(), DEFAULT_NUM_RECORDS);
+            for line in &lines {
+                let records = if inputs.len() == 2 {
+                    vec![&line["r1"], &line["r2"]]
+                } else {
+                    vec![line]
+                };
+                for record in records {
+                    let seq = record["seq"].as_str().unwrap();
+                    assert!(record["id"].is_string());
+                    assert_eq!(record["qual"].as_str().unwrap().len(), seq.len());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_json_string_escapes() -> Result<()> {
+        let mut buf = Vec::new();
+        super::utils::write_json_string(&mut buf, b"a\"b\\c\td\x01")?;
+        let parsed: String = serde_json::from_slice(&buf)?;
+        assert_eq!(parsed, "a\"b\\c\td\u{1}");
+        Ok(())
+    }
+
+    /// Paired decode: mate selection produces the correct number of output records.
+    ///
+    /// The paired CBQ has N records, each holding (R1, R2). Selecting one mate
+    /// yields N output records; selecting both (interleaved) yields 2×N.
+    #[test]
+    fn test_decode_paired_mate_selection() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        assert_eq!(count_binseq(bq_tmp.path())?, DEFAULT_NUM_RECORDS);
+
+        // mate=1 or mate=2: one mate per pair → N records
+        for mate in ["1", "2"] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-m",
+                mate,
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(
+                count_fastx_records(out_tmp.path())?,
+                DEFAULT_NUM_RECORDS,
+                "mate={mate} should yield one record per pair"
+            );
+        }
+
+        // mate=both: R1 and R2 interleaved into a single file → 2×N records
+        {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-m",
+                "both",
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(
+                count_fastx_records(out_tmp.path())?,
+                2 * DEFAULT_NUM_RECORDS,
+                "mate=both should yield two records per pair (interleaved)"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Unpacking the raw 2-bit words with the sidecar lengths recovers the sequences.
+    #[test]
+    fn test_decode_two_bit_raw() -> Result<()> {
+        let in_tmp = write_fastx().include_n(false).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_dir = tempfile::tempdir()?;
+        let out_path = out_dir.path().join("seqs.2bit");
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+            "--two-bit-raw",
+            "-T",
+            "1",
+        ])?;
+        super::run(&cmd)?;
+
+        let lengths = std::fs::read_to_string(out_dir.path().join("seqs.2bit.len"))?;
+        let mut lines = lengths.lines();
+        assert_eq!(lines.next(), Some("slen"));
+        let lengths: Vec<usize> = lines.map(str::parse).collect::<Result<_, _>>()?;
+        assert_eq!(lengths.len(), DEFAULT_NUM_RECORDS);
+
+        let data = std::fs::read(&out_path)?;
+        let words: Vec<u64> = data
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        let mut unpacked = Vec::new();
+        let mut offset = 0;
+        for len in lengths {
+            unpacked.extend(
+                (0..len).map(|i| b"ACGT"[(words[offset + i / 32] >> (2 * (i % 32)) & 3) as usize]),
+            );
+            unpacked.push(b'\n');
+            offset += len.div_ceil(32);
+        }
+        assert_eq!(offset, words.len());
+
+        let fastq = std::fs::read_to_string(in_tmp.path())?;
+        let expected: String = fastq
+            .lines()
+            .skip(1)
+            .step_by(4)
+            .flat_map(|seq| [seq, "\n"])
+            .collect();
+        assert_eq!(String::from_utf8(unpacked)?, expected);
+        Ok(())
+    }
+
+    /// Unflagged pairs are complete; every record of a single-end file is a singleton.
+    #[test]
+    fn test_decode_pair_filter() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let paired = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        let single = NamedTempFile::with_suffix(".vbq")?;
+        encode(r1.path(), single.path())?;
+
+        for (input, filter, expected) in [
+            (&paired, "--paired-only", DEFAULT_NUM_RECORDS),
+            (&paired, "--singletons-only", 0),
+            (&single, "--paired-only", 0),
+            (&single, "--singletons-only", DEFAULT_NUM_RECORDS),
+        ] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                input.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-m",
+                "1",
+                filter,
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, expected, "{filter}");
+        }
+        Ok(())
+    }
+
+    /// Well-formed pairs are never orphans, whatever `--on-orphan` does with them.
+    #[test]
+    fn test_decode_on_orphan_complete_pairs() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let paired = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let mut outputs = Vec::new();
+        for action in ["skip", "pad", "error"] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                paired.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--on-orphan",
+                action,
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(
+                count_fastx_records(out_tmp.path())?,
+                2 * DEFAULT_NUM_RECORDS,
+                "{action}"
+            );
+            outputs.push(std::fs::read(out_tmp.path())?);
+        }
+        assert!(outputs.windows(2).all(|w| w[0] == w[1]));
+        Ok(())
+    }
+
+    /// Orphans (empty second mate) are dropped, padded, or rejected by `--on-orphan`.
+    #[test]
+    fn test_decode_on_orphan_actions() -> Result<()> {
+        let nrec = 4;
+        let paired = write_orphan_binseq(nrec)?;
+        let decode = |action: &str, out: &std::path::Path| -> Result<()> {
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                paired.path().to_str().unwrap(),
+                "-o",
+                out.to_str().unwrap(),
+                "-T",
+                "1",
+                "--on-orphan",
+                action,
+            ])?;
+            super::run(&cmd)
+        };
+        let pair =
+            |idx: usize| format!("@seq.{idx}\nACGT\n+\nIIII\n@mate.{idx}\nTTGCA\n+\n#####\n");
+        let padded = |idx: usize| format!("@seq.{idx}\nACGT\n+\nIIII\n@seq.{idx}\nN\n+\n!\n");
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        decode("skip", out_tmp.path())?;
+        let expected: String = (0..nrec).step_by(2).map(pair).collect();
+        assert_eq!(std::fs::read_to_string(out_tmp.path())?, expected);
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        decode("pad", out_tmp.path())?;
+        let expected: String = (0..nrec)
+            .map(|idx| if idx % 2 == 0 { pair(idx) } else { padded(idx) })
+            .collect();
+        assert_eq!(std::fs::read_to_string(out_tmp.path())?, expected);
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let err = decode("error", out_tmp.path()).unwrap_err();
+        assert!(
+            format!("{err:#}").contains("Paired record 1 has an empty second mate"),
+            "{err:#}"
+        );
+        Ok(())
+    }
+
+    /// `--min-length`/`--max-length` keep only records within the inclusive bounds.
+    #[test]
+    fn test_decode_length_filter() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        {
+            use std::io::Write as _;
+            let mut f = std::fs::File::create(in_tmp.path())?;
+            for (i, len) in [10, 20, 25, 30].into_iter().enumerate() {
+                writeln!(f, "@read{i}\n{}\n+\n{}", "A".repeat(len), "I".repeat(len))?;
+            }
+        }
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        for (bounds, expected) in [
+            (vec!["--min-length", "20"], 3),
+            (vec!["--max-length", "20"], 2),
+            (vec!["--min-length", "15", "--max-length", "25"], 2),
+        ] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let mut args = vec![
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+            ];
+            args.extend(bounds);
+            super::run(&crate::cli::DecodeCommand::try_parse_from(args)?)?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, expected);
+        }
+
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--min-length",
+            "30",
+            "--max-length",
+            "10",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    /// A FIFO input is buffered to a temporary file and decodes like the file itself.
+    #[test]
+    fn test_decode_streamed_input() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let vbq = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            vbq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        let decode = |input: &str, output: &std::path::Path| -> Result<()> {
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                input,
+                "-o",
+                output.to_str().unwrap(),
+                "-T",
+                "1",
+            ])?;
+            super::run(&cmd)
+        };
+
+        let expected = NamedTempFile::with_suffix(".fastq")?;
+        decode(vbq.path().to_str().unwrap(), expected.path())?;
+
+        let fifo_dir = tempfile::tempdir()?;
+        let fifo = fifo_dir.path().join("input.vbq");
+        crate::commands::pipe::utils::create_fifo(fifo.to_str().unwrap())?;
+        let source = vbq.path().to_path_buf();
+        let fifo_path = fifo.clone();
+        let feeder = std::thread::spawn(move || -> std::io::Result<()> {
+            let mut fifo = std::fs::OpenOptions::new().write(true).open(fifo_path)?;
+            std::io::copy(&mut std::fs::File::open(source)?, &mut fifo)?;
+            Ok(())
+        });
+        let streamed = NamedTempFile::with_suffix(".fastq")?;
+        decode(fifo.to_str().unwrap(), streamed.path())?;
+        feeder.join().unwrap()?;
+
+        assert_eq!(
+            std::fs::read_to_string(streamed.path())?,
+            std::fs::read_to_string(expected.path())?
+        );
+
+        // other commands reject streams with an actionable error
+        let Err(err) = crate::commands::open_binseq("-") else {
+            panic!("opening stdin as BINSEQ should fail");
+        };
+        assert!(err.to_string().contains("seekable"), "{err}");
+        Ok(())
+    }
+
+    /// `--skip`/`--limit` page through a paired file for interleaved and split outputs.
+    #[test]
+    fn test_decode_skip_limit() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let paired = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        let decode = |extra: &[&str]| -> Result<()> {
+            let mut argv = vec!["decode", paired.path().to_str().unwrap(), "-T", "1"];
+            argv.extend_from_slice(extra);
+            super::run(&crate::cli::DecodeCommand::try_parse_from(argv)?)
+        };
+
+        let full = NamedTempFile::with_suffix(".fastq")?;
+        decode(&["-o", full.path().to_str().unwrap()])?;
+        let page = NamedTempFile::with_suffix(".fastq")?;
+        decode(&[
+            "-o",
+            page.path().to_str().unwrap(),
+            "--skip",
+            "10",
+            "--limit",
+            "5",
+        ])?;
+        // interleaved output holds 8 lines per pair
+        let full = std::fs::read_to_string(full.path())?;
+        let expected: Vec<_> = full.lines().skip(10 * 8).take(5 * 8).collect();
+        let page = std::fs::read_to_string(page.path())?;
+        assert_eq!(page.lines().collect::<Vec<_>>(), expected);
+
+        let out_dir = tempfile::tempdir()?;
+        let prefix = out_dir.path().join("page");
+        decode(&[
+            "-p",
+            prefix.to_str().unwrap(),
+            "-f",
+            "q",
+            "--skip",
+            "10",
+            "--limit",
+            "5",
+        ])?;
+        for name in ["page_R1.fq", "page_R2.fq"] {
+            assert_eq!(count_fastx_records(&out_dir.path().join(name))?, 5);
+        }
+
+        // skipping past the last record is an error; the limit is clipped
+        assert!(decode(&["--skip", "100000", "-o", "-"]).is_err());
+        let tail = NamedTempFile::with_suffix(".fastq")?;
+        let skip = (DEFAULT_NUM_RECORDS - 2).to_string();
+        decode(&[
+            "-o",
+            tail.path().to_str().unwrap(),
+            "--skip",
+            &skip,
+            "--limit",
+            "10",
+        ])?;
+        assert_eq!(count_fastx_records(tail.path())?, 4);
+        Ok(())
+    }
+
+    /// `--defer-flush` writes the same records as flushing per batch.
+    #[test]
+    fn test_decode_defer_flush() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let mut outputs = Vec::new();
+        for extra in [&[][..], &["--defer-flush"]] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let mut argv = vec![
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-T",
+                "1",
+            ];
+            argv.extend_from_slice(extra);
+            super::run(&crate::cli::DecodeCommand::try_parse_from(argv)?)?;
+            outputs.push(std::fs::read_to_string(out_tmp.path())?);
+        }
+        assert_eq!(outputs[0], outputs[1]);
+        assert_eq!(outputs[1].lines().count(), 4 * DEFAULT_NUM_RECORDS);
+        Ok(())
+    }
+
+    /// `--synth-qual linear-decline` fills quality-less records from Q38 down to Q20.
+    #[test]
+    fn test_decode_synth_qual() -> Result<()> {
+        let in_tmp = write_fastx().format(FileFormat::Fasta).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--synth-qual",
+            "linear-decline",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        let mut num_records = 0;
+        for qual in content.lines().skip(3).step_by(4) {
+            let qual = qual.as_bytes();
+            assert_eq!(qual.first(), Some(&(b'!' + 38)));
+            assert_eq!(qual.last(), Some(&(b'!' + 20)));
+            assert!(qual.windows(2).all(|w| w[0] >= w[1]));
+            num_records += 1;
+        }
+        assert_eq!(num_records, DEFAULT_NUM_RECORDS);
+        Ok(())
+    }
+
+    /// Sequence and quality lines of a decoded FASTQ file.
+    fn fastq_bodies(path: &std::path::Path) -> Result<Vec<String>> {
+        Ok(std::fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| i % 4 == 1 || i % 4 == 3)
+            .map(|(_, line)| line.to_string())
+            .collect())
+    }
+
+    /// Decoding with `--revcomp` twice (via a re-encode) returns the original
+    /// records, and `--revcomp both -m 2` writes only R2, reverse complemented.
+    #[test]
+    fn test_decode_revcomp_round_trip() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let paired = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let decode = |input: &std::path::Path, extra: &[&str]| -> Result<NamedTempFile> {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let mut argv = vec![
+                "decode",
+                input.to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+            ];
+            argv.extend_from_slice(extra);
+            super::run(&crate::cli::DecodeCommand::try_parse_from(argv)?)?;
+            Ok(out_tmp)
+        };
+
+        let plain = decode(paired.path(), &["-m", "1"])?;
+        let once = decode(paired.path(), &["-m", "1", "--revcomp", "r1"])?;
+        assert_ne!(fastq_bodies(once.path())?, fastq_bodies(plain.path())?);
+        let reencoded = NamedTempFile::with_suffix(".vbq")?;
+        encode(once.path(), reencoded.path())?;
+        let twice = decode(reencoded.path(), &["--revcomp", "r1"])?;
+        assert_eq!(fastq_bodies(twice.path())?, fastq_bodies(plain.path())?);
+
+        let plain_r2 = decode(paired.path(), &["-m", "2"])?;
+        let rc_r2 = decode(paired.path(), &["-m", "2", "--revcomp", "both"])?;
+        let plain_r2 = fastq_bodies(plain_r2.path())?;
+        let rc_r2 = fastq_bodies(rc_r2.path())?;
+        assert_eq!(rc_r2.len(), 2 * DEFAULT_NUM_RECORDS);
+        for (rc, orig) in rc_r2.chunks(2).zip(plain_r2.chunks(2)) {
+            let mut seq = orig[0].clone().into_bytes();
+            crate::commands::revcomp::reverse_complement(&mut seq);
+            assert_eq!(rc[0].as_bytes(), seq.as_slice());
+            assert_eq!(rc[1], orig[1].chars().rev().collect::<String>());
+        }
+        Ok(())
+    }
+
+    /// `--verify-pairing` passes mates with matching IDs and fails on mismatched ones.
+    #[test]
+    fn test_decode_verify_pairing() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let paired = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            paired.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--verify-pairing",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(
+            count_fastx_records(out_tmp.path())?,
+            2 * DEFAULT_NUM_RECORDS
+        );
+
+        // mates renamed in R2 no longer pair up with R1
+        let renamed = NamedTempFile::with_suffix(".fastq")?;
+        std::fs::write(
+            renamed.path(),
+            std::fs::read_to_string(r2.path())?.replace("@seq.", "@other."),
+        )?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            renamed.path().to_str().unwrap(),
+            "-o",
+            paired.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            paired.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--verify-pairing",
+        ])?;
+        let err = super::run(&cmd).unwrap_err();
+        assert!(err.to_string().contains("different IDs"), "{err}");
+        Ok(())
+    }
+
+    /// `--output-dir` writes one file per input, named after the input.
+    #[test]
+    fn test_decode_output_dir() -> Result<()> {
+        let indir = tempfile::tempdir()?;
+        let outdir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        for (name, nrec) in [("first.vbq", 100), ("second.cbq", 250)] {
+            let in_tmp = write_fastx().nrec(nrec).call()?;
+            let path = indir.path().join(name);
+            encode(in_tmp.path(), &path)?;
+            inputs.push(path.to_str().unwrap().to_string());
+        }
+
+        let mut args = vec!["decode"];
+        args.extend(inputs.iter().map(String::as_str));
+        args.extend(["--output-dir", outdir.path().to_str().unwrap()]);
+        let cmd = crate::cli::DecodeCommand::try_parse_from(args)?;
+        super::run(&cmd)?;
+
+        assert_eq!(count_fastx_records(&outdir.path().join("first.fq"))?, 100);
+        assert_eq!(count_fastx_records(&outdir.path().join("second.fq"))?, 250);
+
+        // several inputs without an output directory are rejected
+        let mut args = vec!["decode"];
+        args.extend(inputs.iter().map(String::as_str));
+        assert!(crate::cli::DecodeCommand::try_parse_from(args).is_err());
+        Ok(())
+    }
+
+    /// Unflagged records are evaluated as flag 0 by `--flag-where`.
+    #[test]
+    fn test_decode_flag_where() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        for (expr, expected) in [
+            ("flag == 0", DEFAULT_NUM_RECORDS),
+            ("!(flag & 0x1) && flag != 4", DEFAULT_NUM_RECORDS),
+            ("(flag & 0x3) == 0x1", 0),
+        ] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--flag-where",
+                expr,
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, expected, "{expr}");
+        }
+
+        assert!(crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "--flag-where",
+            "flag &",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// --span slices a contiguous range of records from the binseq file.
+    ///
+    /// decode --span 0..50  on a 200-record file → exactly 50 records
+    /// decode --span 50..   on a 200-record file → exactly 150 records
+    #[test]
+    fn test_decode_span() -> Result<()> {
+        let nrec = 200usize;
+
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().nrec(nrec).call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+
+            // First 50 records
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--span",
+                "0..50",
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(
+                count_fastx_records(out_tmp.path())?,
+                50,
+                "--span 0..50 should yield 50 records for {mode:?}"
+            );
+
+            // Records from 50 to end
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--span",
+                "50..",
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(
+                count_fastx_records(out_tmp.path())?,
+                nrec - 50,
+                "--span 50.. should yield {} records for {mode:?}",
+                nrec - 50
+            );
+        }
+        Ok(())
+    }
+
+    /// --n-count appends a column matching the number of Ns in each TSV row
+    /// and is rejected for non-TSV output.
+    #[test]
+    fn test_decode_n_count() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                bq_tmp.path().to_str().unwrap(),
+                "-S",
+                "4",
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-f",
+                "t",
+                "--n-count",
+            ])?;
+            super::run(&cmd)?;
+
+            let contents = std::fs::read_to_string(out_tmp.path())?;
+            let mut total_n = 0;
+            for line in contents.lines() {
+                let fields: Vec<&str> = line.split('\t').collect();
+                assert_eq!(fields.len(), 3, "expected 3 columns for {mode:?}: {line}");
+                let expected = fields[1].bytes().filter(|&b| b == b'N').count();
+                assert_eq!(fields[2].parse::<usize>()?, expected, "{mode:?}: {line}");
+                total_n += expected;
+            }
+            assert_eq!(contents.lines().count(), DEFAULT_NUM_RECORDS);
+            assert!(total_n > 0, "fixture should contain Ns for {mode:?}");
+
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--n-count",
+            ])?;
+            assert!(super::run(&cmd).is_err(), "--n-count requires TSV output");
         }
         Ok(())
     }
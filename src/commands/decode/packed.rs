@@ -0,0 +1,138 @@
+use std::{io::Write, sync::Arc};
+
+use binseq::{prelude::*, BitSize};
+use parking_lot::Mutex;
+
+use super::Writer;
+use crate::{cli::Mate, commands::sample::IndexSampler};
+
+/// Appends the packed 64-bit words of a sequence as little-endian bytes
+fn write_words(buf: &mut Vec<u8>, words: &[u64]) {
+    buf.extend(words.iter().flat_map(|word| word.to_le_bytes()));
+}
+
+/// Copies the 2-bit packed sequences of each record to `data` and their
+/// lengths to a TSV `lengths` sidecar (`--two-bit-raw`).
+///
+/// Each selected sequence occupies whole little-endian `u64` words, so the
+/// sidecar lengths are enough to find record boundaries in the data file.
+#[derive(Clone)]
+pub struct PackedDecoder {
+    /// Thread-local buffers
+    data: Vec<u8>,
+    lengths: Vec<u8>,
+    local_count: usize,
+
+    mate: Option<Mate>,
+    sampler: Option<IndexSampler>,
+
+    /// Global writers (data, lengths) and record count
+    writers: Arc<Mutex<(Writer, Writer)>>,
+    num_records: Arc<Mutex<usize>>,
+}
+
+impl PackedDecoder {
+    pub fn new(data: Writer, lengths: Writer, mate: Option<Mate>) -> Self {
+        Self {
+            data: Vec::new(),
+            lengths: Vec::new(),
+            local_count: 0,
+            mate,
+            sampler: None,
+            writers: Arc::new(Mutex::new((data, lengths))),
+            num_records: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    /// Only decode the records selected by `sampler`
+    #[must_use]
+    pub fn with_sampler(mut self, sampler: Option<IndexSampler>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Finishes the data and lengths outputs
+    pub fn finish(&self) -> std::io::Result<()> {
+        let mut writers = self.writers.lock();
+        writers.0.finish()?;
+        writers.1.finish()
+    }
+
+    /// Writes the column names of the lengths sidecar
+    pub fn write_lengths_header(&self) -> std::io::Result<()> {
+        let header: &[u8] = match self.mate {
+            None | Some(Mate::One) => b"slen\n",
+            Some(Mate::Two) => b"xlen\n",
+            Some(Mate::Both) => b"slen\txlen\n",
+        };
+        self.writers.lock().1.write_all(header)
+    }
+
+    pub fn num_records(&self) -> usize {
+        *self.num_records.lock()
+    }
+}
+
+impl ParallelProcessor for PackedDecoder {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        if self
+            .sampler
+            .is_some_and(|sampler| !sampler.includes(record.index()))
+        {
+            return Ok(());
+        }
+        if record.bitsize() != BitSize::Two {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "`--two-bit-raw` requires a 2-bit encoded file (re-encode with `--bitsize 2`)",
+            )
+            .into());
+        }
+        match self.mate {
+            None | Some(Mate::One) => {
+                write_words(&mut self.data, record.sbuf());
+                writeln!(self.lengths, "{}", record.slen())?;
+            }
+            Some(Mate::Two) => {
+                write_words(&mut self.data, record.xbuf());
+                writeln!(self.lengths, "{}", record.xlen())?;
+            }
+            Some(Mate::Both) => {
+                write_words(&mut self.data, record.sbuf());
+                write_words(&mut self.data, record.xbuf());
+                writeln!(self.lengths, "{}\t{}", record.slen(), record.xlen())?;
+            }
+        }
+        self.local_count += 1;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        {
+            // data and lengths are written under one lock to keep them aligned
+            let mut writers = self.writers.lock();
+            writers.0.write_all(&self.data)?;
+            writers.1.write_all(&self.lengths)?;
+            writers.0.flush()?;
+            writers.1.flush()?;
+        }
+        *self.num_records.lock() += self.local_count;
+        self.data.clear();
+        self.lengths.clear();
+        self.local_count = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_words;
+
+    #[test]
+    fn test_write_words_little_endian() {
+        let mut buf = Vec::new();
+        write_words(&mut buf, &[0x0102, u64::MAX]);
+        assert_eq!(&buf[..8], &[2, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&buf[8..], &[0xff; 8]);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{ops::Range, sync::Arc};
+
+use anyhow::Result;
+use binseq::prelude::*;
+use log::debug;
+use parking_lot::Mutex;
+
+use super::{write_record_pair, SplitWriter};
+use crate::{
+    cli::{FileFormat, Mate},
+    commands::{load_block_spans, open_binseq, sample::IndexSampler, BlockSpan},
+};
+
+/// Number of records buffered per chunk for BQ files (which have no blocks)
+const BQ_CHUNK_RECORDS: usize = 1 << 16;
+
+/// Splits `range` into contiguous chunks ordered last-to-first.
+///
+/// VBQ/CBQ chunks follow the block index so each chunk only decodes the blocks
+/// it covers; BQ files are cut into fixed-size chunks.
+pub fn reverse_chunks(blocks: Option<&[BlockSpan]>, range: Range<usize>) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    if let Some(blocks) = blocks {
+        let mut start = 0;
+        for block in blocks {
+            let end = start + block.records as usize;
+            let chunk = start.max(range.start)..end.min(range.end);
+            if !chunk.is_empty() {
+                chunks.push(chunk);
+            }
+            start = end;
+        }
+    } else {
+        let mut start = range.start;
+        while start < range.end {
+            let end = (start + BQ_CHUNK_RECORDS).min(range.end);
+            chunks.push(start..end);
+            start = end;
+        }
+    }
+    chunks.reverse();
+    chunks
+}
+
+/// Decoded records of a chunk with the end offset of each record (or pair)
+#[derive(Clone, Default)]
+struct Chunk {
+    mixed: Vec<u8>,
+    left: Vec<u8>,
+    right: Vec<u8>,
+    /// End offsets into (mixed, left, right) after each record
+    ends: Vec<(usize, usize, usize)>,
+}
+impl Chunk {
+    /// Writes the buffered records to `writer` in reverse order
+    fn write_reversed(&self, writer: &mut SplitWriter) -> Result<usize> {
+        let mut starts = (0, 0, 0);
+        let bounds: Vec<_> = self
+            .ends
+            .iter()
+            .map(|&ends| {
+                let bound = (starts, ends);
+                starts = ends;
+                bound
+            })
+            .collect();
+        for ((ms, ls, rs), (me, le, re)) in bounds.iter().rev().copied() {
+            if writer.is_split() {
+                writer.write_split(&self.left[ls..le], true)?;
+                writer.write_split(&self.right[rs..re], false)?;
+            } else {
+                writer.write_interleaved(&self.mixed[ms..me])?;
+            }
+        }
+        Ok(self.ends.len())
+    }
+}
+
+/// Decodes a single chunk into memory, keeping record boundaries.
+///
+/// Chunks are processed on a single thread, so records arrive in file order.
+#[derive(Clone)]
+struct ChunkDecoder {
+    local: Chunk,
+    squal: Vec<u8>,
+    xqual: Vec<u8>,
+
+    format: FileFormat,
+    delimiter: u8,
+    mate: Option<Mate>,
+    is_split: bool,
+    sampler: Option<IndexSampler>,
+
+    chunk: Arc<Mutex<Chunk>>,
+}
+impl ParallelProcessor for ChunkDecoder {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        if self
+            .sampler
+            .is_some_and(|sampler| !sampler.includes(record.index()))
+        {
+            return Ok(());
+        }
+        let sbuf = record.sseq();
+        let xbuf = record.xseq();
+        let squal = if record.has_quality() {
+            record.squal()
+        } else {
+            if self.squal.len() < sbuf.len() {
+                self.squal.resize(sbuf.len(), b'?');
+            }
+            &self.squal
+        };
+        let xqual = if record.is_paired() && record.has_quality() {
+            record.xqual()
+        } else {
+            if self.xqual.len() < xbuf.len() {
+                self.xqual.resize(xbuf.len(), b'?');
+            }
+            &self.xqual
+        };
+
+        write_record_pair(
+            &mut self.local.left,
+            &mut self.local.right,
+            &mut self.local.mixed,
+            self.mate,
+            self.is_split,
+            sbuf,
+            squal,
+            record.sheader(),
+            xbuf,
+            xqual,
+            record.xheader(),
+            self.format,
+            self.delimiter,
+        )?;
+        self.local.ends.push((
+            self.local.mixed.len(),
+            self.local.left.len(),
+            self.local.right.len(),
+        ));
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        let mut chunk = self.chunk.lock();
+        let offsets = (chunk.mixed.len(), chunk.left.len(), chunk.right.len());
+        chunk.mixed.append(&mut self.local.mixed);
+        chunk.left.append(&mut self.local.left);
+        chunk.right.append(&mut self.local.right);
+        chunk.ends.extend(
+            self.local
+                .ends
+                .drain(..)
+                .map(|(m, l, r)| (m + offsets.0, l + offsets.1, r + offsets.2)),
+        );
+        Ok(())
+    }
+}
+
+/// Decodes `range` of the file at `path` from the last record to the first.
+///
+/// Chunks are visited last-to-first via random access and each is decoded on a
+/// single thread, buffered, and written out reversed, so at most one chunk of
+/// decoded records is held in memory at a time.
+pub fn decode_reversed(
+    path: &str,
+    range: Range<usize>,
+    writer: &mut SplitWriter,
+    format: FileFormat,
+    delimiter: u8,
+    mate: Option<Mate>,
+    sampler: Option<IndexSampler>,
+) -> Result<usize> {
+    let blocks = load_block_spans(path)?;
+    let chunks = reverse_chunks(blocks.as_deref(), range);
+    debug!("Decoding {} chunks in reverse order", chunks.len());
+
+    let mut num_records = 0;
+    for chunk_range in chunks {
+        let shared = Arc::new(Mutex::new(Chunk::default()));
+        let proc = ChunkDecoder {
+            local: Chunk::default(),
+            squal: Vec::new(),
+            xqual: Vec::new(),
+            format,
+            delimiter,
+            mate,
+            is_split: writer.is_split(),
+            sampler,
+            chunk: shared.clone(),
+        };
+        open_binseq(path)?.process_parallel_range(proc, 1, chunk_range)?;
+        num_records += shared.lock().write_reversed(writer)?;
+    }
+    writer.finish()?;
+    Ok(num_records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(records: &[u64]) -> Vec<BlockSpan> {
+        let mut cumulative = 0;
+        records
+            .iter()
+            .map(|&records| {
+                cumulative += records;
+                BlockSpan {
+                    offset: 0,
+                    len: 0,
+                    records,
+                    cumulative_records: cumulative,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reverse_chunks_follow_blocks() {
+        let blocks = blocks(&[10, 20, 5]);
+        assert_eq!(
+            reverse_chunks(Some(&blocks), 0..35),
+            vec![30..35, 10..30, 0..10]
+        );
+        // a span clips the first and last block it touches
+        assert_eq!(
+            reverse_chunks(Some(&blocks), 5..32),
+            vec![30..32, 10..30, 5..10]
+        );
+        assert!(reverse_chunks(Some(&blocks), 0..0).is_empty());
+    }
+
+    #[test]
+    fn test_reverse_chunks_bq() {
+        let n = BQ_CHUNK_RECORDS * 2 + 3;
+        assert_eq!(
+            reverse_chunks(None, 0..n),
+            vec![
+                2 * BQ_CHUNK_RECORDS..n,
+                BQ_CHUNK_RECORDS..2 * BQ_CHUNK_RECORDS,
+                0..BQ_CHUNK_RECORDS
+            ]
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::io::Write;
 use anyhow::Result;
 
 use super::Writer;
-use crate::cli::{FileFormat, Mate};
+use crate::cli::{CommentField, FileFormat, Mate, TsvColumn};
 
 pub fn write_fastq_parts<W: Write>(
     writer: &mut W,
@@ -34,21 +34,226 @@ pub fn write_fasta_parts<W: Write>(
     Ok(())
 }
 
+/// Default field delimiter of TSV output (see `decode --delimiter`)
+pub const TSV_DELIMITER: u8 = b'\t';
+
 pub fn write_tsv_parts<W: Write>(
     writer: &mut W,
     index: &[u8],
     sequence: &[u8],
+    delimiter: u8,
 ) -> std::io::Result<()> {
     writer.write_all(index)?;
-    writer.write_all(b"\t")?;
+    writer.write_all(&[delimiter])?;
     writer.write_all(sequence)?;
     writer.write_all(b"\n")?;
     Ok(())
 }
 
+/// Writes a TSV row with an additional `n_count` column
+pub fn write_tsv_n_count<W: Write>(
+    writer: &mut W,
+    index: &[u8],
+    sequence: &[u8],
+    delimiter: u8,
+) -> std::io::Result<()> {
+    #[allow(clippy::naive_bytecount)]
+    let n_count = sequence.iter().filter(|&&b| b == b'N' || b == b'n').count();
+    writer.write_all(index)?;
+    writer.write_all(&[delimiter])?;
+    writer.write_all(sequence)?;
+    writer.write_all(&[delimiter])?;
+    writeln!(writer, "{n_count}")?;
+    Ok(())
+}
+
+/// Fields of a record available to [`write_tsv_columns`]
+pub struct TsvFields<'a> {
+    pub index: u64,
+    pub sheader: &'a [u8],
+    pub sseq: &'a [u8],
+    pub squal: &'a [u8],
+    pub xheader: &'a [u8],
+    pub xseq: &'a [u8],
+    pub xqual: &'a [u8],
+    pub flag: Option<u64>,
+}
+
+/// Writes the `delimiter`-separated header row naming `columns`
+pub fn write_tsv_header<W: Write>(
+    writer: &mut W,
+    columns: &[TsvColumn],
+    delimiter: u8,
+) -> std::io::Result<()> {
+    for (idx, column) in columns.iter().enumerate() {
+        if idx > 0 {
+            writer.write_all(&[delimiter])?;
+        }
+        writer.write_all(column.name().as_bytes())?;
+    }
+    writer.write_all(b"\n")
+}
+
+/// Writes one `delimiter`-separated row holding the selected `columns` of a record
+pub fn write_tsv_columns<W: Write>(
+    writer: &mut W,
+    columns: &[TsvColumn],
+    fields: &TsvFields,
+    delimiter: u8,
+) -> std::io::Result<()> {
+    for (idx, column) in columns.iter().enumerate() {
+        if idx > 0 {
+            writer.write_all(&[delimiter])?;
+        }
+        match column {
+            TsvColumn::Id => write!(writer, "{}", fields.index)?,
+            TsvColumn::Sheader => writer.write_all(fields.sheader)?,
+            TsvColumn::Sseq => writer.write_all(fields.sseq)?,
+            TsvColumn::Squal => writer.write_all(fields.squal)?,
+            TsvColumn::Xheader => writer.write_all(fields.xheader)?,
+            TsvColumn::Xseq => writer.write_all(fields.xseq)?,
+            TsvColumn::Xqual => writer.write_all(fields.xqual)?,
+            TsvColumn::Flag => {
+                if let Some(flag) = fields.flag {
+                    write!(writer, "{flag}")?;
+                }
+            }
+        }
+    }
+    writer.write_all(b"\n")
+}
+
+/// Writes `bytes` as a quoted JSON string, escaping quotes, backslashes, and control bytes.
+///
+/// Quality strings routinely contain `"` and `\`, so this is not only for headers.
+pub fn write_json_string<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(b"\"")?;
+    let mut start = 0;
+    for (idx, &byte) in bytes.iter().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\t' => b"\\t",
+            b'\r' => b"\\r",
+            0..=0x1f => b"",
+            _ => continue,
+        };
+        writer.write_all(&bytes[start..idx])?;
+        if escaped.is_empty() {
+            write!(writer, "\\u{byte:04x}")?;
+        } else {
+            writer.write_all(escaped)?;
+        }
+        start = idx + 1;
+    }
+    writer.write_all(&bytes[start..])?;
+    writer.write_all(b"\"")
+}
+
+/// Writes a record as a JSON object without a trailing newline
+fn write_json_object<W: Write>(
+    writer: &mut W,
+    index: &[u8],
+    sequence: &[u8],
+    quality: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(b"{\"id\":")?;
+    write_json_string(writer, index)?;
+    writer.write_all(b",\"seq\":")?;
+    write_json_string(writer, sequence)?;
+    writer.write_all(b",\"qual\":")?;
+    write_json_string(writer, quality)?;
+    writer.write_all(b"}")
+}
+
+pub fn write_jsonl_parts<W: Write>(
+    writer: &mut W,
+    index: &[u8],
+    sequence: &[u8],
+    quality: &[u8],
+) -> std::io::Result<()> {
+    write_json_object(writer, index, sequence, quality)?;
+    writer.write_all(b"\n")
+}
+
+/// Writes both mates of a pair as one nested `{"r1":{...},"r2":{...}}` line
+pub fn write_jsonl_pair<W: Write>(
+    writer: &mut W,
+    sheader: &[u8],
+    sbuf: &[u8],
+    squal: &[u8],
+    xheader: &[u8],
+    xbuf: &[u8],
+    xqual: &[u8],
+) -> std::io::Result<()> {
+    writer.write_all(b"{\"r1\":")?;
+    write_json_object(writer, sheader, sbuf, &squal[..sbuf.len()])?;
+    writer.write_all(b",\"r2\":")?;
+    write_json_object(writer, xheader, xbuf, &xqual[..xbuf.len()])?;
+    writer.write_all(b"}\n")
+}
+
+/// Writes `header` followed by a space-separated `key=value` comment of `fields` into `buf`
+pub fn write_header_comment(
+    buf: &mut Vec<u8>,
+    header: &[u8],
+    sequence: &[u8],
+    flag: Option<u64>,
+    fields: &[CommentField],
+) {
+    buf.clear();
+    buf.extend_from_slice(header);
+    for field in fields {
+        // Writing to a Vec cannot fail
+        match field {
+            CommentField::Len => write!(buf, " len={}", sequence.len()).ok(),
+            CommentField::Gc => {
+                let gc = sequence
+                    .iter()
+                    .filter(|b| matches!(b, b'G' | b'C' | b'g' | b'c'))
+                    .count();
+                let frac = gc as f64 / sequence.len().max(1) as f64;
+                write!(buf, " gc={frac:.4}").ok()
+            }
+            CommentField::Flag => flag.and_then(|flag| write!(buf, " flag={flag}").ok()),
+        };
+    }
+}
+
+/// Opens the `n`-th output of a [`Rotation`]
+pub type ShardOpener = Box<dyn FnMut(usize) -> Result<SplitWriter> + Send>;
+
+/// Byte-based output rotation (`--split-bytes`)
+pub struct Rotation {
+    inner: SplitWriter,
+    open: ShardOpener,
+    limit: u64,
+    written: u64,
+    index: usize,
+}
+impl Rotation {
+    /// Moves on to the next output once the current one has reached the limit.
+    ///
+    /// Only called before a batch of whole records (or pairs) so that shards never
+    /// split a record and R1/R2 shards stay in sync.
+    fn rotate_if_full(&mut self) -> std::io::Result<()> {
+        if self.written < self.limit {
+            return Ok(());
+        }
+        self.index += 1;
+        let next = (self.open)(self.index).map_err(std::io::Error::other)?;
+        let mut previous = std::mem::replace(&mut self.inner, next);
+        previous.finish()?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
 pub enum SplitWriter {
     Interleaved { inner: Writer },
     Split { left: Writer, right: Writer },
+    Rotating(Box<Rotation>),
 }
 impl SplitWriter {
     pub fn new_interleaved(writer: Writer) -> Self {
@@ -59,10 +264,24 @@ impl SplitWriter {
         Self::Split { left, right }
     }
 
+    /// Writes to the outputs returned by `open`, starting a new one whenever
+    /// `limit` bytes (before compression) have been written to the current one.
+    pub fn new_rotating(mut open: ShardOpener, limit: u64) -> Result<Self> {
+        let inner = open(0)?;
+        Ok(Self::Rotating(Box::new(Rotation {
+            inner,
+            open,
+            limit,
+            written: 0,
+            index: 0,
+        })))
+    }
+
     pub fn is_split(&self) -> bool {
         match self {
             Self::Interleaved { .. } => false,
             Self::Split { .. } => true,
+            Self::Rotating(rotation) => rotation.inner.is_split(),
         }
     }
 
@@ -72,6 +291,14 @@ impl SplitWriter {
                 inner.write_all(buf)?;
                 Ok(())
             }
+            SplitWriter::Rotating(rotation) => {
+                if !buf.is_empty() {
+                    rotation.rotate_if_full()?;
+                }
+                rotation.inner.write_interleaved(buf)?;
+                rotation.written += buf.len() as u64;
+                Ok(())
+            }
             SplitWriter::Split { .. } => {
                 panic!("Unable to write to interleaved as the writer is split")
             }
@@ -90,6 +317,15 @@ impl SplitWriter {
                 }
                 Ok(())
             }
+            SplitWriter::Rotating(rotation) => {
+                // a batch writes R1 before R2, so only rotate ahead of R1
+                if write_to_left && !buf.is_empty() {
+                    rotation.rotate_if_full()?;
+                }
+                rotation.inner.write_split(buf, write_to_left)?;
+                rotation.written += buf.len() as u64;
+                Ok(())
+            }
         }
     }
     pub fn flush(&mut self) -> Result<(), std::io::Error> {
@@ -103,6 +339,19 @@ impl SplitWriter {
                 right.flush()?;
                 Ok(())
             }
+            SplitWriter::Rotating(rotation) => rotation.inner.flush(),
+        }
+    }
+
+    /// Finishes the current output(s), writing any compression trailer
+    pub fn finish(&mut self) -> Result<(), std::io::Error> {
+        match self {
+            SplitWriter::Interleaved { inner } => inner.finish(),
+            SplitWriter::Split { left, right } => {
+                left.finish()?;
+                right.finish()
+            }
+            SplitWriter::Rotating(rotation) => rotation.inner.finish(),
         }
     }
 }
@@ -113,12 +362,14 @@ pub fn write_record<W: Write>(
     sequence: &[u8],
     quality: &[u8],
     format: FileFormat,
+    delimiter: u8,
 ) -> Result<(), std::io::Error> {
     let qual_buf = &quality[..sequence.len()];
     match format {
         FileFormat::Fasta => write_fasta_parts(writer, header, sequence),
         FileFormat::Fastq => write_fastq_parts(writer, header, sequence, qual_buf),
-        FileFormat::Tsv => write_tsv_parts(writer, header, sequence),
+        FileFormat::Tsv => write_tsv_parts(writer, header, sequence, delimiter),
+        FileFormat::Jsonl => write_jsonl_parts(writer, header, sequence, qual_buf),
         FileFormat::Bam => unimplemented!("Cannot write BAM record from here"),
     }
 }
@@ -137,26 +388,30 @@ pub fn write_record_pair<W: Write>(
     xqual: &[u8],
     xheader: &[u8],
     format: FileFormat,
+    delimiter: u8,
 ) -> Result<()> {
     match mate {
         Some(Mate::Both) => {
             if split {
-                write_record(left, sheader, sbuf, squal, format)?;
+                write_record(left, sheader, sbuf, squal, format, delimiter)?;
                 if !xbuf.is_empty() {
-                    write_record(right, xheader, xbuf, xqual, format)?;
+                    write_record(right, xheader, xbuf, xqual, format, delimiter)?;
                 }
+            } else if format == FileFormat::Jsonl && !xbuf.is_empty() {
+                // Keep interleaved mates together on one line
+                write_jsonl_pair(mixed, sheader, sbuf, squal, xheader, xbuf, xqual)?;
             } else {
-                write_record(mixed, sheader, sbuf, squal, format)?;
+                write_record(mixed, sheader, sbuf, squal, format, delimiter)?;
                 if !xbuf.is_empty() {
-                    write_record(mixed, xheader, xbuf, xqual, format)?;
+                    write_record(mixed, xheader, xbuf, xqual, format, delimiter)?;
                 }
             }
         }
         Some(Mate::One) | None => {
-            write_record(mixed, sheader, sbuf, squal, format)?;
+            write_record(mixed, sheader, sbuf, squal, format, delimiter)?;
         }
         Some(Mate::Two) => {
-            write_record(mixed, xheader, xbuf, xqual, format)?;
+            write_record(mixed, xheader, xbuf, xqual, format, delimiter)?;
         }
     }
 
@@ -0,0 +1,365 @@
+use std::{fmt::Write, fs::File, path::Path};
+
+use anyhow::{bail, Result};
+use paraseq::{fastx, Record};
+use serde::Serialize;
+
+use crate::{
+    cli::{BinseqMode, DoctorCommand, FileFormat},
+    commands::{
+        formats::{features, Feature},
+        open_binseq, temp_path,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    /// Informational, e.g. an optional feature that is not compiled
+    Note,
+    Warn,
+    Fail,
+}
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Note => "note",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        }
+    }
+}
+
+/// Outcome of a single diagnostic
+#[derive(Debug, Serialize)]
+struct Check {
+    name: String,
+    status: Status,
+    detail: String,
+}
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Library {
+    name: &'static str,
+    version: String,
+}
+
+#[derive(Serialize)]
+struct Report {
+    version: &'static str,
+    features: Vec<Feature>,
+    libraries: Vec<Library>,
+    environment: Vec<Check>,
+    files: Vec<Check>,
+}
+impl Report {
+    fn collect(inputs: &[String]) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features: features(),
+            libraries: libraries(),
+            environment: environment_checks(),
+            files: inputs.iter().map(|path| probe_file(path)).collect(),
+        }
+    }
+
+    fn num_failed(&self) -> usize {
+        self.environment
+            .iter()
+            .chain(&self.files)
+            .filter(|check| check.status == Status::Fail)
+            .count()
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "bqtools {}", self.version).ok();
+        writeln!(out, "\nFeatures:").ok();
+        for feature in &self.features {
+            let status = if feature.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            writeln!(
+                out,
+                "  {:<8}{status:<10}{}",
+                feature.name, feature.description
+            )
+            .ok();
+        }
+        writeln!(out, "\nLibraries:").ok();
+        for library in &self.libraries {
+            writeln!(out, "  {:<8}{}", library.name, library.version).ok();
+        }
+        for (title, checks) in [("Environment", &self.environment), ("Files", &self.files)] {
+            if checks.is_empty() {
+                continue;
+            }
+            writeln!(out, "\n{title}:").ok();
+            for check in checks {
+                writeln!(
+                    out,
+                    "  [{:<4}] {:<12}{}",
+                    check.status.label(),
+                    check.name,
+                    check.detail
+                )
+                .ok();
+            }
+        }
+        out
+    }
+}
+
+/// Versions of the native libraries linked into this build
+fn libraries() -> Vec<Library> {
+    vec![
+        Library {
+            name: "zstd",
+            version: zstd::zstd_safe::version_string().to_string(),
+        },
+        #[cfg(feature = "htslib")]
+        Library {
+            name: "htslib",
+            version: htslib_version(),
+        },
+    ]
+}
+
+#[cfg(feature = "htslib")]
+fn htslib_version() -> String {
+    // SAFETY: `hts_version` returns a pointer to a static NUL-terminated string
+    unsafe { std::ffi::CStr::from_ptr(paraseq::rust_htslib::htslib::hts_version()) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn environment_checks() -> Vec<Check> {
+    let mut checks = Vec::new();
+    checks.push(if cfg!(feature = "htslib") {
+        Check::new("htslib", Status::Ok, "SAM/BAM/CRAM input available")
+    } else {
+        Check::new(
+            "htslib",
+            Status::Warn,
+            "not compiled: SAM/BAM/CRAM input is unavailable. Reinstall with default \
+             features (`cargo install bqtools`); building htslib needs clang, make, \
+             and the zlib, bzip2, and liblzma headers",
+        )
+    });
+    checks.push(if cfg!(feature = "gcs") {
+        Check::new("gcs", Status::Ok, "gs:// input paths available")
+    } else {
+        Check::new(
+            "gcs",
+            Status::Note,
+            "not compiled: gs:// paths are unavailable (`cargo install bqtools -F gcs`)",
+        )
+    });
+    checks.push(Check::new(
+        "threads",
+        Status::Ok,
+        format!("{} available", num_cpus::get()),
+    ));
+    checks.push(tmpdir_check());
+    if let Ok(filter) = std::env::var("BQTOOLS_LOG") {
+        checks.push(Check::new(
+            "log",
+            Status::Note,
+            format!("BQTOOLS_LOG={filter} overrides -q/-v"),
+        ));
+    }
+    checks
+}
+
+/// Whether FIFOs and temporary files can be created in `--tmpdir`
+fn tmpdir_check() -> Check {
+    let probe = temp_path(&format!(".bqtools-doctor-{}", std::process::id()));
+    let dir = probe
+        .parent()
+        .unwrap_or(Path::new(""))
+        .display()
+        .to_string();
+    match File::create(&probe) {
+        Ok(_) => {
+            std::fs::remove_file(&probe).ok();
+            Check::new("tmpdir", Status::Ok, format!("{dir} is writable"))
+        }
+        Err(err) => Check::new(
+            "tmpdir",
+            Status::Warn,
+            format!("{dir} is not writable ({err}); `pipe` needs it, set `--tmpdir`"),
+        ),
+    }
+}
+
+/// Opens `path` and identifies its format from the extension and first record
+fn probe_file(path: &str) -> Check {
+    if let Err(err) = File::open(path) {
+        return Check::new(path, Status::Fail, format!("cannot open: {err}"));
+    }
+    let result = if let Ok(mode) = BinseqMode::determine(path) {
+        probe_binseq(path, mode)
+    } else {
+        match FileFormat::from_path(path) {
+            Some(format @ (FileFormat::Fasta | FileFormat::Fastq)) => probe_fastx(path, format),
+            Some(FileFormat::Bam) => probe_htslib(path),
+            _ => {
+                return Check::new(
+                    path,
+                    Status::Warn,
+                    "unrecognized extension (expected .bq/.vbq/.cbq, FASTX, or SAM/BAM/CRAM)",
+                )
+            }
+        }
+    };
+    match result {
+        Ok(detail) => Check::new(path, Status::Ok, detail),
+        Err(err) => Check::new(path, Status::Fail, format!("{err:#}")),
+    }
+}
+
+fn probe_binseq(path: &str, mode: BinseqMode) -> Result<String> {
+    let reader = open_binseq(path)?;
+    let layout = if reader.is_paired() {
+        "paired"
+    } else {
+        "single-end"
+    };
+    Ok(format!(
+        "{}, {} records, {layout}",
+        &mode.extension()[1..],
+        reader.num_records()?
+    ))
+}
+
+fn probe_fastx(path: &str, format: FileFormat) -> Result<String> {
+    let (inner, compression) = niffler::send::from_path(path)?;
+    let mut reader = fastx::Reader::new(inner)?;
+    let mut rset = reader.new_record_set_with_size(1);
+    if !rset.fill(&mut reader)? {
+        bail!("no records found");
+    }
+    let Some(record) = rset.iter().next() else {
+        bail!("no records found");
+    };
+    let record = record?;
+    let name = match format {
+        FileFormat::Fasta => "fasta",
+        _ => "fastq",
+    };
+    let compression = match compression {
+        niffler::send::compression::Format::No => "uncompressed".to_string(),
+        other => format!("{other:?}").to_lowercase(),
+    };
+    Ok(format!(
+        "{name} ({compression}), first record: {}",
+        String::from_utf8_lossy(record.id())
+    ))
+}
+
+#[cfg(feature = "htslib")]
+fn probe_htslib(path: &str) -> Result<String> {
+    use paraseq::rust_htslib::bam::{self, Read};
+
+    let mut reader = bam::Reader::from_path(path)?;
+    match reader.records().next() {
+        Some(record) => Ok(format!(
+            "alignment file, first record: {}",
+            String::from_utf8_lossy(record?.qname())
+        )),
+        None => bail!("no records found"),
+    }
+}
+
+#[cfg(not(feature = "htslib"))]
+fn probe_htslib(_path: &str) -> Result<String> {
+    bail!("SAM/BAM/CRAM input requires the `htslib` feature (see the htslib check)")
+}
+
+pub fn run(args: &DoctorCommand) -> Result<()> {
+    let report = Report::collect(&args.inputs);
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report.render());
+    }
+    let failed = report.num_failed();
+    if failed > 0 {
+        bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use super::{probe_file, Report, Status};
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    #[test]
+    fn test_probe_files() -> Result<()> {
+        let fq = write_fastx().call()?;
+        let vbq = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            fq.path().to_str().unwrap(),
+            "-o",
+            vbq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let check = probe_file(fq.path().to_str().unwrap());
+        assert_eq!(check.status, Status::Ok, "{}", check.detail);
+        assert!(check.detail.starts_with("fastq"), "{}", check.detail);
+
+        let check = probe_file(vbq.path().to_str().unwrap());
+        assert_eq!(check.status, Status::Ok, "{}", check.detail);
+        assert_eq!(
+            check.detail,
+            format!("vbq, {DEFAULT_NUM_RECORDS} records, single-end")
+        );
+
+        // a FASTQ renamed to .vbq is caught
+        let bogus = NamedTempFile::with_suffix(".vbq")?;
+        std::fs::copy(fq.path(), bogus.path())?;
+        assert_eq!(
+            probe_file(bogus.path().to_str().unwrap()).status,
+            Status::Fail
+        );
+        assert_eq!(probe_file("missing.fastq").status, Status::Fail);
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_reflects_build() {
+        let report = Report::collect(&[]);
+        let htslib = report
+            .environment
+            .iter()
+            .find(|check| check.name == "htslib")
+            .unwrap();
+        assert_eq!(htslib.status == Status::Ok, cfg!(feature = "htslib"));
+        assert_eq!(
+            report.libraries.iter().any(|lib| lib.name == "htslib"),
+            cfg!(feature = "htslib")
+        );
+        assert_eq!(report.num_failed(), 0);
+        assert!(report.render().contains("Environment:"));
+    }
+}
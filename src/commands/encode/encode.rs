@@ -1,16 +1,16 @@
 use anyhow::{bail, Result};
 use binseq::BinseqWriterBuilder;
-use log::trace;
+use log::{info, trace};
 use paraseq::{
     fastx::{self},
     prelude::{PairedParallelProcessor, ParallelProcessor},
 };
 
 use crate::{
-    cli::{BinseqConfig, BinseqMode},
+    cli::{BinseqConfig, BinseqMode, NameFrom},
     commands::{
         encode::{
-            processor::Encoder,
+            processor::{Encoder, IndexMap},
             utils::{get_interleaved_sequence_len, get_sequence_len},
         },
         match_output,
@@ -18,12 +18,24 @@ use crate::{
     types::BoxedReader,
 };
 
+/// Reports the records dropped by `--min-complexity`, separately from N-policy skips
+fn log_low_complexity(count: usize) {
+    if count > 0 {
+        info!("Skipped {count} low-complexity records");
+    }
+}
+
+/// Encodes a fastx collection, returning the number of records written and skipped.
 pub fn encode_collection(
     mut collection: fastx::Collection<BoxedReader>,
     opath: Option<&str>,
     mode: BinseqMode,
     mut config: BinseqConfig,
+    index_map: Option<&IndexMap>,
 ) -> Result<(usize, usize)> {
+    if config.name_from == NameFrom::Index {
+        config.threads = 1;
+    }
     if let Some(infmt) = collection.unique_format() {
         if infmt == fastx::Format::Fasta {
             config.quality = false;
@@ -50,18 +62,18 @@ pub fn encode_collection(
         match collection.collection_type() {
             fastx::CollectionType::Single => {
                 let inner = collection.inner_mut();
-                let slen = get_sequence_len(&mut inner[0])?;
+                let slen = get_sequence_len(&mut inner[0], config.poly_trim)?;
                 builder = builder.slen(slen as u32);
             }
             fastx::CollectionType::Paired => {
                 let inner = collection.inner_mut();
-                let slen = get_sequence_len(&mut inner[0])?;
-                let xlen = get_sequence_len(&mut inner[1])?;
+                let slen = get_sequence_len(&mut inner[0], config.poly_trim)?;
+                let xlen = get_sequence_len(&mut inner[1], config.poly_trim)?;
                 builder = builder.slen(slen as u32).xlen(xlen as u32);
             }
             fastx::CollectionType::Interleaved => {
                 let inner = collection.inner_mut();
-                let (slen, xlen) = get_interleaved_sequence_len(&mut inner[0])?;
+                let (slen, xlen) = get_interleaved_sequence_len(&mut inner[0], config.poly_trim)?;
                 builder = builder.slen(slen).xlen(xlen);
             }
             _ => {
@@ -70,9 +82,22 @@ pub fn encode_collection(
         }
     }
     let writer = builder.build(ohandle)?;
-    let mut processor = Encoder::new(writer)?;
+    let mut processor = Encoder::from_config(writer, &config)?
+        .with_strict_pairing(
+            config.strict_pairing
+                && matches!(collection.collection_type(), fastx::CollectionType::Paired),
+        )
+        .with_interleaving_check(
+            config.check_interleaving
+                && matches!(
+                    collection.collection_type(),
+                    fastx::CollectionType::Interleaved
+                ),
+        )
+        .with_index_map(index_map.cloned());
     process_collection(collection, &mut processor, config.threads)?;
     processor.finish()?;
+    log_low_complexity(processor.get_global_low_complexity_count());
 
     Ok((
         processor.get_global_record_count(),
@@ -117,17 +142,14 @@ where
 }
 
 #[cfg(feature = "htslib")]
-pub fn encode_htslib(
+fn htslib_builder(
     inpath: &str,
-    opath: Option<&str>,
     mode: BinseqMode,
     config: BinseqConfig,
     paired: bool,
-) -> Result<(usize, usize)> {
+) -> Result<BinseqWriterBuilder> {
     use super::utils::get_sequence_len_htslib;
-    use paraseq::{htslib, prelude::*};
 
-    let ohandle = match_output(opath)?;
     let mut builder = BinseqWriterBuilder::new(mode.into())
         .block_size(config.block_size)
         .compression_level(config.compression_level)
@@ -138,21 +160,359 @@ pub fn encode_htslib(
         .paired(paired);
 
     if matches!(mode, BinseqMode::Bq) {
-        let (slen, xlen) = get_sequence_len_htslib(inpath, paired)?;
+        let (slen, xlen) = get_sequence_len_htslib(inpath, paired, config.poly_trim)?;
         builder = builder.slen(slen).xlen(xlen);
     }
-    let reader = htslib::Reader::from_path(inpath)?;
+    Ok(builder)
+}
+
+#[cfg(feature = "htslib")]
+pub fn encode_htslib(
+    inpath: &str,
+    opath: Option<&str>,
+    mode: BinseqMode,
+    mut config: BinseqConfig,
+    paired: bool,
+    index_map: Option<&IndexMap>,
+    batch_size: Option<usize>,
+) -> Result<(usize, usize)> {
+    use paraseq::{htslib, prelude::*};
+
+    if config.name_from == NameFrom::Index {
+        config.threads = 1;
+    }
+    let ohandle = match_output(opath)?;
+    let builder = htslib_builder(inpath, mode, config, paired)?;
+    let reader = if let Some(size) = batch_size {
+        htslib::Reader::from_path_with_batch_size(inpath, size)?
+    } else {
+        htslib::Reader::from_path(inpath)?
+    };
     let writer = builder.build(ohandle)?;
-    let mut processor = Encoder::new(writer)?;
+    let mut processor = Encoder::from_config(writer, &config)?.with_index_map(index_map.cloned());
     if paired {
         reader.process_parallel_interleaved(&mut processor, config.threads)
     } else {
         reader.process_parallel(&mut processor, config.threads)
     }?;
     processor.finish()?;
+    log_low_complexity(processor.get_global_low_complexity_count());
 
     Ok((
         processor.get_global_record_count(),
         processor.get_global_skip_count(),
     ))
 }
+
+/// How records are routed by their `RG` aux tag in [`encode_htslib_read_groups`]
+#[cfg(feature = "htslib")]
+#[derive(Debug, Clone, Copy)]
+pub enum ReadGroupSelection<'a> {
+    /// Keep only records of a single read group
+    Only(&'a str),
+    /// Write each read group to its own output
+    Split,
+}
+
+/// An owned copy of the fields of an htslib record needed for encoding
+#[cfg(feature = "htslib")]
+struct HtsRead {
+    header: Vec<u8>,
+    seq: Vec<u8>,
+    qual: Option<Vec<u8>>,
+    read_group: Option<String>,
+}
+#[cfg(feature = "htslib")]
+impl HtsRead {
+    fn from_record(record: &paraseq::rust_htslib::bam::Record) -> Self {
+        use paraseq::rust_htslib::bam::record::Aux;
+
+        // htslib marks missing qualities with 0xff
+        let qual = record.qual();
+        let qual = (qual.first() != Some(&0xff)).then(|| qual.iter().map(|q| q + 33).collect());
+        let read_group = match record.aux(b"RG") {
+            Ok(Aux::String(rg)) => Some(rg.to_string()),
+            _ => None,
+        };
+        Self {
+            header: record.qname().to_vec(),
+            seq: record.seq().as_bytes(),
+            qual,
+            read_group,
+        }
+    }
+}
+
+/// Records (or pairs) handed to a routing worker at a time; the worker then
+/// completes a batch on every read group encoder it holds
+#[cfg(feature = "htslib")]
+const READ_GROUP_BATCH_SIZE: usize = 1024;
+
+/// A chunk of records (with their mates, for interleaved input) to route
+#[cfg(feature = "htslib")]
+type ReadChunk = Vec<(HtsRead, Option<HtsRead>)>;
+
+/// Read group encoders shared by the routing workers of [`encode_htslib_read_groups`]
+#[cfg(feature = "htslib")]
+struct ReadGroupRouter<'a> {
+    inpath: &'a str,
+    opath: Option<&'a str>,
+    mode: BinseqMode,
+    config: BinseqConfig,
+    paired: bool,
+    selection: ReadGroupSelection<'a>,
+    /// `--max-open-files` bound on the number of outputs when splitting
+    limit: Option<usize>,
+    /// (encoder, output path) of each read group; workers route through clones
+    encoders: parking_lot::Mutex<
+        hashbrown::HashMap<String, (Encoder<crate::types::BoxedWriter>, Option<String>)>,
+    >,
+    /// Input position of the next record, shared across groups (`--name-from index`)
+    next_index: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+    /// Records outside the selected read group
+    num_filtered: std::sync::atomic::AtomicUsize,
+}
+#[cfg(feature = "htslib")]
+impl ReadGroupRouter<'_> {
+    /// Output key for records without an `RG` tag when splitting
+    const UNASSIGNED: &'static str = "unassigned";
+
+    /// Returns a clone of the read group's encoder, opening its output the
+    /// first time any worker sees the group
+    fn open_encoder(&self, key: &str) -> Result<Encoder<crate::types::BoxedWriter>> {
+        use super::utils::read_group_output_name;
+
+        let mut encoders = self.encoders.lock();
+        if let Some((encoder, _)) = encoders.get(key) {
+            return Ok(encoder.clone());
+        }
+        if let Some(limit) = self.limit.filter(|&limit| encoders.len() >= limit) {
+            bail!("`--bam-split-rg` would open more than `--max-open-files {limit}` outputs (read group {key} is not declared in the header; BINSEQ outputs cannot be reopened)");
+        }
+        let path = match self.selection {
+            ReadGroupSelection::Only(_) => self.opath.map(String::from),
+            ReadGroupSelection::Split => self.opath.map(|p| read_group_output_name(p, key)),
+        };
+        trace!("Opening output for read group {key}");
+        let writer = htslib_builder(self.inpath, self.mode, self.config, self.paired)?
+            .build(match_output(path.as_deref())?)?;
+        let encoder =
+            Encoder::from_config(writer, &self.config)?.with_index_counter(self.next_index.clone());
+        encoders.insert(key.to_string(), (encoder.clone(), path));
+        Ok(encoder)
+    }
+
+    /// Worker loop: routes each received chunk to this worker's clones of the
+    /// read group encoders until the reader hangs up
+    fn route(
+        &self,
+        receiver: &parking_lot::Mutex<std::sync::mpsc::Receiver<ReadChunk>>,
+    ) -> Result<()> {
+        use std::sync::atomic::Ordering;
+
+        use hashbrown::{hash_map::Entry, HashMap};
+
+        let mut local = HashMap::new();
+        while let Ok(chunk) = receiver.lock().recv() {
+            for (r1, r2) in chunk {
+                let key = match self.selection {
+                    ReadGroupSelection::Only(id) => {
+                        if r1.read_group.as_deref() != Some(id) {
+                            self.num_filtered.fetch_add(1, Ordering::Relaxed);
+                            if self.config.name_from == NameFrom::Index {
+                                self.next_index.fetch_add(1, Ordering::Relaxed);
+                            }
+                            continue;
+                        }
+                        id.to_string()
+                    }
+                    ReadGroupSelection::Split => r1
+                        .read_group
+                        .clone()
+                        .unwrap_or_else(|| Self::UNASSIGNED.to_string()),
+                };
+                let encoder = match local.entry(key) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let encoder = self.open_encoder(entry.key())?;
+                        entry.insert(encoder)
+                    }
+                };
+                if let Some(r2) = &r2 {
+                    encoder.encode_pair(
+                        &r1.header,
+                        &r1.seq,
+                        r1.qual.as_deref(),
+                        &r2.header,
+                        &r2.seq,
+                        r2.qual.as_deref(),
+                    )?;
+                } else {
+                    encoder.encode_single(&r1.header, &r1.seq, r1.qual.as_deref())?;
+                }
+            }
+            for encoder in local.values_mut() {
+                encoder.complete_batch()?;
+            }
+        }
+        for encoder in local.values_mut() {
+            encoder.complete_thread()?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes an htslib input while filtering or splitting records by read group
+///
+/// Records are read on one thread and handed in chunks to `config.threads`
+/// workers, which route each record to its read group's [`Encoder`] and apply
+/// the same preprocessing as [`encode_htslib`]. Workers share each group's
+/// writer, like the threads of a single parallel encode. For interleaved input
+/// the read group of R1 determines the pair's destination. With `--name-from
+/// index` a single worker keeps records numbered in input order.
+#[cfg(feature = "htslib")]
+pub fn encode_htslib_read_groups(
+    inpath: &str,
+    opath: Option<&str>,
+    mode: BinseqMode,
+    config: BinseqConfig,
+    paired: bool,
+    selection: ReadGroupSelection,
+) -> Result<(usize, usize)> {
+    use std::sync::{atomic::AtomicUsize, mpsc::sync_channel, Arc};
+
+    use anyhow::anyhow;
+    use hashbrown::HashMap;
+    use log::warn;
+    use paraseq::rust_htslib::bam::{self, Read as BamRead};
+    use parking_lot::Mutex;
+
+    use crate::commands::max_open_files;
+
+    if matches!(selection, ReadGroupSelection::Split) && opath.is_none() {
+        bail!("Splitting by read group requires an output path (cannot write to stdout)");
+    }
+
+    let mut reader = bam::Reader::from_path(inpath)?;
+    reader.set_threads(config.threads.max(1))?;
+
+    // BINSEQ writers cannot be closed and reopened mid-stream, so every read
+    // group declared in the header must fit within the bound up front
+    let limit = max_open_files().filter(|_| matches!(selection, ReadGroupSelection::Split));
+    if let Some(limit) = limit {
+        let declared = bam::Header::from_template(reader.header())
+            .to_hashmap()
+            .get("RG")
+            .map_or(0, Vec::len);
+        if declared > limit {
+            bail!("`--bam-split-rg` would open {declared} outputs, more than `--max-open-files {limit}` (BINSEQ outputs cannot be reopened)");
+        }
+    }
+
+    let router = ReadGroupRouter {
+        inpath,
+        opath,
+        mode,
+        config,
+        paired,
+        selection,
+        limit,
+        encoders: Mutex::new(HashMap::new()),
+        next_index: Arc::new(AtomicUsize::new(0)),
+        num_filtered: AtomicUsize::new(0),
+    };
+    let num_workers = if config.name_from == NameFrom::Index {
+        1
+    } else {
+        config.threads.max(1)
+    };
+    let (sender, receiver) = sync_channel::<ReadChunk>(2 * num_workers);
+    let receiver = Mutex::new(receiver);
+    let results: Vec<Result<()>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..num_workers)
+            .map(|_| scope.spawn(|| router.route(&receiver)))
+            .collect();
+        let reading = (|| -> Result<()> {
+            let mut chunk = ReadChunk::with_capacity(READ_GROUP_BATCH_SIZE);
+            let mut pending: Option<HtsRead> = None;
+            for result in reader.records() {
+                let read = HtsRead::from_record(&result?);
+                let pair = if paired {
+                    match pending.take() {
+                        Some(r1) => (r1, Some(read)),
+                        None => {
+                            pending = Some(read);
+                            continue;
+                        }
+                    }
+                } else {
+                    (read, None)
+                };
+                chunk.push(pair);
+                if chunk.len() == READ_GROUP_BATCH_SIZE {
+                    let full = std::mem::replace(
+                        &mut chunk,
+                        ReadChunk::with_capacity(READ_GROUP_BATCH_SIZE),
+                    );
+                    // workers only hang up after an error, which is reported below
+                    if sender.send(full).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            if pending.is_some() {
+                bail!("Interleaved input has an odd number of records - missing R2 for the final pair");
+            }
+            if !chunk.is_empty() {
+                // a hang-up is again a worker error, reported below
+                sender.send(chunk).ok();
+            }
+            Ok(())
+        })();
+        // hang up so the workers drain the queue and stop
+        drop(sender);
+        std::iter::once(reading)
+            .chain(workers.into_iter().map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow!("Read group worker panicked")))
+            }))
+            .collect()
+    });
+    for result in results {
+        result?;
+    }
+
+    let encoders = router.encoders.into_inner();
+    if encoders.is_empty() {
+        if let ReadGroupSelection::Only(id) = selection {
+            warn!("No records found for read group {id}");
+            // still produce an (empty) output so downstream steps find the file
+            htslib_builder(inpath, mode, config, paired)?
+                .build(match_output(opath)?)?
+                .finish()?;
+        }
+    }
+
+    let (mut num_records, mut num_skipped, mut num_low_complexity) = (0, 0, 0);
+    for (read_group, (mut encoder, path)) in encoders {
+        encoder.finish()?;
+        let count = encoder.get_global_record_count();
+        if matches!(selection, ReadGroupSelection::Split) {
+            info!(
+                "Wrote {count} records for read group {read_group} to: {}",
+                path.as_deref().unwrap_or("stdout")
+            );
+        }
+        num_records += count;
+        num_skipped += encoder.get_global_skip_count();
+        num_low_complexity += encoder.get_global_low_complexity_count();
+    }
+    let num_filtered = router.num_filtered.into_inner();
+    if num_filtered > 0 {
+        info!("Excluded {num_filtered} records outside the selected read group");
+    }
+    log_low_complexity(num_low_complexity);
+
+    Ok((num_records, num_skipped))
+}
@@ -0,0 +1,27 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+
+use crate::commands::{load_block_spans, match_output};
+
+/// Writes the block index of a finished VBQ/CBQ file as a standalone TSV.
+///
+/// Columns: `block`, `offset`, `len`, `records`, `cumulative_records`.
+/// Returns the number of blocks written.
+pub fn write_index_sidecar(binseq_path: &str, index_path: &str) -> Result<usize> {
+    let Some(spans) = load_block_spans(binseq_path)? else {
+        bail!("BQ files have no block index - `--emit-index` requires VBQ or CBQ output")
+    };
+
+    let mut writer = match_output(Some(index_path))?;
+    writeln!(writer, "block\toffset\tlen\trecords\tcumulative_records")?;
+    for (block, span) in spans.iter().enumerate() {
+        writeln!(
+            writer,
+            "{block}\t{}\t{}\t{}\t{}",
+            span.offset, span.len, span.records, span.cumulative_records
+        )?;
+    }
+    writer.flush()?;
+    Ok(spans.len())
+}
@@ -3,6 +3,7 @@ use std::{
     io::{BufRead, BufReader},
     os::unix::fs::FileTypeExt,
     path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use anyhow::{bail, Result};
@@ -14,31 +15,112 @@ use walkdir::WalkDir;
 #[cfg(feature = "htslib")]
 use anyhow::Context;
 #[cfg(feature = "htslib")]
-use encode::encode_htslib;
+use encode::{encode_htslib, encode_htslib_read_groups, ReadGroupSelection};
 
 use crate::{
-    cli::{EncodeCommand, FileFormat},
-    commands::encode::utils::{
-        collate_groups, generate_output_name, pair_r1_r2_files, pull_single_files,
+    cli::{EncodeCommand, FileFormat, NameFrom},
+    commands::encode::{
+        processor::IndexMap,
+        utils::{
+            collate_groups, expand_globs, generate_output_name, pair_r1_r2_files,
+            pull_single_files, OutputManifest,
+        },
     },
+    commands::{progress, OutputExists},
 };
 
 mod encode;
+mod index;
 pub mod processor;
 pub mod utils;
 
-use encode::encode_collection;
+pub use encode::encode_collection;
+use index::write_index_sidecar;
 
-/// Run the encoding process for an atomic single/paired input
-fn run_atomic(args: &EncodeCommand) -> Result<()> {
-    let opath = args.output_path()?;
-    let (num_records, num_skipped) = if args.input.paired() {
+/// Error returned when an output holds fewer records than `--min-records`.
+///
+/// Batch encoding moves on to the remaining entries but fails once the queue is done.
+#[derive(Debug)]
+struct TooFewRecords {
+    num_records: usize,
+    min_records: usize,
+}
+impl std::fmt::Display for TooFewRecords {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Wrote {} records, fewer than the required `--min-records {}`",
+            self.num_records, self.min_records
+        )
+    }
+}
+impl std::error::Error for TooFewRecords {}
+
+/// Dispatches an htslib input to the plain or read-group-aware encoder
+#[cfg(feature = "htslib")]
+fn run_htslib(
+    args: &EncodeCommand,
+    inpath: &str,
+    opath: Option<&str>,
+    paired: bool,
+    index_map: Option<&IndexMap>,
+) -> Result<(usize, usize)> {
+    let selection = if let Some(id) = args.input.bam_rg.as_deref() {
+        Some(ReadGroupSelection::Only(id))
+    } else if args.input.bam_split_rg {
+        Some(ReadGroupSelection::Split)
+    } else {
+        None
+    };
+    if let Some(selection) = selection {
+        if index_map.is_some() {
+            warn!("`--index-map` is ignored when filtering or splitting by read group");
+        }
+        trace!("launching read group encoding (htslib): {selection:?}");
+        encode_htslib_read_groups(
+            inpath,
+            opath,
+            args.mode()?,
+            args.config(),
+            paired,
+            selection,
+        )
+    } else {
+        encode_htslib(
+            inpath,
+            opath,
+            args.mode()?,
+            args.config(),
+            paired,
+            index_map,
+            args.input.bam_batch_size,
+        )
+    }
+}
+
+/// Encodes the single, paired, or interleaved input of `args`
+///
+/// Returns the number of records written and skipped.
+fn encode_input(
+    args: &EncodeCommand,
+    opath: Option<&str>,
+    index_map: Option<&IndexMap>,
+) -> Result<(usize, usize)> {
+    if args.input.paired() && args.input.strict_pairing {
+        trace!("launching paired encoding (strict pairing)");
+        let (collection, pairing) = args.input.build_strict_paired_collection()?;
+        let result = encode_collection(collection, opath, args.mode()?, args.config(), index_map);
+        // a reader that ended early is the likelier cause of a failed pair
+        pairing.verify()?;
+        result
+    } else if args.input.paired() {
         trace!("launching paired encoding");
         encode_collection(
             args.input.build_paired_collection()?,
-            opath.as_deref(),
+            opath,
             args.mode()?,
-            args.output.options.into(),
+            args.config(),
+            index_map,
         )
     } else if args.input.interleaved {
         if let Some(FileFormat::Bam) = args.input.format() {
@@ -51,23 +133,24 @@ fn run_atomic(args: &EncodeCommand) -> Result<()> {
             #[cfg(feature = "htslib")]
             {
                 trace!("launching interleaved encoding (htslib)");
-                encode_htslib(
+                run_htslib(
+                    args,
                     args.input
                         .single_path()?
                         .context("Must provide an input path for HTSLib")?,
-                    opath.as_deref(),
-                    args.mode()?,
-                    args.output.options.into(),
+                    opath,
                     true,
+                    index_map,
                 )
             }
         } else {
             trace!("launching interleaved encoding (fastx)");
             encode_collection(
                 args.input.build_interleaved_collection()?,
-                opath.as_deref(),
+                opath,
                 args.mode()?,
-                args.output.options.into(),
+                args.config(),
+                index_map,
             )
         }
     } else if let Some(FileFormat::Bam) = args.input.format() {
@@ -80,25 +163,50 @@ fn run_atomic(args: &EncodeCommand) -> Result<()> {
         #[cfg(feature = "htslib")]
         {
             trace!("launching single encoding (htslib)");
-            encode_htslib(
+            run_htslib(
+                args,
                 args.input
                     .single_path()?
                     .context("Must provide an input path for HTSlib")?,
-                opath.as_deref(),
-                args.mode()?,
-                args.output.options.into(),
+                opath,
                 false,
+                index_map,
             )
         }
     } else {
         trace!("launching single encoding (fastx)");
         encode_collection(
             args.input.build_single_collection()?,
-            opath.as_deref(),
+            opath,
             args.mode()?,
-            args.output.options.into(),
+            args.config(),
+            index_map,
         )
-    }?;
+    }
+}
+
+/// Run the encoding process for an atomic single/paired input
+///
+/// Returns the number of records written.
+fn run_atomic(args: &EncodeCommand) -> Result<usize> {
+    let opath = args.output_path()?;
+    if (args.input.bam_rg.is_some() || args.input.bam_split_rg)
+        && !matches!(args.input.format(), Some(FileFormat::Bam))
+    {
+        warn!("Read group options (`--bam-rg`, `--bam-split-rg`) only apply to BAM/SAM/CRAM inputs and will be ignored");
+    }
+    let index_map = args
+        .index_map
+        .as_deref()
+        .map(|path| IndexMap::create(path, args.input.paired() || args.input.interleaved))
+        .transpose()?;
+    if args.name_from == NameFrom::Index && args.output.threads() > 1 {
+        warn!("`--name-from index` encodes on a single thread to keep headers in input order");
+    }
+    if args.name_from != NameFrom::Id && !args.output.options.headers() {
+        warn!("`--name-from` has no effect when headers are not stored (`-H`)");
+    }
+    let (num_records, num_skipped) = encode_input(args, opath.as_deref(), index_map.as_ref())?;
 
     if let Some(opath) = opath {
         info!("Wrote {num_records} records to: {opath}");
@@ -108,95 +216,91 @@ fn run_atomic(args: &EncodeCommand) -> Result<()> {
     if num_skipped > 0 {
         info!("Skipped {num_skipped} records");
     }
+    if let Some(min_records) = args.min_records {
+        if num_records < min_records {
+            return Err(TooFewRecords {
+                num_records,
+                min_records,
+            }
+            .into());
+        }
+    }
+
+    Ok(num_records)
+}
 
+/// Encodes each entry of the queue, distributing threads across entries.
+///
+/// `output_override` replaces the derived output name for single/paired entries,
+/// and is only provided when the whole queue collapses to a single output.
+/// Fails once every entry has been attempted if any of them failed.
+fn process_queue(
+    args: &EncodeCommand,
+    queue: Vec<Vec<PathBuf>>,
+    regex: &Regex,
+    manifest: Option<&OutputManifest>,
+    output_override: Option<&str>,
+) -> Result<()> {
+    let num_entries = queue.len();
+    let num_failed = if let Some(parallel_files) = args.input.batch_encoding_options.parallel_files
+    {
+        process_queue_pooled(
+            args,
+            &queue,
+            regex,
+            manifest,
+            output_override,
+            parallel_files,
+        )
+    } else {
+        process_queue_threaded(args, queue, regex, manifest, output_override)
+    };
+    if num_failed > 0 {
+        bail!("{num_failed} of {num_entries} batch entries failed (see errors above)");
+    }
     Ok(())
 }
 
-fn process_queue(args: &EncodeCommand, queue: Vec<Vec<PathBuf>>, regex: &Regex) -> Result<()> {
+/// Encodes the queue with the threads split across concurrent entries,
+/// returning the number of entries that failed.
+fn process_queue_threaded(
+    args: &EncodeCommand,
+    queue: Vec<Vec<PathBuf>>,
+    regex: &Regex,
+    manifest: Option<&OutputManifest>,
+    output_override: Option<&str>,
+) -> usize {
+    let mut num_failed = 0;
     let num_threads = args.output.threads();
 
     // Case where there are more threads than files
     if queue.len() <= num_threads {
-        let base_threads_per_file = num_threads / queue.len();
-        let leftover_threads = num_threads % queue.len();
+        let sizes: Vec<u64> = queue.iter().map(|entry| entry_size(entry)).collect();
+        let allocation = size_weighted_threads(&sizes, num_threads);
 
         info!(
             "Distributing {} threads across {} files",
             num_threads,
             queue.len()
         );
-        if leftover_threads > 0 {
-            debug!(
-                "Base threads per file: {base_threads_per_file}, extra threads for first {leftover_threads} file(s)"
-            );
-        } else {
-            debug!("Threads per file: {base_threads_per_file}");
-        }
 
         let mut handles = vec![];
-        for (i, pair) in queue.into_iter().enumerate() {
+        for (pair, threads_for_this_file) in queue.into_iter().zip(allocation) {
             let thread_args = args.clone();
             let thread_regex = regex.clone();
-            let mode = args.mode()?;
-
-            // First `leftover_threads` files get one extra thread
-            let threads_for_this_file = if i < leftover_threads {
-                base_threads_per_file + 1
-            } else {
-                base_threads_per_file
-            };
+            let thread_manifest = manifest.cloned();
+            let thread_override = output_override.map(str::to_string);
+            debug!("Threads for {}: {threads_for_this_file}", pair[0].display());
 
             let handle = std::thread::spawn(move || -> Result<()> {
-                let mut file_args = thread_args.clone();
-
-                let outpath = match pair.len() {
-                    1 => {
-                        let inpath = pair[0].to_str().unwrap().to_string();
-                        let outpath = thread_regex
-                            .replace_all(&inpath, mode.extension())
-                            .to_string();
-                        file_args.input.input = vec![inpath];
-                        file_args.output.output = Some(outpath.clone());
-                        file_args.output.options.threads = threads_for_this_file;
-                        outpath
-                    }
-                    2 => {
-                        let inpaths: Vec<String> = pair
-                            .iter()
-                            .map(|path| path.to_str().unwrap().to_string())
-                            .collect();
-                        let outpath = generate_output_name(&pair, mode.extension())?;
-
-                        file_args.input.input = inpaths;
-                        file_args.output.output = Some(outpath.clone());
-                        file_args.output.options.threads = threads_for_this_file;
-                        outpath
-                    }
-                    _ => {
-                        let inpaths: Vec<String> = pair
-                            .iter()
-                            .map(|path| path.to_str().unwrap().to_string())
-                            .collect();
-                        let outpath = thread_args.output_path()?.ok_or_else(|| {
-                            anyhow::anyhow!("Output path must be provided when collating files")
-                        })?;
-
-                        file_args.input.input = inpaths;
-                        file_args.output.output = Some(outpath.clone());
-                        file_args.output.options.threads = threads_for_this_file;
-                        outpath
-                    }
-                };
-
-                match run_atomic(&file_args) {
-                    Ok(()) => (),
-                    Err(err) => {
-                        error!("Error generating output: {outpath}\n{err:?}\nSkipping.");
-                        trace!("Removing partial file: {outpath}");
-                        std::fs::remove_file(outpath)?;
-                    }
-                }
-                Ok(())
+                encode_queue_entry(
+                    &thread_args,
+                    &pair,
+                    &thread_regex,
+                    thread_manifest.as_ref(),
+                    thread_override.as_deref(),
+                    threads_for_this_file,
+                )
             });
             handles.push(handle);
         }
@@ -205,14 +309,23 @@ fn process_queue(args: &EncodeCommand, queue: Vec<Vec<PathBuf>>, regex: &Regex)
             match handle.join() {
                 Ok(res) => match res {
                     Ok(()) => (),
-                    Err(err) => error!("Error in thread: {err:?}"),
+                    Err(err) => {
+                        error!("Error in thread: {err:?}");
+                        num_failed += 1;
+                    }
                 },
-                Err(err) => error!("Error joining thread: {err:?}"),
+                Err(err) => {
+                    error!("Error joining thread: {err:?}");
+                    num_failed += 1;
+                }
             }
         }
 
     // Case where there are more files than threads (batching)
     } else {
+        // start the largest jobs first so they do not trail the batch
+        let mut queue = queue;
+        queue.sort_by_cached_key(|entry| std::cmp::Reverse(entry_size(entry)));
         let mut num_processed = 0;
         loop {
             let rbound = (num_processed + num_threads).min(queue.len());
@@ -221,10 +334,180 @@ fn process_queue(args: &EncodeCommand, queue: Vec<Vec<PathBuf>>, regex: &Regex)
             }
             let subqueue = queue[num_processed..rbound].to_vec();
             num_processed += subqueue.len();
-            process_queue(args, subqueue, regex)?;
+            num_failed += process_queue_threaded(args, subqueue, regex, manifest, output_override);
         }
     }
 
+    num_failed
+}
+
+/// Total size in bytes of a queue entry's input files (0 for FIFOs or unreadable paths)
+fn entry_size(entry: &[PathBuf]) -> u64 {
+    entry
+        .iter()
+        .filter_map(|path| path.metadata().ok())
+        .filter(std::fs::Metadata::is_file)
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Splits `num_threads` across entries in proportion to their `sizes`.
+///
+/// Every entry gets at least one thread; the rest are divided by size, with
+/// rounding leftovers going to the entries with the largest remainders (earlier
+/// entries first on ties). Entries of equal size get an even split.
+/// Requires `sizes.len() <= num_threads`.
+fn size_weighted_threads(sizes: &[u64], num_threads: usize) -> Vec<usize> {
+    let extra = (num_threads - sizes.len()) as u128;
+    // a zero-size entry (e.g. a FIFO) still weighs as much as a one-byte file
+    let weights: Vec<u128> = sizes.iter().map(|&size| u128::from(size.max(1))).collect();
+    let total: u128 = weights.iter().sum();
+
+    let mut allocation: Vec<usize> = weights
+        .iter()
+        .map(|w| 1 + (extra * w / total) as usize)
+        .collect();
+    let assigned: usize = allocation.iter().sum();
+
+    let mut by_remainder: Vec<usize> = (0..sizes.len()).collect();
+    by_remainder.sort_by_key(|&i| std::cmp::Reverse(extra * weights[i] % total));
+    for &i in by_remainder.iter().take(num_threads - assigned) {
+        allocation[i] += 1;
+    }
+    allocation
+}
+
+/// Encodes the queue with `parallel_files` workers, each using the full thread count,
+/// returning the number of entries that failed.
+///
+/// Workers pull the next entry as soon as they finish one, so a long file does
+/// not hold up the rest of the queue.
+fn process_queue_pooled(
+    args: &EncodeCommand,
+    queue: &[Vec<PathBuf>],
+    regex: &Regex,
+    manifest: Option<&OutputManifest>,
+    output_override: Option<&str>,
+    parallel_files: usize,
+) -> usize {
+    let threads_per_file = args.output.threads();
+    let num_workers = parallel_files.min(queue.len());
+    info!(
+        "Encoding {} files, {num_workers} at a time with {threads_per_file} threads each",
+        queue.len()
+    );
+
+    let next = AtomicUsize::new(0);
+    let num_failed = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..num_workers)
+            .map(|_| {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(entry) = queue.get(i) else {
+                        return;
+                    };
+                    if let Err(err) = encode_queue_entry(
+                        args,
+                        entry,
+                        regex,
+                        manifest,
+                        output_override,
+                        threads_per_file,
+                    ) {
+                        error!("Error in thread: {err:?}");
+                        num_failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            if let Err(err) = handle.join() {
+                error!("Error joining thread: {err:?}");
+                num_failed.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+    num_failed.into_inner()
+}
+
+/// Encodes a single queue entry (one file, a pair, or a collated group) with `threads`.
+///
+/// Failures are logged and the partial output removed so the rest of the batch
+/// can proceed; successful outputs are appended to the manifest. An output short
+/// of `--min-records` is still returned as an error so the batch fails at the end.
+fn encode_queue_entry(
+    args: &EncodeCommand,
+    entry: &[PathBuf],
+    regex: &Regex,
+    manifest: Option<&OutputManifest>,
+    output_override: Option<&str>,
+    threads: usize,
+) -> Result<()> {
+    let mut file_args = args.clone();
+    // a single sidecar cannot describe several outputs
+    file_args.index_map = None;
+    let outpath = entry_output_path(args, entry, regex, output_override)?;
+    file_args.input.input = entry
+        .iter()
+        .map(|path| path.to_str().unwrap().to_string())
+        .collect();
+    file_args.output.output = Some(outpath.clone());
+    file_args.output.options.threads = threads;
+    record_entry_result(run_atomic(&file_args), &outpath, manifest)
+}
+
+/// Output path of a queue entry: derived from its input name(s) unless overridden.
+///
+/// Collated groups (more than two inputs) always write to the `-o` path.
+fn entry_output_path(
+    args: &EncodeCommand,
+    entry: &[PathBuf],
+    regex: &Regex,
+    output_override: Option<&str>,
+) -> Result<String> {
+    let mode = args.mode()?;
+    match (entry.len(), output_override) {
+        (1 | 2, Some(outpath)) => Ok(outpath.to_string()),
+        (1, None) => Ok(regex
+            .replace_all(entry[0].to_str().unwrap(), mode.extension())
+            .to_string()),
+        (2, None) => generate_output_name(entry, mode.extension()),
+        _ => args
+            .output_path()?
+            .ok_or_else(|| anyhow::anyhow!("Output path must be provided when collating files")),
+    }
+}
+
+/// Appends a finished entry to the manifest, or logs its failure and removes the partial output.
+fn record_entry_result(
+    result: Result<usize>,
+    outpath: &str,
+    manifest: Option<&OutputManifest>,
+) -> Result<()> {
+    match result {
+        Ok(num_records) => {
+            if let Some(manifest) = manifest {
+                manifest.append(outpath, num_records)?;
+            }
+        }
+        Err(err) if err.is::<OutputExists>() => {
+            error!("{err}\nSkipping.");
+        }
+        Err(err) => {
+            error!("Error generating output: {outpath}\n{err:?}\nSkipping.");
+            trace!("Removing partial file: {outpath}");
+            // The output may never have been created; don't mask the original error
+            match std::fs::remove_file(outpath) {
+                Ok(()) => {}
+                Err(remove_err) if remove_err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(remove_err) => warn!("Failed to remove partial file {outpath}: {remove_err}"),
+            }
+            if err.is::<TooFewRecords>() {
+                return Err(err.context(format!("Too few records in: {outpath}")));
+            }
+        }
+    }
     Ok(())
 }
 
@@ -263,6 +546,9 @@ fn process_file_list(args: &EncodeCommand, file_queue: Vec<PathBuf>) -> Result<(
 
     let mut sorted_queue = file_queue;
     sorted_queue.sort_unstable();
+    if progress::tracking_bytes() {
+        progress::add_total_bytes(progress::input_bytes(&sorted_queue));
+    }
 
     // Build the regex for output naming
     let regex = build_file_regex(args.input.batch_encoding_options.paired)?;
@@ -292,11 +578,31 @@ fn process_file_list(args: &EncodeCommand, file_queue: Vec<PathBuf>) -> Result<(
         info!("Total files found: {}", pqueue.len());
     }
 
-    if pqueue.len() > 1 && args.output.output.is_some() {
-        warn!("Output path specified but ignored when batch encoding multiple files.");
-    }
+    // An explicit output path is only meaningful when the queue yields one output
+    let output_override = if pqueue.len() == 1 {
+        args.output.output.as_deref()
+    } else {
+        if args.output.output.is_some() {
+            warn!("Output path specified but ignored when batch encoding multiple outputs.");
+        }
+        None
+    };
 
-    process_queue(args, pqueue, &regex)
+    let batch_options = &args.input.batch_encoding_options;
+    let manifest = OutputManifest::create(
+        batch_options.output_manifest.as_deref(),
+        batch_options.checksum_manifest.as_deref(),
+    )?;
+
+    process_queue(args, pqueue, &regex, manifest.as_ref(), output_override)?;
+
+    if let Some(path) = &batch_options.output_manifest {
+        info!("Wrote output manifest to: {path}");
+    }
+    if let Some(path) = &batch_options.checksum_manifest {
+        info!("Wrote checksum manifest to: {path}");
+    }
+    Ok(())
 }
 
 fn run_recursive(args: &EncodeCommand) -> Result<()> {
@@ -341,29 +647,72 @@ fn run_manifest(args: &EncodeCommand) -> Result<()> {
 fn run_manifest_inline(args: &EncodeCommand) -> Result<()> {
     let regex = build_file_regex(args.input.batch_encoding_options.paired)?;
 
-    let file_queue = filter_valid_paths(args.input.input.iter().map(PathBuf::from), &regex)?;
+    let paths = expand_globs(&args.input.input)?;
+    let file_queue = filter_valid_paths(paths.into_iter(), &regex)?;
 
     process_file_list(args, file_queue)
 }
 
 pub fn run(args: &EncodeCommand) -> Result<()> {
+    let progress = args.progress.spawn();
+    let result = run_mode(args);
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    result
+}
+
+fn run_mode(args: &EncodeCommand) -> Result<()> {
+    let inline_batch = args.input.num_files() > 2 || args.input.has_glob();
+    let batch = args.input.recursive || args.input.manifest.is_some() || inline_batch;
+    if args.emit_index.is_some() && batch {
+        warn!("`--emit-index` is ignored when batch encoding multiple files");
+    }
+    if args.index_map.is_some() && batch {
+        warn!("`--index-map` is ignored when batch encoding multiple files");
+    }
     if args.input.recursive {
         trace!("launching encode-recursive");
         run_recursive(args)
     } else if args.input.manifest.is_some() {
         trace!("launching encode-manifest");
         run_manifest(args)
-    } else if args.input.num_files() > 2 {
+    } else if inline_batch {
         trace!("launching inline manifest");
         run_manifest_inline(args)
     } else {
         trace!("launching encode-atomic");
-        run_atomic(args)
+        if args.input.batch_encoding_options.output_manifest.is_some() {
+            warn!("`--output-manifest` is ignored unless batch encoding multiple files.");
+        }
+        if args
+            .input
+            .batch_encoding_options
+            .checksum_manifest
+            .is_some()
+        {
+            warn!("`--checksum-manifest` is ignored unless batch encoding multiple files.");
+        }
+        // HTSlib inputs are read outside the byte-counted readers
+        if progress::tracking_bytes() && !matches!(args.input.format(), Some(FileFormat::Bam)) {
+            progress::add_total_bytes(progress::input_bytes(&args.input.input));
+        }
+        run_atomic(args)?;
+        if let Some(index_path) = &args.emit_index {
+            let Some(opath) = args.output_path()? else {
+                bail!("`--emit-index` requires a file output (cannot read the index back from stdout)");
+            };
+            let n_blocks = write_index_sidecar(&opath, index_path)?;
+            info!("Wrote block index ({n_blocks} blocks) to: {index_path}");
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Write;
+
     use anyhow::Result;
     use clap::Parser;
     use itertools::iproduct;
@@ -401,6 +750,59 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_encoding_min_records() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        for (min_records, ok) in [
+            (DEFAULT_NUM_RECORDS, true),
+            (DEFAULT_NUM_RECORDS + 1, false),
+        ] {
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--min-records",
+                &min_records.to_string(),
+            ])?;
+            assert_eq!(super::run(&cmd).is_ok(), ok, "--min-records {min_records}");
+        }
+        Ok(())
+    }
+
+    /// A short output fails a batch run at the end without stopping the other entries.
+    #[test]
+    fn test_encoding_min_records_batch() -> Result<()> {
+        for parallel_files in [None, Some("2")] {
+            let dir = tempfile::tempdir()?;
+            for (name, nrec) in [("full.fastq", DEFAULT_NUM_RECORDS), ("short.fastq", 10)] {
+                let in_tmp = write_fastx().nrec(nrec).call()?;
+                std::fs::copy(in_tmp.path(), dir.path().join(name))?;
+            }
+            let mut args = vec![
+                "encode",
+                dir.path().to_str().unwrap(),
+                "-r",
+                "--min-records",
+                "50",
+            ];
+            if let Some(n) = parallel_files {
+                args.extend(["--parallel-files", n]);
+            }
+            let cmd = crate::cli::EncodeCommand::try_parse_from(args)?;
+            let err = format!("{:#}", super::run(&cmd).unwrap_err());
+            assert!(err.contains("1 of 2"), "{err}");
+
+            assert_eq!(
+                count_binseq(&dir.path().join("full.cbq"))?,
+                DEFAULT_NUM_RECORDS
+            );
+            assert!(!dir.path().join("short.cbq").exists());
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_encoding_thread_counts() -> Result<()> {
         for threads in ["0", "1", "4"] {
@@ -486,6 +888,883 @@ mod tests {
         Ok(())
     }
 
+    /// `--output-manifest` lists every batch-encoded output with its record count.
+    #[test]
+    fn test_batch_output_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut inputs = Vec::new();
+        for name in ["a", "b", "c"] {
+            let in_tmp = write_fastx().call()?;
+            let path = dir.path().join(format!("{name}.fastq"));
+            std::fs::copy(in_tmp.path(), &path)?;
+            inputs.push(path.to_str().unwrap().to_string());
+        }
+        let manifest_path = dir.path().join("manifest.tsv");
+
+        let mut args = vec!["encode".to_string()];
+        args.extend(inputs.iter().cloned());
+        args.extend([
+            "--output-manifest".to_string(),
+            manifest_path.to_str().unwrap().to_string(),
+        ]);
+        let cmd = crate::cli::EncodeCommand::try_parse_from(args)?;
+        super::run(&cmd)?;
+
+        let contents = std::fs::read_to_string(&manifest_path)?;
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some("path\tnum_records"));
+        let mut entries: Vec<_> = lines.collect();
+        entries.sort_unstable();
+        assert_eq!(entries.len(), 3);
+        for (entry, input) in entries.iter().zip(&inputs) {
+            let (path, count) = entry.split_once('\t').unwrap();
+            assert_eq!(path, input.replace(".fastq", ".cbq"));
+            assert_eq!(count.parse::<usize>()?, DEFAULT_NUM_RECORDS);
+            assert_eq!(
+                count_binseq(std::path::Path::new(path))?,
+                DEFAULT_NUM_RECORDS
+            );
+        }
+        Ok(())
+    }
+
+    /// Quoted glob patterns are expanded internally and paired like shell-expanded paths.
+    #[test]
+    fn test_batch_glob_patterns() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in ["a", "b"] {
+            for mate in ["R1", "R2"] {
+                let in_tmp = write_fastx().call()?;
+                std::fs::copy(
+                    in_tmp.path(),
+                    dir.path().join(format!("{name}_{mate}.fastq")),
+                )?;
+            }
+        }
+        let pattern = |mate: &str| format!("{}/*_{mate}.fastq", dir.path().display());
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode".to_string(),
+            pattern("R1"),
+            pattern("R2"),
+            "--paired".to_string(),
+        ])?;
+        super::run(&cmd)?;
+        for name in ["a", "b"] {
+            let out_path = dir.path().join(format!("{name}.cbq"));
+            assert_eq!(count_binseq(&out_path)?, DEFAULT_NUM_RECORDS);
+        }
+
+        // a pattern matching nothing is an error rather than an empty batch
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode".to_string(),
+            format!("{}/*.fq.gz", dir.path().display()),
+        ])?;
+        let err = super::run(&cmd).unwrap_err();
+        assert!(err.to_string().contains("No files match"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_size_weighted_threads() {
+        // equal sizes split evenly, leftovers to the first entries
+        assert_eq!(super::size_weighted_threads(&[10, 10, 10], 8), [3, 3, 2]);
+        // a large file takes most threads but small files keep one each
+        assert_eq!(
+            super::size_weighted_threads(&[50_000, 10, 10, 10], 16),
+            [13, 1, 1, 1]
+        );
+        // proportional split with every thread assigned
+        let allocation = super::size_weighted_threads(&[300, 100, 0], 10);
+        assert_eq!(allocation, [6, 3, 1]);
+        assert_eq!(super::size_weighted_threads(&[0, 0], 2), [1, 1]);
+    }
+
+    /// `--parallel-files` encodes every file of the batch regardless of the pool size.
+    #[test]
+    fn test_batch_parallel_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut args = vec!["encode".to_string()];
+        let mut outputs = Vec::new();
+        for name in ["a", "b", "c", "d", "e"] {
+            let in_tmp = write_fastx().call()?;
+            let path = dir.path().join(format!("{name}.fastq"));
+            std::fs::copy(in_tmp.path(), &path)?;
+            args.push(path.to_str().unwrap().to_string());
+            outputs.push(path.with_extension("cbq"));
+        }
+        args.extend(["--parallel-files", "2", "-T", "2"].map(String::from));
+        let cmd = crate::cli::EncodeCommand::try_parse_from(args)?;
+        super::run(&cmd)?;
+
+        for output in outputs {
+            assert_eq!(count_binseq(&output)?, DEFAULT_NUM_RECORDS);
+        }
+
+        // a zero-sized pool is rejected at parse time
+        assert!(crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            "a.fastq",
+            "b.fastq",
+            "c.fastq",
+            "--parallel-files",
+            "0"
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// `--input-threads` decodes BGZF inputs in parallel and falls back for plain gzip.
+    #[test]
+    fn test_encode_input_threads() -> Result<()> {
+        use gzp::{deflate::Bgzf, par::compress::ParCompressBuilder, ZWriter};
+        use std::io::Write;
+
+        let plain = write_fastx().call()?;
+        let bgzf = NamedTempFile::with_suffix(".fastq.gz")?;
+        let mut wtr = ParCompressBuilder::<Bgzf>::new()
+            .num_threads(2)?
+            .from_writer(std::fs::File::create(bgzf.path())?);
+        wtr.write_all(&std::fs::read(plain.path())?)?;
+        wtr.finish()?;
+        let gzip = write_fastx().comp(Compression::Gzip).call()?;
+
+        for input in [bgzf.path(), gzip.path()] {
+            let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                input.to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--input-threads",
+                "4",
+            ])?;
+            super::run(&cmd)?;
+            assert_eq!(count_binseq(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+        }
+        Ok(())
+    }
+
+    /// `--checksum-manifest` records the size and xxh3 digest of each output.
+    #[test]
+    fn test_batch_checksum_manifest() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut args = vec!["encode".to_string()];
+        for name in ["a", "b", "c"] {
+            let in_tmp = write_fastx().call()?;
+            let path = dir.path().join(format!("{name}.fastq"));
+            std::fs::copy(in_tmp.path(), &path)?;
+            args.push(path.to_str().unwrap().to_string());
+        }
+        let manifest_path = dir.path().join("checksums.tsv");
+        args.extend([
+            "--checksum-manifest".to_string(),
+            manifest_path.to_str().unwrap().to_string(),
+        ]);
+        let cmd = crate::cli::EncodeCommand::try_parse_from(args)?;
+        super::run(&cmd)?;
+
+        let contents = std::fs::read_to_string(&manifest_path)?;
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some("path\tsize\txxh3"));
+        let entries: Vec<_> = lines.collect();
+        assert_eq!(entries.len(), 3);
+        for entry in entries {
+            let fields: Vec<_> = entry.split('\t').collect();
+            let bytes = std::fs::read(fields[0])?;
+            assert_eq!(fields[1].parse::<usize>()?, bytes.len());
+            assert_eq!(
+                fields[2],
+                format!("{:016x}", xxhash_rust::xxh3::xxh3_64(&bytes))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_canonicalize_soft_masked() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fasta")?;
+        std::fs::write(in_tmp.path(), ">masked\nacgtRYacgtACGT\n")?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--canonicalize",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_binseq(out_tmp.path())?, 1);
+
+        let out_fa = NamedTempFile::with_suffix(".fasta")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fa.path().to_str().unwrap(),
+            "-f",
+            "a",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fa.path())?;
+        let seq = content.lines().nth(1).unwrap();
+        assert_eq!(seq.len(), 14);
+        assert!(seq.bytes().all(|b| b"ACGT".contains(&b)), "{seq}");
+        assert!(seq.starts_with("ACGT") && seq.ends_with("ACGTACGT"));
+        Ok(())
+    }
+
+    /// BQ takes its fixed length from the trimmed records.
+    #[test]
+    fn test_encode_trim_poly_tails() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        std::fs::write(
+            in_tmp.path(),
+            "@r1\nACGTACGTGGGGGG\n+\nIIIIIIII######\n@r2\nTTGCACCTAAAAGGGG\n+\nIIIIIIII########\n",
+        )?;
+        let out_tmp = NamedTempFile::with_suffix(".bq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--trim-polyg",
+            "4",
+            "--trim-polya",
+            "4",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_binseq(out_tmp.path())?, 2);
+
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+            "-T",
+            "1",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines[1], "ACGTACGT");
+        assert_eq!(lines[5], "TTGCACCT");
+        assert_eq!(lines[3].len(), 8);
+        assert_eq!(lines[7].len(), 8);
+        Ok(())
+    }
+
+    /// Index map rows follow input order even when more threads are requested.
+    #[test]
+    fn test_encode_index_map() -> Result<()> {
+        // enough records for several batches per thread
+        let nrec = 20_000;
+        let r1 = write_fastx().nrec(nrec).include_n(false).call()?;
+        let r2 = write_fastx().nrec(nrec).include_n(false).call()?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let map_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-T",
+            "4",
+            "--index-map",
+            map_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+
+        let content = std::fs::read_to_string(map_tmp.path())?;
+        let mut lines = content.lines();
+        assert_eq!(lines.next(), Some("index\tsheader\txheader"));
+        let rows: Vec<_> = lines.collect();
+        assert_eq!(rows.len(), nrec);
+
+        // each row names the record at that index of the output
+        let fa_tmp = NamedTempFile::with_suffix(".fasta")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            fa_tmp.path().to_str().unwrap(),
+            "-T",
+            "1",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let decoded = std::fs::read_to_string(fa_tmp.path())?;
+        let headers: Vec<_> = decoded
+            .lines()
+            .filter_map(|line| line.strip_prefix('>'))
+            .collect();
+        for (idx, (row, mates)) in rows.iter().zip(headers.chunks(2)).enumerate() {
+            assert_eq!(*row, format!("{idx}\t{}\t{}", mates[0], mates[1]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_max_quality() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        std::fs::write(in_tmp.path(), "@r1\nACGTACGT\n+\nJJ#IKK5J\n")?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--max-quality",
+            "40",
+        ])?;
+        super::run(&cmd)?;
+
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        assert_eq!(content.lines().nth(3), Some("II#III5I"));
+        Ok(())
+    }
+
+    /// `--name-from index` replaces each header with the record's input position.
+    #[test]
+    fn test_encode_name_from_index() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--name-from",
+            "index",
+        ])?;
+        super::run(&cmd)?;
+
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+            "-T",
+            "1",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let headers: Vec<_> = content.lines().step_by(4).collect();
+        let expected: Vec<_> = (0..DEFAULT_NUM_RECORDS).map(|i| format!("@{i}")).collect();
+        assert_eq!(headers, expected);
+        Ok(())
+    }
+
+    /// `--min-complexity` drops homopolymer and repeat reads but keeps diverse ones.
+    #[test]
+    fn test_encode_min_complexity() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        std::fs::write(
+            in_tmp.path(),
+            "@poly\nAAAAAAAAAAAAAAAAAAAAAAAA\n+\nIIIIIIIIIIIIIIIIIIIIIIII\n\
+             @diverse\nGATTACAGCTTGCAATCGGATCCT\n+\nIIIIIIIIIIIIIIIIIIIIIIII\n\
+             @repeat\nACACACACACACACACACACACAC\n+\nIIIIIIIIIIIIIIIIIIIIIIII\n",
+        )?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--min-complexity",
+            "0.5",
+        ])?;
+        super::run(&cmd)?;
+
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let headers: Vec<_> = content.lines().step_by(4).collect();
+        assert_eq!(headers, ["@diverse"]);
+
+        assert!(crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "--min-complexity",
+            "1.5",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// `--sort-batch` reorders records by sequence but keeps each record intact.
+    #[test]
+    fn test_encode_sort_batch() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        std::fs::write(
+            in_tmp.path(),
+            "@r1\nTTTTACGT\n+\nIIIIIIII\n@r2\nGGGGACGT\n+\n55555555\n\
+             @r3\nAAAAACGT\n+\nJJJJJJJJ\n@r4\nCCCCACGT\n+\n########\n",
+        )?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--sort-batch",
+            "-T",
+            "1",
+        ])?;
+        super::run(&cmd)?;
+
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let records: Vec<_> = content
+            .lines()
+            .collect::<Vec<_>>()
+            .chunks(4)
+            .map(|rec| (rec[0].to_string(), rec[1].to_string(), rec[3].to_string()))
+            .collect();
+        let expected = [
+            ("@r3", "AAAAACGT", "JJJJJJJJ"),
+            ("@r4", "CCCCACGT", "########"),
+            ("@r2", "GGGGACGT", "55555555"),
+            ("@r1", "TTTTACGT", "IIIIIIII"),
+        ];
+        assert_eq!(records.len(), expected.len());
+        for ((header, seq, qual), (eh, es, eq)) in records.iter().zip(expected) {
+            assert_eq!((header.as_str(), seq.as_str(), qual.as_str()), (eh, es, eq));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_ascii_headers() -> Result<()> {
+        let in_tmp = NamedTempFile::with_suffix(".fasta")?;
+        std::fs::write(in_tmp.path(), ">read\u{e9}1\nACGTACGT\n>read2\nACGTACGT\n")?;
+
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-H",
+            "--ascii-headers",
+            "strict",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-H",
+            "--ascii-headers",
+            "sanitize",
+        ])?;
+        super::run(&cmd)?;
+
+        let out_fa = NamedTempFile::with_suffix(".fasta")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            out_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fa.path().to_str().unwrap(),
+            "-f",
+            "a",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fa.path())?;
+        assert!(content.is_ascii());
+        assert!(content.contains(">read__1"), "{content}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_emit_index() -> Result<()> {
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let in_tmp = write_fastx().call()?;
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let index_tmp = NamedTempFile::with_suffix(".tsv")?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--emit-index",
+                index_tmp.path().to_str().unwrap(),
+            ])?;
+            super::run(&cmd)?;
+
+            let content = std::fs::read_to_string(index_tmp.path())?;
+            let mut lines = content.lines();
+            assert_eq!(
+                lines.next(),
+                Some("block\toffset\tlen\trecords\tcumulative_records")
+            );
+            let total: usize = lines
+                .map(|l| l.split('\t').nth(3).unwrap().parse::<usize>().unwrap())
+                .sum();
+            assert_eq!(total, DEFAULT_NUM_RECORDS, "index records for {mode:?}");
+        }
+
+        let in_tmp = write_fastx().call()?;
+        let out_tmp = NamedTempFile::with_suffix(".bq")?;
+        let index_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--emit-index",
+            index_tmp.path().to_str().unwrap(),
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    /// CBQ offsets chain from the file header through each block header and body.
+    #[test]
+    fn test_encode_emit_index_cbq_blocks() -> Result<()> {
+        let in_tmp = write_fastx().nrec(5000).call()?;
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let index_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-B",
+            "16K",
+            "--emit-index",
+            index_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+
+        let content = std::fs::read_to_string(index_tmp.path())?;
+        let rows: Vec<Vec<u64>> = content
+            .lines()
+            .skip(1)
+            .map(|line| line.split('\t').map(|f| f.parse().unwrap()).collect())
+            .collect();
+        assert!(
+            rows.len() > 1,
+            "expected several blocks, got {}",
+            rows.len()
+        );
+
+        let mut offset = size_of::<binseq::cbq::FileHeader>() as u64;
+        let mut cumulative = 0;
+        for (block, row) in rows.iter().enumerate() {
+            let &[index, block_offset, len, records, cumulative_records] = row.as_slice() else {
+                panic!("malformed row: {row:?}");
+            };
+            cumulative += records;
+            assert_eq!(index, block as u64);
+            assert_eq!(block_offset, offset, "offset of block {block}");
+            assert_eq!(
+                cumulative_records, cumulative,
+                "records up to block {block}"
+            );
+            offset += size_of::<binseq::cbq::BlockHeader>() as u64 + len;
+        }
+        assert_eq!(cumulative, 5000);
+        assert!(offset < std::fs::metadata(out_tmp.path())?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_progress_json() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--progress-json",
+            "--progress-interval",
+            "1",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_binseq(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+
+        // the interval is meaningless without the reporter
+        assert!(crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "--progress-interval",
+            "1",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// A batch that collapses to a single output honors the explicit `-o`.
+    #[test]
+    fn test_batch_single_output_honors_output_path() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let in_tmp = write_fastx().call()?;
+        let in_path = dir.path().join("a.fastq");
+        std::fs::copy(in_tmp.path(), &in_path)?;
+
+        let out_dir = tempfile::tempdir()?;
+        let out_path = out_dir.path().join("explicit.cbq");
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            dir.path().to_str().unwrap(),
+            "-r",
+            "-o",
+            out_path.to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+
+        assert_eq!(count_binseq(&out_path)?, DEFAULT_NUM_RECORDS);
+        assert!(!dir.path().join("a.cbq").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_pairing_rejects_desynced_mates() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().nrec(DEFAULT_NUM_RECORDS / 2).call()?;
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--strict-pairing",
+        ])?;
+        let err = super::run(&cmd).unwrap_err().to_string();
+        assert!(err.contains(r1.path().to_str().unwrap()), "{err}");
+        assert!(err.contains(r2.path().to_str().unwrap()), "{err}");
+        assert!(err.contains(&DEFAULT_NUM_RECORDS.to_string()), "{err}");
+        assert!(
+            err.contains(&(DEFAULT_NUM_RECORDS / 2).to_string()),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_pairing_accepts_synced_mates() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--strict-pairing",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_binseq(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+        Ok(())
+    }
+
+    /// Read-group splitting applies the encoder's preprocessing and `--sort-batch`.
+    #[cfg(feature = "htslib")]
+    #[test]
+    fn test_bam_split_rg_preprocesses_and_sorts() -> Result<()> {
+        let sam = NamedTempFile::with_suffix(".sam")?;
+        let mut body = String::from("@HD\tVN:1.6\n@RG\tID:a\n@RG\tID:b\n");
+        for (name, seq, rg) in [
+            ("r0", "TTTTCCAAAAAAAA", "a"),
+            ("r1", "GGGG", "b"),
+            ("r2", "CCCCTTAAAAAAAA", "a"),
+            ("r3", "AAAAGGAAAAAAAA", "a"),
+        ] {
+            let qual = "I".repeat(seq.len());
+            body.push_str(&format!(
+                "{name}\t4\t*\t0\t0\t*\t*\t0\t0\t{seq}\t{qual}\tRG:Z:{rg}\n"
+            ));
+        }
+        std::fs::write(sam.path(), body)?;
+
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("out.vbq");
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            sam.path().to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+            "--bam-split-rg",
+            "--sort-batch",
+            "--trim-polya",
+            "8",
+        ])?;
+        super::run(&cmd)?;
+
+        let group_a = super::utils::read_group_output_name(out_path.to_str().unwrap(), "a");
+        let decoded = dir.path().join("a.fq");
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            &group_a,
+            "-o",
+            decoded.to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(&decoded)?;
+        let seqs: Vec<&str> = content.lines().skip(1).step_by(4).collect();
+        assert_eq!(seqs, ["AAAAGG", "CCCCTT", "TTTTCC"]);
+        Ok(())
+    }
+
+    /// Equal record counts still fail when a pair's mate IDs disagree.
+    #[test]
+    fn test_strict_pairing_rejects_mismatched_ids() -> Result<()> {
+        let r1 = NamedTempFile::with_suffix(".fq")?;
+        let r2 = NamedTempFile::with_suffix(".fq")?;
+        std::fs::write(r1.path(), "@a/1\nACGT\n+\nIIII\n@b/1\nACGT\n+\nIIII\n")?;
+        std::fs::write(r2.path(), "@a/2\nTTGG\n+\nIIII\n@c/2\nTTGG\n+\nIIII\n")?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--strict-pairing",
+        ])?;
+        let err = format!("{:#}", super::run(&cmd).unwrap_err());
+        assert!(err.contains("b/1") && err.contains("c/2"), "{err}");
+        Ok(())
+    }
+
+    /// A desynced pair is caught on streamed inputs, which cannot be read twice.
+    #[cfg(unix)]
+    #[test]
+    fn test_strict_pairing_rejects_desynced_fifo() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().nrec(DEFAULT_NUM_RECORDS / 2).call()?;
+        let dir = tempfile::tempdir()?;
+        let fifo = dir.path().join("r2.fq");
+        nix::unistd::mkfifo(
+            fifo.as_path(),
+            nix::sys::stat::Mode::S_IRUSR | nix::sys::stat::Mode::S_IWUSR,
+        )?;
+        let writer = {
+            let (src, fifo) = (r2.path().to_path_buf(), fifo.clone());
+            std::thread::spawn(move || std::fs::write(fifo, std::fs::read(src)?))
+        };
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            fifo.to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--strict-pairing",
+        ])?;
+        let err = super::run(&cmd).unwrap_err().to_string();
+        writer.join().unwrap()?;
+        assert!(err.contains(fifo.to_str().unwrap()), "{err}");
+        assert!(
+            err.contains(&format!("has {}", DEFAULT_NUM_RECORDS / 2)),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    fn write_interleaved(ids: &[(&str, &str)]) -> Result<NamedTempFile> {
+        let mut tmp = NamedTempFile::with_suffix(".fq")?;
+        for (r1, r2) in ids {
+            write!(
+                tmp,
+                "@{r1}\nACGTACGT\n+\nIIIIIIII\n@{r2}\nTTGGCCAA\n+\nIIIIIIII\n"
+            )?;
+        }
+        tmp.flush()?;
+        Ok(tmp)
+    }
+
+    #[test]
+    fn test_check_interleaving() -> Result<()> {
+        let encode = |input: &NamedTempFile| -> Result<()> {
+            let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                input.path().to_str().unwrap(),
+                "-I",
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--check-interleaving",
+            ])?;
+            super::run(&cmd)
+        };
+
+        // mate suffixes and comments are ignored
+        let synced = write_interleaved(&[("r0/1", "r0/2"), ("r1 1:N:0", "r1 2:N:0")])?;
+        encode(&synced)?;
+
+        let desynced = write_interleaved(&[("r0/1", "r0/2"), ("r1/1", "r2/2")])?;
+        let err = format!("{:#}", encode(&desynced).unwrap_err());
+        assert!(err.contains("records r1/1 and r2/2 are not mates"), "{err}");
+
+        // the check only applies to interleaved input
+        assert!(crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            synced.path().to_str().unwrap(),
+            "--check-interleaving",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// Interleaving is checked inline, so streamed inputs are covered too.
+    #[cfg(unix)]
+    #[test]
+    fn test_check_interleaving_fifo() -> Result<()> {
+        let desynced = write_interleaved(&[("r0/1", "r0/2"), ("r1/1", "r2/2")])?;
+        let dir = tempfile::tempdir()?;
+        let fifo = dir.path().join("interleaved.fq");
+        nix::unistd::mkfifo(
+            fifo.as_path(),
+            nix::sys::stat::Mode::S_IRUSR | nix::sys::stat::Mode::S_IWUSR,
+        )?;
+        let writer = {
+            let (src, fifo) = (desynced.path().to_path_buf(), fifo.clone());
+            std::thread::spawn(move || std::fs::write(fifo, std::fs::read(src)?))
+        };
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            fifo.to_str().unwrap(),
+            "-I",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--check-interleaving",
+        ])?;
+        let err = format!("{:#}", super::run(&cmd).unwrap_err());
+        writer.join().unwrap()?;
+        assert!(err.contains("r2/2"), "{err}");
+        Ok(())
+    }
+
     #[test]
     fn test_paired_encoding() -> Result<()> {
         for mode in BinseqMode::enum_iter() {
@@ -508,4 +1787,28 @@ mod tests {
         }
         Ok(())
     }
+
+    /// A failed entry whose output was never created still reports the original
+    /// error (for `TooFewRecords`) rather than the missing-file error.
+    #[test]
+    fn test_record_entry_result_missing_output() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let outpath = dir.path().join("never_created.vbq");
+        let outpath = outpath.to_str().unwrap();
+
+        super::record_entry_result(Err(anyhow::anyhow!("boom")), outpath, None)?;
+
+        let err = super::record_entry_result(
+            Err(super::TooFewRecords {
+                num_records: 1,
+                min_records: 2,
+            }
+            .into()),
+            outpath,
+            None,
+        )
+        .unwrap_err();
+        assert!(err.is::<super::TooFewRecords>(), "{err:#}");
+        Ok(())
+    }
 }
@@ -1,13 +1,247 @@
-use std::{io::Write, ops::AddAssign, sync::Arc};
+use std::{
+    io::Write,
+    ops::AddAssign,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use binseq::{BinseqWriter, SequencingRecordBuilder};
 use log::trace;
 use paraseq::prelude::{IntoProcessError, PairedParallelProcessor, ParallelProcessor};
 use parking_lot::Mutex;
 
+use crate::{
+    cli::{mate_id, AsciiHeaders, BinseqConfig, NameFrom, PolyTrim},
+    commands::{match_output, progress},
+};
+
 /// Default debug interval for logging progress (batches)
 const DEBUG_INTERVAL: usize = 1024;
 
+/// Number of leading bases compared when sorting a batch (`--sort-batch`)
+pub const SORT_PREFIX_LEN: usize = 32;
+
+/// Writes an uppercased copy of `seq` into `buf`, mapping IUPAC ambiguity codes to `N`.
+///
+/// `N` is then resolved by the writer's N-policy like any other ambiguous base.
+pub fn canonicalize_into(seq: &[u8], buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.extend(seq.iter().map(|b| match b.to_ascii_uppercase() {
+        b'R' | b'Y' | b'S' | b'W' | b'K' | b'M' | b'B' | b'D' | b'H' | b'V' => b'N',
+        upper => upper,
+    }));
+}
+
+/// Applies the `--ascii-headers` policy to a record header.
+///
+/// Returns the header unchanged when it is already ASCII (or no policy is set),
+/// a sanitized copy written into `buf` in [`AsciiHeaders::Sanitize`] mode, or an
+/// error in [`AsciiHeaders::Strict`] mode.
+pub fn ascii_header<'a>(
+    header: &'a [u8],
+    mode: Option<AsciiHeaders>,
+    buf: &'a mut Vec<u8>,
+) -> std::io::Result<&'a [u8]> {
+    match mode {
+        Some(_) if header.is_ascii() => Ok(header),
+        None => Ok(header),
+        Some(AsciiHeaders::Strict) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Non-ASCII record header: {}",
+                String::from_utf8_lossy(header)
+            ),
+        )),
+        Some(AsciiHeaders::Sanitize) => {
+            buf.clear();
+            buf.extend(header.iter().map(|&b| if b.is_ascii() { b } else { b'_' }));
+            Ok(buf)
+        }
+    }
+}
+
+/// The header stored for a record named `id` at input position `index` (`--name-from`).
+///
+/// Input names pass through [`ascii_header`]; positional names are written into `buf`.
+pub fn stored_header<'a>(
+    id: &'a [u8],
+    index: usize,
+    name_from: NameFrom,
+    ascii_headers: Option<AsciiHeaders>,
+    buf: &'a mut Vec<u8>,
+) -> std::io::Result<&'a [u8]> {
+    match name_from {
+        NameFrom::Id => ascii_header(id, ascii_headers, buf),
+        NameFrom::None => Ok(&[]),
+        NameFrom::Index => {
+            buf.clear();
+            write!(buf, "{index}")?;
+            Ok(buf)
+        }
+    }
+}
+
+/// Length of `seq` after removing 3' homopolymer tails (see [`PolyTrim`]).
+///
+/// A trailing G run is removed first, then a trailing A run, each only when it
+/// spans at least the configured number of bases (case-insensitive).
+pub fn poly_trimmed_len(seq: &[u8], trim: PolyTrim) -> usize {
+    let mut end = seq.len();
+    for (base, min_len) in [(b'G', trim.polyg), (b'A', trim.polya)] {
+        let Some(min_len) = min_len else {
+            continue;
+        };
+        let run = seq[..end]
+            .iter()
+            .rev()
+            .take_while(|b| b.eq_ignore_ascii_case(&base))
+            .count();
+        if run >= min_len.max(1) {
+            end -= run;
+        }
+    }
+    end
+}
+
+/// Truncates `qual` to `len` and caps its Phred scores at `max_quality` (`--max-quality`).
+///
+/// Returns the truncated qualities unchanged when no cap is set, or a capped
+/// copy written into `buf`.
+pub fn capped_quality<'a>(
+    qual: Option<&'a [u8]>,
+    len: usize,
+    max_quality: Option<u8>,
+    buf: &'a mut Vec<u8>,
+) -> Option<&'a [u8]> {
+    let qual = &qual?[..len];
+    let Some(max_quality) = max_quality else {
+        return Some(qual);
+    };
+    let cap = max_quality.saturating_add(b'!');
+    buf.clear();
+    buf.extend(qual.iter().map(|&q| q.min(cap)));
+    Some(buf)
+}
+
+/// Sequence complexity as the normalized Shannon entropy of its ACGT 3-mers (`--min-complexity`).
+///
+/// Returns a value in `[0, 1]`: homopolymers score 0, short tandem repeats
+/// score low, and random sequence approaches 1. 3-mers spanning a non-ACGT
+/// base are ignored. The entropy is normalized by the maximum reachable for
+/// the number of 3-mers observed, so short reads are not penalized.
+pub fn trimer_complexity(seq: &[u8]) -> f64 {
+    let mut counts = [0u32; 64];
+    let mut total = 0u32;
+    let mut kmer = 0usize;
+    let mut valid = 0;
+    for &base in seq {
+        let code = match base.to_ascii_uppercase() {
+            b'A' => 0,
+            b'C' => 1,
+            b'G' => 2,
+            b'T' => 3,
+            _ => {
+                valid = 0;
+                continue;
+            }
+        };
+        kmer = ((kmer << 2) | code) & 63;
+        valid += 1;
+        if valid >= 3 {
+            counts[kmer] += 1;
+            total += 1;
+        }
+    }
+    if total <= 1 {
+        return 0.0;
+    }
+    let total = f64::from(total);
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = f64::from(c) / total;
+            -p * p.log2()
+        })
+        .sum();
+    entropy / total.min(64.0).log2()
+}
+
+/// Whether `seq` only contains bases representable in 2-bit encoding
+pub fn is_acgt(seq: &[u8]) -> bool {
+    seq.iter().all(|b| matches!(b, b'A' | b'C' | b'G' | b'T'))
+}
+
+/// Shared `--index-map` sidecar pairing each record index with its original header(s).
+///
+/// Rows are numbered in the order their records reach the output file: encoders
+/// write a batch's rows while still holding the writer lock used to ingest it.
+#[derive(Clone)]
+pub struct IndexMap {
+    state: Arc<Mutex<IndexMapState>>,
+}
+struct IndexMapState {
+    handle: Box<dyn Write + Send>,
+    /// Index of the next record written to the output
+    next_index: usize,
+}
+impl IndexMap {
+    pub fn create(path: &str, paired: bool) -> anyhow::Result<Self> {
+        let mut handle = match_output(Some(path))?;
+        if paired {
+            writeln!(handle, "index\tsheader\txheader")?;
+        } else {
+            writeln!(handle, "index\theader")?;
+        }
+        Ok(Self {
+            state: Arc::new(Mutex::new(IndexMapState {
+                handle,
+                next_index: 0,
+            })),
+        })
+    }
+
+    /// Writes newline-terminated `names` as rows numbered from the next record index
+    fn write_batch(&self, names: &[u8]) -> std::io::Result<()> {
+        let mut state = self.state.lock();
+        let IndexMapState { handle, next_index } = &mut *state;
+        for name in names.split_inclusive(|&b| b == b'\n') {
+            write!(handle, "{next_index}\t")?;
+            handle.write_all(name)?;
+            *next_index += 1;
+        }
+        handle.flush()
+    }
+}
+
+/// Owned copy of a record held back until its batch is sorted (`--sort-batch`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PendingRecord {
+    pub sseq: Vec<u8>,
+    pub squal: Option<Vec<u8>>,
+    pub sheader: Vec<u8>,
+    pub xseq: Option<Vec<u8>>,
+    pub xqual: Option<Vec<u8>>,
+    pub xheader: Vec<u8>,
+    /// Original header(s) for the `--index-map` sidecar
+    pub sname: Vec<u8>,
+    pub xname: Option<Vec<u8>>,
+}
+impl PendingRecord {
+    /// Sort key: the first [`SORT_PREFIX_LEN`] bases of the primary sequence
+    pub fn prefix(&self) -> &[u8] {
+        &self.sseq[..self.sseq.len().min(SORT_PREFIX_LEN)]
+    }
+}
+
+/// Orders a batch by sequence prefix, keeping input order among equal prefixes.
+pub fn sort_pending(pending: &mut [PendingRecord]) {
+    pending.sort_by(|a, b| a.prefix().cmp(b.prefix()));
+}
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct Encoder<W: Write + Send> {
     /// Thread-local writer for the encoder.
     t_writer: BinseqWriter<Vec<u8>>,
@@ -15,6 +249,46 @@ pub struct Encoder<W: Write + Send> {
     t_count: usize,
     /// Thread-local skip count for the encoder.
     t_skip: usize,
+    /// Thread-local count of BINSEQ records with non-ACGT bases.
+    t_invalid: usize,
+    /// Thread-local count of records dropped by `--min-complexity`.
+    t_low_complexity: usize,
+    /// Thread-local buffers for canonicalized sequences.
+    t_sbuf: Vec<u8>,
+    t_xbuf: Vec<u8>,
+    /// Thread-local buffers for sanitized headers.
+    t_shbuf: Vec<u8>,
+    t_xhbuf: Vec<u8>,
+    /// Thread-local buffers for capped qualities.
+    t_sqbuf: Vec<u8>,
+    t_xqbuf: Vec<u8>,
+    /// Thread-local original headers of the written records (`--index-map`).
+    t_names: Vec<u8>,
+    /// Thread-local records awaiting the end-of-batch sort (`--sort-batch`).
+    t_pending: Vec<PendingRecord>,
+
+    /// Uppercase and map IUPAC codes to `N` before encoding.
+    canonicalize: bool,
+    /// Policy for non-ASCII headers.
+    ascii_headers: Option<AsciiHeaders>,
+    /// Source of the stored headers.
+    name_from: NameFrom,
+    /// Homopolymer tails trimmed from fastx/htslib input sequences.
+    poly_trim: PolyTrim,
+    /// Cap on the Phred scores of fastx/htslib input qualities.
+    max_quality: Option<u8>,
+    /// Count BINSEQ input records containing non-ACGT bases.
+    audit_bases: bool,
+    /// Sort each batch of fastx/htslib records by sequence prefix before writing.
+    sort_batch: bool,
+    /// Minimum [`trimer_complexity`] of fastx/htslib input sequences.
+    min_complexity: Option<f64>,
+    /// Fail on paired fastx records whose mates have different IDs.
+    strict_pairing: bool,
+    /// Fail on interleaved fastx records whose mates have different IDs.
+    check_interleaving: bool,
+    /// Sidecar receiving the original headers of written records.
+    index_map: Option<IndexMap>,
 
     /// Global writer for the encoder.
     writer: Arc<Mutex<BinseqWriter<W>>>,
@@ -22,6 +296,12 @@ pub struct Encoder<W: Write + Send> {
     count: Arc<Mutex<usize>>,
     /// Global skip count for the encoder.
     skip: Arc<Mutex<usize>>,
+    /// Global count of BINSEQ records with non-ACGT bases.
+    invalid: Arc<Mutex<usize>>,
+    /// Global count of records dropped by `--min-complexity`.
+    low_complexity: Arc<Mutex<usize>>,
+    /// Input position of the next fastx/htslib record (`--name-from index`).
+    next_index: Arc<AtomicUsize>,
     /// Debug interval for logging progress
     debug_interval: Arc<Mutex<usize>>,
 }
@@ -31,9 +311,33 @@ impl<W: Write + Send> Clone for Encoder<W> {
             t_writer: self.t_writer.clone(),
             t_count: self.t_count,
             t_skip: self.t_skip,
+            t_invalid: self.t_invalid,
+            t_low_complexity: self.t_low_complexity,
+            t_sbuf: Vec::new(),
+            t_xbuf: Vec::new(),
+            t_shbuf: Vec::new(),
+            t_xhbuf: Vec::new(),
+            t_sqbuf: Vec::new(),
+            t_xqbuf: Vec::new(),
+            t_names: Vec::new(),
+            t_pending: Vec::new(),
+            canonicalize: self.canonicalize,
+            ascii_headers: self.ascii_headers,
+            name_from: self.name_from,
+            poly_trim: self.poly_trim,
+            max_quality: self.max_quality,
+            audit_bases: self.audit_bases,
+            sort_batch: self.sort_batch,
+            min_complexity: self.min_complexity,
+            strict_pairing: self.strict_pairing,
+            check_interleaving: self.check_interleaving,
+            index_map: self.index_map.clone(),
             writer: self.writer.clone(),
             count: self.count.clone(),
             skip: self.skip.clone(),
+            invalid: self.invalid.clone(),
+            low_complexity: self.low_complexity.clone(),
+            next_index: self.next_index.clone(),
             debug_interval: self.debug_interval.clone(),
         }
     }
@@ -46,31 +350,239 @@ impl<W: Write + Send> Encoder<W> {
             t_writer,
             t_count: 0,
             t_skip: 0,
+            t_invalid: 0,
+            t_low_complexity: 0,
+            t_sbuf: Vec::new(),
+            t_xbuf: Vec::new(),
+            t_shbuf: Vec::new(),
+            t_xhbuf: Vec::new(),
+            t_sqbuf: Vec::new(),
+            t_xqbuf: Vec::new(),
+            t_names: Vec::new(),
+            t_pending: Vec::new(),
+            canonicalize: false,
+            ascii_headers: None,
+            name_from: NameFrom::default(),
+            poly_trim: PolyTrim::default(),
+            max_quality: None,
+            audit_bases: false,
+            sort_batch: false,
+            min_complexity: None,
+            strict_pairing: false,
+            check_interleaving: false,
+            index_map: None,
             count: Arc::new(Mutex::new(0)),
             skip: Arc::new(Mutex::new(0)),
+            invalid: Arc::new(Mutex::new(0)),
+            low_complexity: Arc::new(Mutex::new(0)),
+            next_index: Arc::new(AtomicUsize::new(0)),
             debug_interval: Arc::new(Mutex::new(DEBUG_INTERVAL)),
         })
     }
 
+    /// Creates an encoder applying the fastx/htslib preprocessing options of `config`.
+    ///
+    /// Input-specific checks (`--strict-pairing`, `--check-interleaving`) and the
+    /// `--index-map` sidecar are left to the caller.
+    pub fn from_config(writer: BinseqWriter<W>, config: &BinseqConfig) -> binseq::Result<Self> {
+        Ok(Self::new(writer)?
+            .with_canonicalize(config.canonicalize)
+            .with_ascii_headers(config.ascii_headers)
+            .with_name_from(config.name_from)
+            .with_poly_trim(config.poly_trim)
+            .with_max_quality(config.max_quality)
+            .with_sort_batch(config.sort_batch)
+            .with_min_complexity(config.min_complexity))
+    }
+
+    /// Enables canonicalization of fastx/htslib input sequences (see [`canonicalize_into`]).
+    #[must_use]
+    pub fn with_canonicalize(mut self, canonicalize: bool) -> Self {
+        self.canonicalize = canonicalize;
+        self
+    }
+
+    /// Sets the policy for non-ASCII fastx/htslib headers (see [`ascii_header`]).
+    #[must_use]
+    pub fn with_ascii_headers(mut self, ascii_headers: Option<AsciiHeaders>) -> Self {
+        self.ascii_headers = ascii_headers;
+        self
+    }
+
+    /// Sets the source of the stored fastx/htslib headers (see [`stored_header`]).
+    ///
+    /// With [`NameFrom::Index`] records are numbered from a counter shared by all
+    /// clones; numbering only follows input order when encoding on one thread.
+    #[must_use]
+    pub fn with_name_from(mut self, name_from: NameFrom) -> Self {
+        self.name_from = name_from;
+        self
+    }
+
+    /// Shares the `--name-from index` counter with other encoders, so records
+    /// routed between them keep their input positions.
+    #[cfg(feature = "htslib")]
+    #[must_use]
+    pub fn with_index_counter(mut self, next_index: Arc<AtomicUsize>) -> Self {
+        self.next_index = next_index;
+        self
+    }
+
+    /// Claims the input position of the next record (only tracked for `--name-from index`)
+    fn next_index(&self) -> usize {
+        if matches!(self.name_from, NameFrom::Index) {
+            self.next_index.fetch_add(1, Ordering::Relaxed)
+        } else {
+            0
+        }
+    }
+
+    /// Trims 3' homopolymer tails of fastx/htslib input sequences (see [`poly_trimmed_len`]).
+    #[must_use]
+    pub fn with_poly_trim(mut self, poly_trim: PolyTrim) -> Self {
+        self.poly_trim = poly_trim;
+        self
+    }
+
+    /// Caps the Phred scores of fastx/htslib input qualities (see [`capped_quality`]).
+    #[must_use]
+    pub fn with_max_quality(mut self, max_quality: Option<u8>) -> Self {
+        self.max_quality = max_quality;
+        self
+    }
+
+    /// Sorts each batch of fastx/htslib records by sequence prefix (see [`sort_pending`]).
+    #[must_use]
+    pub fn with_sort_batch(mut self, sort_batch: bool) -> Self {
+        self.sort_batch = sort_batch;
+        self
+    }
+
+    /// Drops fastx/htslib records whose sequence scores below `min_complexity` (see [`trimer_complexity`]).
+    ///
+    /// Paired records are dropped when either mate is below the threshold.
+    #[must_use]
+    pub fn with_min_complexity(mut self, min_complexity: Option<f64>) -> Self {
+        self.min_complexity = min_complexity;
+        self
+    }
+
+    /// Fails on paired fastx records whose mates have different IDs (see [`mate_id`]).
+    #[must_use]
+    pub fn with_strict_pairing(mut self, strict_pairing: bool) -> Self {
+        self.strict_pairing = strict_pairing;
+        self
+    }
+
+    /// Fails on interleaved fastx records whose mates have different IDs (see [`mate_id`]).
+    #[must_use]
+    pub fn with_interleaving_check(mut self, check_interleaving: bool) -> Self {
+        self.check_interleaving = check_interleaving;
+        self
+    }
+
+    /// Whether `seq` falls below the `--min-complexity` threshold
+    fn is_low_complexity(&self, seq: &[u8]) -> bool {
+        self.min_complexity
+            .is_some_and(|min| trimer_complexity(seq) < min)
+    }
+
+    /// Records the original fastx/htslib header(s) of each written record (see [`IndexMap`]).
+    ///
+    /// Batches are then ingested whole, so VBQ/CBQ blocks also end at batch boundaries.
+    #[must_use]
+    pub fn with_index_map(mut self, index_map: Option<IndexMap>) -> Self {
+        self.index_map = index_map;
+        self
+    }
+
+    /// Counts BINSEQ input records containing non-ACGT bases (see [`Self::get_global_invalid_count`]).
+    ///
+    /// Used when re-encoding to 2-bit, where such records are resolved by the N-policy.
+    #[must_use]
+    pub fn with_base_audit(mut self, audit_bases: bool) -> Self {
+        self.audit_bases = audit_bases;
+        self
+    }
+
     fn write_batch(&mut self) -> binseq::Result<()> {
-        self.writer.lock().ingest_completed(&mut self.t_writer)
+        let mut writer = self.writer.lock();
+        match &self.index_map {
+            // the whole batch (including its partial block) reaches the file
+            // under this lock, so its rows get the indices of its records
+            Some(index_map) => {
+                writer.ingest(&mut self.t_writer)?;
+                index_map.write_batch(&self.t_names)?;
+                self.t_names.clear();
+                Ok(())
+            }
+            None => writer.ingest_completed(&mut self.t_writer),
+        }
     }
 
     fn write_final(&mut self) -> binseq::Result<()> {
         self.writer.lock().ingest(&mut self.t_writer)
     }
 
+    /// Appends a newline-terminated index map row body for a written record
+    fn push_names(&mut self, sheader: &[u8], xheader: Option<&[u8]>) {
+        if self.index_map.is_none() {
+            return;
+        }
+        self.t_names.extend_from_slice(sheader);
+        if let Some(xheader) = xheader {
+            self.t_names.push(b'\t');
+            self.t_names.extend_from_slice(xheader);
+        }
+        self.t_names.push(b'\n');
+    }
+
+    /// Sorts the held-back records of this batch and pushes them to the writer
+    fn flush_pending(&mut self) -> binseq::Result<()> {
+        if self.t_pending.is_empty() {
+            return Ok(());
+        }
+        let mut pending = std::mem::take(&mut self.t_pending);
+        sort_pending(&mut pending);
+        for record in pending.drain(..) {
+            let mut builder = SequencingRecordBuilder::default()
+                .s_seq(&record.sseq)
+                .opt_s_qual(record.squal.as_deref())
+                .s_header(&record.sheader);
+            if let Some(xseq) = &record.xseq {
+                builder = builder
+                    .x_seq(xseq)
+                    .opt_x_qual(record.xqual.as_deref())
+                    .x_header(&record.xheader);
+            }
+            if self.t_writer.push(builder.build()?)? {
+                self.t_count += 1;
+                self.push_names(&record.sname, record.xname.as_deref());
+            } else {
+                self.t_skip += 1;
+            }
+        }
+        // keep the allocation for the next batch
+        self.t_pending = pending;
+        Ok(())
+    }
+
     fn update_global_counters(&mut self) {
         // update counts
         {
             self.count.lock().add_assign(self.t_count);
             self.skip.lock().add_assign(self.t_skip);
+            self.invalid.lock().add_assign(self.t_invalid);
+            self.low_complexity.lock().add_assign(self.t_low_complexity);
             self.debug_interval.lock().add_assign(1);
+            progress::add_records(self.t_count);
         }
         // reset local
         {
             self.t_count = 0;
             self.t_skip = 0;
+            self.t_invalid = 0;
+            self.t_low_complexity = 0;
         }
         // handle debug interval
         {
@@ -95,15 +607,61 @@ impl<W: Write + Send> Encoder<W> {
     pub fn get_global_skip_count(&self) -> usize {
         *self.skip.lock()
     }
+
+    pub fn get_global_invalid_count(&self) -> usize {
+        *self.invalid.lock()
+    }
+
+    pub fn get_global_low_complexity_count(&self) -> usize {
+        *self.low_complexity.lock()
+    }
 }
 
-impl<W: Write + Send, Rf: paraseq::Record> ParallelProcessor<Rf> for Encoder<W> {
-    fn process_record(&mut self, record: Rf) -> paraseq::Result<()> {
-        let seq = record.seq();
+/// Preprocessing and batching shared by the fastx/htslib processors, also used
+/// directly to route records between per-group encoders.
+impl<W: Write + Send> Encoder<W> {
+    /// Preprocesses a single fastx/htslib record and buffers it for writing
+    pub fn encode_single(
+        &mut self,
+        id: &[u8],
+        raw: &[u8],
+        qual: Option<&[u8]>,
+    ) -> paraseq::Result<()> {
+        let index = self.next_index();
+        let seq: &[u8] = if self.canonicalize {
+            canonicalize_into(raw, &mut self.t_sbuf);
+            &self.t_sbuf
+        } else {
+            raw
+        };
+        let slen = poly_trimmed_len(seq, self.poly_trim);
+        if self.is_low_complexity(&seq[..slen]) {
+            self.t_low_complexity += 1;
+            return Ok(());
+        }
+        let squal = capped_quality(qual, slen, self.max_quality, &mut self.t_sqbuf);
+        let header = stored_header(
+            id,
+            index,
+            self.name_from,
+            self.ascii_headers,
+            &mut self.t_shbuf,
+        )
+        .map_err(IntoProcessError::into_process_error)?;
+        if self.sort_batch {
+            self.t_pending.push(PendingRecord {
+                sseq: seq[..slen].to_vec(),
+                squal: squal.map(<[u8]>::to_vec),
+                sheader: header.to_vec(),
+                sname: id.to_vec(),
+                ..PendingRecord::default()
+            });
+            return Ok(());
+        }
         let rec = SequencingRecordBuilder::default()
-            .s_seq(&seq)
-            .opt_s_qual(record.qual())
-            .s_header(record.id())
+            .s_seq(&seq[..slen])
+            .opt_s_qual(squal)
+            .s_header(header)
             .build()
             .map_err(IntoProcessError::into_process_error)?;
         if self
@@ -112,33 +670,93 @@ impl<W: Write + Send, Rf: paraseq::Record> ParallelProcessor<Rf> for Encoder<W>
             .map_err(IntoProcessError::into_process_error)?
         {
             self.t_count += 1;
+            self.push_names(id, None);
         } else {
             self.t_skip += 1;
         }
         Ok(())
     }
-    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
-        self.update_global_counters();
-        self.write_batch()
-            .map_err(IntoProcessError::into_process_error)
-    }
-    fn on_thread_complete(&mut self) -> paraseq::Result<()> {
-        self.write_final()
-            .map_err(IntoProcessError::into_process_error)
-    }
-}
 
-impl<W: Write + Send, Rf: paraseq::Record> PairedParallelProcessor<Rf> for Encoder<W> {
-    fn process_record_pair(&mut self, record1: Rf, record2: Rf) -> paraseq::Result<()> {
-        let s_seq = record1.seq();
-        let x_seq = record2.seq();
+    /// Preprocesses a fastx/htslib record pair and buffers it for writing
+    pub fn encode_pair(
+        &mut self,
+        id1: &[u8],
+        raw1: &[u8],
+        qual1: Option<&[u8]>,
+        id2: &[u8],
+        raw2: &[u8],
+        qual2: Option<&[u8]>,
+    ) -> paraseq::Result<()> {
+        let index = self.next_index();
+        if (self.strict_pairing || self.check_interleaving) && mate_id(id1) != mate_id(id2) {
+            let msg = if self.check_interleaving {
+                format!(
+                    "Interleaved input is out of sync: adjacent records {} and {} are not mates",
+                    String::from_utf8_lossy(id1),
+                    String::from_utf8_lossy(id2),
+                )
+            } else {
+                format!(
+                    "Paired inputs are out of sync: mates {} and {} have different IDs",
+                    String::from_utf8_lossy(id1),
+                    String::from_utf8_lossy(id2),
+                )
+            };
+            return Err(
+                std::io::Error::new(std::io::ErrorKind::InvalidData, msg).into_process_error()
+            );
+        }
+        let (s_seq, x_seq): (&[u8], &[u8]) = if self.canonicalize {
+            canonicalize_into(raw1, &mut self.t_sbuf);
+            canonicalize_into(raw2, &mut self.t_xbuf);
+            (&self.t_sbuf, &self.t_xbuf)
+        } else {
+            (raw1, raw2)
+        };
+        let slen = poly_trimmed_len(s_seq, self.poly_trim);
+        let xlen = poly_trimmed_len(x_seq, self.poly_trim);
+        if self.is_low_complexity(&s_seq[..slen]) || self.is_low_complexity(&x_seq[..xlen]) {
+            self.t_low_complexity += 1;
+            return Ok(());
+        }
+        let squal = capped_quality(qual1, slen, self.max_quality, &mut self.t_sqbuf);
+        let xqual = capped_quality(qual2, xlen, self.max_quality, &mut self.t_xqbuf);
+        let s_header = stored_header(
+            id1,
+            index,
+            self.name_from,
+            self.ascii_headers,
+            &mut self.t_shbuf,
+        )
+        .map_err(IntoProcessError::into_process_error)?;
+        let x_header = stored_header(
+            id2,
+            index,
+            self.name_from,
+            self.ascii_headers,
+            &mut self.t_xhbuf,
+        )
+        .map_err(IntoProcessError::into_process_error)?;
+        if self.sort_batch {
+            self.t_pending.push(PendingRecord {
+                sseq: s_seq[..slen].to_vec(),
+                squal: squal.map(<[u8]>::to_vec),
+                sheader: s_header.to_vec(),
+                xseq: Some(x_seq[..xlen].to_vec()),
+                xqual: xqual.map(<[u8]>::to_vec),
+                xheader: x_header.to_vec(),
+                sname: id1.to_vec(),
+                xname: Some(id2.to_vec()),
+            });
+            return Ok(());
+        }
         let rec = SequencingRecordBuilder::default()
-            .s_seq(&s_seq)
-            .opt_s_qual(record1.qual())
-            .s_header(record1.id())
-            .x_seq(&x_seq)
-            .opt_x_qual(record2.qual())
-            .x_header(record2.id())
+            .s_seq(&s_seq[..slen])
+            .opt_s_qual(squal)
+            .s_header(s_header)
+            .x_seq(&x_seq[..xlen])
+            .opt_x_qual(xqual)
+            .x_header(x_header)
             .build()
             .map_err(IntoProcessError::into_process_error)?;
         if self
@@ -147,23 +765,66 @@ impl<W: Write + Send, Rf: paraseq::Record> PairedParallelProcessor<Rf> for Encod
             .map_err(IntoProcessError::into_process_error)?
         {
             self.t_count += 1;
+            self.push_names(id1, Some(id2));
         } else {
             self.t_skip += 1;
         }
         Ok(())
     }
-    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
+
+    /// Sorts (`--sort-batch`) and hands the buffered batch to the shared writer
+    pub fn complete_batch(&mut self) -> paraseq::Result<()> {
+        self.flush_pending()
+            .map_err(IntoProcessError::into_process_error)?;
         self.update_global_counters();
         self.write_batch()
             .map_err(IntoProcessError::into_process_error)
     }
-    fn on_thread_complete(&mut self) -> paraseq::Result<()> {
+
+    /// Hands the remaining partial block to the shared writer
+    pub fn complete_thread(&mut self) -> paraseq::Result<()> {
         self.write_final()
             .map_err(IntoProcessError::into_process_error)
     }
 }
+
+impl<W: Write + Send, Rf: paraseq::Record> ParallelProcessor<Rf> for Encoder<W> {
+    fn process_record(&mut self, record: Rf) -> paraseq::Result<()> {
+        self.encode_single(record.id(), &record.seq(), record.qual())
+    }
+    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
+        self.complete_batch()
+    }
+    fn on_thread_complete(&mut self) -> paraseq::Result<()> {
+        self.complete_thread()
+    }
+}
+
+impl<W: Write + Send, Rf: paraseq::Record> PairedParallelProcessor<Rf> for Encoder<W> {
+    fn process_record_pair(&mut self, record1: Rf, record2: Rf) -> paraseq::Result<()> {
+        self.encode_pair(
+            record1.id(),
+            &record1.seq(),
+            record1.qual(),
+            record2.id(),
+            &record2.seq(),
+            record2.qual(),
+        )
+    }
+    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
+        self.complete_batch()
+    }
+    fn on_thread_complete(&mut self) -> paraseq::Result<()> {
+        self.complete_thread()
+    }
+}
 impl<W: Write + Send> binseq::ParallelProcessor for Encoder<W> {
     fn process_record<R: binseq::BinseqRecord>(&mut self, record: R) -> binseq::Result<()> {
+        if self.audit_bases
+            && !(is_acgt(record.sseq()) && (!record.is_paired() || is_acgt(record.xseq())))
+        {
+            self.t_invalid += 1;
+        }
         let rec = if self.t_writer.is_paired() {
             SequencingRecordBuilder::default()
                 .s_seq(record.sseq())
@@ -172,12 +833,14 @@ impl<W: Write + Send> binseq::ParallelProcessor for Encoder<W> {
                 .x_seq(record.xseq())
                 .opt_x_qual(record.has_quality().then(|| record.xqual()))
                 .x_header(record.xheader())
+                .opt_flag(record.flag())
                 .build()?
         } else {
             SequencingRecordBuilder::default()
                 .s_seq(record.sseq())
                 .opt_s_qual(record.has_quality().then(|| record.squal()))
                 .s_header(record.sheader())
+                .opt_flag(record.flag())
                 .build()?
         };
         if self.t_writer.push(rec)? {
@@ -195,3 +858,130 @@ impl<W: Write + Send> binseq::ParallelProcessor for Encoder<W> {
         self.write_final()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ascii_header, canonicalize_into, capped_quality, is_acgt, poly_trimmed_len, sort_pending,
+        stored_header, trimer_complexity, PendingRecord, SORT_PREFIX_LEN,
+    };
+    use crate::cli::{AsciiHeaders, NameFrom, PolyTrim};
+
+    #[test]
+    fn test_canonicalize_into() {
+        let mut buf = Vec::new();
+        canonicalize_into(b"acgtnRYskmbdhvwACGT-", &mut buf);
+        assert_eq!(buf, b"ACGTNNNNNNNNNNNACGT-");
+    }
+
+    #[test]
+    fn test_poly_trimmed_len() {
+        let trim = PolyTrim {
+            polya: Some(3),
+            polyg: Some(4),
+        };
+        assert_eq!(poly_trimmed_len(b"ACGTGGGG", trim), 4);
+        // runs shorter than the minimum are kept
+        assert_eq!(poly_trimmed_len(b"ACGTGGG", trim), 7);
+        // poly-G is removed before poly-A
+        assert_eq!(poly_trimmed_len(b"CCaaaaggggg", trim), 2);
+        assert_eq!(poly_trimmed_len(b"GGGG", trim), 0);
+        assert_eq!(poly_trimmed_len(b"ACGTGGGG", PolyTrim::default()), 8);
+    }
+
+    #[test]
+    fn test_capped_quality() {
+        let mut buf = Vec::new();
+        // `J` is Q41, `I` Q40, `#` Q2
+        let qual = b"J#IJ5".as_slice();
+        assert_eq!(
+            capped_quality(Some(qual), 5, Some(40), &mut buf),
+            Some(b"I#II5".as_slice())
+        );
+        assert_eq!(
+            capped_quality(Some(qual), 3, None, &mut buf),
+            Some(b"J#I".as_slice())
+        );
+        assert_eq!(capped_quality(None, 0, Some(40), &mut buf), None);
+    }
+
+    #[test]
+    fn test_sort_pending() {
+        let record = |seq: &[u8], name: &[u8]| PendingRecord {
+            sseq: seq.to_vec(),
+            sname: name.to_vec(),
+            ..PendingRecord::default()
+        };
+        // equal prefixes keep their input order
+        let shared = vec![b'A'; SORT_PREFIX_LEN];
+        let mut pending = vec![
+            record(b"TTGA", b"t"),
+            record(&[shared.as_slice(), b"T"].concat(), b"a1"),
+            record(b"CA", b"c"),
+            record(&[shared.as_slice(), b"C"].concat(), b"a2"),
+        ];
+        sort_pending(&mut pending);
+        let names: Vec<_> = pending.iter().map(|r| r.sname.as_slice()).collect();
+        assert_eq!(names, [b"a1".as_slice(), b"a2", b"c", b"t"]);
+    }
+
+    // Single-trimer and too-short inputs score exactly 0.0, and the case check
+    // compares two identical computations.
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_trimer_complexity() {
+        assert_eq!(trimer_complexity(b"AAAAAAAAAAAAAAAAAAAA"), 0.0);
+        assert_eq!(trimer_complexity(b"AC"), 0.0);
+        assert_eq!(trimer_complexity(b"NNNNNNNN"), 0.0);
+        let repeat = trimer_complexity(b"ACACACACACACACACACACACACACACAC");
+        assert!(repeat > 0.0 && repeat < 0.3, "{repeat}");
+        let random = trimer_complexity(b"GATTACAGCTTGCAATCGGATCCTAGGCATTACGCGTAACTGA");
+        assert!(random > 0.9, "{random}");
+        // case-insensitive
+        assert_eq!(
+            trimer_complexity(b"gattacagcttgca"),
+            trimer_complexity(b"GATTACAGCTTGCA")
+        );
+    }
+
+    #[test]
+    fn test_is_acgt() {
+        assert!(is_acgt(b"ACGTTGCA"));
+        assert!(is_acgt(b""));
+        assert!(!is_acgt(b"ACGNT"));
+        assert!(!is_acgt(b"acgt"));
+    }
+
+    #[test]
+    fn test_stored_header() {
+        let mut buf = Vec::new();
+        assert_eq!(
+            stored_header(b"read1", 7, NameFrom::Id, None, &mut buf).unwrap(),
+            b"read1"
+        );
+        assert_eq!(
+            stored_header(b"read1", 7, NameFrom::None, None, &mut buf).unwrap(),
+            b""
+        );
+        assert_eq!(
+            stored_header(b"read1", 42, NameFrom::Index, None, &mut buf).unwrap(),
+            b"42"
+        );
+    }
+
+    #[test]
+    fn test_ascii_header() {
+        let mut buf = Vec::new();
+        let header = "read\u{e9}1".as_bytes();
+        assert_eq!(ascii_header(header, None, &mut buf).unwrap(), header);
+        assert_eq!(
+            ascii_header(b"read1", Some(AsciiHeaders::Strict), &mut buf).unwrap(),
+            b"read1"
+        );
+        assert!(ascii_header(header, Some(AsciiHeaders::Strict), &mut buf).is_err());
+        assert_eq!(
+            ascii_header(header, Some(AsciiHeaders::Sanitize), &mut buf).unwrap(),
+            b"read__1"
+        );
+    }
+}
@@ -1,17 +1,27 @@
-use std::{io::Read, path::PathBuf};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use anyhow::{bail, Result};
 use hashbrown::HashMap;
-use log::{error, warn};
+use log::{debug, error, warn};
 use paraseq::{fastx, Record};
+use parking_lot::Mutex;
 use regex::Regex;
+use xxhash_rust::xxh3::Xxh3;
+
+use super::processor::poly_trimmed_len;
+use crate::{cli::PolyTrim, commands::check_clobber};
 
 #[cfg(feature = "htslib")]
 use paraseq::rust_htslib::{self, bam::Read as BamRead};
 
 type BoxReader = Box<dyn Read + Send>;
 
-pub fn get_sequence_len(reader: &mut fastx::Reader<BoxReader>) -> Result<u32> {
+pub fn get_sequence_len(reader: &mut fastx::Reader<BoxReader>, trim: PolyTrim) -> Result<u32> {
     let mut rset = reader.new_record_set_with_size(1);
     let slen = if rset.fill(reader)? {
         let record = if let Some(record) = rset.iter().next() {
@@ -19,7 +29,7 @@ pub fn get_sequence_len(reader: &mut fastx::Reader<BoxReader>) -> Result<u32> {
         } else {
             bail!("Input file is empty - cannot convert");
         };
-        record.seq().len()
+        poly_trimmed_len(&record.seq(), trim)
     } else {
         bail!("Input file is empty - cannot convert");
     };
@@ -28,7 +38,7 @@ pub fn get_sequence_len(reader: &mut fastx::Reader<BoxReader>) -> Result<u32> {
 }
 
 #[cfg(feature = "htslib")]
-pub fn get_sequence_len_htslib(path: &str, paired: bool) -> Result<(u32, u32)> {
+pub fn get_sequence_len_htslib(path: &str, paired: bool, trim: PolyTrim) -> Result<(u32, u32)> {
     let mut reader = rust_htslib::bam::Reader::from_path(path)?;
     let mut slen = 0;
     let mut xlen = 0;
@@ -37,19 +47,22 @@ pub fn get_sequence_len_htslib(path: &str, paired: bool) -> Result<(u32, u32)> {
 
     if let Some(res) = rc_records.next() {
         let rec = res?;
-        slen = rec.seq_len();
+        slen = poly_trimmed_len(&rec.seq().as_bytes(), trim);
     }
 
     if paired {
         if let Some(res) = rc_records.next() {
             let rec = res?;
-            xlen = rec.seq_len();
+            xlen = poly_trimmed_len(&rec.seq().as_bytes(), trim);
         }
     }
     Ok((slen as u32, xlen as u32))
 }
 
-pub fn get_interleaved_sequence_len(reader: &mut fastx::Reader<BoxReader>) -> Result<(u32, u32)> {
+pub fn get_interleaved_sequence_len(
+    reader: &mut fastx::Reader<BoxReader>,
+    trim: PolyTrim,
+) -> Result<(u32, u32)> {
     let mut rset = reader.new_record_set_with_size(2);
     let (slen, xlen) = if rset.fill(reader)? {
         let mut rset_iter = rset.iter();
@@ -63,7 +76,10 @@ pub fn get_interleaved_sequence_len(reader: &mut fastx::Reader<BoxReader>) -> Re
         } else {
             bail!("Input file is empty - cannot convert");
         };
-        (r1.seq().len(), r2.seq().len())
+        (
+            poly_trimmed_len(&r1.seq(), trim),
+            poly_trimmed_len(&r2.seq(), trim),
+        )
     } else {
         bail!("Input file (interleaved) is missing R2 - cannot convert");
     };
@@ -178,6 +194,34 @@ pub fn generate_output_name(input_files: &[PathBuf], new_extension: &str) -> Res
     }
 }
 
+/// Whether `arg` contains glob metacharacters and does not name an existing path
+pub fn is_glob_pattern(arg: &str) -> bool {
+    arg.contains(['*', '?', '[']) && !std::path::Path::new(arg).exists()
+}
+
+/// Expands glob patterns among `args`, keeping other arguments as-is.
+///
+/// Used when the shell did not expand the patterns itself (e.g. they were quoted).
+/// Fails if a pattern is malformed or matches no paths.
+pub fn expand_globs(args: &[String]) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for arg in args {
+        if !is_glob_pattern(arg) {
+            paths.push(PathBuf::from(arg));
+            continue;
+        }
+        let matches = glob::glob(arg)
+            .map_err(|e| anyhow::anyhow!("Invalid glob pattern `{arg}`: {e}"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            bail!("No files match the pattern: {arg}");
+        }
+        debug!("Expanded `{arg}` to {} paths", matches.len());
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
 pub fn pull_single_files(input_files: &[PathBuf]) -> Result<Vec<Vec<PathBuf>>> {
     let mut num_suspect = 0;
     let pair_regex = Regex::new(r".+_R[12].+")?;
@@ -197,10 +241,97 @@ pub fn pull_single_files(input_files: &[PathBuf]) -> Result<Vec<Vec<PathBuf>>> {
     Ok(pqueue)
 }
 
+/// Derives the per-read-group output path for `--bam-split-rg`
+///
+/// `sample.vbq` with read group `RG1` becomes `sample.RG1.vbq`. Path separators
+/// in the read group ID are replaced so every output lands beside `opath`.
+#[cfg_attr(not(feature = "htslib"), allow(dead_code))]
+pub fn read_group_output_name(opath: &str, read_group: &str) -> String {
+    let path = std::path::Path::new(opath);
+    let stem = path
+        .file_stem()
+        .map_or(opath.into(), |s| s.to_string_lossy());
+    let read_group = read_group.replace(['/', '\\'], "_");
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{read_group}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{read_group}"),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
 pub fn collate_groups(pqueue: &[Vec<PathBuf>]) -> Vec<Vec<PathBuf>> {
     vec![pqueue.iter().flatten().cloned().collect()]
 }
 
+/// Creates a manifest TSV with the given header row
+fn create_manifest(path: &str, header: &str) -> Result<Arc<Mutex<File>>> {
+    check_clobber(path)?;
+    let mut handle = File::create(path)?;
+    writeln!(handle, "{header}")?;
+    Ok(Arc::new(Mutex::new(handle)))
+}
+
+/// Xxh3 digest and size in bytes of the file at `path`
+pub fn file_checksum(path: &str) -> Result<(u64, u64)> {
+    let mut handle = File::open(path)?;
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0; 1 << 20];
+    let mut size = 0;
+    loop {
+        let n = handle.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        size += n as u64;
+    }
+    Ok((hasher.digest(), size))
+}
+
+/// Shared handles to the `--output-manifest` and `--checksum-manifest` TSVs
+/// written during batch encoding.
+///
+/// Per-file encoding threads append to them as they finish, so each line is
+/// written and flushed under the lock to keep concurrent entries whole.
+#[derive(Clone)]
+pub struct OutputManifest {
+    records: Option<Arc<Mutex<File>>>,
+    checksums: Option<Arc<Mutex<File>>>,
+}
+impl OutputManifest {
+    /// Returns `None` when neither manifest was requested
+    pub fn create(records: Option<&str>, checksums: Option<&str>) -> Result<Option<Self>> {
+        if records.is_none() && checksums.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            records: records
+                .map(|path| create_manifest(path, "path\tnum_records"))
+                .transpose()?,
+            checksums: checksums
+                .map(|path| create_manifest(path, "path\tsize\txxh3"))
+                .transpose()?,
+        }))
+    }
+
+    /// Records a completed output; its checksum is computed from the file on disk
+    pub fn append(&self, path: &str, num_records: usize) -> Result<()> {
+        if let Some(handle) = &self.records {
+            let mut handle = handle.lock();
+            writeln!(handle, "{path}\t{num_records}")?;
+            handle.flush()?;
+        }
+        if let Some(handle) = &self.checksums {
+            // hash outside the lock so concurrent outputs are hashed in parallel
+            let (hash, size) = file_checksum(path)?;
+            let mut handle = handle.lock();
+            writeln!(handle, "{path}\t{size}\t{hash:016x}")?;
+            handle.flush()?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -246,6 +377,19 @@ mod tests {
         assert_eq!(pairs[1][1], PathBuf::from("library_A_R2_lane2.fastq"));
     }
 
+    #[test]
+    fn test_read_group_output_name() {
+        assert_eq!(
+            read_group_output_name("sample.vbq", "RG1"),
+            "sample.RG1.vbq"
+        );
+        assert_eq!(
+            read_group_output_name("out/sample.cbq", "lane/1"),
+            "out/sample.lane_1.cbq"
+        );
+        assert_eq!(read_group_output_name("sample", "RG1"), "sample.RG1");
+    }
+
     #[test]
     fn test_pair_r1_r2_files_missing_pairs() {
         let files = vec![
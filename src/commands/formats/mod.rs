@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{
+    cli::{BinseqMode, FileFormat, FormatsCommand},
+    commands::CompressionType,
+};
+
+#[derive(Serialize)]
+struct Entry {
+    name: String,
+    description: String,
+}
+impl Entry {
+    /// Builds an entry from a clap value, using its `--help` text as the description
+    fn from_value<T: ValueEnum>(value: &T, detail: &str) -> Option<Self> {
+        let possible = value.to_possible_value()?;
+        let help = possible.get_help().map(ToString::to_string);
+        let description = match help {
+            Some(help) if !detail.is_empty() => format!("{help} ({detail})"),
+            Some(help) => help,
+            None => detail.to_string(),
+        };
+        Some(Self {
+            name: possible.get_name().to_string(),
+            description,
+        })
+    }
+}
+
+/// An optional compile-time feature and whether this build includes it
+#[derive(Serialize)]
+pub struct Feature {
+    pub name: &'static str,
+    pub enabled: bool,
+    pub description: &'static str,
+}
+
+/// Optional features of the running binary
+pub fn features() -> Vec<Feature> {
+    vec![
+        Feature {
+            name: "htslib",
+            enabled: cfg!(feature = "htslib"),
+            description: "SAM/BAM/CRAM input",
+        },
+        Feature {
+            name: "gcs",
+            enabled: cfg!(feature = "gcs"),
+            description: "gs:// input paths",
+        },
+        Feature {
+            name: "fuzzy",
+            enabled: cfg!(feature = "fuzzy"),
+            description: "fuzzy matching in grep (-z)",
+        },
+    ]
+}
+
+/// Capabilities of the running binary
+#[derive(Serialize)]
+struct Capabilities {
+    version: &'static str,
+    input_formats: Vec<Entry>,
+    output_formats: Vec<Entry>,
+    binseq_modes: Vec<Entry>,
+    compression: Vec<Entry>,
+    features: Vec<Feature>,
+}
+impl Capabilities {
+    fn collect() -> Self {
+        let htslib = cfg!(feature = "htslib");
+        let input_formats = FileFormat::value_variants()
+            .iter()
+            .filter(|f| match f {
+                FileFormat::Tsv | FileFormat::Jsonl => false,
+                FileFormat::Bam => htslib,
+                _ => true,
+            })
+            .filter_map(|f| Entry::from_value(f, format_extensions(*f)))
+            .collect();
+        // BAM output is only written by `decode`
+        let output_formats = FileFormat::value_variants()
+            .iter()
+            .filter(|f| **f != FileFormat::Bam || htslib)
+            .filter_map(|f| {
+                let detail = match f {
+                    FileFormat::Bam => ".sam, .bam (decode only)",
+                    _ => format_extensions(*f),
+                };
+                Entry::from_value(f, detail)
+            })
+            .collect();
+        let binseq_modes = BinseqMode::value_variants()
+            .iter()
+            .filter_map(|m| Entry::from_value(m, mode_description(*m)))
+            .collect();
+        let compression = CompressionType::value_variants()
+            .iter()
+            .filter_map(|c| {
+                let detail = c
+                    .extension()
+                    .map_or("uncompressed".to_string(), |ext| format!(".{ext}"));
+                Entry::from_value(c, &detail)
+            })
+            .collect();
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            input_formats,
+            output_formats,
+            binseq_modes,
+            compression,
+            features: features(),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "bqtools {}", self.version).ok();
+        for (title, entries) in [
+            ("Input formats (encode -f)", &self.input_formats),
+            ("Output formats (-f)", &self.output_formats),
+            ("BINSEQ modes (-m)", &self.binseq_modes),
+            ("Compression (-c)", &self.compression),
+        ] {
+            writeln!(out, "\n{title}:").ok();
+            for entry in entries {
+                writeln!(out, "  {:<6}{}", entry.name, entry.description).ok();
+            }
+        }
+        writeln!(out, "\nFeatures:").ok();
+        for feature in &self.features {
+            let status = if feature.enabled {
+                "enabled"
+            } else {
+                "disabled"
+            };
+            writeln!(
+                out,
+                "  {:<8}{status:<10}{}",
+                feature.name, feature.description
+            )
+            .ok();
+        }
+        out
+    }
+}
+
+fn format_extensions(format: FileFormat) -> &'static str {
+    match format {
+        FileFormat::Fasta => ".fa, .fasta",
+        FileFormat::Fastq => ".fq, .fastq",
+        FileFormat::Bam => ".sam, .bam, .cram",
+        FileFormat::Tsv => ".tsv, .txt",
+        FileFormat::Jsonl => ".jsonl, .ndjson",
+    }
+}
+
+fn mode_description(mode: BinseqMode) -> &'static str {
+    match mode {
+        BinseqMode::Bq => "fixed-length records, .bq",
+        BinseqMode::Vbq => "variable-length records in compressed blocks, .vbq",
+        BinseqMode::Cbq => "columnar compressed blocks, .cbq",
+    }
+}
+
+pub fn run(args: &FormatsCommand) -> Result<()> {
+    let capabilities = Capabilities::collect();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&capabilities)?);
+    } else {
+        print!("{}", capabilities.render());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Capabilities;
+
+    #[test]
+    fn test_capabilities_reflect_build() {
+        let capabilities = Capabilities::collect();
+        let names = |entries: &[super::Entry]| -> Vec<String> {
+            entries.iter().map(|e| e.name.clone()).collect()
+        };
+        assert_eq!(names(&capabilities.binseq_modes), ["bq", "vbq", "cbq"]);
+        assert_eq!(names(&capabilities.compression), ["u", "g", "z"]);
+        let outputs: &[&str] = if cfg!(feature = "htslib") {
+            &["a", "q", "b", "t", "j"]
+        } else {
+            &["a", "q", "t", "j"]
+        };
+        assert_eq!(names(&capabilities.output_formats), outputs);
+        assert_eq!(
+            names(&capabilities.input_formats).contains(&"b".to_string()),
+            cfg!(feature = "htslib")
+        );
+
+        let rendered = capabilities.render();
+        assert!(rendered.contains("BINSEQ modes"));
+        let htslib_line = rendered
+            .lines()
+            .find(|l| l.trim_start().starts_with("htslib"))
+            .unwrap();
+        assert_eq!(
+            htslib_line.split_whitespace().nth(1) == Some("enabled"),
+            cfg!(feature = "htslib"),
+            "{htslib_line}"
+        );
+    }
+}
@@ -77,16 +77,41 @@ fn write_colored_sequence<W: Write>(
     Ok(())
 }
 
+/// Writes the merged match intervals as comma-separated `start-end` pairs (end-exclusive).
+fn write_match_coordinates<W: Write>(
+    writer: &mut W,
+    matches: &HashSet<Interval>,
+    interval_buffer: &mut Vec<Interval>,
+) -> Result<()> {
+    load_and_merge_matches(matches, interval_buffer);
+    for (i, (start, end)) in interval_buffer.iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        write!(writer, "{start}-{end}")?;
+    }
+    Ok(())
+}
+
+/// Writes a TSV row, either highlighting matches inline or (with `coordinates`)
+/// leaving the sequence untouched and appending the match positions as a column.
 fn write_colored_tsv<W: Write>(
     writer: &mut W,
     index: &[u8],
     buffer: &[u8],
     matches: &HashSet<Interval>,
     interval_buffer: &mut Vec<Interval>,
+    coordinates: bool,
 ) -> Result<()> {
     writer.write_all(index)?;
     writer.write_all(b"\t")?;
-    write_colored_sequence(writer, buffer, matches, interval_buffer)?;
+    if coordinates {
+        writer.write_all(buffer)?;
+        writer.write_all(b"\t")?;
+        write_match_coordinates(writer, matches, interval_buffer)?;
+    } else {
+        write_colored_sequence(writer, buffer, matches, interval_buffer)?;
+    }
     writer.write_all(b"\n")?;
     Ok(())
 }
@@ -125,6 +150,7 @@ fn write_colored_fastq<W: Write>(
 }
 
 #[allow(clippy::match_wildcard_for_single_variants)]
+#[allow(clippy::too_many_arguments)]
 fn write_colored_record<W: Write>(
     writer: &mut W,
     index: &[u8],
@@ -133,10 +159,18 @@ fn write_colored_record<W: Write>(
     matches: &HashSet<Interval>,
     format: FileFormat,
     interval_buffer: &mut Vec<Interval>,
+    coordinates: bool,
 ) -> Result<()> {
     let qual_buf = &quality[..sequence.len()];
     match format {
-        FileFormat::Tsv => write_colored_tsv(writer, index, sequence, matches, interval_buffer),
+        FileFormat::Tsv => write_colored_tsv(
+            writer,
+            index,
+            sequence,
+            matches,
+            interval_buffer,
+            coordinates,
+        ),
         FileFormat::Fasta => write_colored_fasta(writer, index, sequence, matches, interval_buffer),
         FileFormat::Fastq => {
             write_colored_fastq(writer, index, sequence, qual_buf, matches, interval_buffer)
@@ -159,6 +193,7 @@ pub fn write_colored_record_pair<W: Write>(
     xmatch: &HashSet<Interval>,
     format: FileFormat,
     interval_buffer: &mut Vec<Interval>,
+    coordinates: bool,
 ) -> Result<()> {
     match mate {
         Some(Mate::Both) => {
@@ -170,6 +205,7 @@ pub fn write_colored_record_pair<W: Write>(
                 smatch,
                 format,
                 interval_buffer,
+                coordinates,
             )?;
             write_colored_record(
                 writer,
@@ -179,6 +215,7 @@ pub fn write_colored_record_pair<W: Write>(
                 xmatch,
                 format,
                 interval_buffer,
+                coordinates,
             )?;
             Ok(())
         }
@@ -190,6 +227,7 @@ pub fn write_colored_record_pair<W: Write>(
             smatch,
             format,
             interval_buffer,
+            coordinates,
         ),
         Some(Mate::Two) => write_colored_record(
             writer,
@@ -199,6 +237,45 @@ pub fn write_colored_record_pair<W: Write>(
             xmatch,
             format,
             interval_buffer,
+            coordinates,
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colored_tsv_coordinates_column() {
+        let matches: HashSet<Interval> = [(2, 4), (3, 6), (8, 10)].into_iter().collect();
+        let mut out = Vec::new();
+        let mut interval_buffer = Vec::new();
+        write_colored_tsv(
+            &mut out,
+            b"read1",
+            b"ACGTACGTAC",
+            &matches,
+            &mut interval_buffer,
+            true,
+        )
+        .unwrap();
+        assert_eq!(out, b"read1\tACGTACGTAC\t2-6,8-10\n");
+    }
+
+    #[test]
+    fn test_colored_tsv_coordinates_column_without_matches() {
+        let mut out = Vec::new();
+        let mut interval_buffer = Vec::new();
+        write_colored_tsv(
+            &mut out,
+            b"read1",
+            b"ACGT",
+            &HashSet::new(),
+            &mut interval_buffer,
+            true,
+        )
+        .unwrap();
+        assert_eq!(out, b"read1\tACGT\t\n");
+    }
+}
@@ -4,7 +4,10 @@ use anyhow::Result;
 use fixedbitset::FixedBitSet;
 use sassy::{profiles::Iupac, EncodedPatterns, Searcher};
 
-use crate::commands::utils::build_fuzzy_searcher;
+use crate::{
+    cli::DistanceType,
+    commands::utils::{accepts_alignment, build_fuzzy_searcher},
+};
 
 type Profile = Iupac;
 type Patterns = Vec<Vec<u8>>;
@@ -25,6 +28,9 @@ pub struct FuzzyMatcher {
     /// Left-offset relevant for range matching
     offset: usize,
 
+    /// Distance metric used to accept matches
+    distance_type: DistanceType,
+
     /// Fixed-bitset for pat1
     bs1: FixedBitSet,
     /// Fixed-bitset for pat2
@@ -67,6 +73,7 @@ impl FuzzyMatcher {
             k,
             inexact,
             offset,
+            distance_type: DistanceType::default(),
             bs1,
             bs2,
             bs,
@@ -75,6 +82,13 @@ impl FuzzyMatcher {
             searcher,
         })
     }
+
+    /// Sets the distance metric used to accept matches (default: Levenshtein)
+    #[must_use]
+    pub fn with_distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = distance_type;
+        self
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -87,13 +101,14 @@ fn find_and_insert_matches(
     k: usize,
     inexact: bool,
     offset: usize,
+    distance_type: DistanceType,
 ) -> bool {
     let mut found = false;
     searcher
         .search_encoded_patterns(patterns, sequence, k)
         .iter()
         .for_each(|m| {
-            if inexact && m.cost == 0 {
+            if (inexact && m.cost == 0) || !accepts_alignment(m, distance_type) {
                 return;
             }
             matches.insert((m.text_start + offset, m.text_end + offset));
@@ -126,6 +141,7 @@ impl PatternMatch for FuzzyMatcher {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
             if and_logic {
                 has_any_match && self.bs1.is_full()
@@ -155,6 +171,7 @@ impl PatternMatch for FuzzyMatcher {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
             if and_logic {
                 has_any_match && self.bs2.is_full()
@@ -186,6 +203,7 @@ impl PatternMatch for FuzzyMatcher {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
             let secondary_has_any_match = find_and_insert_matches(
                 epat,
@@ -196,6 +214,7 @@ impl PatternMatch for FuzzyMatcher {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
             let has_any_match = primary_has_any_match || secondary_has_any_match;
             if and_logic {
@@ -116,6 +116,8 @@ mod matcher_unit_tests {
 
     #[cfg(feature = "fuzzy")]
     use super::FuzzyMatcher;
+    #[cfg(feature = "fuzzy")]
+    use crate::cli::DistanceType;
 
     #[test]
     fn test_regex_matcher_primary() {
@@ -363,6 +365,43 @@ mod matcher_unit_tests {
         assert!(matcher_k2.match_primary(seq_mismatch, &mut matches3, true));
     }
 
+    #[cfg(feature = "fuzzy")]
+    #[test]
+    fn test_fuzzy_matcher_hamming_rejects_indels() {
+        let pat1 = vec![b"ACGTACGT".to_vec()];
+        let seq_substitution = b"TTTTACGTTCGTTTTT";
+        let seq_deletion = b"TTTTACGTCGTTTTT";
+
+        let mut levenshtein =
+            FuzzyMatcher::new(&pat1.clone(), &vec![], &vec![], 1, false, 0, None).unwrap();
+        assert!(levenshtein.match_primary(seq_substitution, &mut HashSet::new(), true));
+        assert!(levenshtein.match_primary(seq_deletion, &mut HashSet::new(), true));
+
+        let mut hamming = FuzzyMatcher::new(&pat1, &vec![], &vec![], 1, false, 0, None)
+            .unwrap()
+            .with_distance_type(DistanceType::Hamming);
+        assert!(hamming.match_primary(seq_substitution, &mut HashSet::new(), true));
+        assert!(!hamming.match_primary(seq_deletion, &mut HashSet::new(), true));
+    }
+
+    /// An insertion and a deletion cancel out in span length but are still indels.
+    #[cfg(feature = "fuzzy")]
+    #[test]
+    fn test_fuzzy_matcher_hamming_rejects_balanced_indels() {
+        let pat1 = vec![b"ACGTTGCAAGTC".to_vec()];
+        // pattern with the G at 2 deleted and a T inserted after position 8
+        let seq = b"GGGGGGACTTGCAATGTCGGGGGG";
+
+        let mut levenshtein =
+            FuzzyMatcher::new(&pat1.clone(), &vec![], &vec![], 2, false, 0, None).unwrap();
+        assert!(levenshtein.match_primary(seq, &mut HashSet::new(), true));
+
+        let mut hamming = FuzzyMatcher::new(&pat1, &vec![], &vec![], 2, false, 0, None)
+            .unwrap()
+            .with_distance_type(DistanceType::Hamming);
+        assert!(!hamming.match_primary(seq, &mut HashSet::new(), true));
+    }
+
     #[cfg(feature = "fuzzy")]
     #[test]
     fn test_fuzzy_matcher_inexact_only() {
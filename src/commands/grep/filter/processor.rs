@@ -1,13 +1,13 @@
 use crate::{
     cli::{FileFormat, Mate},
     commands::{
-        decode::{write_record_pair, SplitWriter},
-        grep::{color::write_colored_record_pair, SimpleRange},
+        decode::{write_record_pair, LengthFilter, SplitWriter, TSV_DELIMITER},
+        grep::{color::write_colored_record_pair, FlagFilter, SimpleRange},
     },
 };
 use binseq::prelude::*;
 use parking_lot::Mutex;
-use std::sync::Arc;
+use std::{io::Write, sync::Arc};
 
 use super::{MatchRanges, PatternMatch};
 
@@ -34,12 +34,42 @@ pub struct FilterProcessor<Pm: PatternMatch> {
     /// Match against the sequence header instead of the sequence
     header: bool,
 
+    /// Evaluate the pattern matcher (false when only filtering on flags)
+    match_patterns: bool,
+
+    /// Flag bit predicate
+    flags: Option<FlagFilter>,
+
+    /// Sequence length bounds
+    length_filter: Option<LengthFilter>,
+
+    /// Accumulate match start positions instead of writing records
+    position_matrix: bool,
+
+    /// Write one TSV row per match interval instead of writing records
+    positions: bool,
+
+    /// Collect the indices of matching records (`--index-out`)
+    collect_indices: bool,
+
+    /// Skip writing matching records (`--index-only`)
+    index_only: bool,
+
+    /// Local match start histogram (indexed by position)
+    local_positions: Vec<usize>,
+
+    /// Local indices of matching records
+    local_indices: Vec<u64>,
+
     /// Local count
     local_count: usize,
 
     /// Local total records processed
     local_total: usize,
 
+    /// Local records dropped by the length filter
+    local_length_skipped: usize,
+
     /// Local primary/extended sequence match indices
     smatches: MatchRanges,
     xmatches: MatchRanges,
@@ -59,11 +89,16 @@ pub struct FilterProcessor<Pm: PatternMatch> {
     mate: Option<Mate>,
     is_split: bool,
     color: bool,
+    coordinates: bool,
+    defer_flush: bool,
 
     /// Global values
     global_writer: Arc<Mutex<SplitWriter>>,
     global_count: Arc<Mutex<usize>>,
     global_total: Arc<Mutex<usize>>,
+    global_positions: Arc<Mutex<Vec<usize>>>,
+    global_length_skipped: Arc<Mutex<usize>>,
+    global_indices: Arc<Mutex<Vec<u64>>>,
 }
 impl<Pm: PatternMatch> FilterProcessor<Pm> {
     #[allow(clippy::fn_params_excessive_bools)]
@@ -80,6 +115,9 @@ impl<Pm: PatternMatch> FilterProcessor<Pm> {
         format: FileFormat,
         mate: Option<Mate>,
         color: bool,
+        coordinates: bool,
+        match_patterns: bool,
+        flags: Option<FlagFilter>,
     ) -> Self {
         Self {
             mixed: Vec::new(),
@@ -97,15 +135,132 @@ impl<Pm: PatternMatch> FilterProcessor<Pm> {
             frac,
             range,
             header,
+            match_patterns,
+            flags,
             format,
             mate,
             color,
+            coordinates,
+            defer_flush: false,
             is_split: writer.is_split(),
             global_writer: Arc::new(Mutex::new(writer)),
+            position_matrix: false,
+            positions: false,
+            collect_indices: false,
+            index_only: false,
+            local_positions: Vec::new(),
+            local_indices: Vec::new(),
             local_count: 0,
             local_total: 0,
             global_count: Arc::new(Mutex::new(0)),
             global_total: Arc::new(Mutex::new(0)),
+            global_positions: Arc::new(Mutex::new(Vec::new())),
+            length_filter: None,
+            local_length_skipped: 0,
+            global_length_skipped: Arc::new(Mutex::new(0)),
+            global_indices: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Histogram match start positions instead of writing matching records
+    #[must_use]
+    pub fn with_position_matrix(mut self, position_matrix: bool) -> Self {
+        self.position_matrix = position_matrix;
+        self
+    }
+
+    /// Drop records whose length is outside the bounds before matching
+    #[must_use]
+    pub fn with_length_filter(mut self, length_filter: Option<LengthFilter>) -> Self {
+        self.length_filter = length_filter;
+        self
+    }
+
+    /// Collect the indices of matching records (see [`Self::match_indices`])
+    #[must_use]
+    pub fn with_match_indices(mut self, collect_indices: bool) -> Self {
+        self.collect_indices = collect_indices;
+        self
+    }
+
+    /// Only collect matches, without writing the matching records
+    #[must_use]
+    pub fn with_index_only(mut self, index_only: bool) -> Self {
+        self.index_only = index_only;
+        self
+    }
+
+    /// Indices of all matching records in ascending order
+    ///
+    /// Threads merge their matches in completion order, so the indices are
+    /// sorted here rather than as they arrive.
+    pub fn match_indices(&self) -> Vec<u64> {
+        let mut indices = self.global_indices.lock().clone();
+        indices.sort_unstable();
+        indices
+    }
+
+    /// Skip the per-batch flush, leaving it to [`Self::finish`]
+    #[must_use]
+    pub fn with_defer_flush(mut self, defer_flush: bool) -> Self {
+        self.defer_flush = defer_flush;
+        self
+    }
+
+    /// Finishes the shared writer (the only flush with `--defer-flush`)
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.global_writer.lock().finish()
+    }
+
+    /// Number of records dropped by the length filter
+    pub fn num_length_skipped(&self) -> usize {
+        *self.global_length_skipped.lock()
+    }
+
+    /// Write a `record_index, mate, start, end, match` TSV row per match interval
+    #[must_use]
+    pub fn with_positions(mut self, positions: bool) -> Self {
+        self.positions = positions;
+        self
+    }
+
+    /// Header of the `--positions` TSV
+    pub const POSITIONS_HEADER: &'static [u8] = b"record_index\tmate\tstart\tend\tmatch\n";
+
+    /// Appends the sorted match intervals of both mates as `--positions` rows
+    fn write_positions(&mut self, index: u64, sbuf: &[u8], xbuf: &[u8]) -> std::io::Result<()> {
+        for (mate, matches, seq) in [(1, &self.smatches, sbuf), (2, &self.xmatches, xbuf)] {
+            self.interval_buffer.clear();
+            self.interval_buffer.extend(matches.iter().copied());
+            self.interval_buffer.sort_unstable();
+            for &(start, end) in &self.interval_buffer {
+                write!(self.mixed, "{index}\t{mate}\t{start}\t{end}\t")?;
+                self.mixed
+                    .extend_from_slice(seq.get(start..end).unwrap_or_default());
+                self.mixed.push(b'\n');
+            }
+        }
+        Ok(())
+    }
+
+    fn record_positions(&mut self) {
+        for &(start, _) in self.smatches.iter().chain(self.xmatches.iter()) {
+            if self.local_positions.len() <= start {
+                self.local_positions.resize(start + 1, 0);
+            }
+            self.local_positions[start] += 1;
+        }
+    }
+
+    /// Match start histogram across all matched records (index = position)
+    pub fn positions(&self) -> Vec<usize> {
+        self.global_positions.lock().clone()
+    }
+
+    pub fn pprint_positions(&self) {
+        println!("position\tcount");
+        for (position, count) in self.positions().iter().enumerate() {
+            println!("{position}\t{count}");
         }
     }
     pub fn clear_matches(&mut self) {
@@ -169,16 +324,40 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
         self.clear_matches();
         self.local_total += 1;
 
+        if self.flags.is_some_and(|f| !f.accepts(record.flag())) {
+            return Ok(());
+        }
+        if self
+            .length_filter
+            .is_some_and(|filter| !filter.includes(&record))
+        {
+            self.local_length_skipped += 1;
+            return Ok(());
+        }
+
         let sbuf = record.sseq();
         let xbuf = record.xseq();
-        let matched = if self.header {
+        let matched = if !self.match_patterns {
+            true
+        } else if self.header {
             self.pattern_match(record.sheader(), record.xheader())
         } else {
             self.pattern_match(sbuf, xbuf)
         };
         if matched {
             self.local_count += 1;
-            if self.count {
+            if self.collect_indices {
+                self.local_indices.push(record.index());
+            }
+            if self.position_matrix {
+                self.record_positions();
+                return Ok(());
+            }
+            if self.positions {
+                self.write_positions(record.index(), sbuf, xbuf)?;
+                return Ok(());
+            }
+            if self.count || self.index_only {
                 // No further processing needed
                 return Ok(());
             }
@@ -208,7 +387,7 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
                 &self.xqual
             };
 
-            if self.color {
+            if self.color || self.coordinates {
                 write_colored_record_pair(
                     &mut self.mixed,
                     self.mate,
@@ -222,6 +401,7 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
                     &self.xmatches,
                     self.format,
                     &mut self.interval_buffer,
+                    self.coordinates,
                 )
             } else {
                 write_record_pair(
@@ -237,6 +417,7 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
                     xqual,
                     record.xheader(),
                     self.format,
+                    TSV_DELIMITER,
                 )
             }?;
         }
@@ -246,7 +427,7 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
 
     fn on_batch_complete(&mut self) -> binseq::Result<()> {
         // Lock the mutex to write to the global buffer
-        if !self.count {
+        if !self.count && !self.position_matrix && !self.index_only {
             let mut writer = self.global_writer.lock();
             if writer.is_split() {
                 writer.write_split(&self.left, true)?;
@@ -254,7 +435,9 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
             } else {
                 writer.write_interleaved(&self.mixed)?;
             }
-            writer.flush()?;
+            if !self.defer_flush {
+                writer.flush()?;
+            }
         }
 
         // Clear the local buffer and reset the local record count
@@ -270,6 +453,26 @@ impl<Pm: PatternMatch> ParallelProcessor for FilterProcessor<Pm> {
         *self.global_total.lock() += self.local_total;
         self.local_total = 0;
 
+        if self.local_length_skipped > 0 {
+            *self.global_length_skipped.lock() += self.local_length_skipped;
+            self.local_length_skipped = 0;
+        }
+
+        if !self.local_indices.is_empty() {
+            self.global_indices.lock().append(&mut self.local_indices);
+        }
+
+        if !self.local_positions.is_empty() {
+            let mut positions = self.global_positions.lock();
+            if positions.len() < self.local_positions.len() {
+                positions.resize(self.local_positions.len(), 0);
+            }
+            for (global, local) in positions.iter_mut().zip(self.local_positions.iter_mut()) {
+                *global += *local;
+                *local = 0;
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,266 @@
+use std::str::FromStr;
+
+/// A bit mask over record flags, parsed from decimal or `0x`-prefixed hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlagMask(pub u64);
+impl FromStr for FlagMask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let parsed = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            u64::from_str_radix(hex, 16)
+        } else {
+            s.parse::<u64>()
+        };
+        match parsed {
+            Ok(mask) => Ok(Self(mask)),
+            Err(_) => Err(format!(
+                "Invalid flag mask: {s} (expected decimal or 0x-prefixed hex)"
+            )),
+        }
+    }
+}
+
+/// Flag predicate applied alongside the pattern logic.
+///
+/// A record passes when all `require` bits are set and no `exclude` bits are set.
+/// Records without a flag are treated as having no bits set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FlagFilter {
+    pub require: u64,
+    pub exclude: u64,
+}
+impl FlagFilter {
+    pub fn accepts(&self, flag: Option<u64>) -> bool {
+        let flag = flag.unwrap_or(0);
+        flag & self.require == self.require && flag & self.exclude == 0
+    }
+}
+
+/// Binary operators of a [`FlagExpr`], loosest binding first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    BitOr,
+    BitXor,
+    BitAnd,
+}
+impl FlagOp {
+    /// Binding strength, following Rust (bit operators bind tighter than `==`)
+    fn precedence(self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And => 2,
+            Self::Eq | Self::Ne => 3,
+            Self::BitOr => 4,
+            Self::BitXor => 5,
+            Self::BitAnd => 6,
+        }
+    }
+
+    fn apply(self, lhs: u64, rhs: u64) -> u64 {
+        match self {
+            Self::Or => u64::from(lhs != 0 || rhs != 0),
+            Self::And => u64::from(lhs != 0 && rhs != 0),
+            Self::Eq => u64::from(lhs == rhs),
+            Self::Ne => u64::from(lhs != rhs),
+            Self::BitOr => lhs | rhs,
+            Self::BitXor => lhs ^ rhs,
+            Self::BitAnd => lhs & rhs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FlagToken {
+    Flag,
+    Num(u64),
+    Op(FlagOp),
+    Not,
+    Open,
+    Close,
+}
+
+fn tokenize_flag_expr(input: &str) -> Result<Vec<FlagToken>, String> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let two = bytes.get(i..i + 2).unwrap_or_default();
+        let (token, len) = match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'(' => (FlagToken::Open, 1),
+            b')' => (FlagToken::Close, 1),
+            _ if two == b"||" => (FlagToken::Op(FlagOp::Or), 2),
+            _ if two == b"&&" => (FlagToken::Op(FlagOp::And), 2),
+            _ if two == b"==" => (FlagToken::Op(FlagOp::Eq), 2),
+            _ if two == b"!=" => (FlagToken::Op(FlagOp::Ne), 2),
+            b'|' => (FlagToken::Op(FlagOp::BitOr), 1),
+            b'^' => (FlagToken::Op(FlagOp::BitXor), 1),
+            b'&' => (FlagToken::Op(FlagOp::BitAnd), 1),
+            b'!' => (FlagToken::Not, 1),
+            b if b.is_ascii_alphanumeric() => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric())
+                    .count();
+                let word = &input[i..i + len];
+                let token = if word == "flag" {
+                    FlagToken::Flag
+                } else {
+                    FlagToken::Num(word.parse::<FlagMask>()?.0)
+                };
+                (token, len)
+            }
+            _ => return Err(format!("Unexpected character in flag expression: {input}")),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// A predicate over record flags, e.g. `(flag & 0x3) == 0x1 && !(flag & 0x100)`.
+///
+/// Supports `flag`, decimal or `0x`-hex numbers, parentheses, the bit operators
+/// `&`, `^`, `|`, comparisons `==` and `!=`, and the logical `!`, `&&`, `||`.
+/// Precedence follows Rust, so bit operators bind tighter than comparisons. A
+/// record passes when the expression is non-zero; records without a flag are
+/// evaluated with a flag of 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlagExpr {
+    Flag,
+    Const(u64),
+    Not(Box<FlagExpr>),
+    Binary(FlagOp, Box<FlagExpr>, Box<FlagExpr>),
+}
+impl FlagExpr {
+    pub fn accepts(&self, flag: Option<u64>) -> bool {
+        self.eval(flag.unwrap_or(0)) != 0
+    }
+
+    fn eval(&self, flag: u64) -> u64 {
+        match self {
+            Self::Flag => flag,
+            Self::Const(value) => *value,
+            Self::Not(inner) => u64::from(inner.eval(flag) == 0),
+            Self::Binary(op, lhs, rhs) => op.apply(lhs.eval(flag), rhs.eval(flag)),
+        }
+    }
+
+    /// Precedence climbing over `tokens`, consuming operators binding at least `min_prec`
+    fn parse_binary(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<FlagToken>>,
+        min_prec: u8,
+    ) -> Result<Self, String> {
+        let mut lhs = Self::parse_unary(tokens)?;
+        while let Some(&FlagToken::Op(op)) = tokens.peek() {
+            if op.precedence() < min_prec {
+                break;
+            }
+            tokens.next();
+            let rhs = Self::parse_binary(tokens, op.precedence() + 1)?;
+            lhs = Self::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<FlagToken>>,
+    ) -> Result<Self, String> {
+        match tokens.next() {
+            Some(FlagToken::Flag) => Ok(Self::Flag),
+            Some(FlagToken::Num(value)) => Ok(Self::Const(value)),
+            Some(FlagToken::Not) => Ok(Self::Not(Box::new(Self::parse_unary(tokens)?))),
+            Some(FlagToken::Open) => {
+                let inner = Self::parse_binary(tokens, 0)?;
+                match tokens.next() {
+                    Some(FlagToken::Close) => Ok(inner),
+                    _ => Err("Unbalanced parentheses in flag expression".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {token:?} in flag expression")),
+            None => Err("Incomplete flag expression".to_string()),
+        }
+    }
+}
+impl FromStr for FlagExpr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = tokenize_flag_expr(s)?.into_iter().peekable();
+        let expr = Self::parse_binary(&mut tokens, 0)?;
+        match tokens.next() {
+            None => Ok(expr),
+            Some(token) => Err(format!("Unexpected {token:?} in flag expression: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flag_expr() {
+        let expr: FlagExpr = "(flag & 0x3) == 0x1".parse().unwrap();
+        assert!(expr.accepts(Some(0x1)));
+        assert!(expr.accepts(Some(0x5)));
+        assert!(!expr.accepts(Some(0x3)));
+        assert!(!expr.accepts(None));
+
+        // bit operators bind tighter than comparisons, comparisons than `&&`
+        let expr: FlagExpr = "flag & 3 == 1 && !(flag & 0x100) || flag == 7"
+            .parse()
+            .unwrap();
+        assert!(expr.accepts(Some(0x1)));
+        assert!(!expr.accepts(Some(0x101)));
+        assert!(expr.accepts(Some(7)));
+
+        let expr: FlagExpr = "!flag".parse().unwrap();
+        assert!(expr.accepts(None));
+        assert!(!expr.accepts(Some(2)));
+
+        for bad in [
+            "", "flag &", "(flag", "flag)", "flag = 1", "seq == 1", "0xZZ",
+        ] {
+            assert!(bad.parse::<FlagExpr>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_flag_mask_parsing() {
+        assert_eq!("0x100".parse::<FlagMask>().unwrap(), FlagMask(256));
+        assert_eq!("0X1f".parse::<FlagMask>().unwrap(), FlagMask(31));
+        assert_eq!("12".parse::<FlagMask>().unwrap(), FlagMask(12));
+        assert!("0xZZ".parse::<FlagMask>().is_err());
+        assert!("-1".parse::<FlagMask>().is_err());
+    }
+
+    #[test]
+    fn test_flag_filter_accepts() {
+        let filter = FlagFilter {
+            require: 0x1,
+            exclude: 0x100,
+        };
+        assert!(filter.accepts(Some(0x1)));
+        assert!(filter.accepts(Some(0x3)));
+        assert!(!filter.accepts(Some(0x2)));
+        assert!(!filter.accepts(Some(0x101)));
+        assert!(!filter.accepts(None));
+
+        let exclude_only = FlagFilter {
+            require: 0,
+            exclude: 0x100,
+        };
+        assert!(exclude_only.accepts(None));
+        assert!(!exclude_only.accepts(Some(0x100)));
+    }
+}
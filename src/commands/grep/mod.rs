@@ -1,16 +1,18 @@
 mod color;
 mod filter;
+mod flags;
 mod pattern_count;
 mod patterns;
 mod range;
 
 #[cfg(feature = "fuzzy")]
 use filter::FuzzyMatcher;
-use log::{error, warn};
+use log::{error, info, warn};
 #[cfg(feature = "fuzzy")]
 use pattern_count::FuzzyPatternCounter;
 
 use filter::{FilterProcessor, PatternMatcher, RegexMatcher};
+pub use flags::{FlagExpr, FlagFilter, FlagMask};
 use pattern_count::{
     AhoCorasickPatternCounter, PatternCount, PatternCountProcessor, PatternCounter,
     RegexPatternCounter,
@@ -22,11 +24,17 @@ pub use range::SimpleRange;
 use super::decode::build_writer;
 use crate::{
     cli::{FileFormat, GrepCommand, Mate},
-    commands::{decode::SplitWriter, grep::filter::AhoCorasickMatcher},
+    commands::{
+        decode::SplitWriter,
+        grep::filter::AhoCorasickMatcher,
+        match_output, open_binseq, progress,
+        utils::{process_binseq, span_len},
+    },
 };
 
+use std::io::Write;
+
 use anyhow::{bail, Result};
-use binseq::prelude::*;
 
 /// Returns true if all patterns across multiple sets are fixed DNA strings.
 pub fn all_patterns_fixed(pattern_sets: &[&PatternCollection]) -> bool {
@@ -112,7 +120,8 @@ fn build_counter(args: &GrepCommand) -> Result<PatternCounter> {
             args.grep.fuzzy_args.inexact,
             args.grep.invert,
             args.grep.fuzzy_args.max_n_frac,
-        )?;
+        )?
+        .with_distance_type(args.grep.fuzzy_args.distance_type);
         return Ok(PatternCounter::Fuzzy(Box::new(counter)));
     }
 
@@ -138,21 +147,17 @@ fn build_counter(args: &GrepCommand) -> Result<PatternCounter> {
     }
 }
 
-fn run_pattern_count(args: &GrepCommand, reader: BinseqReader) -> Result<()> {
+fn run_pattern_count(args: &GrepCommand) -> Result<()> {
     let counter = build_counter(args)?;
     let pattern_names = counter.pattern_names();
     let proc =
         PatternCountProcessor::new(counter, args.grep.range, args.grep.header, pattern_names);
-    if let Some(mut span) = args.input.span {
-        let num_records = reader.num_records()?;
-        reader.process_parallel_range(
-            proc.clone(),
-            args.output.threads(),
-            span.get_range(num_records)?,
-        )?;
-    } else {
-        reader.process_parallel(proc.clone(), args.output.threads())?;
-    }
+    process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.input.span,
+    )?;
     proc.pprint_pattern_counts()?;
     Ok(())
 }
@@ -175,7 +180,8 @@ fn build_matcher(args: &GrepCommand) -> Result<(PatternMatcher, bool)> {
             args.grep.fuzzy_args.inexact,
             args.grep.range.map_or(0, |r| r.offset()),
             args.grep.fuzzy_args.max_n_frac,
-        )?;
+        )?
+        .with_distance_type(args.grep.fuzzy_args.distance_type);
         return Ok((PatternMatcher::Fuzzy(Box::new(matcher)), and_logic));
     }
 
@@ -211,15 +217,35 @@ fn build_matcher(args: &GrepCommand) -> Result<(PatternMatcher, bool)> {
     }
 }
 
+/// Writes `--index-out`: one record index per line
+fn write_match_indices(path: &str, indices: &[u64]) -> Result<()> {
+    let mut handle = match_output(Some(path))?;
+    for index in indices {
+        writeln!(handle, "{index}")?;
+    }
+    handle.flush()?;
+    Ok(())
+}
+
 fn run_grep(
     args: &GrepCommand,
-    reader: BinseqReader,
-    writer: SplitWriter,
+    mut writer: SplitWriter,
     format: FileFormat,
     mate: Option<Mate>,
 ) -> Result<()> {
     let count = args.grep.count || args.grep.frac;
-    let (matcher, and_logic) = build_matcher(args)?;
+    let match_patterns = args.grep.has_patterns();
+    let (matcher, and_logic) = if match_patterns {
+        build_matcher(args)?
+    } else {
+        // flag-only filtering: the matcher is never consulted
+        let matcher = RegexMatcher::new(Vec::new(), Vec::new(), Vec::new(), 0);
+        (PatternMatcher::Regex(matcher), false)
+    };
+    let length_filter = args.length.filter()?;
+    if args.grep.positions {
+        writer.write_interleaved(FilterProcessor::<PatternMatcher>::POSITIONS_HEADER)?;
+    }
     let proc = FilterProcessor::new(
         matcher,
         and_logic,
@@ -232,19 +258,40 @@ fn run_grep(
         format,
         mate,
         args.should_color(),
+        args.should_report_coordinates(),
+        match_patterns,
+        args.grep.flag_filter(),
+    )
+    .with_position_matrix(args.grep.position_matrix)
+    .with_positions(args.grep.positions)
+    .with_length_filter(length_filter)
+    .with_match_indices(args.grep.index_out.is_some())
+    .with_index_only(args.grep.index_only)
+    .with_defer_flush(args.output.defer_flush);
+
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.input.span,
     );
-
-    if let Some(mut span) = args.input.span {
-        let num_records = reader.num_records()?;
-        reader.process_parallel_range(
-            proc.clone(),
-            args.output.threads(),
-            span.get_range(num_records)?,
-        )?;
-    } else {
-        reader.process_parallel(proc.clone(), args.output.threads())?;
+    let finished = proc.finish();
+    result?;
+    finished?;
+    if length_filter.is_some() {
+        info!(
+            "Skipped {} records outside the length bounds",
+            proc.num_length_skipped()
+        );
+    }
+    if let Some(path) = &args.grep.index_out {
+        let indices = proc.match_indices();
+        write_match_indices(path, &indices)?;
+        info!("Wrote {} matching record indices to: {path}", indices.len());
     }
-    if count {
+    if args.grep.position_matrix {
+        proc.pprint_positions();
+    } else if count {
         proc.pprint_counts();
     }
 
@@ -253,7 +300,12 @@ fn run_grep(
 
 pub fn run(args: &GrepCommand) -> Result<()> {
     args.grep.validate()?;
-    let reader = BinseqReader::new(args.input.path())?;
+    if args.grep.color_safe && !args.should_report_coordinates() {
+        warn!(
+            "`--color-safe` only applies to single-stream TSV output of sequence matches; ignoring"
+        );
+    }
+    let reader = open_binseq(args.input.path())?;
     let writer = build_writer(&args.output, reader.is_paired())?;
     let format = args.output.format()?;
     let mate = if reader.is_paired() {
@@ -261,12 +313,26 @@ pub fn run(args: &GrepCommand) -> Result<()> {
     } else {
         None
     };
-
-    if args.grep.pattern_count {
-        run_pattern_count(args, reader)
+    let num_records = span_len(&reader, args.input.span)?;
+    info!("Scanning {num_records} records...");
+    let progress = args.progress.spawn();
+    progress::add_total_records(num_records);
+
+    let result = if args.grep.pattern_count {
+        if args.grep.flag_filter().is_some() {
+            warn!("`--flag-require`/`--flag-exclude` are ignored when counting patterns (-P)");
+        }
+        if args.length.filter()?.is_some() {
+            warn!("`--min-length`/`--max-length` are ignored when counting patterns (-P)");
+        }
+        run_pattern_count(args)
     } else {
-        run_grep(args, reader, writer, format, mate)
+        run_grep(args, writer, format, mate)
+    };
+    if let Some(progress) = progress {
+        progress.finish();
     }
+    result
 }
 
 #[cfg(test)]
@@ -277,7 +343,9 @@ mod tests {
     use tempfile::NamedTempFile;
 
     use crate::cli::{BinseqMode, FileFormat};
-    use crate::testutils::{count_fastx_records, write_fastx, DEFAULT_NUM_RECORDS};
+    use crate::testutils::{
+        count_fastx_records, write_fastx, write_flagged_binseq, DEFAULT_NUM_RECORDS,
+    };
 
     fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
         let cmd = crate::cli::EncodeCommand::try_parse_from([
@@ -307,6 +375,161 @@ mod tests {
         count_fastx_records(out_tmp.path())
     }
 
+    /// Flags of the records written to a FASTQ output (records are named
+    /// `seq.<index>` and flagged `index + 1` by `write_flagged_binseq`)
+    fn output_flags(path: &std::path::Path) -> Result<Vec<u64>> {
+        let mut flags: Vec<u64> = std::fs::read_to_string(path)?
+            .lines()
+            .step_by(4)
+            .map(|header| {
+                header
+                    .trim_start_matches("@seq.")
+                    .parse::<u64>()
+                    .map(|idx| idx + 1)
+            })
+            .collect::<Result<_, _>>()?;
+        flags.sort_unstable();
+        Ok(flags)
+    }
+
+    /// Flag masks filter without a pattern, keeping exactly the records whose
+    /// flags have every required bit and no excluded bit.
+    #[test]
+    fn test_grep_flag_masks_without_pattern() -> Result<()> {
+        let nrec = 64;
+        for mode in BinseqMode::enum_iter() {
+            let bq_tmp = write_flagged_binseq(mode, nrec)?;
+            let all_flags = 1..=nrec as u64;
+
+            for (flag_args, require, exclude) in [
+                (&["--flag-require", "0x1"][..], 0x1, 0x0),
+                (&["--flag-exclude", "0x1"], 0x0, 0x1),
+                (
+                    &["--flag-require", "0x2", "--flag-exclude", "0x4"],
+                    0x2,
+                    0x4,
+                ),
+                (
+                    &["--flag-require", "0x3", "--flag-exclude", "0x20"],
+                    0x3,
+                    0x20,
+                ),
+                (&["--flag-exclude", "0x100"], 0x0, 0x100),
+            ] {
+                let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+                let mut args = vec![
+                    "grep",
+                    bq_tmp.path().to_str().unwrap(),
+                    "-o",
+                    out_tmp.path().to_str().unwrap(),
+                ];
+                args.extend(flag_args);
+                let cmd = crate::cli::GrepCommand::try_parse_from(args)?;
+                super::run(&cmd)?;
+                let expected: Vec<u64> = all_flags
+                    .clone()
+                    .filter(|flag| flag & require == require && flag & exclude == 0)
+                    .collect();
+                assert_eq!(
+                    output_flags(out_tmp.path())?,
+                    expected,
+                    "unexpected records for {flag_args:?} in {mode:?}"
+                );
+            }
+        }
+
+        // flag masks combine with pattern logic: only odd flags survive
+        let bq_tmp = write_flagged_binseq(BinseqMode::Vbq, nrec)?;
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "A",
+            "--flag-require",
+            "1",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+        let flags = output_flags(out_tmp.path())?;
+        assert!(!flags.is_empty());
+        assert!(flags.iter().all(|flag| flag & 1 == 1), "{flags:?}");
+
+        // still need something to filter on
+        let cmd =
+            crate::cli::GrepCommand::try_parse_from(["grep", bq_tmp.path().to_str().unwrap()])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    /// `--index-out` lists the matching records in order; `--index-only` skips the records.
+    #[test]
+    fn test_grep_index_out() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let idx_tmp = NamedTempFile::with_suffix(".txt")?;
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "AAA",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--index-out",
+            idx_tmp.path().to_str().unwrap(),
+            "-T",
+            "4",
+        ])?;
+        super::run(&cmd)?;
+        let indices: Vec<u64> = std::fs::read_to_string(idx_tmp.path())?
+            .lines()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        assert!(!indices.is_empty());
+        assert!(
+            indices.windows(2).all(|w| w[0] < w[1]),
+            "indices not sorted"
+        );
+
+        // input records are named `seq.<index>`
+        let mut expected: Vec<u64> = std::fs::read_to_string(out_tmp.path())?
+            .lines()
+            .step_by(4)
+            .map(|header| header.trim_start_matches("@seq.").parse())
+            .collect::<Result<_, _>>()?;
+        expected.sort_unstable();
+        assert_eq!(indices, expected);
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "AAA",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--index-out",
+            idx_tmp.path().to_str().unwrap(),
+            "--index-only",
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_fastx_records(out_tmp.path())?, 0);
+        assert_eq!(
+            std::fs::read_to_string(idx_tmp.path())?.lines().count(),
+            indices.len()
+        );
+
+        assert!(crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "AAA",
+            "--index-only",
+        ])
+        .is_err());
+        Ok(())
+    }
+
     /// grep returns a count ≤ total records and > 0 for a short common pattern.
     #[test]
     fn test_grep_basic_count() -> Result<()> {
@@ -325,6 +548,86 @@ mod tests {
         Ok(())
     }
 
+    /// `--position-matrix` reports a histogram instead of writing records and
+    /// needs a pattern to take positions from.
+    #[test]
+    fn test_grep_position_matrix() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+        let bq_path = bq_tmp.path().to_str().unwrap();
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let out_path = out_tmp.path().to_str().unwrap();
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_path,
+            "A",
+            "--position-matrix",
+            "-o",
+            out_path,
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_fastx_records(out_tmp.path())?, 0);
+
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_path,
+            "--flag-exclude",
+            "0x100",
+            "--position-matrix",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        assert!(crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_path,
+            "A",
+            "--position-matrix",
+            "-C",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    /// `--positions` rows slice the matched bases from the full sequence, so
+    /// they only read back as the pattern when `--range` offsets are applied.
+    #[test]
+    fn test_grep_positions() -> Result<()> {
+        let in_tmp = write_fastx().nrec(1000).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "AAA",
+            "--range",
+            "10..",
+            "--positions",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+
+        let contents = std::fs::read_to_string(out_tmp.path())?;
+        let mut lines = contents.lines();
+        assert_eq!(lines.next(), Some("record_index\tmate\tstart\tend\tmatch"));
+        let mut rows = 0;
+        for line in lines {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let start: usize = fields[2].parse()?;
+            let end: usize = fields[3].parse()?;
+            assert_eq!(fields[1], "1");
+            assert!(start >= 10, "match before range start: {line}");
+            assert_eq!(end - start, 3);
+            assert_eq!(fields[4], "AAA");
+            rows += 1;
+        }
+        assert!(rows > 0);
+        Ok(())
+    }
+
     /// A single fixed-string pattern under the default AND logic must not
     /// panic (Aho-Corasick doesn't support AND) and must match the count
     /// produced with explicit OR logic, since AND/OR are equivalent with
@@ -405,6 +708,103 @@ mod tests {
         Ok(())
     }
 
+    /// `--color-safe` TSV output appends a column of match coordinates and
+    /// never writes ANSI escapes.
+    #[test]
+    fn test_grep_color_safe_tsv_coordinates() -> Result<()> {
+        use std::io::Write as _;
+
+        let seq = "ACGTACGTGATTACAACGTACGT";
+        let in_tmp = NamedTempFile::with_suffix(".fastq")?;
+        {
+            let mut f = std::fs::File::create(in_tmp.path())?;
+            writeln!(f, "@read1")?;
+            writeln!(f, "{seq}")?;
+            writeln!(f, "+")?;
+            writeln!(f, "{}", "I".repeat(seq.len()))?;
+        }
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".tsv")?;
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "GATTACA",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "-f",
+            "t",
+            "--color-safe",
+        ])?;
+        super::run(&cmd)?;
+
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        assert!(
+            !content.contains('\x1b'),
+            "unexpected ANSI escapes: {content:?}"
+        );
+        let columns: Vec<&str> = content.trim_end().split('\t').collect();
+        assert_eq!(columns.len(), 3, "{content:?}");
+        assert_eq!(columns[1], seq);
+        assert_eq!(columns[2], "8-15");
+        Ok(())
+    }
+
+    /// Split mate outputs (`--prefix`) ignore `--color-safe` instead of dropping
+    /// the coordinate rows, so both mates are still written as plain TSV.
+    #[test]
+    fn test_grep_color_safe_split_output() -> Result<()> {
+        let seq = "ACGTACGTGATTACAACGTACGT";
+        let (r1, r2) = (
+            NamedTempFile::with_suffix(".fastq")?,
+            NamedTempFile::with_suffix(".fastq")?,
+        );
+        for tmp in [&r1, &r2] {
+            std::fs::write(
+                tmp.path(),
+                format!("@read1\n{seq}\n+\n{}\n", "I".repeat(seq.len())),
+            )?;
+        }
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let dir = tempfile::tempdir()?;
+        let prefix = dir.path().join("split");
+        let cmd = crate::cli::GrepCommand::try_parse_from([
+            "grep",
+            bq_tmp.path().to_str().unwrap(),
+            "GATTACA",
+            "-p",
+            prefix.to_str().unwrap(),
+            "-f",
+            "t",
+            "--color-safe",
+        ])?;
+        assert!(!cmd.should_report_coordinates());
+        super::run(&cmd)?;
+
+        let mut outputs = std::fs::read_dir(dir.path())?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        outputs.sort();
+        assert_eq!(outputs.len(), 2, "{outputs:?}");
+        for path in outputs {
+            let content = std::fs::read_to_string(&path)?;
+            let columns: Vec<&str> = content.trim_end().split('\t').collect();
+            assert_eq!(columns.len(), 2, "{path:?}: {content:?}");
+            assert_eq!(columns[1], seq);
+        }
+        Ok(())
+    }
+
     /// `--rc` should reverse complement the pattern before matching: searching
     /// for the reverse complement of a known substring should match exactly
     /// as if the substring itself had been used directly (without --rc).
@@ -4,7 +4,10 @@ use anyhow::Result;
 use fixedbitset::FixedBitSet;
 use sassy::{profiles::Iupac, EncodedPatterns, Match, Searcher};
 
-use crate::commands::utils::build_fuzzy_searcher;
+use crate::{
+    cli::DistanceType,
+    commands::utils::{accepts_alignment, build_fuzzy_searcher},
+};
 
 type Profile = Iupac;
 
@@ -18,6 +21,9 @@ pub struct FuzzyPatternCounter {
     inexact: bool,                          // whether to only report inexact matches
     invert: bool,                           // invert the match
 
+    /// Distance metric used to accept matches
+    distance_type: DistanceType,
+
     /// Fixed bitset for pat1
     bits1: FixedBitSet,
     /// Fixed bitset for pat2
@@ -64,6 +70,7 @@ impl FuzzyPatternCounter {
             k,
             inexact,
             invert,
+            distance_type: DistanceType::default(),
             all_patterns,
             bits1,
             bits2,
@@ -74,13 +81,21 @@ impl FuzzyPatternCounter {
         })
     }
 
+    /// Sets the distance metric used to accept matches (default: Levenshtein)
+    #[must_use]
+    pub fn with_distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = distance_type;
+        self
+    }
+
     fn match_primary(&mut self, sequence: &[u8]) {
         if let Some(ref epat) = self.pat1 {
             self.searcher_1
                 .search_encoded_patterns(epat, sequence, self.k)
                 .iter()
                 .for_each(|m| {
-                    let counted = !self.inexact || m.cost != 0;
+                    let counted =
+                        (!self.inexact || m.cost != 0) && accepts_alignment(m, self.distance_type);
                     if counted {
                         self.bits1.set(m.pattern_idx, true);
                     }
@@ -94,7 +109,8 @@ impl FuzzyPatternCounter {
                 .search_encoded_patterns(epat, sequence, self.k)
                 .iter()
                 .for_each(|m| {
-                    let counted = !self.inexact || m.cost != 0;
+                    let counted =
+                        (!self.inexact || m.cost != 0) && accepts_alignment(m, self.distance_type);
                     if counted {
                         self.bits2.set(m.pattern_idx, true);
                     }
@@ -105,7 +121,8 @@ impl FuzzyPatternCounter {
     fn match_either(&mut self, primary: &[u8], secondary: &[u8]) {
         if let Some(ref epat) = self.pat {
             let mut eval = |m: &Match| {
-                let counted = !self.inexact || m.cost != 0;
+                let counted =
+                    (!self.inexact || m.cost != 0) && accepts_alignment(m, self.distance_type);
                 if counted {
                     self.bits.set(m.pattern_idx, true);
                 }
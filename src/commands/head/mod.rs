@@ -0,0 +1,96 @@
+use anyhow::Result;
+use log::info;
+
+use super::decode::{build_writer, Decoder};
+use super::utils::{open_binseq, process_binseq};
+use crate::cli::HeadCommand;
+
+pub fn run(args: &HeadCommand) -> Result<()> {
+    let reader = open_binseq(args.input.path())?;
+    let writer = build_writer(&args.output, reader.is_paired())?;
+    let format = args.output.format()?;
+    let mate = if reader.is_paired() {
+        Some(args.output.mate())
+    } else {
+        None
+    };
+    let span = args.input.span.unwrap_or_default().first(args.num);
+    let proc = Decoder::new(writer, format, mate).with_defer_flush(args.output.defer_flush);
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        Some(span),
+    );
+    let finished = proc.finish();
+    result?;
+    finished?;
+    info!("Wrote the first {} records...", proc.num_records());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::testutils::{count_fastx_records, write_fastx};
+
+    fn head(bq_path: &std::path::Path, out_path: &std::path::Path, extra: &[&str]) -> Result<()> {
+        let mut args = vec![
+            "head",
+            bq_path.to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+        ];
+        args.extend_from_slice(extra);
+        let cmd = crate::cli::HeadCommand::try_parse_from(args)?;
+        super::run(&cmd)
+    }
+
+    /// Exactly the first N records are written, in file order, clipped to the file.
+    #[test]
+    fn test_head() -> Result<()> {
+        let nrec = 200;
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().nrec(nrec).call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                bq_tmp.path().to_str().unwrap(),
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            head(bq_tmp.path(), out_tmp.path(), &["-n", "25", "-T", "4"])?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, 25, "{mode:?}");
+
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            head(bq_tmp.path(), out_tmp.path(), &["-n", "3", "-T", "1"])?;
+            let sequences = |path: &std::path::Path| -> Result<Vec<String>> {
+                Ok(std::fs::read_to_string(path)?
+                    .lines()
+                    .skip(1)
+                    .step_by(4)
+                    .map(String::from)
+                    .collect())
+            };
+            let expected: Vec<String> = sequences(in_tmp.path())?.into_iter().take(3).collect();
+            let written = sequences(out_tmp.path())?;
+            assert_eq!(written, expected, "{mode:?}");
+
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            head(
+                bq_tmp.path(),
+                out_tmp.path(),
+                &["-n", "500", "--span", "150.."],
+            )?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, 50, "{mode:?}");
+        }
+        Ok(())
+    }
+}
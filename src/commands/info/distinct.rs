@@ -0,0 +1,235 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use binseq::prelude::*;
+use hashbrown::HashSet;
+use parking_lot::Mutex;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::commands::utils::process_binseq;
+
+/// Bits of the hash used to pick a register (2^14 registers, ~0.8% standard error)
+const PRECISION: u32 = 14;
+
+/// Number of `HyperLogLog` registers
+const NUM_REGISTERS: usize = 1 << PRECISION;
+
+/// `HyperLogLog` cardinality sketch over 64-bit hashes
+#[derive(Clone)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; NUM_REGISTERS],
+        }
+    }
+}
+impl HyperLogLog {
+    fn insert(&mut self, hash: u64) {
+        let idx = (hash >> (64 - PRECISION)) as usize;
+        // position of the first set bit among the remaining bits
+        let rank = ((hash << PRECISION).leading_zeros().min(64 - PRECISION) + 1) as u8;
+        if rank > self.registers[idx] {
+            self.registers[idx] = rank;
+        }
+    }
+
+    /// Folds `other` into `self` and clears it
+    fn merge(&mut self, other: &mut Self) {
+        for (total, local) in self.registers.iter_mut().zip(other.registers.iter_mut()) {
+            *total = (*total).max(*local);
+            *local = 0;
+        }
+    }
+
+    /// Cardinality estimate, with linear counting for small cardinalities
+    fn estimate(&self) -> f64 {
+        let m = NUM_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        #[allow(clippy::naive_bytecount)]
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+/// Distinct primary sequences seen so far, approximate or exact
+#[derive(Clone)]
+enum Sketch {
+    Approximate(HyperLogLog),
+    Exact(HashSet<Vec<u8>>),
+}
+impl Sketch {
+    fn new(exact: bool) -> Self {
+        if exact {
+            Self::Exact(HashSet::new())
+        } else {
+            Self::Approximate(HyperLogLog::default())
+        }
+    }
+
+    fn insert(&mut self, seq: &[u8]) {
+        match self {
+            Self::Approximate(hll) => hll.insert(xxh3_64(seq)),
+            Self::Exact(set) => {
+                if !set.contains(seq) {
+                    set.insert(seq.to_vec());
+                }
+            }
+        }
+    }
+
+    /// Folds `other` into `self` and clears it
+    fn merge(&mut self, other: &mut Self) {
+        match (self, other) {
+            (Self::Approximate(total), Self::Approximate(local)) => total.merge(local),
+            (Self::Exact(total), Self::Exact(local)) => total.extend(local.drain()),
+            _ => unreachable!("sketches of one run share a kind"),
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn count(&self) -> usize {
+        match self {
+            Self::Approximate(hll) => hll.estimate().round() as usize,
+            Self::Exact(set) => set.len(),
+        }
+    }
+}
+
+/// Counts records and their distinct primary sequences
+#[derive(Clone)]
+struct DistinctCounter {
+    local: (usize, Sketch),
+    global: Arc<Mutex<(usize, Sketch)>>,
+}
+impl DistinctCounter {
+    fn new(exact: bool) -> Self {
+        Self {
+            local: (0, Sketch::new(exact)),
+            global: Arc::new(Mutex::new((0, Sketch::new(exact)))),
+        }
+    }
+
+    /// `(num_records, distinct)`
+    fn counts(&self) -> (usize, usize) {
+        let global = self.global.lock();
+        (global.0, global.1.count())
+    }
+}
+impl ParallelProcessor for DistinctCounter {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local.0 += 1;
+        self.local.1.insert(record.sseq());
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        let mut global = self.global.lock();
+        global.0 += std::mem::take(&mut self.local.0);
+        global.1.merge(&mut self.local.1);
+        Ok(())
+    }
+}
+
+/// Prints a TSV row of distinct primary sequence counts and complexity for each path
+#[allow(clippy::cast_precision_loss)]
+pub fn print_distinct_counts(paths: &[String], threads: usize, exact: bool) -> Result<()> {
+    println!("path\tnum_records\tdistinct\tcomplexity");
+    for path in paths {
+        let proc = DistinctCounter::new(exact);
+        process_binseq(path, proc.clone(), threads, None)?;
+        let (num_records, distinct) = proc.counts();
+        // an estimate can overshoot the record count on tiny inputs
+        let distinct = distinct.min(num_records);
+        let complexity = if num_records == 0 {
+            0.0
+        } else {
+            distinct as f64 / num_records as f64
+        };
+        println!("{path}\t{num_records}\t{distinct}\t{complexity:.4}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+    use xxhash_rust::xxh3::xxh3_64;
+
+    use super::{DistinctCounter, HyperLogLog};
+    use crate::commands::utils::process_binseq;
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    /// The sketch stays within a few percent and merging matches a single pass.
+    #[test]
+    fn test_hyperloglog_estimate() {
+        let (mut even, mut odd, mut all) = (
+            HyperLogLog::default(),
+            HyperLogLog::default(),
+            HyperLogLog::default(),
+        );
+        for i in 0..100_000u64 {
+            let hash = xxh3_64(&i.to_le_bytes());
+            all.insert(hash);
+            if i % 2 == 0 {
+                even.insert(hash);
+            } else {
+                odd.insert(hash);
+            }
+        }
+        let estimate = all.estimate();
+        assert!(
+            (estimate - 100_000.0).abs() < 3_000.0,
+            "estimate {estimate}"
+        );
+
+        even.merge(&mut odd);
+        assert_eq!(even.registers, all.registers);
+        assert!(odd.registers.iter().all(|&rank| rank == 0));
+    }
+
+    /// Duplicated records collapse in both the exact and approximate counts.
+    #[test]
+    fn test_distinct_counts() -> Result<()> {
+        let in_tmp = write_fastx().include_n(false).call()?;
+        let dup_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let contents = std::fs::read(in_tmp.path())?;
+        std::fs::write(dup_tmp.path(), [contents.as_slice(), &contents].concat())?;
+
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            dup_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        for exact in [true, false] {
+            let proc = DistinctCounter::new(exact);
+            process_binseq(bq_tmp.path().to_str().unwrap(), proc.clone(), 4, None)?;
+            let (num_records, distinct) = proc.counts();
+            assert_eq!(num_records, 2 * DEFAULT_NUM_RECORDS);
+            if exact {
+                assert_eq!(distinct, DEFAULT_NUM_RECORDS);
+            } else {
+                assert!(distinct.abs_diff(DEFAULT_NUM_RECORDS) <= DEFAULT_NUM_RECORDS / 20);
+            }
+        }
+        Ok(())
+    }
+}
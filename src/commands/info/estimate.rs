@@ -0,0 +1,58 @@
+use anyhow::Result;
+use log::info;
+
+use crate::commands::{load_block_spans, open_binseq, utils::BlockSpan};
+
+/// Total record count stored in the block index: the last block's cumulative count
+fn index_records(blocks: &[BlockSpan]) -> u64 {
+    blocks.last().map_or(0, |block| block.cumulative_records)
+}
+
+/// Prints a TSV row of record counts and file sizes for each path
+///
+/// VBQ/CBQ counts come from the per-block record counts in the block index,
+/// so no block is decompressed; BQ counts follow from the file size.
+pub fn print_estimates(paths: &[String]) -> Result<()> {
+    println!("path\tnum_blocks\tnum_records\tbytes");
+    let mut indexed = false;
+    for path in paths {
+        let bytes = std::fs::metadata(path)?.len();
+        let (num_blocks, num_records) = match load_block_spans(path)? {
+            Some(blocks) => {
+                indexed = true;
+                (blocks.len(), index_records(&blocks))
+            }
+            None => (0, open_binseq(path)?.num_records()? as u64),
+        };
+        println!("{path}\t{num_blocks}\t{num_records}\t{bytes}");
+    }
+    if indexed {
+        info!("VBQ/CBQ record counts were read from the block index without decoding");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::index_records;
+    use crate::commands::utils::BlockSpan;
+
+    fn block(records: u64, cumulative_records: u64) -> BlockSpan {
+        BlockSpan {
+            offset: 0,
+            len: 0,
+            records,
+            cumulative_records,
+        }
+    }
+
+    #[test]
+    fn test_index_records() {
+        assert_eq!(index_records(&[]), 0);
+        assert_eq!(index_records(&[block(7, 7)]), 7);
+        assert_eq!(
+            index_records(&[block(100, 100), block(60, 160), block(40, 200)]),
+            200
+        );
+    }
+}
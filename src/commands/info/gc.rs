@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use binseq::prelude::*;
+use parking_lot::Mutex;
+
+use crate::commands::utils::process_binseq;
+
+/// Running per-record GC statistics with an optional equal-width histogram
+#[derive(Clone, Default)]
+struct GcStats {
+    num_records: usize,
+    sum: f64,
+    sum_sq: f64,
+    bins: Vec<usize>,
+}
+impl GcStats {
+    fn new(num_bins: usize) -> Self {
+        Self {
+            bins: vec![0; num_bins],
+            ..Self::default()
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    fn push(&mut self, gc: f64) {
+        self.num_records += 1;
+        self.sum += gc;
+        self.sum_sq += gc * gc;
+        if !self.bins.is_empty() {
+            let num_bins = self.bins.len();
+            let bin = ((gc * num_bins as f64) as usize).min(num_bins - 1);
+            self.bins[bin] += 1;
+        }
+    }
+
+    fn ingest(&mut self, other: &mut Self) {
+        self.num_records += other.num_records;
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        for (total, local) in self.bins.iter_mut().zip(other.bins.iter_mut()) {
+            *total += *local;
+            *local = 0;
+        }
+        other.num_records = 0;
+        other.sum = 0.0;
+        other.sum_sq = 0.0;
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn mean(&self) -> f64 {
+        if self.num_records == 0 {
+            0.0
+        } else {
+            self.sum / self.num_records as f64
+        }
+    }
+
+    /// Population standard deviation
+    #[allow(clippy::cast_precision_loss)]
+    fn stdev(&self) -> f64 {
+        if self.num_records == 0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        (self.sum_sq / self.num_records as f64 - mean * mean)
+            .max(0.0)
+            .sqrt()
+    }
+}
+
+/// Accumulates the GC fraction of each record (both mates combined)
+#[derive(Clone)]
+struct GcCounter {
+    local: GcStats,
+    global: Arc<Mutex<GcStats>>,
+}
+impl GcCounter {
+    fn new(num_bins: usize) -> Self {
+        Self {
+            local: GcStats::new(num_bins),
+            global: Arc::new(Mutex::new(GcStats::new(num_bins))),
+        }
+    }
+
+    fn stats(&self) -> GcStats {
+        self.global.lock().clone()
+    }
+}
+impl ParallelProcessor for GcCounter {
+    #[allow(clippy::cast_precision_loss)]
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        let is_gc = |base: &&u8| matches!(**base, b'G' | b'g' | b'C' | b'c');
+        let (sseq, xseq) = (record.sseq(), record.xseq());
+        let len = sseq.len() + xseq.len();
+        if len > 0 {
+            let gc = sseq.iter().filter(is_gc).count() + xseq.iter().filter(is_gc).count();
+            self.local.push(gc as f64 / len as f64);
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        self.global.lock().ingest(&mut self.local);
+        Ok(())
+    }
+}
+
+/// Prints a TSV row of GC mean and standard deviation for each path, then the
+/// per-path histograms if `num_bins` is set
+#[allow(clippy::cast_precision_loss)]
+pub fn print_gc_summary(paths: &[String], threads: usize, num_bins: Option<usize>) -> Result<()> {
+    let mut all_stats = Vec::with_capacity(paths.len());
+    println!("path\tnum_records\tmean_gc\tstdev_gc");
+    for path in paths {
+        let proc = GcCounter::new(num_bins.unwrap_or(0));
+        process_binseq(path, proc.clone(), threads, None)?;
+        let stats = proc.stats();
+        println!(
+            "{path}\t{}\t{:.4}\t{:.4}",
+            stats.num_records,
+            stats.mean(),
+            stats.stdev()
+        );
+        all_stats.push(stats);
+    }
+    if let Some(num_bins) = num_bins {
+        println!();
+        println!("path\tgc_from\tgc_to\tcount");
+        for (path, stats) in paths.iter().zip(&all_stats) {
+            for (i, count) in stats.bins.iter().enumerate() {
+                let from = i as f64 / num_bins as f64;
+                let to = (i + 1) as f64 / num_bins as f64;
+                println!("{path}\t{from:.4}\t{to:.4}\t{count}");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::float_cmp)]
+mod tests {
+    use super::GcStats;
+
+    /// Fractions are binned half-open, with a GC of 1 in the last bin.
+    #[test]
+    fn test_gc_stats() {
+        let mut stats = GcStats::new(4);
+        for gc in [0.0, 0.25, 0.5, 1.0] {
+            stats.push(gc);
+        }
+        assert_eq!(stats.bins, [1, 1, 1, 1]);
+        assert_eq!(stats.mean(), 0.4375);
+        assert!((stats.stdev() - 0.369_75).abs() < 1e-4);
+
+        let mut total = GcStats::new(4);
+        total.ingest(&mut stats);
+        assert_eq!(total.num_records, 4);
+        assert_eq!(stats.num_records, 0);
+        assert_eq!(stats.bins, [0, 0, 0, 0]);
+    }
+}
@@ -1,22 +1,38 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use binseq::{
     bq, cbq,
     vbq::{self, BlockIndex},
     BinseqReader,
 };
-use log::warn;
+use log::{info, warn};
 use serde::Serialize;
 use thousands::Separable;
 
-use crate::cli::InfoCommand;
+use crate::{
+    cli::{count_records, InfoCommand},
+    commands::{load_block_spans, open_binseq},
+};
+
+mod distinct;
+mod estimate;
+mod gc;
+mod ns;
+mod query;
+mod weighted;
+
+pub use query::RecordQuery;
 
 #[derive(Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 struct BqInfo {
     path: String,
     format: &'static str,
     version: u8,
     bitsize: u8,
     paired: bool,
+    /// BQ stores neither qualities nor headers; kept so every format shares these keys
+    quality: bool,
+    headers: bool,
     flags: bool,
     sequence_length: u32,
     extended_length: Option<u32>,
@@ -32,6 +48,8 @@ impl BqInfo {
             version: header.format,
             bitsize,
             paired: header.xlen > 0,
+            quality: false,
+            headers: false,
             flags: header.flags,
             sequence_length: header.slen,
             extended_length: if header.xlen > 0 {
@@ -77,7 +95,9 @@ struct VbqInfo {
     headers: bool,
     flags: bool,
     block_size: u64,
-    n_blocks: usize,
+    /// Serialized as `n_blocks` to keep existing VBQ JSON consumers working
+    #[serde(rename = "n_blocks")]
+    num_blocks: usize,
     num_records: usize,
     #[serde(skip)]
     block_index: BlockIndex,
@@ -98,7 +118,7 @@ impl VbqInfo {
             headers: header.headers,
             flags: header.flags,
             block_size: header.block,
-            n_blocks: index.n_blocks(),
+            num_blocks: index.n_blocks(),
             num_records,
             block_index: index,
         })
@@ -129,7 +149,7 @@ impl VbqInfo {
         println!("-------------------------------");
         println!("            Data               ");
         println!("-------------------------------");
-        println!("Number of blocks    : {}", self.n_blocks);
+        println!("Number of blocks    : {}", self.num_blocks);
         println!(
             "Number of records   : {}",
             self.num_records.separate_with_underscores()
@@ -239,7 +259,7 @@ enum BinseqInfo {
 }
 impl BinseqInfo {
     pub fn from_path(path: &str) -> Result<Self> {
-        let reader = BinseqReader::new(path)?;
+        let reader = open_binseq(path)?;
         let num_records = reader.num_records()?;
         match reader {
             BinseqReader::Bq(bq_reader) => Ok(BinseqInfo::Bq(BqInfo::new(
@@ -286,6 +306,62 @@ impl BinseqInfo {
     }
 }
 
+/// Summary of a distribution of per-block values
+#[derive(Debug, PartialEq)]
+struct Distribution {
+    min: u64,
+    median: u64,
+    mean: f64,
+    p95: u64,
+    max: u64,
+}
+impl Distribution {
+    /// Summarizes `values`, or `None` if empty. Quantiles use the nearest rank.
+    #[allow(clippy::cast_sign_loss)]
+    fn new(mut values: Vec<u64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_unstable();
+        let rank =
+            |q: f64| values[((q * values.len() as f64).ceil() as usize).clamp(1, values.len()) - 1];
+        Some(Self {
+            min: values[0],
+            median: rank(0.5),
+            mean: values.iter().sum::<u64>() as f64 / values.len() as f64,
+            p95: rank(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+
+    fn row(&self, path: &str, metric: &str) -> String {
+        format!(
+            "{path}\t{metric}\t{}\t{}\t{:.1}\t{}\t{}",
+            self.min, self.median, self.mean, self.p95, self.max
+        )
+    }
+}
+
+/// Prints the block size and records-per-block distributions of each VBQ/CBQ path
+fn print_block_stats(paths: &[String]) -> Result<()> {
+    println!("path\tmetric\tmin\tmedian\tmean\tp95\tmax");
+    for path in paths {
+        let Some(blocks) = load_block_spans(path)? else {
+            warn!("No block index for BQ path: {path}");
+            continue;
+        };
+        let sizes = Distribution::new(blocks.iter().map(|block| block.len).collect());
+        let records = Distribution::new(blocks.iter().map(|block| block.records).collect());
+        if let (Some(sizes), Some(records)) = (sizes, records) {
+            println!("{}", sizes.row(path, "block_bytes"));
+            println!("{}", records.row(path, "block_records"));
+        } else {
+            warn!("No blocks in path: {path}");
+        }
+    }
+    Ok(())
+}
+
 fn pprint_block_size<T>(block_size: T) -> String
 where
     T: Into<f64> + Copy,
@@ -306,6 +382,33 @@ where
     }
 }
 
+/// Number of records (pairs for paired BINSEQ) in a BINSEQ or FASTX file
+fn count_any(path: &str) -> Result<usize> {
+    match open_binseq(path) {
+        Ok(reader) => Ok(reader.num_records()?),
+        Err(_) => count_records(path, None),
+    }
+}
+
+/// Prints the record count of every path and errors unless they all match
+fn assert_equal_counts(paths: &[String]) -> Result<()> {
+    if paths.len() < 2 {
+        bail!("`--assert-equal` needs at least two inputs");
+    }
+    let counts = paths
+        .iter()
+        .map(|path| count_any(path))
+        .collect::<Result<Vec<_>>>()?;
+    for (path, count) in paths.iter().zip(&counts) {
+        println!("{count}\t{path}");
+    }
+    if counts.windows(2).any(|pair| pair[0] != pair[1]) {
+        bail!("Record counts differ across {} inputs", paths.len());
+    }
+    info!("All {} inputs hold {} records", paths.len(), counts[0]);
+    Ok(())
+}
+
 pub fn run(args: &InfoCommand) -> Result<()> {
     // case for just CBQ with block headers
     if args.opts.show_headers {
@@ -324,6 +427,43 @@ pub fn run(args: &InfoCommand) -> Result<()> {
         return Ok(());
     }
 
+    if args.opts.block_stats {
+        return print_block_stats(&args.input);
+    }
+
+    if args.opts.assert_equal {
+        return assert_equal_counts(&args.input);
+    }
+
+    if args.opts.estimate {
+        return estimate::print_estimates(&args.input);
+    }
+
+    let threads = match args.opts.threads {
+        0 => num_cpus::get(),
+        n => n,
+    };
+
+    if args.opts.ns {
+        return ns::print_n_counts(&args.input, threads);
+    }
+
+    if args.opts.weighted {
+        return weighted::print_weighted_counts(&args.input, threads);
+    }
+
+    if args.opts.gc_summary {
+        return gc::print_gc_summary(&args.input, threads, args.opts.gc_bins);
+    }
+
+    if args.opts.distinct {
+        return distinct::print_distinct_counts(&args.input, threads, args.opts.exact);
+    }
+
+    if let Some(query) = &args.opts.where_expr {
+        return query::print_query_counts(&args.input, threads, query);
+    }
+
     // all other cases
     let all_info: Vec<BinseqInfo> = args
         .input
@@ -417,7 +557,15 @@ mod tests {
             let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
             encode(in_tmp.path(), bq_tmp.path())?;
 
-            for flags in [&[][..], &["--num"], &["--json"]] {
+            for flags in [
+                &[][..],
+                &["--num"],
+                &["--json"],
+                &["--block-stats"],
+                &["--estimate"],
+                &["--distinct"],
+                &["--where", "len > 10 && gc < 0.5"],
+            ] {
                 let mut args = vec!["info"];
                 args.extend_from_slice(flags);
                 args.push(bq_tmp.path().to_str().unwrap());
@@ -429,4 +577,92 @@ mod tests {
         }
         Ok(())
     }
+
+    /// `--json` output shares its core keys across BQ, VBQ, and CBQ.
+    #[test]
+    fn test_info_json_common_keys() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+
+            let info = super::BinseqInfo::from_path(bq_tmp.path().to_str().unwrap())?;
+            let value = serde_json::to_value(&info)?;
+            for key in [
+                "path",
+                "format",
+                "version",
+                "paired",
+                "quality",
+                "headers",
+                "num_records",
+            ] {
+                assert!(value.get(key).is_some(), "{mode:?} JSON is missing `{key}`");
+            }
+            assert_eq!(value["num_records"], DEFAULT_NUM_RECORDS);
+            match mode {
+                BinseqMode::Bq => {}
+                BinseqMode::Vbq => assert!(value.get("n_blocks").is_some()),
+                BinseqMode::Cbq => assert!(value.get("num_blocks").is_some()),
+            }
+            if !matches!(mode, BinseqMode::Bq) {
+                assert!(value.get("block_size").is_some());
+            }
+        }
+        Ok(())
+    }
+
+    /// Info modes are mutually exclusive.
+    #[test]
+    fn test_info_modes_conflict() {
+        for flags in [
+            &["--num", "--json"][..],
+            &["--ns", "--distinct"],
+            &["--estimate", "--where", "len > 10"],
+            &["--weighted", "--gc-summary"],
+        ] {
+            let mut args = vec!["info"];
+            args.extend_from_slice(flags);
+            args.push("input.vbq");
+            assert!(
+                crate::cli::InfoCommand::try_parse_from(args).is_err(),
+                "{flags:?} should conflict"
+            );
+        }
+    }
+
+    /// `--assert-equal` compares BINSEQ and FASTX record counts.
+    #[test]
+    fn test_info_assert_equal() -> Result<()> {
+        let in_tmp = write_fastx().comp(Compression::Gzip).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+        let other = write_fastx().nrec(DEFAULT_NUM_RECORDS + 1).call()?;
+
+        let assert_equal = |paths: &[&std::path::Path]| {
+            let mut args = vec!["info", "--assert-equal"];
+            args.extend(paths.iter().map(|p| p.to_str().unwrap()));
+            super::run(&crate::cli::InfoCommand::try_parse_from(args)?)
+        };
+        assert_equal(&[bq_tmp.path(), in_tmp.path()])?;
+        assert!(assert_equal(&[bq_tmp.path(), other.path()]).is_err());
+        assert!(assert_equal(&[bq_tmp.path()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_size_distribution() {
+        use super::Distribution;
+        assert_eq!(Distribution::new(Vec::new()), None);
+        let dist = Distribution::new((1..=100).rev().collect()).unwrap();
+        assert_eq!(dist.min, 1);
+        assert_eq!(dist.median, 50);
+        assert_eq!(dist.p95, 95);
+        assert_eq!(dist.max, 100);
+        assert!((dist.mean - 50.5).abs() < f64::EPSILON);
+        assert_eq!(
+            dist.row("x.vbq", "block_bytes"),
+            "x.vbq\tblock_bytes\t1\t50\t50.5\t95\t100"
+        );
+    }
 }
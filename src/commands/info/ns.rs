@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use binseq::{prelude::*, BinseqReader, BitSize};
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::commands::utils::{open_binseq, process_binseq};
+
+/// Bases packed into a single 4-bit word
+const BASES_PER_WORD: usize = 16;
+
+/// Low bit of every nibble in a word
+const NIBBLE_LSB: u64 = 0x1111_1111_1111_1111;
+
+/// Counts the `N` nibbles among the first `len` bases of a 4-bit packed sequence.
+///
+/// `N` is stored as `0b1111` (every base bit set, as for IUPAC "any"). Bases are packed first-base-first from the low bits, so padding in the last
+/// word is masked off.
+fn count_packed_ns(words: &[u64], len: usize) -> usize {
+    let mut count = 0;
+    for (idx, word) in words.iter().enumerate() {
+        // low bit of each nibble whose four bits are all set
+        let all_set = word & (word >> 1) & (word >> 2) & (word >> 3) & NIBBLE_LSB;
+        let remaining = len.saturating_sub(idx * BASES_PER_WORD);
+        let mask = if remaining >= BASES_PER_WORD {
+            u64::MAX
+        } else {
+            (1u64 << (remaining * 4)) - 1
+        };
+        count += (all_set & mask).count_ones() as usize;
+    }
+    count
+}
+
+/// How `N`s are stored in a file, if at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NSource {
+    /// 4-bit BQ/VBQ: scan the packed words
+    Packed,
+    /// CBQ: scan the decoded sequence
+    Decoded,
+}
+impl NSource {
+    /// Returns `None` for 2-bit files, where Ns were replaced at encoding
+    fn of(reader: &BinseqReader) -> Option<Self> {
+        let bits = match reader {
+            BinseqReader::Bq(reader) => reader.header().bits,
+            BinseqReader::Vbq(reader) => reader.header().bits,
+            BinseqReader::Cbq(_) => return Some(Self::Decoded),
+        };
+        (bits == BitSize::Four).then_some(Self::Packed)
+    }
+}
+
+/// Per-file N counts: `(num_records, records_with_n, num_n)`
+#[derive(Clone)]
+struct NCounter {
+    source: NSource,
+    local: (usize, usize, usize),
+    global: Arc<Mutex<(usize, usize, usize)>>,
+}
+impl NCounter {
+    fn new(source: NSource) -> Self {
+        Self {
+            source,
+            local: (0, 0, 0),
+            global: Arc::new(Mutex::new((0, 0, 0))),
+        }
+    }
+
+    fn count<B: BinseqRecord>(&self, record: &B) -> usize {
+        match self.source {
+            NSource::Packed => {
+                count_packed_ns(record.sbuf(), record.slen() as usize)
+                    + count_packed_ns(record.xbuf(), record.xlen() as usize)
+            }
+            NSource::Decoded => {
+                #[allow(clippy::naive_bytecount)]
+                let ns = |seq: &[u8]| seq.iter().filter(|&&b| b == b'N').count();
+                ns(record.sseq())
+                    + if record.is_paired() {
+                        ns(record.xseq())
+                    } else {
+                        0
+                    }
+            }
+        }
+    }
+
+    fn counts(&self) -> (usize, usize, usize) {
+        *self.global.lock()
+    }
+}
+impl ParallelProcessor for NCounter {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        let num_n = self.count(&record);
+        self.local.0 += 1;
+        self.local.1 += usize::from(num_n > 0);
+        self.local.2 += num_n;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        let mut global = self.global.lock();
+        global.0 += self.local.0;
+        global.1 += self.local.1;
+        global.2 += self.local.2;
+        self.local = (0, 0, 0);
+        Ok(())
+    }
+}
+
+/// Prints a TSV row of N counts for each path (`NA` for 2-bit files)
+pub fn print_n_counts(paths: &[String], threads: usize) -> Result<()> {
+    println!("path\tnum_records\trecords_with_n\tnum_n");
+    for path in paths {
+        let reader = open_binseq(path)?;
+        let Some(source) = NSource::of(&reader) else {
+            warn!("N not representable in 2-bit file: {path}");
+            println!("{path}\t{}\tNA\tNA", reader.num_records()?);
+            continue;
+        };
+        let proc = NCounter::new(source);
+        process_binseq(path, proc.clone(), threads, None)?;
+        let (num_records, records_with_n, num_n) = proc.counts();
+        println!("{path}\t{num_records}\t{records_with_n}\t{num_n}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use binseq::BinseqReader;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use super::{count_packed_ns, NCounter, NSource};
+    use crate::commands::utils::process_binseq;
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    #[test]
+    fn test_count_packed_ns() {
+        // bases 0 and 2 are N, base 1 is A (0b0001)
+        let word = 0xF | (0b0001 << 4) | (0xF << 8);
+        assert_eq!(count_packed_ns(&[word], 3), 2);
+        // padding past the sequence end is ignored
+        assert_eq!(count_packed_ns(&[u64::MAX], 5), 5);
+        assert_eq!(count_packed_ns(&[u64::MAX, u64::MAX], 20), 20);
+        assert_eq!(count_packed_ns(&[], 0), 0);
+    }
+
+    /// Packed 4-bit counting agrees with scanning the decoded sequences.
+    #[test]
+    fn test_packed_matches_decoded() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+            "-S",
+            "4",
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let path = bq_tmp.path().to_str().unwrap();
+        assert_eq!(
+            NSource::of(&BinseqReader::new(path)?),
+            Some(NSource::Packed)
+        );
+        let mut counts = Vec::new();
+        for source in [NSource::Packed, NSource::Decoded] {
+            let proc = NCounter::new(source);
+            process_binseq(path, proc.clone(), 2, None)?;
+            counts.push(proc.counts());
+        }
+        assert_eq!(counts[0], counts[1]);
+        assert_eq!(counts[0].0, DEFAULT_NUM_RECORDS);
+        assert!(counts[0].2 > 0);
+        Ok(())
+    }
+}
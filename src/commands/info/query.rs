@@ -0,0 +1,388 @@
+use std::{str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use binseq::prelude::*;
+use parking_lot::Mutex;
+
+use crate::commands::utils::process_binseq;
+
+/// Values derived from a record that a [`RecordQuery`] can compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryField {
+    /// Bases over both mates
+    Len,
+    /// Fraction of G/C bases over both mates (0 without bases)
+    Gc,
+    /// Record flag (0 when unflagged)
+    Flag,
+    /// Mean Phred+33 quality over both mates (0 without qualities)
+    MeanQual,
+}
+impl QueryField {
+    fn parse(word: &str) -> Result<Self, String> {
+        match word {
+            "len" => Ok(Self::Len),
+            "gc" => Ok(Self::Gc),
+            "flag" => Ok(Self::Flag),
+            "mean_qual" => Ok(Self::MeanQual),
+            _ => Err(format!(
+                "Unknown field in query: {word} (expected len, gc, flag, or mean_qual)"
+            )),
+        }
+    }
+}
+
+/// Comparison operators of a [`RecordQuery`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryCmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+impl QueryCmp {
+    fn apply(self, lhs: f64, rhs: f64) -> bool {
+        match self {
+            Self::Lt => lhs < rhs,
+            Self::Le => lhs <= rhs,
+            Self::Gt => lhs > rhs,
+            Self::Ge => lhs >= rhs,
+            Self::Eq => (lhs - rhs).abs() < f64::EPSILON,
+            Self::Ne => (lhs - rhs).abs() >= f64::EPSILON,
+        }
+    }
+
+    /// The same comparison with its operands swapped (`1 < len` is `len > 1`)
+    fn flip(self) -> Self {
+        match self {
+            Self::Lt => Self::Gt,
+            Self::Le => Self::Ge,
+            Self::Gt => Self::Lt,
+            Self::Ge => Self::Le,
+            Self::Eq | Self::Ne => self,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum QueryToken {
+    Field(QueryField),
+    Num(f64),
+    Cmp(QueryCmp),
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn tokenize_query(input: &str) -> Result<Vec<QueryToken>, String> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let two = bytes.get(i..i + 2).unwrap_or_default();
+        let (token, len) = match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'(' => (QueryToken::Open, 1),
+            b')' => (QueryToken::Close, 1),
+            _ if two == b"||" => (QueryToken::Or, 2),
+            _ if two == b"&&" => (QueryToken::And, 2),
+            _ if two == b"==" => (QueryToken::Cmp(QueryCmp::Eq), 2),
+            _ if two == b"!=" => (QueryToken::Cmp(QueryCmp::Ne), 2),
+            _ if two == b"<=" => (QueryToken::Cmp(QueryCmp::Le), 2),
+            _ if two == b">=" => (QueryToken::Cmp(QueryCmp::Ge), 2),
+            b'<' => (QueryToken::Cmp(QueryCmp::Lt), 1),
+            b'>' => (QueryToken::Cmp(QueryCmp::Gt), 1),
+            b if b.is_ascii_alphabetic() => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'_')
+                    .count();
+                (
+                    QueryToken::Field(QueryField::parse(&input[i..i + len])?),
+                    len,
+                )
+            }
+            b if b.is_ascii_digit() || b == b'.' => {
+                let len = bytes[i..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_digit() || **b == b'.')
+                    .count();
+                let word = &input[i..i + len];
+                let value = word
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid number in query: {word}"))?;
+                (QueryToken::Num(value), len)
+            }
+            _ => return Err(format!("Unexpected character in query: {input}")),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+/// The fields of one record, computed once per record
+#[derive(Debug, Clone, Copy, Default)]
+struct RecordFields {
+    len: f64,
+    gc: f64,
+    flag: f64,
+    mean_qual: f64,
+}
+impl RecordFields {
+    fn of<B: BinseqRecord>(record: &B) -> Self {
+        let is_gc = |base: &&u8| matches!(**base, b'G' | b'g' | b'C' | b'c');
+        let (sseq, xseq) = (record.sseq(), record.xseq());
+        let len = sseq.len() + xseq.len();
+        let gc = sseq.iter().filter(is_gc).count() + xseq.iter().filter(is_gc).count();
+        let (squal, xqual) = (record.squal(), record.xqual());
+        let num_qual = squal.len() + xqual.len();
+        let qual_sum: u64 = squal
+            .iter()
+            .chain(xqual)
+            .map(|&q| u64::from(q.saturating_sub(33)))
+            .sum();
+        Self {
+            len: len as f64,
+            gc: if len == 0 {
+                0.0
+            } else {
+                gc as f64 / len as f64
+            },
+            flag: record.flag().unwrap_or(0) as f64,
+            mean_qual: if num_qual == 0 {
+                0.0
+            } else {
+                qual_sum as f64 / num_qual as f64
+            },
+        }
+    }
+
+    fn get(&self, field: QueryField) -> f64 {
+        match field {
+            QueryField::Len => self.len,
+            QueryField::Gc => self.gc,
+            QueryField::Flag => self.flag,
+            QueryField::MeanQual => self.mean_qual,
+        }
+    }
+}
+
+/// A predicate over derived record fields, e.g. `len > 100 && gc > 0.5`.
+///
+/// Each comparison pairs a field (`len`, `gc`, `flag`, `mean_qual`) with a
+/// number using `<`, `<=`, `>`, `>=`, `==`, or `!=`, in either order.
+/// Comparisons combine with `&&` and `||` (`&&` binds tighter) and may be
+/// grouped with parentheses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordQuery {
+    Cmp(QueryField, QueryCmp, f64),
+    And(Box<RecordQuery>, Box<RecordQuery>),
+    Or(Box<RecordQuery>, Box<RecordQuery>),
+}
+impl RecordQuery {
+    pub fn accepts<B: BinseqRecord>(&self, record: &B) -> bool {
+        self.eval(&RecordFields::of(record))
+    }
+
+    fn eval(&self, fields: &RecordFields) -> bool {
+        match self {
+            Self::Cmp(field, cmp, value) => cmp.apply(fields.get(*field), *value),
+            Self::And(lhs, rhs) => lhs.eval(fields) && rhs.eval(fields),
+            Self::Or(lhs, rhs) => lhs.eval(fields) || rhs.eval(fields),
+        }
+    }
+
+    fn parse_or(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<QueryToken>>,
+    ) -> Result<Self, String> {
+        let mut lhs = Self::parse_and(tokens)?;
+        while tokens.next_if_eq(&QueryToken::Or).is_some() {
+            lhs = Self::Or(Box::new(lhs), Box::new(Self::parse_and(tokens)?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<QueryToken>>,
+    ) -> Result<Self, String> {
+        let mut lhs = Self::parse_atom(tokens)?;
+        while tokens.next_if_eq(&QueryToken::And).is_some() {
+            lhs = Self::And(Box::new(lhs), Box::new(Self::parse_atom(tokens)?));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_atom(
+        tokens: &mut std::iter::Peekable<std::vec::IntoIter<QueryToken>>,
+    ) -> Result<Self, String> {
+        if tokens.next_if_eq(&QueryToken::Open).is_some() {
+            let inner = Self::parse_or(tokens)?;
+            return match tokens.next() {
+                Some(QueryToken::Close) => Ok(inner),
+                _ => Err("Unbalanced parentheses in query".to_string()),
+            };
+        }
+        match (tokens.next(), tokens.next(), tokens.next()) {
+            (
+                Some(QueryToken::Field(field)),
+                Some(QueryToken::Cmp(cmp)),
+                Some(QueryToken::Num(value)),
+            ) => Ok(Self::Cmp(field, cmp, value)),
+            (
+                Some(QueryToken::Num(value)),
+                Some(QueryToken::Cmp(cmp)),
+                Some(QueryToken::Field(field)),
+            ) => Ok(Self::Cmp(field, cmp.flip(), value)),
+            (None, _, _) => Err("Incomplete query".to_string()),
+            _ => Err("Expected a comparison like `len > 100` in query".to_string()),
+        }
+    }
+}
+impl FromStr for RecordQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = tokenize_query(s)?.into_iter().peekable();
+        let query = Self::parse_or(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(query),
+            Some(token) => Err(format!("Unexpected {token:?} in query: {s}")),
+        }
+    }
+}
+
+/// Counts records and the records matching a query: `(num_records, matching)`
+#[derive(Clone)]
+struct QueryCounter {
+    query: RecordQuery,
+    local: (usize, usize),
+    global: Arc<Mutex<(usize, usize)>>,
+}
+impl QueryCounter {
+    fn new(query: RecordQuery) -> Self {
+        Self {
+            query,
+            local: (0, 0),
+            global: Arc::new(Mutex::new((0, 0))),
+        }
+    }
+
+    fn counts(&self) -> (usize, usize) {
+        *self.global.lock()
+    }
+}
+impl ParallelProcessor for QueryCounter {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local.0 += 1;
+        if self.query.accepts(&record) {
+            self.local.1 += 1;
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        let mut global = self.global.lock();
+        global.0 += self.local.0;
+        global.1 += self.local.1;
+        self.local = (0, 0);
+        Ok(())
+    }
+}
+
+/// Prints a TSV row of total and query-matching record counts for each path
+pub fn print_query_counts(paths: &[String], threads: usize, query: &RecordQuery) -> Result<()> {
+    println!("path\tnum_records\tmatching");
+    for path in paths {
+        let proc = QueryCounter::new(query.clone());
+        process_binseq(path, proc.clone(), threads, None)?;
+        let (num_records, matching) = proc.counts();
+        println!("{path}\t{num_records}\t{matching}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use super::{QueryCounter, RecordFields, RecordQuery};
+    use crate::commands::utils::process_binseq;
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    fn fields(len: f64, gc: f64, flag: f64, mean_qual: f64) -> RecordFields {
+        RecordFields {
+            len,
+            gc,
+            flag,
+            mean_qual,
+        }
+    }
+
+    #[test]
+    fn test_record_query() {
+        let query: RecordQuery = "len > 100 && gc > 0.5".parse().unwrap();
+        assert!(query.eval(&fields(150.0, 0.6, 0.0, 30.0)));
+        assert!(!query.eval(&fields(150.0, 0.4, 0.0, 30.0)));
+        assert!(!query.eval(&fields(100.0, 0.6, 0.0, 30.0)));
+
+        // `&&` binds tighter than `||`, and numbers may lead
+        let query: RecordQuery = "flag == 1 || 20 > mean_qual && len <= 50".parse().unwrap();
+        assert!(query.eval(&fields(500.0, 0.0, 1.0, 40.0)));
+        assert!(query.eval(&fields(50.0, 0.0, 0.0, 10.0)));
+        assert!(!query.eval(&fields(51.0, 0.0, 0.0, 10.0)));
+
+        let query: RecordQuery = "(flag != 0 || gc < 0.2) && len >= 10".parse().unwrap();
+        assert!(query.eval(&fields(10.0, 0.1, 0.0, 0.0)));
+        assert!(!query.eval(&fields(9.0, 0.1, 1.0, 0.0)));
+
+        for bad in [
+            "",
+            "len >",
+            "len > 1 &&",
+            "(len > 1",
+            "len > 1)",
+            "len = 1",
+            "seq > 1",
+            "len > gc",
+            "len > 1.2.3",
+        ] {
+            assert!(bad.parse::<RecordQuery>().is_err(), "{bad}");
+        }
+    }
+
+    /// Every record matches a tautology and none an impossible length.
+    #[test]
+    fn test_query_counts() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        for (query, expected) in [
+            (
+                "len == 100 && gc >= 0 && mean_qual >= 0",
+                DEFAULT_NUM_RECORDS,
+            ),
+            ("len > 100 || flag != 0", 0),
+        ] {
+            let proc = QueryCounter::new(query.parse().unwrap());
+            process_binseq(bq_tmp.path().to_str().unwrap(), proc.clone(), 2, None)?;
+            assert_eq!(proc.counts(), (DEFAULT_NUM_RECORDS, expected), "{query}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use binseq::prelude::*;
+use parking_lot::Mutex;
+
+use crate::commands::utils::process_binseq;
+
+/// Sums record flags as multiplicities: `(num_records, weighted_count)`
+#[derive(Clone, Default)]
+struct WeightCounter {
+    local: (usize, u64),
+    global: Arc<Mutex<(usize, u64)>>,
+}
+impl WeightCounter {
+    /// Unflagged records stand for themselves only
+    fn weight<B: BinseqRecord>(record: &B) -> u64 {
+        record.flag().unwrap_or(1)
+    }
+
+    fn counts(&self) -> (usize, u64) {
+        *self.global.lock()
+    }
+}
+impl ParallelProcessor for WeightCounter {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local.0 += 1;
+        self.local.1 += Self::weight(&record);
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        let mut global = self.global.lock();
+        global.0 += self.local.0;
+        global.1 += self.local.1;
+        self.local = (0, 0);
+        Ok(())
+    }
+}
+
+/// Prints a TSV row of record and flag-weighted counts for each path
+pub fn print_weighted_counts(paths: &[String], threads: usize) -> Result<()> {
+    println!("path\tnum_records\tweighted_count");
+    for path in paths {
+        let proc = WeightCounter::default();
+        process_binseq(path, proc.clone(), threads, None)?;
+        let (num_records, weighted_count) = proc.counts();
+        println!("{path}\t{num_records}\t{weighted_count}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use super::WeightCounter;
+    use crate::cli::BinseqMode;
+    use crate::commands::utils::process_binseq;
+    use crate::testutils::{write_fastx, write_flagged_binseq, DEFAULT_NUM_RECORDS};
+
+    /// Without flags every record has a weight of one.
+    #[test]
+    fn test_unflagged_weights() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let proc = WeightCounter::default();
+        process_binseq(bq_tmp.path().to_str().unwrap(), proc.clone(), 2, None)?;
+        assert_eq!(
+            proc.counts(),
+            (DEFAULT_NUM_RECORDS, DEFAULT_NUM_RECORDS as u64)
+        );
+        Ok(())
+    }
+
+    /// Flags 1..=n weigh records by their flag, summing to n(n+1)/2.
+    #[test]
+    fn test_flagged_weights() -> Result<()> {
+        let n = DEFAULT_NUM_RECORDS;
+        for mode in BinseqMode::enum_iter() {
+            let bq_tmp = write_flagged_binseq(mode, n)?;
+            let proc = WeightCounter::default();
+            process_binseq(bq_tmp.path().to_str().unwrap(), proc.clone(), 2, None)?;
+            assert_eq!(
+                proc.counts(),
+                (n, (n * (n + 1) / 2) as u64),
+                "weighted count wrong for {mode:?}"
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+pub mod overlap;
+mod processor;
+
+use anyhow::{bail, Result};
+use log::info;
+
+use crate::{
+    cli::{BinseqMode, MergePairsCommand},
+    commands::{open_binseq, revcomp::get_builder, utils::process_binseq},
+};
+use processor::MergeProcessor;
+
+pub fn run(args: &MergePairsCommand) -> Result<()> {
+    args.merge.validate()?;
+    let reader = open_binseq(args.input.path())?;
+    if !reader.is_paired() {
+        bail!("Cannot merge pairs: input BINSEQ file is not paired");
+    }
+    if matches!(args.input.mode()?, BinseqMode::Bq) {
+        bail!("Merged reads have variable lengths and cannot be written as BQ. Re-encode the input as VBQ or CBQ first.");
+    }
+
+    // mirror the input configuration, but write single-end records
+    let builder = get_builder(&args.input)?.paired(false);
+    let ohandle = args.output.as_writer()?;
+    let writer = builder.build(ohandle)?;
+    let mut processor =
+        MergeProcessor::new(writer, args.merge.min_overlap, args.merge.max_mismatch_rate)?;
+
+    process_binseq(
+        args.input.path(),
+        processor.clone(),
+        args.output.threads(),
+        args.input.span,
+    )?;
+    processor.finish()?;
+
+    let merged = processor.get_merged_count();
+    let total = processor.get_total_count();
+    info!(
+        "Merged {merged} of {total} read pairs ({} without an acceptable overlap)",
+        total - merged
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write as _;
+
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::commands::revcomp::reverse_complement;
+    use crate::testutils::count_binseq;
+
+    const FRAGMENT: &str =
+        "CCGTAATGCCTTTCCCTAACAGAGTTTTTCGAACTCGTGTTGTCGAGCGACGGAATTAGATCAGTTAAATGGCAGAAAAC";
+
+    fn write_fastq(seqs: &[&str]) -> Result<NamedTempFile> {
+        let tmp = NamedTempFile::with_suffix(".fastq")?;
+        let mut f = std::fs::File::create(tmp.path())?;
+        for (i, seq) in seqs.iter().enumerate() {
+            writeln!(f, "@pair{i}\n{seq}\n+\n{}", "I".repeat(seq.len()))?;
+        }
+        Ok(tmp)
+    }
+
+    /// Builds one overlapping pair (sequenced from `FRAGMENT`) and one pair
+    /// with no overlap at all, encoded as a paired BINSEQ file.
+    fn paired_input(mode: BinseqMode) -> Result<NamedTempFile> {
+        let mut r2 = FRAGMENT.as_bytes()[30..].to_vec();
+        reverse_complement(&mut r2);
+        let r2 = String::from_utf8(r2)?;
+        let no_overlap = "G".repeat(50);
+
+        let r1_tmp = write_fastq(&[&FRAGMENT[..50], &no_overlap])?;
+        let r2_tmp = write_fastq(&[&r2, &no_overlap])?;
+
+        let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1_tmp.path().to_str().unwrap(),
+            r2_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        Ok(bq_tmp)
+    }
+
+    #[test]
+    fn test_merge_pairs_reconstructs_fragment() -> Result<()> {
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let bq_tmp = paired_input(mode)?;
+            let merged_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::MergePairsCommand::try_parse_from([
+                "merge-pairs",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                merged_tmp.path().to_str().unwrap(),
+            ])?;
+            super::run(&cmd)?;
+
+            assert_eq!(
+                count_binseq(merged_tmp.path())?,
+                1,
+                "only the overlapping pair should merge for {mode:?}"
+            );
+
+            let out_fa = NamedTempFile::with_suffix(".fasta")?;
+            let cmd = crate::cli::DecodeCommand::try_parse_from([
+                "decode",
+                merged_tmp.path().to_str().unwrap(),
+                "-o",
+                out_fa.path().to_str().unwrap(),
+                "-f",
+                "a",
+            ])?;
+            crate::commands::decode::run(&cmd)?;
+            let content = std::fs::read_to_string(out_fa.path())?;
+            assert!(
+                content.lines().any(|l| l == FRAGMENT),
+                "merged read should reconstruct the fragment for {mode:?}: {content}"
+            );
+        }
+        Ok(())
+    }
+
+    /// `decode --merge-overlap` merges the overlapping pair and interleaves the other.
+    #[test]
+    fn test_decode_merge_overlap() -> Result<()> {
+        let bq_tmp = paired_input(BinseqMode::Vbq)?;
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+            "--merge-overlap",
+            "-T",
+            "1",
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let seqs: Vec<&str> = content.lines().skip(1).step_by(4).collect();
+        assert_eq!(seqs, [FRAGMENT, &"G".repeat(50), &"G".repeat(50)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_pairs_rejects_single_end() -> Result<()> {
+        let in_tmp = write_fastq(&[FRAGMENT])?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            bq_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::MergePairsCommand::try_parse_from([
+            "merge-pairs",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+/// An accepted overlap between the tail of R1 and the head of reverse complemented R2.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    /// Number of overlapping bases
+    pub len: usize,
+    /// Number of mismatched (or ambiguous) bases within the overlap
+    pub mismatches: usize,
+}
+
+/// Finds the best ungapped overlap between the 3' end of `r1` and the 5' end of `r2rc`.
+///
+/// Every overlap length from `min(r1.len(), r2rc.len())` down to `min_overlap` is
+/// scored, and the one with the lowest mismatch rate within `max_mismatch_rate` is
+/// returned. Ties favor the longer overlap. `N` bases always count as mismatches
+/// so that ambiguous stretches cannot produce a spurious perfect overlap.
+#[allow(clippy::cast_precision_loss)]
+pub fn find_overlap(
+    r1: &[u8],
+    r2rc: &[u8],
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+) -> Option<Overlap> {
+    let max_len = r1.len().min(r2rc.len());
+    let mut best: Option<Overlap> = None;
+    for len in (min_overlap.max(1)..=max_len).rev() {
+        let tail = &r1[r1.len() - len..];
+        let head = &r2rc[..len];
+        let limit = max_mismatch_rate * len as f64;
+
+        let mut mismatches = 0;
+        for (a, b) in tail.iter().zip(head) {
+            if a != b || *a == b'N' {
+                mismatches += 1;
+                if mismatches as f64 > limit {
+                    break;
+                }
+            }
+        }
+        if mismatches as f64 > limit {
+            continue;
+        }
+
+        // compare rates by cross-multiplication: m / len < best.m / best.len
+        let candidate = Overlap { len, mismatches };
+        if best.is_none_or(|b| mismatches * b.len < b.mismatches * len) {
+            best = Some(candidate);
+        }
+    }
+    best
+}
+
+/// Resolves a single overlapping position into a consensus base and quality.
+///
+/// Agreeing bases keep the higher quality. On disagreement a known base wins over
+/// `N`, otherwise the higher quality base wins (R1 on ties) and its quality is
+/// lowered to the quality difference to reflect the conflict.
+fn consensus(a: u8, qa: u8, b: u8, qb: u8) -> (u8, u8) {
+    if a == b {
+        (a, qa.max(qb))
+    } else if a == b'N' {
+        (b, qb)
+    } else if b == b'N' {
+        (a, qa)
+    } else {
+        let (base, hi, lo) = if qa >= qb { (a, qa, qb) } else { (b, qb, qa) };
+        (base, b'!' + (hi - lo).max(2))
+    }
+}
+
+/// Writes the merged sequence (and quality, when both mates carry one) of a pair.
+///
+/// The merged read is the non-overlapping prefix of R1, the consensus of the
+/// overlap, and the non-overlapping suffix of reverse complemented R2. Without
+/// qualities, disagreements resolve to the R1 base unless it is `N`.
+pub fn merge_pair(
+    r1: &[u8],
+    q1: Option<&[u8]>,
+    r2rc: &[u8],
+    q2rc: Option<&[u8]>,
+    overlap_len: usize,
+    seq: &mut Vec<u8>,
+    qual: &mut Vec<u8>,
+) {
+    seq.clear();
+    qual.clear();
+
+    let offset = r1.len() - overlap_len;
+    seq.extend_from_slice(&r1[..offset]);
+    if let (Some(q1), Some(_)) = (q1, q2rc) {
+        qual.extend_from_slice(&q1[..offset]);
+    }
+
+    for i in 0..overlap_len {
+        let a = r1[offset + i];
+        let b = r2rc[i];
+        if let (Some(q1), Some(q2rc)) = (q1, q2rc) {
+            let (base, q) = consensus(a, q1[offset + i], b, q2rc[i]);
+            seq.push(base);
+            qual.push(q);
+        } else {
+            seq.push(if a == b'N' { b } else { a });
+        }
+    }
+
+    seq.extend_from_slice(&r2rc[overlap_len..]);
+    if let (Some(_), Some(q2rc)) = (q1, q2rc) {
+        qual.extend_from_slice(&q2rc[overlap_len..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_overlap_exact() {
+        let r1 = b"GGGGGACGTACGTAC";
+        let r2rc = b"ACGTACGTACTTTTT";
+        let overlap = find_overlap(r1, r2rc, 5, 0.0).unwrap();
+        assert_eq!(overlap.len, 10);
+        assert_eq!(overlap.mismatches, 0);
+    }
+
+    #[test]
+    fn test_find_overlap_with_mismatch() {
+        let r1 = b"GGGGGACGTACGTAC";
+        let r2rc = b"ACGTTCGTACTTTTT";
+        assert!(find_overlap(r1, r2rc, 5, 0.0).is_none_or(|o| o.len != 10));
+        let overlap = find_overlap(r1, r2rc, 5, 0.1).unwrap();
+        assert_eq!(overlap.len, 10);
+        assert_eq!(overlap.mismatches, 1);
+    }
+
+    #[test]
+    fn test_find_overlap_respects_min_overlap() {
+        let r1 = b"GGGGGGGGGGACGTA";
+        let r2rc = b"ACGTATTTTTTTTTT";
+        assert_eq!(find_overlap(r1, r2rc, 5, 0.0).map(|o| o.len), Some(5));
+        assert!(find_overlap(r1, r2rc, 6, 0.0).is_none());
+    }
+
+    #[test]
+    fn test_find_overlap_counts_n_as_mismatch() {
+        let r1 = b"NNNNNNNNNN";
+        let r2rc = b"NNNNNNNNNN";
+        assert!(find_overlap(r1, r2rc, 5, 0.1).is_none());
+    }
+
+    #[test]
+    fn test_merge_pair_consensus() {
+        let r1 = b"GGGGGACGTA";
+        let q1 = b"IIIIIIIII#";
+        let r2rc = b"ACGTCTTTTT";
+        let q2rc = b"IIIIITTTTT";
+        let (mut seq, mut qual) = (Vec::new(), Vec::new());
+        merge_pair(r1, Some(q1), r2rc, Some(q2rc), 5, &mut seq, &mut qual);
+        assert_eq!(seq, b"GGGGGACGTCTTTTT");
+        assert_eq!(qual.len(), seq.len());
+        assert_eq!(&qual[..5], b"IIIII");
+        assert_eq!(qual[8], b'I');
+        assert_eq!(qual[9], b'!' + (b'I' - b'#'));
+        assert_eq!(&qual[10..], b"TTTTT");
+    }
+
+    #[test]
+    fn test_merge_pair_without_quality() {
+        let r1 = b"GGGGGACNTA";
+        let r2rc = b"ACGTCTTTTT";
+        let (mut seq, mut qual) = (Vec::new(), Vec::new());
+        merge_pair(r1, None, r2rc, None, 5, &mut seq, &mut qual);
+        assert_eq!(seq, b"GGGGGACGTATTTTT");
+        assert!(qual.is_empty());
+    }
+}
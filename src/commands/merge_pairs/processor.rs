@@ -0,0 +1,155 @@
+use std::{io::Write, sync::Arc};
+
+use binseq::{BinseqRecord, BinseqWriter, ParallelProcessor, SequencingRecordBuilder};
+use parking_lot::Mutex;
+
+use super::overlap::{find_overlap, merge_pair};
+use crate::commands::revcomp::reverse_complement;
+
+pub struct MergeProcessor<W: Write + Send> {
+    /// Overlap options
+    min_overlap: usize,
+    max_mismatch_rate: f64,
+
+    /// Thread-local writer for the processor
+    t_writer: BinseqWriter<Vec<u8>>,
+    /// Thread-local count of merged pairs
+    t_merged: usize,
+    /// Thread-local count of pairs seen
+    t_total: usize,
+
+    /// Thread-local scratch buffers for the reverse complemented R2
+    xseq: Vec<u8>,
+    xqual: Vec<u8>,
+    /// Thread-local scratch buffers for the merged read
+    seq: Vec<u8>,
+    qual: Vec<u8>,
+
+    /// Global writer for the processor
+    writer: Arc<Mutex<BinseqWriter<W>>>,
+    /// Global count of merged pairs
+    merged: Arc<Mutex<usize>>,
+    /// Global count of pairs seen
+    total: Arc<Mutex<usize>>,
+}
+impl<W: Write + Send> Clone for MergeProcessor<W> {
+    fn clone(&self) -> Self {
+        Self {
+            min_overlap: self.min_overlap,
+            max_mismatch_rate: self.max_mismatch_rate,
+            t_writer: self.t_writer.clone(),
+            t_merged: 0,
+            t_total: 0,
+            xseq: Vec::new(),
+            xqual: Vec::new(),
+            seq: Vec::new(),
+            qual: Vec::new(),
+            writer: self.writer.clone(),
+            merged: self.merged.clone(),
+            total: self.total.clone(),
+        }
+    }
+}
+impl<W: Write + Send> MergeProcessor<W> {
+    pub fn new(
+        writer: BinseqWriter<W>,
+        min_overlap: usize,
+        max_mismatch_rate: f64,
+    ) -> binseq::Result<Self> {
+        let t_writer = writer.new_headless_buffer()?;
+        Ok(Self {
+            min_overlap,
+            max_mismatch_rate,
+            t_writer,
+            t_merged: 0,
+            t_total: 0,
+            xseq: Vec::new(),
+            xqual: Vec::new(),
+            seq: Vec::new(),
+            qual: Vec::new(),
+            writer: Arc::new(Mutex::new(writer)),
+            merged: Arc::new(Mutex::new(0)),
+            total: Arc::new(Mutex::new(0)),
+        })
+    }
+
+    fn write_batch(&mut self) -> binseq::Result<()> {
+        self.writer.lock().ingest_completed(&mut self.t_writer)
+    }
+
+    fn write_final(&mut self) -> binseq::Result<()> {
+        self.writer.lock().ingest(&mut self.t_writer)
+    }
+
+    pub fn finish(&mut self) -> binseq::Result<()> {
+        self.writer.lock().finish()
+    }
+
+    pub fn get_merged_count(&self) -> usize {
+        *self.merged.lock()
+    }
+
+    pub fn get_total_count(&self) -> usize {
+        *self.total.lock()
+    }
+}
+
+impl<W: Write + Send> ParallelProcessor for MergeProcessor<W> {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.t_total += 1;
+        let has_quality = record.has_quality();
+
+        self.xseq.clear();
+        self.xseq.extend_from_slice(record.xseq());
+        reverse_complement(&mut self.xseq);
+        if has_quality {
+            self.xqual.clear();
+            self.xqual.extend_from_slice(record.xqual());
+            self.xqual.reverse();
+        }
+
+        let sseq = record.sseq();
+        let Some(overlap) =
+            find_overlap(sseq, &self.xseq, self.min_overlap, self.max_mismatch_rate)
+        else {
+            return Ok(());
+        };
+
+        let (squal, xqual) = if has_quality {
+            (Some(record.squal()), Some(self.xqual.as_slice()))
+        } else {
+            (None, None)
+        };
+        merge_pair(
+            sseq,
+            squal,
+            &self.xseq,
+            xqual,
+            overlap.len,
+            &mut self.seq,
+            &mut self.qual,
+        );
+
+        let rec = SequencingRecordBuilder::default()
+            .s_seq(&self.seq)
+            .opt_s_qual(has_quality.then_some(self.qual.as_slice()))
+            .s_header(record.sheader())
+            .build()?;
+        if self.t_writer.push(rec)? {
+            self.t_merged += 1;
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        *self.merged.lock() += self.t_merged;
+        *self.total.lock() += self.t_total;
+        self.t_merged = 0;
+        self.t_total = 0;
+        self.write_batch()
+    }
+
+    fn on_thread_complete(&mut self) -> binseq::Result<()> {
+        self.write_final()
+    }
+}
@@ -1,14 +1,35 @@
+pub mod benchmark;
 pub mod cat;
+pub mod corrupt;
 pub mod decode;
+pub mod doctor;
 pub mod encode;
+pub mod formats;
 pub mod grep;
+pub mod head;
 pub mod info;
+pub mod merge_pairs;
 pub mod pipe;
+pub mod progress;
 pub mod qc;
+pub mod recompress;
 pub mod revcomp;
+mod rsyncable;
 pub mod sample;
+pub mod sort;
 pub mod split;
+mod tmp;
 mod utils;
+pub mod validate;
 pub mod verify;
 
-pub use utils::{compress_passthrough, match_output, CompressionType};
+pub use tmp::{
+    install_cleanup_handler, scope_to_tmpdir, set_tmpdir, temp_dir, temp_path, TempPaths,
+};
+pub use utils::{
+    check_clobber, compress_passthrough, is_stdio, is_stream, load_block_spans, match_output,
+    match_output_append, max_open_files, open_binseq, set_max_open_files, set_no_clobber,
+    BlockSpan, CompressionType, OutputExists, OutputWriter,
+};
+#[cfg(feature = "gcs")]
+pub use utils::{gcs_retries, set_gcs_retries, DEFAULT_GCS_RETRIES};
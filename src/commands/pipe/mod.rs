@@ -3,16 +3,19 @@ pub mod processor;
 pub mod utils;
 
 use std::io::Write;
+use std::ops::Range;
 use std::thread;
 
 use anyhow::Result;
-use binseq::BinseqReader;
 use log::{info, warn};
 
-use crate::cli::{FileFormat, PipeCommand};
+use crate::{
+    cli::{Balance, FileFormat, PipeCommand},
+    commands::{load_block_spans, open_binseq, scope_to_tmpdir, TempPaths},
+};
 use exec::ExecMode;
 use processor::PipeProcessor;
-use utils::{create_fifos, FifoGuard};
+use utils::{byte_balanced_ranges, create_fifos, record_balanced_ranges};
 
 pub type BoxedWriter = Box<dyn Write + Send>;
 
@@ -22,6 +25,8 @@ pub enum RecordPair {
     R1,
     R2,
     Unpaired,
+    /// Both mates, R1 then R2, to a single FIFO
+    Interleaved,
 }
 
 /// Which channels to create FIFOs and writer threads for in paired mode.
@@ -36,15 +41,54 @@ pub enum PairedChannels {
     R2Only,
 }
 
+/// Determines which channels to create FIFOs and writer threads for.
+///
+/// In exec mode the template drives this — a template with only {R1} skips the
+/// R2 FIFO and writer entirely. Without exec, both channels are always created.
+/// Only meaningful for paired files; unpaired always uses a single unlabelled FIFO.
+fn paired_channels(args: &PipeCommand, paired: bool) -> PairedChannels {
+    if paired {
+        args.exec()
+            .or_else(|| args.exec_batch())
+            .map_or(PairedChannels::Both, exec::required_channels)
+    } else {
+        PairedChannels::Both
+    }
+}
+
+/// Splits the records into one range per pipe according to `--balance`
+fn pipe_ranges(
+    args: &PipeCommand,
+    num_records: usize,
+    num_pipes: usize,
+) -> Result<Vec<Range<usize>>> {
+    Ok(match args.balance() {
+        Balance::Records => record_balanced_ranges(num_records, num_pipes),
+        Balance::Bytes => {
+            if let Some(blocks) = load_block_spans(args.input.path())? {
+                byte_balanced_ranges(&blocks, num_pipes)
+            } else {
+                // BQ records are fixed-size, so equal records are equal bytes
+                record_balanced_ranges(num_records, num_pipes)
+            }
+        }
+    })
+}
+
 pub fn run(args: &PipeCommand) -> Result<()> {
     if args.input.span.is_some() {
         warn!("Span is ignored when using pipe subcommand");
     }
 
     let format = args.format()?;
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     let num_records = reader.num_records()?;
-    let paired = reader.is_paired();
+    if args.interleaved() && !reader.is_paired() {
+        warn!("`--interleaved` is ignored for single-end files");
+    }
+    // Interleaved paired files are laid out like single-end files: one FIFO per pipe
+    let interleaved = args.interleaved() && reader.is_paired();
+    let paired = reader.is_paired() && !interleaved;
     let num_pipes = if paired {
         (args.num_pipes() / 2).max(1)
     } else {
@@ -57,28 +101,27 @@ pub fn run(args: &PipeCommand) -> Result<()> {
         exec::validate_template(t, paired)?;
     }
 
-    // Determine which channels to create FIFOs and writer threads for. In exec
-    // mode the template drives this — a template with only {R1} skips the R2
-    // FIFO and writer entirely. Without exec, both channels are always created.
-    // Only meaningful for paired files; unpaired always uses a single unlabelled FIFO.
-    let channels = if paired {
-        args.exec()
-            .or_else(|| args.exec_batch())
-            .map_or(PairedChannels::Both, exec::required_channels)
-    } else {
-        PairedChannels::Both
-    };
-
-    let basename = args.basepath();
-    // Wrap the FIFOs in a guard immediately so they are unlinked on any early
-    // return or panic below, not just on the happy path.
-    let fifo_guard = FifoGuard::new(create_fifos(basename, paired, num_pipes, format, channels)?);
+    let channels = paired_channels(args, paired);
+
+    let basepath = scope_to_tmpdir(args.basepath());
+    let basename = basepath.as_str();
+    // FIFOs are guarded as they are created so they are unlinked on any early
+    // return, panic, or interrupt below, not just on the happy path.
+    let mut fifo_guard = TempPaths::new();
+    create_fifos(
+        basename,
+        paired,
+        num_pipes,
+        format,
+        channels,
+        &mut fifo_guard,
+    )?;
     info!(
         "{} FIFOs created. Waiting for readers to connect...",
         fifo_guard.paths().len()
     );
 
-    let records_per_pipe = num_records / num_pipes;
+    let ranges = pipe_ranges(args, num_records, num_pipes)?;
 
     // Spawn consumer processes before writer threads: opening a FIFO for writing
     // blocks until a reader connects, so readers must be in-flight first.
@@ -95,14 +138,7 @@ pub fn run(args: &PipeCommand) -> Result<()> {
     // For each pipe, open a thread which handles the init and exit of the writer.
     // Named pipes block on open until both reader and writer connect.
     let mut handles = Vec::new();
-    for pid in 0..num_pipes {
-        let rstart = records_per_pipe * pid;
-        let rend = if pid == num_pipes - 1 {
-            num_records
-        } else {
-            rstart + records_per_pipe
-        };
-
+    for (pid, range) in ranges.into_iter().enumerate() {
         if paired {
             if matches!(channels, PairedChannels::Both | PairedChannels::R1Only) {
                 handles.push(spawn_pipe_thread(
@@ -111,7 +147,7 @@ pub fn run(args: &PipeCommand) -> Result<()> {
                     pid,
                     format,
                     RecordPair::R1,
-                    rstart..rend,
+                    range.clone(),
                 ));
             }
             if matches!(channels, PairedChannels::Both | PairedChannels::R2Only) {
@@ -121,17 +157,22 @@ pub fn run(args: &PipeCommand) -> Result<()> {
                     pid,
                     format,
                     RecordPair::R2,
-                    rstart..rend,
+                    range.clone(),
                 ));
             }
         } else {
+            let record_pair = if interleaved {
+                RecordPair::Interleaved
+            } else {
+                RecordPair::Unpaired
+            };
             handles.push(spawn_pipe_thread(
                 basename.to_string(),
                 args.input.path().to_string(),
                 pid,
                 format,
-                RecordPair::Unpaired,
-                rstart..rend,
+                record_pair,
+                range,
             ));
         }
     }
@@ -163,7 +204,7 @@ fn spawn_pipe_thread(
     range: std::ops::Range<usize>,
 ) -> thread::JoinHandle<Result<()>> {
     thread::spawn(move || -> Result<()> {
-        let handle_reader = BinseqReader::new(&input_path)?;
+        let handle_reader = open_binseq(&input_path)?;
         let proc = PipeProcessor::new(&basename, pid, format, record_pair)?;
         handle_reader.process_parallel_range(proc, 1, range)?;
         Ok(())
@@ -252,6 +293,42 @@ mod tests {
         Ok(())
     }
 
+    /// Byte-balanced ranges still deliver every record exactly once.
+    #[test]
+    fn test_pipe_balance_bytes() -> Result<()> {
+        for ext in [".vbq", ".cbq", ".bq"] {
+            let fastq = write_fastx().call()?;
+            let bq = NamedTempFile::with_suffix(ext)?;
+            encode(fastq.path(), bq.path())?;
+
+            let fifo_dir = tempfile::tempdir()?;
+            let basepath = fifo_dir.path().join("pipe").to_str().unwrap().to_string();
+            let out = NamedTempFile::with_suffix(".fastq")?;
+            let out_path = out.path().to_str().unwrap().to_string();
+
+            let cmd = crate::cli::PipeCommand::try_parse_from([
+                "pipe",
+                bq.path().to_str().unwrap(),
+                "-b",
+                &basepath,
+                "-p",
+                "2",
+                "--balance",
+                "bytes",
+                "-X",
+                &format!("cat {{}} > {out_path}"),
+            ])?;
+            super::run(&cmd)?;
+
+            assert_eq!(
+                count_fastx_records(out.path())?,
+                DEFAULT_NUM_RECORDS,
+                "record count mismatch for --balance bytes ({ext})"
+            );
+        }
+        Ok(())
+    }
+
     /// Paired-end pipe with `-x`: one command per pair, `{R1}` and `{R2}` substituted.
     #[test]
     fn test_pipe_exec_paired() -> Result<()> {
@@ -342,6 +419,49 @@ mod tests {
         Ok(())
     }
 
+    /// Paired-end pipe with `--interleaved`: each pipe writes R1 then R2 to a
+    /// single FIFO, substituted through `{}` like a single-end file.
+    #[test]
+    fn test_pipe_interleaved() -> Result<()> {
+        let r1 = write_fastx().call()?;
+        let r2 = write_fastx().call()?;
+        let cbq = NamedTempFile::with_suffix(".cbq")?;
+        let encode_cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            r1.path().to_str().unwrap(),
+            r2.path().to_str().unwrap(),
+            "-o",
+            cbq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&encode_cmd)?;
+
+        let fifo_dir = tempfile::tempdir()?;
+        let basepath = fifo_dir.path().join("pipe").to_str().unwrap().to_string();
+        let out = NamedTempFile::with_suffix(".fastq")?;
+        let out_path = out.path().to_str().unwrap().to_string();
+
+        let cmd = crate::cli::PipeCommand::try_parse_from([
+            "pipe",
+            cbq.path().to_str().unwrap(),
+            "-b",
+            &basepath,
+            "-p",
+            "2",
+            "--interleaved",
+            "-X",
+            &format!("cat {{}} > {out_path}"),
+        ])?;
+        super::run(&cmd)?;
+
+        let content = std::fs::read_to_string(out.path())?;
+        let headers: Vec<_> = content.lines().step_by(4).collect();
+        assert_eq!(headers.len(), DEFAULT_NUM_RECORDS * 2);
+        for pair in headers.chunks(2) {
+            assert_eq!(pair[0], pair[1], "mates are not adjacent");
+        }
+        Ok(())
+    }
+
     /// Missing `{}` in a single-end template must be caught before any FIFO is created.
     #[test]
     fn test_pipe_exec_missing_token_single() -> Result<()> {
@@ -517,7 +637,7 @@ mod tests {
     }
 
     /// When a consumer exits non-zero, `run` returns an error — but the FIFOs must
-    /// still be unlinked from disk by the `FifoGuard`, not leaked.
+    /// still be unlinked from disk by the `TempPaths` guard, not leaked.
     #[test]
     fn test_pipe_fifos_cleaned_up_on_error() -> Result<()> {
         let fastq = write_fastx().call()?;
@@ -8,7 +8,7 @@ use super::{BoxedWriter, RecordPair};
 use crate::{
     cli::FileFormat,
     commands::{
-        decode::write_record,
+        decode::{write_record, TSV_DELIMITER},
         pipe::utils::{name_fifo, open_fifo},
     },
 };
@@ -39,46 +39,51 @@ impl PipeProcessor {
         })
     }
 }
+/// Writes one mate to `local`, substituting `?` qualities when none are stored
+fn write_mate(
+    local: &mut Vec<u8>,
+    qbuf: &mut Vec<u8>,
+    header: &[u8],
+    seq: &[u8],
+    qual: Option<&[u8]>,
+    format: FileFormat,
+) -> binseq::Result<()> {
+    let qual = if let Some(qual) = qual {
+        qual
+    } else {
+        if qbuf.len() != seq.len() {
+            qbuf.resize(seq.len(), b'?');
+        }
+        qbuf
+    };
+    write_record(local, header, seq, qual, format, TSV_DELIMITER)?;
+    Ok(())
+}
+
 impl ParallelProcessor for PipeProcessor {
     fn process_record<R: binseq::BinseqRecord>(&mut self, record: R) -> binseq::Result<()> {
-        match self.pair {
-            RecordPair::Unpaired | RecordPair::R1 => {
-                // handle missing quality if record has no quality
-                let qual = if record.has_quality() {
-                    record.squal()
-                } else {
-                    if self.squal.len() != record.slen() as usize {
-                        self.squal.resize(record.slen() as usize, b'?');
-                    }
-                    &self.squal
-                };
-
-                write_record(
-                    &mut self.local,
-                    record.sheader(),
-                    record.sseq(),
-                    qual,
-                    self.format,
-                )?;
-            }
-            RecordPair::R2 => {
-                // handle missing quality if record has no quality
-                let qual = if record.has_quality() {
-                    record.xqual()
-                } else {
-                    if self.xqual.len() != record.xlen() as usize {
-                        self.xqual.resize(record.xlen() as usize, b'?');
-                    }
-                    &self.xqual
-                };
-                write_record(
-                    &mut self.local,
-                    record.xheader(),
-                    record.xseq(),
-                    qual,
-                    self.format,
-                )?;
-            }
+        if matches!(
+            self.pair,
+            RecordPair::Unpaired | RecordPair::R1 | RecordPair::Interleaved
+        ) {
+            write_mate(
+                &mut self.local,
+                &mut self.squal,
+                record.sheader(),
+                record.sseq(),
+                record.has_quality().then(|| record.squal()),
+                self.format,
+            )?;
+        }
+        if matches!(self.pair, RecordPair::R2 | RecordPair::Interleaved) {
+            write_mate(
+                &mut self.local,
+                &mut self.xqual,
+                record.xheader(),
+                record.xseq(),
+                record.has_quality().then(|| record.xqual()),
+                self.format,
+            )?;
         }
         Ok(())
     }
@@ -1,15 +1,19 @@
 use nix::errno::Errno;
 use std::fs::File;
 use std::io::BufWriter;
+use std::ops::Range;
 use std::path::Path;
 
 use anyhow::Result;
-use log::{trace, warn};
+use log::trace;
 use nix::sys::stat;
 use nix::unistd;
 
 use super::{BoxedWriter, PairedChannels, RecordPair};
-use crate::cli::FileFormat;
+use crate::{
+    cli::FileFormat,
+    commands::{BlockSpan, TempPaths},
+};
 
 /// Creates many FIFOs (named-pipes) at the given basepath.
 ///
@@ -17,6 +21,9 @@ use crate::cli::FileFormat;
 /// unpaired files, `channels` is ignored and a single unlabelled FIFO per
 /// thread is created.
 ///
+/// Each FIFO is pushed to `fifo_paths` as soon as it exists, so a failure
+/// part-way through still unlinks the ones already created.
+///
 /// Note: this does not open the FIFOs for writing.
 pub fn create_fifos(
     basepath: &str,
@@ -24,8 +31,8 @@ pub fn create_fifos(
     num_threads: usize,
     format: FileFormat,
     channels: PairedChannels,
-) -> Result<Vec<String>> {
-    let mut fifo_paths = Vec::new();
+    fifo_paths: &mut TempPaths,
+) -> Result<()> {
     if paired {
         for idx in 0..num_threads {
             if matches!(channels, PairedChannels::Both | PairedChannels::R1Only) {
@@ -46,7 +53,7 @@ pub fn create_fifos(
             fifo_paths.push(path);
         }
     }
-    Ok(fifo_paths)
+    Ok(())
 }
 
 /// Create a FIFO (named-pipe) at the given path
@@ -71,50 +78,109 @@ pub fn open_fifo(path: &str) -> Result<BoxedWriter> {
     Ok(Box::new(handle))
 }
 
-/// RAII guard that unlinks a set of FIFOs when dropped.
+pub fn name_fifo(basepath: &str, pid: usize, pair: RecordPair, format: FileFormat) -> String {
+    match pair {
+        RecordPair::R1 => format!("{}_{}_R1.{}", basepath, pid, format.extension()),
+        RecordPair::R2 => format!("{}_{}_R2.{}", basepath, pid, format.extension()),
+        RecordPair::Unpaired | RecordPair::Interleaved => {
+            format!("{}_{}.{}", basepath, pid, format.extension())
+        }
+    }
+}
+
+/// Divides `num_records` into `num_pipes` contiguous ranges of equal record counts.
 ///
-/// Cleanup is tied to the guard's lifetime rather than the happy path, so the
-/// FIFOs are removed from disk on any early return, `?` propagation, or panic
-/// (via stack unwinding) — not just on successful completion.
-pub struct FifoGuard {
-    paths: Vec<String>,
+/// The final pipe absorbs the remainder.
+pub fn record_balanced_ranges(num_records: usize, num_pipes: usize) -> Vec<Range<usize>> {
+    let records_per_pipe = num_records / num_pipes;
+    (0..num_pipes)
+        .map(|pid| {
+            let rstart = records_per_pipe * pid;
+            let rend = if pid == num_pipes - 1 {
+                num_records
+            } else {
+                rstart + records_per_pipe
+            };
+            rstart..rend
+        })
+        .collect()
 }
 
-impl FifoGuard {
-    pub fn new(paths: Vec<String>) -> Self {
-        Self { paths }
+/// Divides the blocks of a file into `num_pipes` contiguous record ranges of
+/// roughly equal byte size.
+///
+/// Ranges are cut at block boundaries, so a pipe may receive no records when
+/// there are fewer blocks than pipes.
+pub fn byte_balanced_ranges(blocks: &[BlockSpan], num_pipes: usize) -> Vec<Range<usize>> {
+    let total_bytes: u64 = blocks.iter().map(|b| b.len).sum();
+    let mut ranges = Vec::with_capacity(num_pipes);
+    let mut block_iter = blocks.iter().peekable();
+    let (mut bytes, mut records) = (0u64, 0usize);
+    for pid in 0..num_pipes {
+        let rstart = records;
+        // cumulative byte target for the end of this pipe's span
+        let target = total_bytes * (pid as u64 + 1) / num_pipes as u64;
+        while let Some(block) = block_iter.peek() {
+            let is_last_pipe = pid == num_pipes - 1;
+            // take the block if it ends at or before the target, or if stopping
+            // before it would leave us further from the target than taking it
+            let end = bytes + block.len;
+            if !is_last_pipe && end > target && end - target > target.saturating_sub(bytes) {
+                break;
+            }
+            bytes = end;
+            records += block.records as usize;
+            block_iter.next();
+        }
+        ranges.push(rstart..records);
     }
+    ranges
+}
 
-    /// The FIFO paths under guard.
-    pub fn paths(&self) -> &[String] {
-        &self.paths
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(sizes: &[(u64, u64)]) -> Vec<BlockSpan> {
+        let mut cumulative = 0;
+        sizes
+            .iter()
+            .map(|&(len, records)| {
+                cumulative += records;
+                BlockSpan {
+                    offset: 0,
+                    len,
+                    records,
+                    cumulative_records: cumulative,
+                }
+            })
+            .collect()
     }
-}
 
-impl Drop for FifoGuard {
-    fn drop(&mut self) {
-        for path in &self.paths {
-            close_fifo(path);
-        }
+    #[test]
+    fn test_record_balanced_ranges() {
+        assert_eq!(record_balanced_ranges(10, 3), vec![0..3, 3..6, 6..10]);
     }
-}
 
-/// Close a FIFO (unlink the path).
-///
-/// A missing path (`ENOENT`) is treated as success so cleanup is idempotent;
-/// other errors are logged but not propagated, since this runs during teardown.
-fn close_fifo(path: &str) {
-    trace!("Closing FIFO at path: {path}");
-    match unistd::unlink(Path::new(path)) {
-        Ok(()) | Err(Errno::ENOENT) => {}
-        Err(err) => warn!("Failed to unlink FIFO at {path}: {err}"),
+    #[test]
+    fn test_byte_balanced_ranges_follow_bytes() {
+        // one huge block of few long records followed by many small blocks
+        let blocks = blocks(&[(100, 10), (25, 100), (25, 100), (25, 100), (25, 100)]);
+        let ranges = byte_balanced_ranges(&blocks, 2);
+        assert_eq!(ranges, vec![0..10, 10..410]);
     }
-}
 
-pub fn name_fifo(basepath: &str, pid: usize, pair: RecordPair, format: FileFormat) -> String {
-    match pair {
-        RecordPair::R1 => format!("{}_{}_R1.{}", basepath, pid, format.extension()),
-        RecordPair::R2 => format!("{}_{}_R2.{}", basepath, pid, format.extension()),
-        RecordPair::Unpaired => format!("{}_{}.{}", basepath, pid, format.extension()),
+    #[test]
+    fn test_byte_balanced_ranges_cover_all_records() {
+        let blocks = blocks(&[(10, 5), (30, 7), (20, 3), (40, 9), (5, 1)]);
+        for num_pipes in 1..=8 {
+            let ranges = byte_balanced_ranges(&blocks, num_pipes);
+            assert_eq!(ranges.len(), num_pipes);
+            assert_eq!(ranges.first().unwrap().start, 0);
+            assert_eq!(ranges.last().unwrap().end, 25);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+        }
     }
 }
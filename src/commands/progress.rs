@@ -0,0 +1,313 @@
+use std::{
+    io::Read,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use binseq::{BinseqRecord, ParallelProcessor};
+use serde::Serialize;
+
+/// Process-wide count of records completed by processors reporting progress.
+///
+/// Processors bump this once per completed batch, so batch encoding across many
+/// files (each with its own processor) still reports a single running total.
+static RECORDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Process-wide number of records registered with [`add_total_records`]
+static TOTAL_RECORDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Process-wide count of input bytes consumed through a [`CountingReader`]
+static BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Process-wide size of the inputs registered with [`add_total_bytes`]
+static TOTAL_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Whether a [`JsonProgress`] reporter is running (input readers are only
+/// wrapped in a [`CountingReader`] while it is)
+static TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Adds completed records to the process-wide progress counter
+pub fn add_records(n: usize) {
+    RECORDS.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Registers records expected to pass through a [`RecordCounter`]
+pub fn add_total_records(n: usize) {
+    TOTAL_RECORDS.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Registers input bytes expected to pass through a [`CountingReader`]
+pub fn add_total_bytes(n: u64) {
+    TOTAL_BYTES.fetch_add(n, Ordering::Relaxed);
+}
+
+/// Whether input readers should be wrapped in a [`CountingReader`]
+pub fn tracking_bytes() -> bool {
+    TRACKING.load(Ordering::Relaxed)
+}
+
+/// Whether `path` is a local regular file whose size is known up front
+pub fn is_sized_input(path: &Path) -> bool {
+    path.metadata().is_ok_and(|m| m.is_file())
+}
+
+/// Total on-disk size of the local regular files among `paths`
+pub fn input_bytes<P: AsRef<Path>>(paths: &[P]) -> u64 {
+    paths
+        .iter()
+        .filter(|path| is_sized_input(path.as_ref()))
+        .filter_map(|path| path.as_ref().metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Reader adapter adding every byte read to the process-wide byte counter.
+///
+/// Wraps the raw (possibly compressed) file handle, so progress is measured
+/// against the on-disk size even when the input is decompressed on the fly.
+pub struct CountingReader<R> {
+    inner: R,
+}
+impl<R: Read> CountingReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+}
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        BYTES.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Processor adapter adding every completed batch to the process-wide record counter
+#[derive(Clone)]
+pub struct RecordCounter<P> {
+    inner: P,
+    local: usize,
+}
+impl<P> RecordCounter<P> {
+    pub fn new(inner: P) -> Self {
+        Self { inner, local: 0 }
+    }
+}
+impl<P: ParallelProcessor> ParallelProcessor for RecordCounter<P> {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local += 1;
+        self.inner.process_record(record)
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        self.inner.on_batch_complete()?;
+        add_records(std::mem::take(&mut self.local));
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> binseq::Result<()> {
+        self.inner.on_thread_complete()?;
+        add_records(std::mem::take(&mut self.local));
+        Ok(())
+    }
+}
+
+/// Snapshot of the counters when a reporter starts
+#[derive(Clone, Copy)]
+struct Baseline {
+    records: usize,
+    total_records: usize,
+    bytes: u64,
+    total_bytes: u64,
+}
+impl Baseline {
+    fn now() -> Self {
+        Self {
+            records: RECORDS.load(Ordering::Relaxed),
+            total_records: TOTAL_RECORDS.load(Ordering::Relaxed),
+            bytes: BYTES.load(Ordering::Relaxed),
+            total_bytes: TOTAL_BYTES.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Percent complete and seconds remaining at the average rate so far
+fn estimate(done: f64, total: f64, elapsed: f64) -> (f64, Option<f64>) {
+    let percent = (done / total * 1000.0).round() / 10.0;
+    let eta = (done > 0.0).then(|| {
+        let remaining = elapsed * (total - done) / done;
+        (remaining * 10.0).round() / 10.0
+    });
+    (percent, eta)
+}
+
+/// Byte-based completion estimate, available when the input sizes are known
+#[derive(Serialize)]
+struct ByteProgress {
+    bytes: u64,
+    total_bytes: u64,
+    percent: f64,
+    eta: Option<f64>,
+}
+impl ByteProgress {
+    fn new(baseline: Baseline, elapsed: f64) -> Option<Self> {
+        let total_bytes = TOTAL_BYTES
+            .load(Ordering::Relaxed)
+            .saturating_sub(baseline.total_bytes);
+        if total_bytes == 0 {
+            return None;
+        }
+        let bytes = BYTES
+            .load(Ordering::Relaxed)
+            .saturating_sub(baseline.bytes)
+            .min(total_bytes);
+        let (percent, eta) = estimate(bytes as f64, total_bytes as f64, elapsed);
+        Some(Self {
+            bytes,
+            total_bytes,
+            percent,
+            eta,
+        })
+    }
+}
+
+/// Record-based completion estimate, available when the record total is known
+#[derive(Serialize)]
+struct RecordProgress {
+    total_records: usize,
+    percent: f64,
+    eta: Option<f64>,
+}
+impl RecordProgress {
+    fn new(baseline: Baseline, records: usize, elapsed: f64) -> Option<Self> {
+        let total_records = TOTAL_RECORDS
+            .load(Ordering::Relaxed)
+            .saturating_sub(baseline.total_records);
+        if total_records == 0 {
+            return None;
+        }
+        let records = records.min(total_records);
+        let (percent, eta) = estimate(records as f64, total_records as f64, elapsed);
+        Some(Self {
+            total_records,
+            percent,
+            eta,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct ProgressLine {
+    records: usize,
+    elapsed: f64,
+    rate: f64,
+    #[serde(flatten)]
+    progress: Option<ByteProgress>,
+    #[serde(flatten)]
+    record_progress: Option<RecordProgress>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    done: bool,
+}
+impl ProgressLine {
+    fn new(start: Instant, baseline: Baseline, done: bool) -> Self {
+        let records = RECORDS
+            .load(Ordering::Relaxed)
+            .saturating_sub(baseline.records);
+        let elapsed = start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            records as f64 / elapsed
+        } else {
+            0.0
+        };
+        // byte totals take precedence, so `percent`/`eta` are never written twice
+        let progress = ByteProgress::new(baseline, elapsed);
+        let record_progress = if progress.is_none() {
+            RecordProgress::new(baseline, records, elapsed)
+        } else {
+            None
+        };
+        Self {
+            records,
+            elapsed: (elapsed * 1000.0).round() / 1000.0,
+            rate: rate.round(),
+            progress,
+            record_progress,
+            done,
+        }
+    }
+
+    fn emit(&self) {
+        if let Ok(line) = serde_json::to_string(self) {
+            eprintln!("{line}");
+        }
+    }
+}
+
+/// Background thread emitting machine-readable progress lines to stderr.
+///
+/// Every `interval` a line like `{"records":N,"elapsed":S,"rate":R}` is written.
+/// When input sizes were registered with [`add_total_bytes`], the line also
+/// carries `bytes`, `total_bytes`, `percent`, and `eta` (seconds remaining);
+/// otherwise, when record totals were registered with [`add_total_records`],
+/// it carries `total_records`, `percent`, and `eta`.
+/// [`JsonProgress::finish`] stops the thread and writes a final summary line with
+/// `"done":true`.
+pub struct JsonProgress {
+    start: Instant,
+    baseline: Baseline,
+    stop: Sender<()>,
+    handle: JoinHandle<()>,
+}
+impl JsonProgress {
+    pub fn spawn(interval: Duration) -> Self {
+        let start = Instant::now();
+        let baseline = Baseline::now();
+        TRACKING.store(true, Ordering::Relaxed);
+        let (stop, rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(interval) {
+                ProgressLine::new(start, baseline, false).emit();
+            }
+        });
+        Self {
+            start,
+            baseline,
+            stop,
+            handle,
+        }
+    }
+
+    pub fn finish(self) {
+        self.stop.send(()).ok();
+        self.handle.join().ok();
+        TRACKING.store(false, Ordering::Relaxed);
+        ProgressLine::new(self.start, self.baseline, true).emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+
+    #[test]
+    fn test_counting_reader_and_input_bytes() -> std::io::Result<()> {
+        let before = BYTES.load(Ordering::Relaxed);
+        let mut buf = Vec::new();
+        CountingReader::new(Cursor::new(vec![0u8; 1000])).read_to_end(&mut buf)?;
+        assert!(BYTES.load(Ordering::Relaxed) - before >= 1000);
+
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("input.fq");
+        std::fs::write(&file, vec![b'A'; 123])?;
+        let missing = dir.path().join("missing.fq");
+        // directories and missing paths are not sized inputs
+        assert_eq!(input_bytes(&[file.as_path(), dir.path(), &missing]), 123);
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 use anyhow::Result;
-use binseq::{BinseqReader, ParallelReader};
+use binseq::ParallelReader;
 use log::trace;
 
 use crate::cli::QcCommand;
+use crate::commands::open_binseq;
 
 mod base_content;
 mod base_quality;
@@ -23,7 +24,7 @@ pub type QualAbundance = [usize; 94];
 pub const DEFAULT_QUAL_ABUNDANCE: QualAbundance = [0; 94];
 
 pub fn run(args: &QcCommand) -> Result<()> {
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     let paired = reader.is_paired();
     let total_records = reader.num_records()?;
     let range = args
@@ -0,0 +1,396 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use binseq::{vbq, BitSize};
+use log::{info, warn};
+
+use crate::{
+    cli::{BinseqMode, RecompressCommand},
+    commands::{
+        encode::processor::Encoder, match_output, revcomp::get_builder, utils::process_binseq,
+        TempPaths,
+    },
+};
+
+/// Resolves the final output path and the path that is actually written to.
+///
+/// For `--in-place` the data is written to a sibling temporary file first, on
+/// the input's filesystem so it can be renamed over the input.
+fn output_paths(args: &RecompressCommand) -> Result<(String, String)> {
+    if args.in_place {
+        let mut tmp = PathBuf::from(args.input.path()).into_os_string();
+        tmp.push(".recompress.tmp");
+        Ok((
+            args.input.path().to_string(),
+            tmp.to_string_lossy().into_owned(),
+        ))
+    } else if let Some(output) = &args.output {
+        if output == args.input.path() {
+            bail!("Output path is the same as the input - use `--in-place` to replace the input");
+        }
+        Ok((output.clone(), output.clone()))
+    } else {
+        bail!("An output path (`-o`) or `--in-place` is required");
+    }
+}
+
+pub fn run(args: &RecompressCommand) -> Result<()> {
+    let mode = args.input.mode()?;
+    if matches!(mode, BinseqMode::Bq) {
+        bail!("BQ files are not compressed - recompress only supports VBQ and CBQ inputs");
+    }
+    if let Some(output) = &args.output {
+        if BinseqMode::determine(output).is_ok_and(|omode| omode != mode) {
+            bail!(
+                "Output path {output} does not match the input format ({}) - use `encode` to convert between formats",
+                mode.extension()
+            );
+        }
+    }
+    if args.in_place && args.input.span.is_some() {
+        bail!(
+            "Cannot combine `--span` with `--in-place` as records outside the span would be lost"
+        );
+    }
+    if let Some(bitsize) = args.options.bitsize {
+        if !matches!(mode, BinseqMode::Vbq) {
+            bail!("`--bitsize` only applies to VBQ files - CBQ has no nucleotide bitsize");
+        }
+        let current = vbq::MmapReader::new(args.input.path())?.header().bits;
+        if current == bitsize {
+            let bits: u8 = bitsize.into();
+            warn!("Input is already {bits}-bit");
+        }
+    }
+    // Only a 2-bit target can lose information, so only then are bases audited
+    let downconvert = matches!(args.options.bitsize, Some(BitSize::Two));
+    let (final_path, write_path) = output_paths(args)?;
+
+    let mut builder = get_builder(&args.input)?;
+    if let Some(level) = args.options.level {
+        builder = builder.compression(level > 0).compression_level(level);
+    }
+    if let Some(block_size) = args.options.block_size {
+        builder = builder.block_size(block_size);
+    }
+    if let Some(bitsize) = args.options.bitsize {
+        builder = builder
+            .bitsize(bitsize)
+            .policy(args.options.policy().into());
+    }
+
+    let ohandle = match_output(Some(&write_path))?;
+    // removes the in-place temporary on failure or interrupt (a no-op once renamed)
+    let mut guard = TempPaths::new();
+    if args.in_place {
+        guard.push(&write_path);
+    }
+    let writer = builder.build(ohandle)?;
+    let mut processor = Encoder::new(writer)?.with_base_audit(downconvert);
+    process_binseq(
+        args.input.path(),
+        processor.clone(),
+        args.options.threads(),
+        args.input.span,
+    )?;
+    processor.finish()?;
+
+    if args.in_place {
+        std::fs::rename(&write_path, &final_path)?;
+    }
+    info!(
+        "Recompressed {} records to: {final_path}",
+        processor.get_global_record_count()
+    );
+    if downconvert {
+        let invalid = processor.get_global_invalid_count();
+        let skipped = processor.get_global_skip_count();
+        if invalid > 0 {
+            warn!(
+                "{invalid} records contained non-ACGT bases and could not be downconverted losslessly ({skipped} dropped, {} resolved by the N-policy)",
+                invalid - skipped
+            );
+        } else {
+            info!("All records downconverted to 2-bit losslessly");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::testutils::{
+        count_binseq, read_flags, write_fastx, write_flagged_binseq, DEFAULT_NUM_RECORDS,
+    };
+
+    fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_path.to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)
+    }
+
+    /// Decodes a BINSEQ file to FASTQ and returns its records sorted, so
+    /// comparisons are insensitive to reordering from parallel processing.
+    fn sorted_records(bq_path: &std::path::Path) -> Result<Vec<String>> {
+        let out_fq = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_path.to_str().unwrap(),
+            "-o",
+            out_fq.path().to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_fq.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        let mut records: Vec<String> = lines.chunks(4).map(|c| c.join("\n")).collect();
+        records.sort_unstable();
+        Ok(records)
+    }
+
+    #[test]
+    fn test_recompress_preserves_records() -> Result<()> {
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::RecompressCommand::try_parse_from([
+                "recompress",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--level",
+                "19",
+                "--block-size",
+                "256K",
+            ])?;
+            super::run(&cmd)?;
+
+            assert_eq!(count_binseq(out_tmp.path())?, DEFAULT_NUM_RECORDS);
+            assert_eq!(
+                sorted_records(bq_tmp.path())?,
+                sorted_records(out_tmp.path())?,
+                "recompression changed records for {mode:?}"
+            );
+        }
+        Ok(())
+    }
+
+    /// Every worker's final partial block is flushed, so no records are lost
+    /// on the inline single-thread path or when the input is spread over
+    /// several threads.
+    #[test]
+    fn test_recompress_keeps_final_blocks() -> Result<()> {
+        let nrec = 10_000;
+        for (mode, threads) in [BinseqMode::Vbq, BinseqMode::Cbq]
+            .into_iter()
+            .flat_map(|mode| [(mode, "1"), (mode, "4")])
+        {
+            let in_tmp = write_fastx().nrec(nrec).call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let cmd = crate::cli::RecompressCommand::try_parse_from([
+                "recompress",
+                bq_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "-T",
+                threads,
+            ])?;
+            super::run(&cmd)?;
+
+            assert_eq!(
+                count_binseq(out_tmp.path())?,
+                count_binseq(bq_tmp.path())?,
+                "recompression lost records for {mode:?} with {threads} thread(s)"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompress_in_place() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+        let expected = sorted_records(bq_tmp.path())?;
+
+        let cmd = crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            bq_tmp.path().to_str().unwrap(),
+            "--in-place",
+            "--level",
+            "0",
+        ])?;
+        super::run(&cmd)?;
+
+        assert_eq!(sorted_records(bq_tmp.path())?, expected);
+        Ok(())
+    }
+
+    /// A failed `--in-place` run leaves the input untouched and removes its temporary.
+    #[test]
+    fn test_recompress_in_place_failure_cleans_up() -> Result<()> {
+        let in_tmp = write_fastx().include_n(true).call()?;
+        let archive_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            archive_tmp.path().to_str().unwrap(),
+            "--archive",
+        ])?;
+        crate::commands::encode::run(&cmd)?;
+        let original = std::fs::read(archive_tmp.path())?;
+
+        // the N records cannot be downconverted under the break-on-invalid policy
+        let cmd = crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            archive_tmp.path().to_str().unwrap(),
+            "--in-place",
+            "--bitsize",
+            "2",
+            "--policy",
+            "p",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+
+        assert_eq!(std::fs::read(archive_tmp.path())?, original);
+        let (_, write_path) = super::output_paths(&cmd)?;
+        assert!(!std::path::Path::new(&write_path).exists());
+        Ok(())
+    }
+
+    /// A 4-bit archive re-encoded as 2-bit keeps every ACGT record unchanged, and
+    /// the default policy drops records with Ns instead of altering them.
+    #[test]
+    fn test_recompress_downconvert_bitsize() -> Result<()> {
+        for include_n in [false, true] {
+            let in_tmp = write_fastx().include_n(include_n).call()?;
+            let archive_tmp = NamedTempFile::with_suffix(".vbq")?;
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                archive_tmp.path().to_str().unwrap(),
+                "--archive",
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+            let cmd = crate::cli::RecompressCommand::try_parse_from([
+                "recompress",
+                archive_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+                "--bitsize",
+                "2",
+            ])?;
+            super::run(&cmd)?;
+
+            let bits: u8 = binseq::vbq::MmapReader::new(out_tmp.path())?
+                .header()
+                .bits
+                .into();
+            assert_eq!(bits, 2);
+            let expected: Vec<_> = sorted_records(archive_tmp.path())?
+                .into_iter()
+                .filter(|r| !r.lines().nth(1).unwrap().contains('N'))
+                .collect();
+            assert_eq!(sorted_records(out_tmp.path())?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompress_bitsize_requires_vbq() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let cbq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        encode(in_tmp.path(), cbq_tmp.path())?;
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            cbq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+            "--bitsize",
+            "2",
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        assert!(crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            cbq_tmp.path().to_str().unwrap(),
+            "--in-place",
+            "--policy",
+            "a",
+        ])
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompress_rejects_bq_and_format_change() -> Result<()> {
+        let in_tmp = write_fastx().call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".bq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+        let out_tmp = NamedTempFile::with_suffix(".bq")?;
+        let cmd = crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            bq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        assert!(super::run(&cmd).is_err());
+
+        let vbq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), vbq_tmp.path())?;
+        let out_tmp = NamedTempFile::with_suffix(".cbq")?;
+        let cmd = crate::cli::RecompressCommand::try_parse_from([
+            "recompress",
+            vbq_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        assert!(super::run(&cmd).is_err());
+        Ok(())
+    }
+
+    /// Record flags survive recompression, including a `--bitsize` change.
+    #[test]
+    fn test_recompress_preserves_flags() -> Result<()> {
+        let expected: Vec<u64> = (1..=DEFAULT_NUM_RECORDS as u64).collect();
+        for (mode, extra) in [
+            (BinseqMode::Vbq, None),
+            (BinseqMode::Vbq, Some("4")),
+            (BinseqMode::Cbq, None),
+        ] {
+            let in_tmp = write_flagged_binseq(mode, DEFAULT_NUM_RECORDS)?;
+            let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            let mut args = vec![
+                "recompress",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+            ];
+            if let Some(bits) = extra {
+                args.extend(["--bitsize", bits]);
+            }
+            super::run(&crate::cli::RecompressCommand::try_parse_from(args)?)?;
+            assert_eq!(read_flags(out_tmp.path())?, expected, "{mode:?} {extra:?}");
+        }
+        Ok(())
+    }
+}
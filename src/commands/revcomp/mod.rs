@@ -1,26 +1,28 @@
 mod processor;
 
 use anyhow::Result;
-use binseq::{bq, cbq, vbq, BinseqReader, BinseqWriterBuilder, ParallelReader};
+use binseq::{bq, cbq, vbq, BinseqWriterBuilder, ParallelReader};
 use log::{info, warn};
 
-use crate::cli::{BinseqMode, Mate, RevcompCommand};
+use crate::cli::{BinseqMode, InputBinseq, Mate, RevcompCommand};
+use crate::commands::open_binseq;
+pub use processor::reverse_complement;
 use processor::RevCompProcessor;
 
 /// Builds a writer that mirrors the input file's own header/configuration,
 /// since reverse complementing changes sequence content but not schema.
-fn get_builder(args: &RevcompCommand) -> Result<BinseqWriterBuilder> {
-    let builder = match args.input.mode()? {
+pub fn get_builder(input: &InputBinseq) -> Result<BinseqWriterBuilder> {
+    let builder = match input.mode()? {
         BinseqMode::Bq => {
-            let reader = bq::MmapReader::new(args.input.path())?;
+            let reader = bq::MmapReader::new(input.path())?;
             BinseqWriterBuilder::from_bq_header(reader.header())
         }
         BinseqMode::Vbq => {
-            let reader = vbq::MmapReader::new(args.input.path())?;
+            let reader = vbq::MmapReader::new(input.path())?;
             BinseqWriterBuilder::from_vbq_header(reader.header())
         }
         BinseqMode::Cbq => {
-            let reader = cbq::MmapReader::new(args.input.path())?;
+            let reader = cbq::MmapReader::new(input.path())?;
             BinseqWriterBuilder::from_cbq_header(reader.header())
         }
     };
@@ -28,12 +30,12 @@ fn get_builder(args: &RevcompCommand) -> Result<BinseqWriterBuilder> {
 }
 
 pub fn run(args: &RevcompCommand) -> Result<()> {
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     if !reader.is_paired() && args.mate != Mate::Both {
         warn!("Ignoring `--mate/-M` flag as only single channel found in file");
     }
 
-    let builder = get_builder(args)?;
+    let builder = get_builder(&args.input)?;
     let ohandle = args.output.as_writer()?;
     let writer = builder.build(ohandle)?;
     let mut processor = RevCompProcessor::new(writer, args.mate)?;
@@ -9,7 +9,7 @@ use crate::cli::Mate;
 ///
 /// Any byte outside `ACGTacgt` (e.g. `N`) is left untouched, matching the
 /// behavior of 4-bit decoding, which collapses all ambiguity codes to `N`.
-fn reverse_complement(buf: &mut [u8]) {
+pub fn reverse_complement(buf: &mut [u8]) {
     buf.reverse();
     for base in buf.iter_mut() {
         *base = match *base {
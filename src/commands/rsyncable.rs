@@ -0,0 +1,201 @@
+use std::io::{self, Write};
+
+use flate2::{write::GzEncoder, Compression};
+
+/// Minimum size of a gzip member before a content-defined boundary may end it
+const MIN_MEMBER_LEN: usize = 64 * 1024;
+
+/// A boundary falls where these (top 20) hash bits are clear: about one per MiB
+const BOUNDARY_MASK: u64 = !0 << 44;
+
+/// Per-byte values of the rolling gear hash (splitmix64 sequence)
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0; 256];
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Gzip writer that restarts compression at content-defined points (`--rsyncable`).
+///
+/// A rolling hash over the last 64 uncompressed bytes picks the boundaries, and
+/// each boundary closes the current gzip member and opens a new one, so the
+/// compression window never spans one. An edit to the input only changes the
+/// members around it; the rest of the compressed output stays byte-identical
+/// between runs, which rsync and deduplicating backups can exploit.
+/// Decompressors read the concatenated members as a single stream.
+///
+/// Compression runs on the calling thread. `flush` only flushes completed
+/// members, since forcing out a partial member would make the output depend on
+/// when the flushes happened. The last member is only written by
+/// [`Self::finish`]; dropping the writer instead ends it without reporting errors.
+pub struct RsyncableGzip<W: Write> {
+    /// Encoder for the current member (`None` once finished)
+    encoder: Option<GzEncoder<W>>,
+    hash: u64,
+    member_len: usize,
+}
+impl<W: Write> RsyncableGzip<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            encoder: Some(GzEncoder::new(writer, Compression::default())),
+            hash: 0,
+            member_len: 0,
+        }
+    }
+
+    /// Ends the last member and flushes the underlying writer.
+    ///
+    /// Calling it again is a no-op; writing afterwards is an error.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish()?.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn encoder(&mut self) -> io::Result<&mut GzEncoder<W>> {
+        self.encoder
+            .as_mut()
+            .ok_or_else(|| io::Error::other("write to a finished rsyncable gzip stream"))
+    }
+
+    /// Finishes the current member and starts a new one on the same writer
+    fn end_member(&mut self) -> io::Result<()> {
+        let encoder = self
+            .encoder
+            .take()
+            .expect("members only end after a successful write");
+        let writer = encoder.finish()?;
+        self.encoder = Some(GzEncoder::new(writer, Compression::default()));
+        self.member_len = 0;
+        Ok(())
+    }
+}
+impl<W: Write> Write for RsyncableGzip<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut start = 0;
+        for (i, &byte) in buf.iter().enumerate() {
+            self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+            self.member_len += 1;
+            if self.member_len >= MIN_MEMBER_LEN && self.hash & BOUNDARY_MASK == 0 {
+                self.encoder()?.write_all(&buf[start..=i])?;
+                self.end_member()?;
+                start = i + 1;
+            }
+        }
+        self.encoder()?.write_all(&buf[start..])?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder.get_mut().flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+
+    use crate::commands::{compress_passthrough, CompressionType, OutputWriter};
+
+    /// Deterministic incompressible bytes (xorshift64)
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut out = Vec::with_capacity(len + 8);
+        while out.len() < len {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            out.extend_from_slice(&x.to_le_bytes());
+        }
+        out.truncate(len);
+        out
+    }
+
+    fn rsyncable(writer: impl Write + Send + 'static) -> OutputWriter {
+        compress_passthrough(Box::new(writer), CompressionType::Gzip, 1, true).unwrap()
+    }
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let tmp = tempfile::NamedTempFile::new().unwrap();
+        let mut writer = rsyncable(tmp.reopen().unwrap());
+        assert!(matches!(writer, OutputWriter::Rsyncable(_)));
+        // uneven writes, so boundaries fall inside and across buffers
+        for chunk in data.chunks(10_000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        std::fs::read(tmp.path()).unwrap()
+    }
+
+    /// Accepts `.0` more bytes, then fails every write
+    struct Full(usize);
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            let n = buf.len().min(self.0);
+            self.0 -= n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_rsyncable_round_trip() {
+        let data = noise(8 << 20);
+        let compressed = compress(&data);
+        let mut decoded = Vec::new();
+        flate2::read::MultiGzDecoder::new(compressed.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    /// Inserting a byte at the start only changes the first member.
+    #[test]
+    fn test_rsyncable_resynchronizes() {
+        let data = noise(8 << 20);
+        let shifted: Vec<u8> = std::iter::once(b'X').chain(data.iter().copied()).collect();
+        let (a, b) = (compress(&data), compress(&shifted));
+        let shared = a
+            .iter()
+            .rev()
+            .zip(b.iter().rev())
+            .take_while(|(x, y)| x == y)
+            .count();
+        assert!(
+            shared > a.len() / 2,
+            "only {shared} of {} compressed bytes are shared",
+            a.len()
+        );
+    }
+
+    /// A trailer that cannot be written fails `finish` instead of vanishing on drop.
+    #[test]
+    fn test_rsyncable_finish_reports_errors() {
+        // room for the gzip header only; the record stays in the encoder's buffer
+        let mut writer = rsyncable(Full(10));
+        writer.write_all(b"@r\nACGT\n+\nIIII\n").unwrap();
+        assert!(writer.finish().is_err());
+        assert!(writer.write_all(b"ACGT").is_err());
+    }
+}
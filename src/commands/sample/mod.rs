@@ -1,18 +1,89 @@
+use std::collections::{BinaryHeap, HashSet};
+use std::ops::Range;
 use std::sync::Arc;
 
 use crate::cli::{FileFormat, Mate, SampleCommand};
 use anyhow::Result;
 use binseq::prelude::*;
+use log::info;
 use parking_lot::Mutex;
 use rand::{RngExt, SeedableRng};
+use xxhash_rust::xxh3::xxh3_64_with_seed;
 
-use super::decode::{build_writer, write_record_pair, SplitWriter};
+use super::decode::{build_writer, write_record_pair, SplitWriter, TSV_DELIMITER};
+use super::progress;
+use super::utils::{open_binseq, process_binseq, span_range};
+
+/// Stateless per-record inclusion decision for a fixed fraction and seed.
+///
+/// Each record index is hashed with the seed, so whether a record is kept does
+/// not depend on which thread processes it or in what order. The selection is
+/// stable across versions (files sampled separately stay in sync): record `i`
+/// is kept when the top 53 bits of `xxh3_64_with_seed(i.to_le_bytes(), seed)`,
+/// read as a fraction of `2^53`, fall below `fraction`.
+#[derive(Clone, Copy, Debug)]
+pub struct IndexSampler {
+    fraction: f64,
+    seed: u64,
+}
+impl IndexSampler {
+    pub fn new(fraction: f64, seed: u64) -> Self {
+        Self { fraction, seed }
+    }
+
+    pub fn includes(&self, index: u64) -> bool {
+        let hash = xxh3_64_with_seed(&index.to_le_bytes(), self.seed);
+        // top 53 bits give a uniform draw in [0, 1)
+        let draw = (hash >> 11) as f64 / (1_u64 << 53) as f64;
+        draw < self.fraction
+    }
+}
+
+/// Fixed-size uniform selection of record indices for a seed (`--num`).
+///
+/// Every index in the range is keyed by `xxh3_64_with_seed(i.to_le_bytes(), seed)`
+/// and the `num` indices with the smallest keys are kept (bottom-k sampling).
+/// BINSEQ files know their record count, so the selection is made up front from
+/// the indices alone and the records are then decoded in a single pass.
+#[derive(Clone, Copy, Debug)]
+pub struct ReservoirSampler {
+    num: usize,
+    seed: u64,
+}
+impl ReservoirSampler {
+    pub fn new(num: usize, seed: u64) -> Self {
+        Self { num, seed }
+    }
+
+    /// Indices selected from `range`, which is kept whole when it has at most `num` records
+    pub fn select(&self, range: Range<usize>) -> HashSet<u64> {
+        if range.len() <= self.num {
+            return range.map(|i| i as u64).collect();
+        }
+        // max-heap of the `num` smallest (key, index) pairs seen so far
+        let mut reservoir = BinaryHeap::with_capacity(self.num + 1);
+        for index in range.map(|i| i as u64) {
+            let key = (xxh3_64_with_seed(&index.to_le_bytes(), self.seed), index);
+            if reservoir.len() < self.num {
+                reservoir.push(key);
+            } else if reservoir.peek().is_some_and(|top| key < *top) {
+                reservoir.pop();
+                reservoir.push(key);
+            }
+        }
+        reservoir.into_iter().map(|(_, index)| index).collect()
+    }
+}
 
 #[derive(Clone)]
 struct SampleProcessor {
     /// Sampling Options
     fraction: f64,
     rng: rand::rngs::SmallRng,
+    /// Index-keyed selection replacing `rng` (`--by-index`)
+    sampler: Option<IndexSampler>,
+    /// Precomputed selection replacing `rng` (`--num`)
+    selected: Option<Arc<HashSet<u64>>>,
 
     /// Local write buffers
     mixed: Vec<u8>, // General purpose, interleaved or singlets
@@ -23,13 +94,20 @@ struct SampleProcessor {
     squal: Vec<u8>,
     xqual: Vec<u8>,
 
+    /// Local counts of records seen and sampled
+    local_total: usize,
+    local_sampled: usize,
+
     /// Write Options
     format: FileFormat,
     mate: Option<Mate>,
     is_split: bool,
+    defer_flush: bool,
 
     /// Global values
     global_writer: Arc<Mutex<SplitWriter>>,
+    /// Global counts of records seen and sampled
+    counts: Arc<Mutex<(usize, usize)>>,
 }
 impl SampleProcessor {
     pub fn new(
@@ -44,17 +122,60 @@ impl SampleProcessor {
             format,
             mate,
             rng: rand::rngs::SmallRng::seed_from_u64(seed),
+            sampler: None,
+            selected: None,
             mixed: Vec::new(),
             left: Vec::new(),
             right: Vec::new(),
             squal: Vec::new(),
             xqual: Vec::new(),
+            local_total: 0,
+            local_sampled: 0,
             is_split: writer.is_split(),
+            defer_flush: false,
             global_writer: Arc::new(Mutex::new(writer)),
+            counts: Arc::new(Mutex::new((0, 0))),
         }
     }
-    pub fn include_record(&mut self) -> bool {
-        self.rng.random_bool(self.fraction)
+    /// Select records by index instead of drawing from the per-thread RNG
+    #[must_use]
+    pub fn with_index_sampler(mut self, sampler: Option<IndexSampler>) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Keep exactly the records in `selected` instead of drawing from the per-thread RNG
+    #[must_use]
+    pub fn with_selection(mut self, selected: Option<HashSet<u64>>) -> Self {
+        self.selected = selected.map(Arc::new);
+        self
+    }
+
+    /// Skip the per-batch flush, leaving it to [`Self::finish`]
+    #[must_use]
+    pub fn with_defer_flush(mut self, defer_flush: bool) -> Self {
+        self.defer_flush = defer_flush;
+        self
+    }
+
+    /// Finishes the shared writer (the only flush with `--defer-flush`)
+    pub fn finish(&self) -> std::io::Result<()> {
+        self.global_writer.lock().finish()
+    }
+
+    pub fn include_record(&mut self, index: u64) -> bool {
+        if let Some(selected) = &self.selected {
+            return selected.contains(&index);
+        }
+        match self.sampler {
+            Some(sampler) => sampler.includes(index),
+            None => self.rng.random_bool(self.fraction),
+        }
+    }
+
+    /// Number of records seen and sampled across all threads
+    pub fn counts(&self) -> (usize, usize) {
+        *self.counts.lock()
     }
 }
 impl ParallelProcessor for SampleProcessor {
@@ -62,7 +183,9 @@ impl ParallelProcessor for SampleProcessor {
         let sbuf = record.sseq();
         let xbuf = record.xseq();
 
-        if self.include_record() {
+        self.local_total += 1;
+        if self.include_record(record.index()) {
+            self.local_sampled += 1;
             let squal = if record.has_quality() {
                 record.squal()
             } else {
@@ -101,6 +224,7 @@ impl ParallelProcessor for SampleProcessor {
                 xqual,
                 record.xheader(),
                 self.format,
+                TSV_DELIMITER,
             )?;
         }
 
@@ -117,10 +241,19 @@ impl ParallelProcessor for SampleProcessor {
             } else {
                 writer.write_interleaved(&self.mixed)?;
             }
-            writer.flush()?;
+            if !self.defer_flush {
+                writer.flush()?;
+            }
+        }
+        {
+            let mut counts = self.counts.lock();
+            counts.0 += self.local_total;
+            counts.1 += self.local_sampled;
         }
 
-        // Clear the local buffer and reset the local record count
+        // Clear the local buffer and reset the local record counts
+        self.local_total = 0;
+        self.local_sampled = 0;
         self.mixed.clear();
         self.left.clear();
         self.right.clear();
@@ -130,7 +263,7 @@ impl ParallelProcessor for SampleProcessor {
 
 pub fn run(args: &SampleCommand) -> Result<()> {
     args.sample.validate()?;
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     let writer = build_writer(&args.output, reader.is_paired())?;
     let format = args.output.format()?;
     let mate = if reader.is_paired() {
@@ -138,17 +271,49 @@ pub fn run(args: &SampleCommand) -> Result<()> {
     } else {
         None
     };
-    let proc = SampleProcessor::new(args.sample.fraction, args.sample.seed, writer, format, mate);
-    if let Some(mut span) = args.input.span {
-        let num_records = reader.num_records()?;
-        reader.process_parallel_range(
-            proc.clone(),
-            args.output.threads(),
-            span.get_range(num_records)?,
-        )?;
-    } else {
-        reader.process_parallel(proc.clone(), args.output.threads())?;
+    let sampler = args
+        .sample
+        .fraction
+        .filter(|_| args.sample.by_index)
+        .map(|fraction| IndexSampler::new(fraction, args.sample.seed));
+    let range = span_range(&reader, args.input.span)?;
+    let num_records = range.len();
+    let selected = args
+        .sample
+        .num
+        .map(|num| ReservoirSampler::new(num, args.sample.seed).select(range));
+    let fraction = args.sample.fraction.unwrap_or(1.0);
+    let proc = SampleProcessor::new(fraction, args.sample.seed, writer, format, mate)
+        .with_index_sampler(sampler)
+        .with_selection(selected)
+        .with_defer_flush(args.output.defer_flush);
+    info!("Scanning {num_records} records...");
+    let progress = args.progress.spawn();
+    progress::add_total_records(num_records);
+    let result = process_binseq(
+        args.input.path(),
+        proc.clone(),
+        args.output.threads(),
+        args.input.span,
+    );
+    let finished = proc.finish();
+    if let Some(progress) = progress {
+        progress.finish();
+    }
+    result?;
+    finished?;
+    let (num_total, num_sampled) = proc.counts();
+    if let Some(num) = args.sample.num {
+        info!("Sampled {num_sampled} of {num_total} records ({num} requested)");
+        return Ok(());
     }
+    #[allow(clippy::cast_precision_loss)]
+    let observed = if num_total == 0 {
+        0.0
+    } else {
+        num_sampled as f64 / num_total as f64
+    };
+    info!("Sampled {num_sampled} of {num_total} records ({observed:.4} observed vs {fraction} requested)");
     Ok(())
 }
 
@@ -238,6 +403,36 @@ mod tests {
         Ok(())
     }
 
+    /// A fixed seed on a single thread reproduces the exact same output.
+    #[test]
+    fn test_sample_single_thread_deterministic() -> Result<()> {
+        let in_tmp = write_fastx().nrec(1000).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".cbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let mut outputs = Vec::new();
+        for _ in 0..2 {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            let cmd = crate::cli::SampleCommand::try_parse_from([
+                "sample",
+                bq_tmp.path().to_str().unwrap(),
+                "-F",
+                "0.3",
+                "-S",
+                "7",
+                "-T",
+                "1",
+                "-o",
+                out_tmp.path().to_str().unwrap(),
+            ])?;
+            super::run(&cmd)?;
+            outputs.push(std::fs::read(out_tmp.path())?);
+        }
+        assert!(!outputs[0].is_empty());
+        assert_eq!(outputs[0], outputs[1]);
+        Ok(())
+    }
+
     /// Different seeds should (very likely) produce different sample sizes.
     #[test]
     fn test_sample_different_seeds_vary() -> Result<()> {
@@ -262,4 +457,152 @@ mod tests {
         );
         Ok(())
     }
+
+    /// Record indices parsed from the `seq.{idx}` headers of a FASTA file, sorted.
+    fn sampled_indices(path: &std::path::Path) -> Result<Vec<u64>> {
+        let mut indices = std::fs::read_to_string(path)?
+            .lines()
+            .filter_map(|line| line.strip_prefix(">seq."))
+            .map(|idx| Ok(idx.parse()?))
+            .collect::<Result<Vec<u64>>>()?;
+        indices.sort_unstable();
+        Ok(indices)
+    }
+
+    /// `--by-index` keeps the same record indices from separately sampled files.
+    ///
+    /// Two files with the same record order (but different sequences) are
+    /// sampled with the same seed and fraction on different thread counts; both
+    /// must select exactly the indices the sampler picks, with matching headers.
+    #[test]
+    fn test_sample_by_index_in_sync() -> Result<()> {
+        let nrec = 500;
+        let sampler = super::IndexSampler::new(0.2, 7);
+        let expected: Vec<u64> = (0..nrec as u64).filter(|&i| sampler.includes(i)).collect();
+        assert!(!expected.is_empty());
+
+        for mode in BinseqMode::enum_iter() {
+            let mut selections = Vec::new();
+            for threads in ["1", "4"] {
+                let in_tmp = write_fastx().nrec(nrec).call()?;
+                let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+                encode(in_tmp.path(), bq_tmp.path())?;
+
+                let out_tmp = NamedTempFile::with_suffix(".fasta")?;
+                let cmd = crate::cli::SampleCommand::try_parse_from([
+                    "sample",
+                    bq_tmp.path().to_str().unwrap(),
+                    "-F",
+                    "0.2",
+                    "-S",
+                    "7",
+                    "--by-index",
+                    "-T",
+                    threads,
+                    "-o",
+                    out_tmp.path().to_str().unwrap(),
+                ])?;
+                super::run(&cmd)?;
+                selections.push(sampled_indices(out_tmp.path())?);
+            }
+            assert_eq!(
+                selections[0], selections[1],
+                "files out of sync for {mode:?}"
+            );
+            assert_eq!(selections[0], expected, "unexpected selection for {mode:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_sampler() {
+        let sampler = super::IndexSampler::new(0.25, 42);
+        let kept: Vec<u64> = (0..10_000).filter(|&i| sampler.includes(i)).collect();
+        assert!((2_000..3_000).contains(&kept.len()), "{}", kept.len());
+        let again: Vec<u64> = (0..10_000).filter(|&i| sampler.includes(i)).collect();
+        assert_eq!(kept, again);
+        assert!((0..100).all(|i| super::IndexSampler::new(1.0, 7).includes(i)));
+    }
+
+    /// Progress reporting must not change the sampled output.
+    #[test]
+    fn test_sample_progress_json() -> Result<()> {
+        let nrec = 200;
+        let in_tmp = write_fastx().nrec(nrec).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        let cmd = crate::cli::SampleCommand::try_parse_from([
+            "sample",
+            bq_tmp.path().to_str().unwrap(),
+            "-F",
+            "1.0",
+            "--progress-json",
+            "--progress-interval",
+            "1",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+        assert_eq!(count_fastx_records(out_tmp.path())?, nrec);
+        Ok(())
+    }
+
+    fn sample_num(
+        bq_path: &std::path::Path,
+        out_path: &std::path::Path,
+        num: usize,
+        threads: usize,
+    ) -> Result<()> {
+        let cmd = crate::cli::SampleCommand::try_parse_from([
+            "sample",
+            bq_path.to_str().unwrap(),
+            "-n",
+            &num.to_string(),
+            "-T",
+            &threads.to_string(),
+            "-o",
+            out_path.to_str().unwrap(),
+        ])?;
+        super::run(&cmd)
+    }
+
+    /// `--num` keeps exactly K records, the same ones on any thread count.
+    #[test]
+    fn test_sample_num_exact() -> Result<()> {
+        let in_tmp = write_fastx().nrec(1000).call()?;
+        let bq_tmp = NamedTempFile::with_suffix(".vbq")?;
+        encode(in_tmp.path(), bq_tmp.path())?;
+
+        let mut outputs = Vec::new();
+        for threads in [1, 4] {
+            let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+            sample_num(bq_tmp.path(), out_tmp.path(), 100, threads)?;
+            assert_eq!(count_fastx_records(out_tmp.path())?, 100);
+            let mut lines: Vec<String> = std::fs::read_to_string(out_tmp.path())?
+                .lines()
+                .step_by(4)
+                .map(String::from)
+                .collect();
+            lines.sort();
+            outputs.push(lines);
+        }
+        assert_eq!(outputs[0], outputs[1]);
+
+        // more requested than available keeps every record
+        let out_tmp = NamedTempFile::with_suffix(".fastq")?;
+        sample_num(bq_tmp.path(), out_tmp.path(), 5000, 1)?;
+        assert_eq!(count_fastx_records(out_tmp.path())?, 1000);
+        Ok(())
+    }
+
+    /// `--num` and `--fraction` cannot be combined.
+    #[test]
+    fn test_sample_num_conflicts_with_fraction() {
+        let parsed = crate::cli::SampleCommand::try_parse_from([
+            "sample", "in.vbq", "-n", "10", "-F", "0.5",
+        ]);
+        assert!(parsed.is_err());
+    }
 }
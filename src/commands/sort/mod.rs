@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use binseq::{BinseqRecord, ParallelProcessor, SequencingRecordBuilder};
+use log::info;
+use parking_lot::Mutex;
+
+use crate::cli::{BinseqMode, SortCommand, SortKey};
+use crate::commands::{revcomp::get_builder, utils::process_binseq};
+
+/// Extended mate (header, sequence, quality) of a paired record
+type OwnedMate = (Vec<u8>, Vec<u8>, Option<Vec<u8>>);
+
+/// An owned copy of a record, held in memory until every record is sorted
+struct OwnedRecord {
+    index: u64,
+    sheader: Vec<u8>,
+    sseq: Vec<u8>,
+    squal: Option<Vec<u8>>,
+    flag: Option<u64>,
+    /// Extended mate of paired records
+    mate: Option<OwnedMate>,
+}
+impl OwnedRecord {
+    fn new<B: BinseqRecord>(record: &B) -> Self {
+        let has_quality = record.has_quality();
+        Self {
+            index: record.index(),
+            sheader: record.sheader().to_vec(),
+            sseq: record.sseq().to_vec(),
+            squal: has_quality.then(|| record.squal().to_vec()),
+            flag: record.flag(),
+            mate: record.is_paired().then(|| {
+                (
+                    record.xheader().to_vec(),
+                    record.xseq().to_vec(),
+                    has_quality.then(|| record.xqual().to_vec()),
+                )
+            }),
+        }
+    }
+}
+
+/// Sorts records by `key`, breaking ties by input order
+fn sort_records(records: &mut [OwnedRecord], key: SortKey) {
+    match key {
+        SortKey::Seq => {
+            records.sort_unstable_by(|a, b| a.sseq.cmp(&b.sseq).then(a.index.cmp(&b.index)));
+        }
+        SortKey::Header => {
+            records.sort_unstable_by(|a, b| a.sheader.cmp(&b.sheader).then(a.index.cmp(&b.index)));
+        }
+        SortKey::Length => records.sort_unstable_by_key(|r| (r.sseq.len(), r.index)),
+    }
+}
+
+/// Collects owned copies of every record into a shared buffer
+#[derive(Default)]
+struct SortCollector {
+    local: Vec<OwnedRecord>,
+    records: Arc<Mutex<Vec<OwnedRecord>>>,
+}
+impl Clone for SortCollector {
+    /// Each clone starts with an empty local batch and shares the collected records
+    fn clone(&self) -> Self {
+        Self {
+            local: Vec::new(),
+            records: Arc::clone(&self.records),
+        }
+    }
+}
+impl SortCollector {
+    fn take_records(&self) -> Vec<OwnedRecord> {
+        std::mem::take(&mut *self.records.lock())
+    }
+}
+impl ParallelProcessor for SortCollector {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local.push(OwnedRecord::new(&record));
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        self.records.lock().append(&mut self.local);
+        Ok(())
+    }
+}
+
+pub fn run(args: &SortCommand) -> Result<()> {
+    let mode = args.input.mode()?;
+    if let Some(output) = args.output.output_path() {
+        if BinseqMode::determine(output).is_ok_and(|omode| omode != mode) {
+            bail!(
+                "Output path {output} does not match the input format ({}) - use `encode` to convert between formats",
+                mode.extension()
+            );
+        }
+    }
+
+    let collector = SortCollector::default();
+    process_binseq(
+        args.input.path(),
+        collector.clone(),
+        args.output.threads(),
+        args.input.span,
+    )?;
+    let mut records = collector.take_records();
+    info!("Sorting {} records in memory...", records.len());
+    sort_records(&mut records, args.by);
+
+    let mut writer = get_builder(&args.input)?.build(args.output.as_writer()?)?;
+    let mut num_records = 0;
+    for record in &records {
+        let builder = SequencingRecordBuilder::default()
+            .s_seq(&record.sseq)
+            .opt_s_qual(record.squal.as_deref())
+            .s_header(&record.sheader)
+            .opt_flag(record.flag);
+        let builder = match &record.mate {
+            Some((xheader, xseq, xqual)) => builder
+                .x_seq(xseq)
+                .opt_x_qual(xqual.as_deref())
+                .x_header(xheader),
+            None => builder,
+        };
+        if writer.push(builder.build()?)? {
+            num_records += 1;
+        }
+    }
+    writer.finish()?;
+    info!("Wrote {num_records} sorted records");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::testutils::{read_flags, write_fastx, write_flagged_binseq, DEFAULT_NUM_RECORDS};
+
+    fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_path.to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)
+    }
+
+    /// Decodes a BINSEQ file (with `-T 1`, in file order) to FASTA (header, sequence) pairs
+    fn decode_fasta(bq_path: &std::path::Path) -> Result<Vec<(String, String)>> {
+        let out_tmp = NamedTempFile::with_suffix(".fasta")?;
+        let cmd = crate::cli::DecodeCommand::try_parse_from([
+            "decode",
+            bq_path.to_str().unwrap(),
+            "-T",
+            "1",
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        crate::commands::decode::run(&cmd)?;
+        let content = std::fs::read_to_string(out_tmp.path())?;
+        let lines: Vec<&str> = content.lines().collect();
+        Ok(lines
+            .chunks(2)
+            .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+            .collect())
+    }
+
+    /// Output is ordered by the chosen key and keeps every record.
+    #[test]
+    fn test_sort_by_key() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+
+            // BQ stores no headers, so only sequences are meaningful keys
+            let keys: &[&str] = if mode == BinseqMode::Bq {
+                &["seq"]
+            } else {
+                &["seq", "header"]
+            };
+            for &key in keys {
+                let out_tmp = NamedTempFile::with_suffix(mode.extension())?;
+                let cmd = crate::cli::SortCommand::try_parse_from([
+                    "sort",
+                    bq_tmp.path().to_str().unwrap(),
+                    "--by",
+                    key,
+                    "-o",
+                    out_tmp.path().to_str().unwrap(),
+                ])?;
+                super::run(&cmd)?;
+
+                let records = decode_fasta(out_tmp.path())?;
+                assert_eq!(records.len(), DEFAULT_NUM_RECORDS);
+                let keys: Vec<&String> = records
+                    .iter()
+                    .map(|(header, seq)| if key == "seq" { seq } else { header })
+                    .collect();
+                assert!(
+                    keys.windows(2).all(|w| w[0] <= w[1]),
+                    "records not sorted by {key} for {mode:?}"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Each record keeps its flag when records are reordered.
+    #[test]
+    fn test_sort_preserves_flags() -> Result<()> {
+        let in_tmp = write_flagged_binseq(BinseqMode::Vbq, DEFAULT_NUM_RECORDS)?;
+        let out_tmp = NamedTempFile::with_suffix(".vbq")?;
+        let cmd = crate::cli::SortCommand::try_parse_from([
+            "sort",
+            in_tmp.path().to_str().unwrap(),
+            "-o",
+            out_tmp.path().to_str().unwrap(),
+        ])?;
+        super::run(&cmd)?;
+        let expected: Vec<u64> = (1..=DEFAULT_NUM_RECORDS as u64).collect();
+        assert_eq!(read_flags(out_tmp.path())?, expected);
+        Ok(())
+    }
+}
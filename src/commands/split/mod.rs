@@ -1,16 +1,17 @@
 mod splitter;
 
-use anyhow::Result;
-use binseq::{bq, cbq, vbq, BinseqReader, BinseqWriterBuilder, ParallelReader};
+use anyhow::{bail, Result};
+use binseq::{bq, cbq, vbq, BinseqWriterBuilder, ParallelReader};
 
 #[cfg(feature = "fuzzy")]
 use splitter::FuzzySplitter;
-use splitter::{AhoCorasickSplitter, RegexSplitter, SplitProcessor, Splitter};
+use splitter::{AhoCorasickSplitter, RegexSplitter, SequenceSplit, SplitProcessor, Splitter};
 
 use crate::{
     cli::{BinseqMode, SplitCommand},
     commands::{
         grep::{all_patterns_fixed, PatternCollection},
+        max_open_files, open_binseq,
         utils::make_directory,
     },
 };
@@ -49,9 +50,10 @@ fn build_splitter(args: &SplitCommand) -> Result<Splitter> {
     #[cfg(feature = "fuzzy")]
     if args.fuzzy_args.fuzzy {
         log::trace!(
-            "Using fuzzy splitter backend (k={}, inexact={}, backend=sassy)",
+            "Using fuzzy splitter backend (k={}, inexact={}, distance={:?}, backend=sassy)",
             args.fuzzy_args.distance,
             args.fuzzy_args.inexact,
+            args.fuzzy_args.distance_type,
         );
         let splitter = FuzzySplitter::new(
             &patterns.pat1,
@@ -60,7 +62,8 @@ fn build_splitter(args: &SplitCommand) -> Result<Splitter> {
             args.fuzzy_args.distance,
             args.fuzzy_args.inexact,
             args.fuzzy_args.max_n_frac,
-        )?;
+        )?
+        .with_distance_type(args.fuzzy_args.distance_type);
         return Ok(Splitter::Fuzzy(Box::new(splitter)));
     }
 
@@ -112,6 +115,14 @@ fn get_builder(args: &SplitCommand) -> Result<BinseqWriterBuilder> {
 pub fn run(args: &SplitCommand) -> Result<()> {
     args.validate()?;
     let splitter = build_splitter(args)?;
+    // BINSEQ writers cannot be closed and reopened mid-stream, so every output
+    // must fit within the bound up front
+    let num_outputs = splitter.aliases().len() + usize::from(!args.split.skip_unmatched);
+    if let Some(limit) = max_open_files() {
+        if num_outputs > limit {
+            bail!("split would open {num_outputs} outputs, more than `--max-open-files {limit}` (BINSEQ outputs cannot be reopened)");
+        }
+    }
     let builder = get_builder(args)?;
     make_directory(&args.split.basepath)?;
     let mut proc = SplitProcessor::new(
@@ -122,7 +133,7 @@ pub fn run(args: &SplitCommand) -> Result<()> {
         !args.split.skip_unmatched,
         &args.split.unmatched_basename,
     )?;
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     reader.process_parallel(proc.clone(), args.split.threads)?;
     proc.finish()?;
     if !args.split.quiet {
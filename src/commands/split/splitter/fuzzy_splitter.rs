@@ -4,8 +4,13 @@ use anyhow::Result;
 use fixedbitset::FixedBitSet;
 use sassy::{profiles::Iupac, EncodedPatterns, Match, Searcher};
 
-use crate::commands::{
-    grep::PatternCollection, split::splitter::SequenceSplit, utils::build_fuzzy_searcher,
+use crate::{
+    cli::DistanceType,
+    commands::{
+        grep::PatternCollection,
+        split::splitter::SequenceSplit,
+        utils::{accepts_alignment, build_fuzzy_searcher},
+    },
 };
 
 type Profile = Iupac;
@@ -31,6 +36,9 @@ pub struct FuzzySplitter {
     /// Whether to only accept inexact matches
     inexact: bool,
 
+    /// Distance metric used to accept matches
+    distance_type: DistanceType,
+
     /// Primary sequence searcher
     searcher_1: Searcher<Profile>,
     /// Secondary sequence searcher
@@ -96,6 +104,7 @@ impl FuzzySplitter {
             n_pat2: pat2.len(),
             k,
             inexact,
+            distance_type: DistanceType::default(),
             searcher_1,
             searcher_2,
             searcher,
@@ -106,6 +115,13 @@ impl FuzzySplitter {
         })
     }
 
+    /// Sets the distance metric used to accept matches (default: Levenshtein)
+    #[must_use]
+    pub fn with_distance_type(mut self, distance_type: DistanceType) -> Self {
+        self.distance_type = distance_type;
+        self
+    }
+
     fn reset_bits(&mut self) {
         self.all_bits.clear();
         self.unique_bits.clear();
@@ -121,6 +137,7 @@ impl FuzzySplitter {
                 self.k,
                 self.inexact,
                 0,
+                self.distance_type,
             );
         }
     }
@@ -135,6 +152,7 @@ impl FuzzySplitter {
                 self.k,
                 self.inexact,
                 self.n_pat1,
+                self.distance_type,
             );
         }
     }
@@ -150,6 +168,7 @@ impl FuzzySplitter {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
             search(
                 &mut self.searcher,
@@ -159,6 +178,7 @@ impl FuzzySplitter {
                 self.k,
                 self.inexact,
                 offset,
+                self.distance_type,
             );
         }
     }
@@ -185,6 +205,7 @@ impl SequenceSplit for FuzzySplitter {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn search(
     searcher: &mut Searcher<Profile>,
     patterns: &EncodedPatterns<Profile>,
@@ -193,6 +214,7 @@ fn search(
     k: usize,
     inexact: bool,
     offset: usize,
+    distance_type: DistanceType,
 ) {
     if sequence.is_empty() {
         return;
@@ -201,7 +223,7 @@ fn search(
         .search_encoded_patterns(patterns, sequence, k)
         .iter()
         .for_each(|m: &Match| {
-            if inexact && m.cost == 0 {
+            if (inexact && m.cost == 0) || !accepts_alignment(m, distance_type) {
                 return;
             }
             bitset.set(offset + m.pattern_idx, true);
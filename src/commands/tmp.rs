@@ -0,0 +1,162 @@
+use std::{
+    ffi::CString,
+    io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{bail, Result};
+use log::{trace, warn};
+use parking_lot::{const_mutex, Mutex};
+
+/// Directory for FIFOs and temporary files (set once from the top-level `--tmpdir`)
+static TMPDIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Paths currently held by a live [`TempPaths`], unlinked by the signal handler
+static REGISTRY: Mutex<Vec<CString>> = const_mutex(Vec::new());
+
+pub fn set_tmpdir(dir: Option<&Path>) -> Result<()> {
+    if let Some(dir) = dir {
+        if !dir.is_dir() {
+            bail!("Temporary directory does not exist: {}", dir.display());
+        }
+        TMPDIR.set(dir.to_path_buf()).ok();
+    }
+    Ok(())
+}
+
+/// `--tmpdir`, or the system temp directory if none was given
+pub fn temp_dir() -> PathBuf {
+    TMPDIR.get().cloned().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Path for a temporary file named `name` in `--tmpdir` (or the system temp directory)
+pub fn temp_path(name: &str) -> PathBuf {
+    temp_dir().join(name)
+}
+
+/// Places a relative `path` under `--tmpdir` when one was given.
+///
+/// Without `--tmpdir` (or for absolute paths) the path is returned unchanged.
+pub fn scope_to_tmpdir(path: &str) -> String {
+    match TMPDIR.get() {
+        Some(dir) if Path::new(path).is_relative() => dir.join(path).to_string_lossy().into_owned(),
+        _ => path.to_string(),
+    }
+}
+
+/// RAII guard that unlinks the temporary paths (files or FIFOs) pushed to it.
+///
+/// Paths are removed when the guard drops, so cleanup runs on early returns,
+/// `?` propagation, and panics. Paths are also registered with the SIGINT /
+/// SIGTERM handler (see [`install_cleanup_handler`]) so an interrupt does not
+/// leave them behind either.
+#[derive(Default)]
+pub struct TempPaths {
+    paths: Vec<PathBuf>,
+}
+
+impl TempPaths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes ownership of `path`; call this right after creating it.
+    pub fn push<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        if let Ok(cpath) = CString::new(path.as_os_str().as_bytes()) {
+            REGISTRY.lock().push(cpath);
+        }
+        self.paths.push(path);
+    }
+
+    /// The paths under guard
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Drop for TempPaths {
+    fn drop(&mut self) {
+        let mut registry = REGISTRY.lock();
+        for path in &self.paths {
+            trace!("Removing temporary path: {}", path.display());
+            // A missing path is fine: cleanup is idempotent
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => warn!("Failed to remove {}: {err}", path.display()),
+            }
+            let bytes = path.as_os_str().as_bytes();
+            if let Some(pos) = registry.iter().position(|c| c.as_bytes() == bytes) {
+                registry.swap_remove(pos);
+            }
+        }
+    }
+}
+
+extern "C" fn unlink_registered(signal: libc::c_int) {
+    // Skip cleanup rather than deadlock if the interrupted thread holds the lock
+    if let Some(paths) = REGISTRY.try_lock() {
+        for path in paths.iter() {
+            unsafe {
+                libc::unlink(path.as_ptr());
+            }
+        }
+    }
+    // Re-raise with the default disposition so the exit status is unchanged
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+/// Unlinks live [`TempPaths`] on SIGINT / SIGTERM before exiting
+pub fn install_cleanup_handler() {
+    let handler = unlink_registered as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_paths_removed_on_drop() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let created = dir.path().join("created");
+        let missing = dir.path().join("missing");
+        std::fs::write(&created, b"x")?;
+        {
+            let mut guard = TempPaths::new();
+            guard.push(&created);
+            guard.push(&missing);
+            assert_eq!(guard.paths().len(), 2);
+        }
+        assert!(!created.exists());
+        assert!(!REGISTRY
+            .lock()
+            .iter()
+            .any(|c| c.as_bytes() == created.as_os_str().as_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_temp_paths_removed_on_error() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let created = dir.path().join("partial");
+        let fail = || -> Result<()> {
+            let mut guard = TempPaths::new();
+            std::fs::write(&created, b"x")?;
+            guard.push(&created);
+            bail!("failed after creating a temporary file")
+        };
+        assert!(fail().is_err());
+        assert!(!created.exists());
+        Ok(())
+    }
+}
@@ -1,17 +1,31 @@
 use std::{
+    fmt,
     fs::{self, File},
-    io::{self, BufWriter, Write},
-    path::Path,
+    io::{self, BufReader, BufWriter, Seek, SeekFrom, Write},
+    ops::Range,
+    os::unix::fs::FileTypeExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use anyhow::{bail, Result};
+use binseq::{bq, cbq, vbq, BinseqReader, BinseqRecord, ParallelProcessor, ParallelReader};
 use gzp::{
     deflate::Gzip,
     par::compress::{ParCompress, ParCompressBuilder},
 };
-use log::trace;
+use log::{debug, trace, warn};
+use parking_lot::Mutex;
 #[cfg(feature = "fuzzy")]
-use sassy::{profiles::Iupac, EncodedPatterns, Searcher};
+use sassy::{profiles::Iupac, EncodedPatterns, Match, Searcher};
+
+#[cfg(feature = "fuzzy")]
+use crate::cli::DistanceType;
+use crate::cli::Span;
+use crate::commands::{progress::RecordCounter, rsyncable::RsyncableGzip};
 
 pub fn make_directory<P: AsRef<Path>>(path: P) -> Result<()> {
     if path.as_ref().exists() {
@@ -33,9 +47,92 @@ pub fn make_directory<P: AsRef<Path>>(path: P) -> Result<()> {
     Ok(())
 }
 
+/// Refuse to replace existing output files (set once from the top-level `--no-clobber`)
+static NO_CLOBBER: AtomicBool = AtomicBool::new(false);
+
+pub fn set_no_clobber(no_clobber: bool) {
+    NO_CLOBBER.store(no_clobber, Ordering::Relaxed);
+}
+
+/// Bound on simultaneously open output files (set once from the top-level `--max-open-files`, 0 = unbounded)
+static MAX_OPEN_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_max_open_files(max_open_files: Option<usize>) {
+    MAX_OPEN_FILES.store(max_open_files.unwrap_or(0), Ordering::Relaxed);
+}
+
+pub fn max_open_files() -> Option<usize> {
+    match MAX_OPEN_FILES.load(Ordering::Relaxed) {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+/// Attempts to repeat a failed GCS open (set once from the top-level `--gcs-retries`)
+#[cfg(feature = "gcs")]
+static GCS_RETRIES: AtomicUsize = AtomicUsize::new(DEFAULT_GCS_RETRIES);
+
+#[cfg(feature = "gcs")]
+pub const DEFAULT_GCS_RETRIES: usize = 3;
+
+#[cfg(feature = "gcs")]
+pub fn set_gcs_retries(retries: usize) {
+    GCS_RETRIES.store(retries, Ordering::Relaxed);
+}
+
+#[cfg(feature = "gcs")]
+pub fn gcs_retries() -> usize {
+    GCS_RETRIES.load(Ordering::Relaxed)
+}
+
+/// Error returned when `--no-clobber` prevents an existing output from being replaced.
+///
+/// Callers that clean up partial outputs on failure must not remove the path in this case.
+#[derive(Debug)]
+pub struct OutputExists(pub PathBuf);
+impl fmt::Display for OutputExists {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Output file already exists: {} (remove it or pass `--overwrite`)",
+            self.0.display()
+        )
+    }
+}
+impl std::error::Error for OutputExists {}
+
+fn ensure_clobber_allowed(path: &Path, no_clobber: bool) -> Result<()> {
+    // only regular files are protected - FIFOs and devices (e.g. /dev/null) are always writable
+    if no_clobber && fs::metadata(path).is_ok_and(|meta| meta.is_file()) {
+        return Err(OutputExists(path.to_path_buf()).into());
+    }
+    Ok(())
+}
+
+/// Errors if `--no-clobber` is set and `path` is an existing regular file
+pub fn check_clobber<P: AsRef<Path>>(path: P) -> Result<()> {
+    ensure_clobber_allowed(path.as_ref(), NO_CLOBBER.load(Ordering::Relaxed))
+}
+
+/// Whether `path` is `-`, the conventional stand-in for stdin/stdout
+pub fn is_stdio<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref() == Path::new("-")
+}
+
+/// Whether `path` is stdin (`-`) or a pipe/character device that cannot be memory-mapped
+pub fn is_stream<P: AsRef<Path>>(path: P) -> bool {
+    is_stdio(&path)
+        || fs::metadata(path).is_ok_and(|metadata| {
+            let file_type = metadata.file_type();
+            file_type.is_fifo() || file_type.is_char_device()
+        })
+}
+
+/// Opens a buffered writer at `path`, or stdout when no path (or `-`) is given
 pub fn match_output<P: AsRef<Path>>(path: Option<P>) -> Result<Box<dyn Write + Send>> {
-    if let Some(path) = path {
+    if let Some(path) = path.filter(|path| !is_stdio(path)) {
         trace!("Opening writer handle at: {}", path.as_ref().display());
+        check_clobber(&path)?;
         let handle = File::create(path)?;
         let buffer = BufWriter::new(handle);
         let boxed = Box::new(buffer);
@@ -49,6 +146,13 @@ pub fn match_output<P: AsRef<Path>>(path: Option<P>) -> Result<Box<dyn Write + S
     }
 }
 
+/// Reopens an output file written earlier in this run, appending to it
+pub fn match_output_append<P: AsRef<Path>>(path: P) -> Result<Box<dyn Write + Send>> {
+    trace!("Reopening writer handle at: {}", path.as_ref().display());
+    let handle = File::options().append(true).open(path)?;
+    Ok(Box::new(BufWriter::new(handle)))
+}
+
 #[derive(Clone, Copy, Default, Debug, clap::ValueEnum)]
 pub enum CompressionType {
     #[default]
@@ -69,15 +173,70 @@ impl CompressionType {
     }
 }
 
+/// An output stream opened by [`compress_passthrough`].
+///
+/// Output owners close it with [`Self::finish`] rather than by dropping it, so
+/// that errors writing a compression trailer are reported.
+pub enum OutputWriter {
+    Plain(Box<dyn Write + Send>),
+    Rsyncable(RsyncableGzip<Box<dyn Write + Send>>),
+}
+impl OutputWriter {
+    /// Flushes the stream and writes any compression trailer.
+    ///
+    /// Nothing may be written afterwards.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Rsyncable(writer) => writer.finish(),
+        }
+    }
+}
+impl From<Box<dyn Write + Send>> for OutputWriter {
+    fn from(writer: Box<dyn Write + Send>) -> Self {
+        Self::Plain(writer)
+    }
+}
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(writer) => writer.write(buf),
+            Self::Rsyncable(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(writer) => writer.flush(),
+            Self::Rsyncable(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Wraps `writer` in the requested compressor.
+///
+/// `rsyncable` swaps the parallel gzip encoder for [`RsyncableGzip`]; it is
+/// ignored (with a warning) for other compression types.
 pub fn compress_passthrough(
     writer: Box<dyn Write + Send>,
     compression_type: CompressionType,
     num_threads: usize,
-) -> Result<Box<dyn Write + Send>> {
+    rsyncable: bool,
+) -> Result<OutputWriter> {
+    if rsyncable && !matches!(compression_type, CompressionType::Gzip) {
+        warn!("`--rsyncable` only applies to gzip output; ignoring");
+    }
     match compression_type {
-        CompressionType::Uncompressed => Ok(writer),
-        CompressionType::Gzip => compress_gzip_passthrough(writer, num_threads),
-        CompressionType::Zstd => compress_zstd_passthrough(writer, 3, num_threads),
+        CompressionType::Uncompressed => Ok(OutputWriter::Plain(writer)),
+        CompressionType::Gzip if rsyncable => {
+            Ok(OutputWriter::Rsyncable(RsyncableGzip::new(writer)))
+        }
+        CompressionType::Gzip => {
+            compress_gzip_passthrough(writer, num_threads).map(OutputWriter::Plain)
+        }
+        CompressionType::Zstd => {
+            compress_zstd_passthrough(writer, 3, num_threads).map(OutputWriter::Plain)
+        }
     }
 }
 
@@ -102,6 +261,258 @@ pub fn compress_zstd_passthrough(
     Ok(Box::new(encoder))
 }
 
+/// Record indices `process_binseq` visits for `span` (the whole file if `None`)
+pub fn span_range(reader: &BinseqReader, span: Option<Span>) -> Result<Range<usize>> {
+    let num_records = reader.num_records()?;
+    match span {
+        Some(mut span) => span.get_range(num_records),
+        None => Ok(0..num_records),
+    }
+}
+
+/// Number of records `process_binseq` visits for `span` (the whole file if `None`)
+pub fn span_len(reader: &BinseqReader, span: Option<Span>) -> Result<usize> {
+    Ok(span_range(reader, span)?.len())
+}
+
+/// Runs a record processor over the BINSEQ file at `path`, restricted to `span` if provided.
+///
+/// Completed batches are added to the `--progress-json` record counter.
+///
+/// With a single thread the records are processed inline on the calling thread
+/// (see [`process_inline`]), so they are visited (and written) strictly in file
+/// order and any processor state (e.g. the sampling RNG) is reproducible run to
+/// run.
+pub fn process_binseq<P>(path: &str, proc: P, threads: usize, span: Option<Span>) -> Result<()>
+where
+    P: ParallelProcessor + Clone + 'static,
+{
+    let reader = open_binseq(path)?;
+    let proc = RecordCounter::new(proc);
+    if threads == 1 {
+        let range = span_range(&reader, span)?;
+        debug!(
+            "Processing {} records in file order on a single thread",
+            range.len()
+        );
+        process_inline(path, reader, proc, range)?;
+    } else if let Some(mut span) = span {
+        let num_records = reader.num_records()?;
+        reader.process_parallel_range(proc, threads, span.get_range(num_records)?)?;
+    } else {
+        reader.process_parallel(proc, threads)?;
+    }
+    Ok(())
+}
+
+/// Records processed between batch flushes when a BQ file is processed inline
+const INLINE_BATCH_SIZE: usize = 1024;
+
+/// Feeds the records in `range` to `proc` on the calling thread, in file order.
+///
+/// Nothing is spawned or cloned: the processor sees every record itself and
+/// its batch hook runs after each block (every [`INLINE_BATCH_SIZE`] records
+/// for BQ), so output is written in order as it is produced. CBQ blocks before
+/// `range` are skipped using the block index.
+///
+/// The VBQ reader can only be read sequentially from the first block, so a
+/// `range` starting past the first block is instead handed to a single worker
+/// thread that seeks with the index (see [`Forward`]). Records are still
+/// visited in file order by this one processor.
+fn process_inline<P: ParallelProcessor + 'static>(
+    path: &str,
+    reader: BinseqReader,
+    mut proc: P,
+    range: Range<usize>,
+) -> Result<()> {
+    match reader {
+        BinseqReader::Bq(reader) => {
+            for idx in range {
+                proc.process_record(reader.get(idx)?)?;
+                if (idx + 1) % INLINE_BATCH_SIZE == 0 {
+                    proc.on_batch_complete()?;
+                }
+            }
+        }
+        BinseqReader::Vbq(mut reader) => {
+            let index = reader.load_index()?;
+            if index
+                .ranges()
+                .first()
+                .is_some_and(|block| range.start >= block.block_records as usize)
+            {
+                let proc = Forward(Arc::new(Mutex::new(proc)));
+                reader.process_parallel_range(proc, 1, range)?;
+                return Ok(());
+            }
+            let mut block = reader.new_block();
+            let mut idx = 0;
+            while idx < range.end && reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    if range.contains(&idx) {
+                        proc.process_record(record)?;
+                    }
+                    idx += 1;
+                }
+                proc.on_batch_complete()?;
+            }
+        }
+        BinseqReader::Cbq(reader) => {
+            let mut block = cbq::ColumnarBlock::new(reader.header());
+            let mut file = BufReader::new(File::open(path)?);
+            let mut first = 0;
+            for (offset, header) in cbq_blocks(&reader)? {
+                let last = first + header.num_records;
+                if first >= range.end as u64 {
+                    break;
+                }
+                if last > range.start as u64 {
+                    file.seek(SeekFrom::Start(
+                        offset + size_of::<cbq::BlockHeader>() as u64,
+                    ))?;
+                    block.read_from(&mut file, header)?;
+                    block.decompress_columns()?;
+                    for record in block.iter_records(cbq::BlockRange::new(offset, last)) {
+                        if range.contains(&(record.index() as usize)) {
+                            proc.process_record(record)?;
+                        }
+                    }
+                    proc.on_batch_complete()?;
+                }
+                first = last;
+            }
+        }
+    }
+    proc.on_batch_complete()?;
+    proc.on_thread_complete()?;
+    Ok(())
+}
+
+/// Hands every record from a single-worker ranged read to one shared processor.
+///
+/// The reader clones its processor for the worker thread; cloning this wrapper
+/// only shares the original, so no processor state is duplicated.
+struct Forward<P>(Arc<Mutex<P>>);
+impl<P> Clone for Forward<P> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+impl<P: ParallelProcessor> ParallelProcessor for Forward<P> {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.0.lock().process_record(record)
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        self.0.lock().on_batch_complete()
+    }
+
+    fn on_thread_complete(&mut self) -> binseq::Result<()> {
+        self.0.lock().on_thread_complete()
+    }
+}
+
+/// A single block of a VBQ/CBQ file as recorded in its block index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSpan {
+    /// Byte offset of the block within the file
+    pub offset: u64,
+    /// Size of the (compressed) block in bytes
+    pub len: u64,
+    /// Number of records in the block
+    pub records: u64,
+    /// Cumulative record count as stored in the index
+    pub cumulative_records: u64,
+}
+
+/// Opens a BINSEQ file of any mode, whatever its extension.
+///
+/// The mode is first taken from the extension; if that reader rejects the
+/// file (or the extension is missing or unknown), each mode's reader is tried
+/// in turn and the first whose header magic matches is used. Files written to
+/// stdout and saved under another name are therefore read correctly.
+pub fn open_binseq(path: &str) -> Result<BinseqReader> {
+    if is_stream(path) {
+        let source = if is_stdio(path) { "stdin" } else { path };
+        bail!(
+            "Cannot read BINSEQ from {source}: BINSEQ files are memory-mapped and must be seekable. \
+             Write the stream to a file first (`bqtools decode` reads `-` by buffering it to --tmpdir)"
+        );
+    }
+    let err = match BinseqReader::new(path) {
+        Ok(reader) => return Ok(reader),
+        Err(err) => err,
+    };
+    if let Ok(reader) = cbq::MmapReader::new(path) {
+        debug!("Detected CBQ from file contents: {path}");
+        return Ok(BinseqReader::Cbq(reader));
+    }
+    if let Ok(reader) = vbq::MmapReader::new(path) {
+        debug!("Detected VBQ from file contents: {path}");
+        return Ok(BinseqReader::Vbq(reader));
+    }
+    if let Ok(reader) = bq::MmapReader::new(path) {
+        debug!("Detected BQ from file contents: {path}");
+        return Ok(BinseqReader::Bq(reader));
+    }
+    bail!("Not a BQ, VBQ, or CBQ file: {path} ({err})")
+}
+
+/// Headers of the blocks of a CBQ file, each with the byte offset of the block.
+///
+/// Blocks are stored back to back after the file header, so the offsets are a
+/// running sum of the block header and compressed column sizes.
+fn cbq_blocks(reader: &cbq::MmapReader) -> Result<Vec<(u64, cbq::BlockHeader)>> {
+    let mut offset = size_of::<cbq::FileHeader>() as u64;
+    reader
+        .iter_block_headers()
+        .map(|header| {
+            let header = header?;
+            let block_offset = offset;
+            offset += (size_of::<cbq::BlockHeader>() + header.block_len()) as u64;
+            Ok((block_offset, header))
+        })
+        .collect()
+}
+
+/// Loads the block index of a VBQ/CBQ file.
+///
+/// Returns `None` for BQ files, which have fixed-size records and no block index.
+pub fn load_block_spans(path: &str) -> Result<Option<Vec<BlockSpan>>> {
+    let spans = match open_binseq(path)? {
+        BinseqReader::Bq(_) => return Ok(None),
+        BinseqReader::Vbq(_) => {
+            let index = vbq::MmapReader::new(path)?.load_index()?;
+            index
+                .ranges()
+                .iter()
+                .map(|range| BlockSpan {
+                    offset: range.start_offset,
+                    len: range.len,
+                    records: u64::from(range.block_records),
+                    cumulative_records: range.cumulative_records,
+                })
+                .collect()
+        }
+        BinseqReader::Cbq(reader) => {
+            let mut cumulative_records = 0;
+            cbq_blocks(&reader)?
+                .into_iter()
+                .map(|(offset, header)| {
+                    cumulative_records += header.num_records;
+                    BlockSpan {
+                        offset,
+                        len: header.block_len() as u64,
+                        records: header.num_records,
+                        cumulative_records,
+                    }
+                })
+                .collect()
+        }
+    };
+    Ok(Some(spans))
+}
+
 /// Default `max_n_frac` for fuzzy (sassy) matching: `k / pattern_length`.
 ///
 /// Mirrors sassy's semantics for the fraction of `N` bases tolerated within a
@@ -153,3 +564,165 @@ pub fn build_fuzzy_searcher(
     let encoded = (!patterns.is_empty()).then(|| searcher.encode_patterns(patterns));
     Ok((searcher, encoded))
 }
+
+/// Returns true if the fuzzy match `m` is acceptable under `distance_type`.
+///
+/// sassy always searches by edit (Levenshtein) distance. Hamming distance is
+/// emulated by post-filtering: any alignment whose CIGAR contains an insertion
+/// or deletion is rejected, including an insertion and deletion pair whose text
+/// span matches the pattern length.
+///
+/// sassy reports one (cheapest) alignment per end position, so when an indel
+/// alignment beats a substitution-only placement within `k` ending at the same
+/// position, the placement is not reported and Hamming mode misses that hit.
+#[cfg(feature = "fuzzy")]
+pub fn accepts_alignment(m: &Match, distance_type: DistanceType) -> bool {
+    match distance_type {
+        DistanceType::Levenshtein => true,
+        DistanceType::Hamming => !m
+            .cigar
+            .ops
+            .iter()
+            .any(|elem| matches!(elem.op.to_char(), 'I' | 'D')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use binseq::BinseqReader;
+    use clap::Parser;
+
+    use std::{
+        sync::{Arc, Mutex},
+        thread::{self, ThreadId},
+    };
+
+    use binseq::{BinseqRecord, ParallelProcessor};
+
+    use super::{ensure_clobber_allowed, open_binseq, process_binseq, OutputExists};
+    use crate::cli::BinseqMode;
+    use crate::testutils::{write_fastx, DEFAULT_NUM_RECORDS};
+
+    /// Records the index and processing thread of every record it sees
+    #[derive(Clone, Default)]
+    struct Visits(Arc<Mutex<Vec<(u64, ThreadId)>>>);
+    impl ParallelProcessor for Visits {
+        fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((record.index(), thread::current().id()));
+            Ok(())
+        }
+
+        fn on_batch_complete(&mut self) -> binseq::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A single thread processes every record in order without spawning workers.
+    #[test]
+    fn test_process_binseq_single_thread_inline() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().nrec(5000).call()?;
+            let encoded = dir.path().join(format!("encoded{}", mode.extension()));
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                encoded.to_str().unwrap(),
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let visits = Visits::default();
+            process_binseq(encoded.to_str().unwrap(), visits.clone(), 1, None)?;
+            let visits = visits.0.lock().unwrap();
+            let indices: Vec<u64> = visits.iter().map(|(idx, _)| *idx).collect();
+            assert_eq!(indices, (0..5000).collect::<Vec<u64>>());
+            assert!(visits.iter().all(|(_, tid)| *tid == thread::current().id()));
+        }
+        Ok(())
+    }
+
+    /// A late span skips the leading blocks and still visits exactly the span, in order.
+    #[test]
+    fn test_process_binseq_single_thread_late_span() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for mode in [BinseqMode::Vbq, BinseqMode::Cbq] {
+            let in_tmp = write_fastx().nrec(5000).call()?;
+            let encoded = dir.path().join(format!("encoded{}", mode.extension()));
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                encoded.to_str().unwrap(),
+                "-B",
+                "16K",
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+            let path = encoded.to_str().unwrap();
+            assert!(super::load_block_spans(path)?.is_some_and(|blocks| blocks.len() > 2));
+
+            let visits = Visits::default();
+            process_binseq(path, visits.clone(), 1, Some("3000..4500".parse().unwrap()))?;
+            let indices: Vec<u64> = visits
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(idx, _)| *idx)
+                .collect();
+            assert_eq!(indices, (3000..4500).collect::<Vec<u64>>());
+        }
+        Ok(())
+    }
+
+    /// The mode is detected from the header when the extension is missing or wrong.
+    #[test]
+    fn test_open_binseq_detects_mode() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let encoded = dir.path().join(format!("encoded{}", mode.extension()));
+            let cmd = crate::cli::EncodeCommand::try_parse_from([
+                "encode",
+                in_tmp.path().to_str().unwrap(),
+                "-o",
+                encoded.to_str().unwrap(),
+            ])?;
+            crate::commands::encode::run(&cmd)?;
+
+            let wrong = if mode == BinseqMode::Cbq { "bq" } else { "cbq" };
+            for name in ["stdout.bin".to_string(), format!("renamed.{wrong}")] {
+                let path = dir.path().join(name);
+                std::fs::copy(&encoded, &path)?;
+                let reader = open_binseq(path.to_str().unwrap())?;
+                let detected = match reader {
+                    BinseqReader::Bq(_) => BinseqMode::Bq,
+                    BinseqReader::Vbq(_) => BinseqMode::Vbq,
+                    BinseqReader::Cbq(_) => BinseqMode::Cbq,
+                };
+                assert_eq!(detected, mode);
+                assert_eq!(reader.num_records()?, DEFAULT_NUM_RECORDS);
+            }
+        }
+
+        let fastq = write_fastx().call()?;
+        assert!(open_binseq(fastq.path().to_str().unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_clobber_protects_regular_files() -> anyhow::Result<()> {
+        let existing = tempfile::NamedTempFile::new()?;
+        assert!(ensure_clobber_allowed(existing.path(), false).is_ok());
+        let err = ensure_clobber_allowed(existing.path(), true).unwrap_err();
+        assert!(err.is::<OutputExists>());
+
+        let dir = tempfile::tempdir()?;
+        assert!(ensure_clobber_allowed(&dir.path().join("new.vbq"), true).is_ok());
+        assert!(ensure_clobber_allowed(std::path::Path::new("/dev/null"), true).is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use binseq::{bq, prelude::*, BinseqReader};
+use log::{debug, info};
+use parking_lot::Mutex;
+
+use crate::{
+    cli::ValidateCommand,
+    commands::{load_block_spans, open_binseq, utils::process_binseq, BlockSpan},
+};
+
+/// Decodes every record, checking that each sequence has its stored length.
+#[derive(Clone, Default)]
+struct RecordValidator {
+    local_count: usize,
+    count: Arc<Mutex<usize>>,
+}
+impl RecordValidator {
+    fn count(&self) -> usize {
+        *self.count.lock()
+    }
+}
+impl ParallelProcessor for RecordValidator {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        let mut mates = vec![("primary", record.sseq().len(), record.slen())];
+        if record.is_paired() {
+            mates.push(("extended", record.xseq().len(), record.xlen()));
+        }
+        for (mate, decoded, stored) in mates {
+            if decoded as u64 != stored {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "Record {}: {mate} sequence decoded to {decoded} bases but {stored} are stored",
+                        record.index()
+                    ),
+                )
+                .into());
+            }
+        }
+        self.local_count += 1;
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        *self.count.lock() += self.local_count;
+        self.local_count = 0;
+        Ok(())
+    }
+}
+
+/// Bytes per BQ record: the optional flag word plus the packed words of both mates
+fn bq_record_bytes(header: &bq::FileHeader) -> u64 {
+    let bits: u8 = header.bits.into();
+    let bases_per_word = 64 / u64::from(bits);
+    let words = |len: u32| u64::from(len).div_ceil(bases_per_word);
+    8 * (u64::from(header.flags) + words(header.slen) + words(header.xlen))
+}
+
+/// Checks that the BQ body holds a whole number of records.
+fn check_bq_body(path: &str, file_len: u64, num_records: usize) -> Result<()> {
+    let header = bq::MmapReader::new(path)?.header();
+    let record_bytes = bq_record_bytes(&header);
+    let body_len = file_len.saturating_sub(bq::SIZE_HEADER as u64);
+    let trailing = body_len % record_bytes;
+    if trailing != 0 {
+        bail!(
+            "{path}: {trailing} trailing bytes after record {num_records}; the last record is truncated"
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the blocks are ordered, lie within the file, and sum to `num_records`.
+fn check_block_index(
+    path: &str,
+    file_len: u64,
+    blocks: &[BlockSpan],
+    num_records: usize,
+) -> Result<()> {
+    let mut prev_end = 0;
+    let mut indexed = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        if block.offset < prev_end {
+            bail!(
+                "{path}: block {i} at offset {} overlaps the previous block (ends at {prev_end})",
+                block.offset
+            );
+        }
+        prev_end = block.offset + block.len;
+        if prev_end > file_len {
+            bail!(
+                "{path}: block {i} ends at offset {prev_end} beyond the end of the file ({file_len} bytes)"
+            );
+        }
+        indexed += block.records;
+    }
+    if indexed != num_records as u64 {
+        bail!("{path}: block index holds {indexed} records but the file reports {num_records}");
+    }
+    Ok(())
+}
+
+/// Rescans `blocks` one at a time, returning an error naming the first unreadable block.
+fn locate_bad_block(path: &str, blocks: &[BlockSpan]) -> Result<()> {
+    let mut start = 0;
+    for (i, block) in blocks.iter().enumerate() {
+        let end = start + block.records as usize;
+        let proc = RecordValidator::default();
+        if let Err(e) = open_binseq(path)?.process_parallel_range(proc, 1, start..end) {
+            bail!(
+                "{path}: block {i} (offset {}, records {start}..{end}) is unreadable: {e}",
+                block.offset
+            );
+        }
+        start = end;
+    }
+    Ok(())
+}
+
+/// Validates one file, returning its record count.
+fn validate(path: &str, threads: usize) -> Result<usize> {
+    let reader = open_binseq(path)?;
+    let num_records = reader.num_records()?;
+    let file_len = std::fs::metadata(path)?.len();
+    let blocks = load_block_spans(path)?;
+    match (&reader, &blocks) {
+        (BinseqReader::Bq(_), _) => check_bq_body(path, file_len, num_records)?,
+        (_, Some(blocks)) => check_block_index(path, file_len, blocks, num_records)?,
+        (_, None) => {}
+    }
+
+    let proc = RecordValidator::default();
+    if let Err(e) = process_binseq(path, proc.clone(), threads, None) {
+        debug!("{path}: decoding failed ({e}); locating the failing block");
+        if let Some(blocks) = &blocks {
+            locate_bad_block(path, blocks)?;
+        }
+        return Err(e.context(format!("{path}: failed to decode")));
+    }
+    let decoded = proc.count();
+    if decoded != num_records {
+        bail!("{path}: decoded {decoded} records but the file reports {num_records}");
+    }
+    Ok(num_records)
+}
+
+pub fn run(args: &ValidateCommand) -> Result<()> {
+    for path in &args.input {
+        let num_records = validate(path, args.threads())?;
+        info!("{path}: OK ({num_records} records)");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use clap::Parser;
+    use tempfile::NamedTempFile;
+
+    use crate::cli::BinseqMode;
+    use crate::testutils::write_fastx;
+
+    fn encode(in_path: &std::path::Path, out_path: &std::path::Path) -> Result<()> {
+        let cmd = crate::cli::EncodeCommand::try_parse_from([
+            "encode",
+            in_path.to_str().unwrap(),
+            "-o",
+            out_path.to_str().unwrap(),
+        ])?;
+        crate::commands::encode::run(&cmd)
+    }
+
+    fn validate(path: &std::path::Path) -> Result<()> {
+        let cmd =
+            crate::cli::ValidateCommand::try_parse_from(["validate", path.to_str().unwrap()])?;
+        super::run(&cmd)
+    }
+
+    /// Intact files validate; the same files with their tail cut off do not.
+    #[test]
+    fn test_validate_truncated() -> Result<()> {
+        for mode in BinseqMode::enum_iter() {
+            let in_tmp = write_fastx().call()?;
+            let bq_tmp = NamedTempFile::with_suffix(mode.extension())?;
+            encode(in_tmp.path(), bq_tmp.path())?;
+            validate(bq_tmp.path())?;
+
+            let bytes = std::fs::read(bq_tmp.path())?;
+            let truncated = NamedTempFile::with_suffix(mode.extension())?;
+            std::fs::write(truncated.path(), &bytes[..bytes.len() - 100])?;
+            assert!(
+                validate(truncated.path()).is_err(),
+                "truncated {mode:?} file passed validation"
+            );
+        }
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use log::warn;
 use serde::Serialize;
 
 use crate::cli::{Mate, VerifyCommand, VerifyOptions};
+use crate::commands::open_binseq;
 use processor::{FieldMask, VerifyProcessor};
 
 /// Whether `reader`'s underlying file actually stores per-record headers.
@@ -76,7 +77,7 @@ struct VerifyResult {
 fn compute(args: &VerifyCommand) -> Result<VerifyResult> {
     let mut fields = field_mask(&args.opts)?;
 
-    let reader = BinseqReader::new(args.input.path())?;
+    let reader = open_binseq(args.input.path())?;
     if args.opts.mate == Mate::Two && !reader.is_paired() {
         bail!(
             "`--mate/-M 2` was requested but `{}` is single-channel (no extended/mate-2 \
@@ -29,9 +29,12 @@ fn main() -> Result<()> {
     // Handle Ctrl+C gracefully
     reset_sigpipe();
 
+    let args = Cli::parse();
+
+    // `BQTOOLS_LOG` is parsed last so it takes precedence over -q/-v
     env_logger::builder()
         .format_timestamp_millis()
-        .filter_level(log::LevelFilter::Info)
+        .filter_level(args.log_level())
         .filter_module(
             "sassy", // silence sassy's debug output
             log::LevelFilter::Warn,
@@ -39,21 +42,35 @@ fn main() -> Result<()> {
         .parse_env("BQTOOLS_LOG")
         .init();
 
-    let args = Cli::parse();
+    commands::set_no_clobber(args.no_clobber);
+    commands::set_max_open_files(args.max_open_files);
+    commands::set_tmpdir(args.tmpdir.as_deref())?;
+    #[cfg(feature = "gcs")]
+    commands::set_gcs_retries(args.gcs_retries);
+    commands::install_cleanup_handler();
 
     trace!("init");
     match args.command {
         Commands::Encode(ref encode) => commands::encode::run(encode),
         Commands::Decode(ref decode) => commands::decode::run(decode),
-        Commands::Cat(cat) => commands::cat::run(cat),
+        Commands::Head(ref head) => commands::head::run(head),
+        Commands::Cat(ref cat) => commands::cat::run(cat),
         Commands::Info(ref info) => commands::info::run(info),
         Commands::Grep(ref grep) => commands::grep::run(grep),
         Commands::Sample(ref sample) => commands::sample::run(sample),
+        Commands::Sort(ref sort) => commands::sort::run(sort),
         Commands::Split(ref split) => commands::split::run(split),
         Commands::Pipe(ref pipe) => commands::pipe::run(pipe),
         Commands::Qc(ref qc) => commands::qc::run(qc),
         Commands::Revcomp(ref revcomp) => commands::revcomp::run(revcomp),
         Commands::Verify(ref verify) => commands::verify::run(verify),
+        Commands::Validate(ref validate) => commands::validate::run(validate),
+        Commands::MergePairs(ref merge) => commands::merge_pairs::run(merge),
+        Commands::Recompress(ref recompress) => commands::recompress::run(recompress),
+        Commands::Benchmark(ref benchmark) => commands::benchmark::run(benchmark),
+        Commands::Formats(ref formats) => commands::formats::run(formats),
+        Commands::Doctor(ref doctor) => commands::doctor::run(doctor),
+        Commands::Corrupt(ref corrupt) => commands::corrupt::run(corrupt),
     }?;
     trace!("done");
     Ok(())
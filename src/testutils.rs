@@ -1,14 +1,18 @@
 use std::io::Write;
 use std::path::Path;
+use std::sync::Arc;
 
 use anyhow::Result;
-use binseq::BinseqReader;
+use binseq::{
+    BinseqReader, BinseqRecord, BinseqWriterBuilder, ParallelReader, SequencingRecordBuilder,
+};
 use bon::builder;
 use niffler::Level;
+use parking_lot::Mutex;
 use rand::{Rng, RngExt};
 use tempfile::NamedTempFile;
 
-use crate::cli::FileFormat;
+use crate::cli::{BinseqMode, FileFormat};
 
 pub const DEFAULT_NUM_RECORDS: usize = 100;
 pub const DEFAULT_SEQ_LEN: usize = 100;
@@ -122,3 +126,92 @@ pub fn count_fastx_records(path: &Path) -> Result<usize> {
     };
     Ok(count)
 }
+
+/// Writes `nrec` single-end records with flags to a VBQ or CBQ file, giving
+/// record `i` the flag `i + 1`.
+pub fn write_flagged_binseq(mode: BinseqMode, nrec: usize) -> Result<NamedTempFile> {
+    let tmp = NamedTempFile::with_suffix(mode.extension())?;
+    let mut writer = BinseqWriterBuilder::new(mode.into())
+        .headers(true)
+        .flags(true)
+        .build(std::fs::File::create(tmp.path())?)?;
+    let mut rng = rand::rng();
+    for idx in 0..nrec {
+        let seq = random_sequence(&mut rng, DEFAULT_SEQ_LEN, false);
+        let header = format!("seq.{idx}");
+        let record = SequencingRecordBuilder::default()
+            .s_seq(&seq)
+            .s_header(header.as_bytes())
+            .flag(idx as u64 + 1)
+            .build()?;
+        writer.push(record)?;
+    }
+    writer.finish()?;
+    Ok(tmp)
+}
+
+/// Writes `nrec` paired records with qualities to a VBQ file where every odd
+/// record is an orphan (empty second mate and header).
+///
+/// Record `i` has primary `ACGT`/`IIII` named `seq.{i}`; complete pairs carry
+/// the mate `TTGCA`/`#####` named `mate.{i}`.
+pub fn write_orphan_binseq(nrec: usize) -> Result<NamedTempFile> {
+    let tmp = NamedTempFile::with_suffix(".vbq")?;
+    let mut writer = BinseqWriterBuilder::new(BinseqMode::Vbq.into())
+        .headers(true)
+        .quality(true)
+        .paired(true)
+        .build(std::fs::File::create(tmp.path())?)?;
+    for idx in 0..nrec {
+        let orphan = idx % 2 == 1;
+        let sheader = format!("seq.{idx}");
+        let xheader = if orphan {
+            String::new()
+        } else {
+            format!("mate.{idx}")
+        };
+        let (xseq, xqual): (&[u8], &[u8]) = if orphan {
+            (b"", b"")
+        } else {
+            (b"TTGCA", b"#####")
+        };
+        let record = SequencingRecordBuilder::default()
+            .s_seq(b"ACGT")
+            .opt_s_qual(Some(b"IIII".as_slice()))
+            .s_header(sheader.as_bytes())
+            .x_seq(xseq)
+            .opt_x_qual(Some(xqual))
+            .x_header(xheader.as_bytes())
+            .build()?;
+        writer.push(record)?;
+    }
+    writer.finish()?;
+    Ok(tmp)
+}
+
+/// Collects the flag of every record (0 when absent)
+#[derive(Clone, Default)]
+struct FlagCollector {
+    local: Vec<u64>,
+    flags: Arc<Mutex<Vec<u64>>>,
+}
+impl binseq::ParallelProcessor for FlagCollector {
+    fn process_record<B: BinseqRecord>(&mut self, record: B) -> binseq::Result<()> {
+        self.local.push(record.flag().unwrap_or(0));
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        self.flags.lock().append(&mut self.local);
+        Ok(())
+    }
+}
+
+/// Returns the record flags of a BINSEQ file in ascending order
+pub fn read_flags(path: &Path) -> Result<Vec<u64>> {
+    let collector = FlagCollector::default();
+    BinseqReader::new(path.to_str().unwrap())?.process_parallel(collector.clone(), 1)?;
+    let mut flags = std::mem::take(&mut *collector.flags.lock());
+    flags.sort_unstable();
+    Ok(flags)
+}